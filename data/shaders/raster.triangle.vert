@@ -3,6 +3,16 @@
 layout (location = 0) in vec3 inPos;
 layout (location = 0) out vec3 outColor;
 
+layout (binding = 0) uniform Camera {
+  mat4 viewInverse;
+  mat4 projInverse;
+  float aperture;
+  float focusDistance;
+  uint blades;
+  float bladeRotation;
+  mat4 viewProj;
+} camera;
+
 out gl_PerVertex 
 {
     vec4 gl_Position;   
@@ -10,5 +20,8 @@ out gl_PerVertex
 
 void main() {
 	outColor = vec3(abs(inPos.y), max(0.0, sign(inPos.x)), max(0.0, -sign(inPos.x)));
-	gl_Position = vec4(inPos.xyz, 1.0);
+	// the projections map the depth to [-1, 1] while Vulkan clips it to [0, 1]
+	vec4 position = camera.viewProj * vec4(inPos.xyz, 1.0);
+	position.z = (position.z + position.w) * 0.5;
+	gl_Position = position;
 }
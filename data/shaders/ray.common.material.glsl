@@ -0,0 +1,15 @@
+// passed to the callable shaders evaluating materials whose type is not the built-in one.
+// the callable at index `materialType - 1` receives it with `callableDataInEXT` at location 0.
+struct MaterialEvaluation {
+  // inputs in world space
+  vec3 position;
+  vec3 normal;
+  vec3 incomingDirection;
+  vec3 lightDirection;
+  vec2 texcoord;
+  // texture color multiplied by the vertex color
+  vec3 baseColor;
+  float shadowAttenuation;
+  // output
+  vec3 color;
+};
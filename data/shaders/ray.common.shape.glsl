@@ -0,0 +1,228 @@
+
+// laid out as `ProceduralShapeDescription` in `shape.rs`. the shapes traced within their bounds
+// keep them in `parameters[2]` and `parameters[3]`.
+struct ProceduralShape {
+  vec4 parameters[4];
+  // x: shape, y: signed distance function
+  uvec4 kind;
+};
+
+const uint SHAPE_SPHERE = 0;
+const uint SHAPE_BOX = 1;
+const uint SHAPE_CYLINDER = 2;
+const uint SHAPE_CONE = 3;
+const uint SHAPE_DISC = 4;
+const uint SHAPE_TORUS = 5;
+const uint SHAPE_SDF = 6;
+// intersected by `ray.curve.rint`
+const uint SHAPE_CURVE_LINEAR = 7;
+const uint SHAPE_CURVE_CUBIC = 8;
+
+const uint SDF_ROUNDED_BOX = 0;
+const uint SDF_CAPSULE = 1;
+const uint SDF_GYROID = 2;
+// the torus is sphere traced against its exact distance
+const uint SDF_TORUS = 0xffffffff;
+
+// the intersection functions take a normalized direction, returning the distance along the ray
+// with the outward normal in `yzw`, or a negative distance when nothing is hit within `tMin`
+// and `tMax`.
+const vec4 NO_HIT = vec4(-1.0);
+
+float dot2(const vec3 v) {
+  return dot(v, v);
+}
+
+// the entry and exit distances of the ray through the box
+vec2 slabs(const vec3 origin, const vec3 direction, const vec3 boxMin, const vec3 boxMax) {
+  const vec3 inv = 1.0 / direction;
+  const vec3 t0 = (boxMin - origin) * inv;
+  const vec3 t1 = (boxMax - origin) * inv;
+  const vec3 near = min(t0, t1);
+  const vec3 far = max(t0, t1);
+  return vec2(max(max(near.x, near.y), near.z), min(min(far.x, far.y), far.z));
+}
+
+vec4 hitSphere(const vec3 origin, const vec3 direction, const vec4 sphere, const float tMin, const float tMax) {
+  const vec3 oc = origin - sphere.xyz;
+  const float b = dot(oc, direction);
+  const float c = dot(oc, oc) - sphere.w * sphere.w;
+  const float discriminant = b * b - c;
+  if (discriminant < 0.0) {
+    return NO_HIT;
+  }
+  const float h = sqrt(discriminant);
+  // the far root is hit from inside, by refracted rays for instance
+  float t = -b - h;
+  if (t < tMin) {
+    t = -b + h;
+  }
+  if (t < tMin || t > tMax) {
+    return NO_HIT;
+  }
+  return vec4(t, (oc + direction * t) / sphere.w);
+}
+
+vec4 hitBox(const vec3 origin, const vec3 direction, const vec3 boxMin, const vec3 boxMax, const float tMin, const float tMax) {
+  const vec2 t = slabs(origin, direction, boxMin, boxMax);
+  if (t.x > t.y) {
+    return NO_HIT;
+  }
+  const float tHit = (t.x >= tMin) ? t.x : t.y;
+  if (tHit < tMin || tHit > tMax) {
+    return NO_HIT;
+  }
+  // the normal of the face closest to the hit
+  const vec3 center = (boxMin + boxMax) * 0.5;
+  const vec3 halfExtents = (boxMax - boxMin) * 0.5;
+  const vec3 local = (origin + direction * tHit - center) / halfExtents;
+  const vec3 distances = abs(local);
+  vec3 normal;
+  if (distances.x > distances.y && distances.x > distances.z) {
+    normal = vec3(sign(local.x), 0.0, 0.0);
+  } else if (distances.y > distances.z) {
+    normal = vec3(0.0, sign(local.y), 0.0);
+  } else {
+    normal = vec3(0.0, 0.0, sign(local.z));
+  }
+  return vec4(tHit, normal);
+}
+
+// @see https://iquilezles.org/articles/intersectors/
+vec4 hitCone(const vec3 origin, const vec3 direction, const vec3 pa, const vec3 pb, const float ra, const float rb, const float tMin, const float tMax) {
+  const vec3 ba = pb - pa;
+  const vec3 oa = origin - pa;
+  const vec3 ob = origin - pb;
+  const float m0 = dot(ba, ba);
+  const float m1 = dot(oa, ba);
+  const float m2 = dot(direction, ba);
+  const float m3 = dot(direction, oa);
+  const float m5 = dot(oa, oa);
+  const float m9 = dot(ob, ba);
+  // caps
+  if (m1 < 0.0) {
+    if (dot2(oa * m2 - direction * m1) < (ra * ra * m2 * m2)) {
+      const float t = -m1 / m2;
+      return (t >= tMin && t <= tMax) ? vec4(t, -ba * inversesqrt(m0)) : NO_HIT;
+    }
+  } else if (m9 > 0.0) {
+    const float t = -m9 / m2;
+    if (dot2(ob + direction * t) < (rb * rb)) {
+      return (t >= tMin && t <= tMax) ? vec4(t, ba * inversesqrt(m0)) : NO_HIT;
+    }
+  }
+  // body
+  const float rr = ra - rb;
+  const float hy = m0 + rr * rr;
+  const float k2 = m0 * m0 - m2 * m2 * hy;
+  const float k1 = m0 * m0 * m3 - m1 * m2 * hy + m0 * ra * (rr * m2 * 1.0);
+  const float k0 = m0 * m0 * m5 - m1 * m1 * hy + m0 * ra * (rr * m1 * 2.0 - m0 * ra);
+  const float h = k1 * k1 - k2 * k0;
+  if (h < 0.0) {
+    return NO_HIT;
+  }
+  const float t = (-k1 - sqrt(h)) / k2;
+  const float y = m1 + t * m2;
+  if (y < 0.0 || y > m0 || t < tMin || t > tMax) {
+    return NO_HIT;
+  }
+  return vec4(t, normalize(m0 * (m0 * (oa + t * direction) + rr * ba * ra) - ba * hy * y));
+}
+
+vec4 hitDisc(const vec3 origin, const vec3 direction, const vec3 center, const vec3 normal, const float radius, const float tMin, const float tMax) {
+  const vec3 o = origin - center;
+  const float t = -dot(normal, o) / dot(direction, normal);
+  const vec3 q = o + direction * t;
+  if (dot(q, q) > radius * radius || t < tMin || t > tMax) {
+    return NO_HIT;
+  }
+  // faces the ray since the disc has no inside
+  return vec4(t, normal * -sign(dot(direction, normal)));
+}
+
+float sdfBox(const vec3 p, const vec3 halfExtents) {
+  const vec3 q = abs(p) - halfExtents;
+  return length(max(q, 0.0)) + min(max(q.x, max(q.y, q.z)), 0.0);
+}
+
+// @see https://iquilezles.org/articles/distfunctions/
+float sdf(const uint kind, const vec3 p, const ProceduralShape shape) {
+  if (kind == SDF_ROUNDED_BOX) {
+    return sdfBox(p - shape.parameters[0].xyz, shape.parameters[1].xyz) - shape.parameters[0].w;
+  }
+  if (kind == SDF_CAPSULE) {
+    const vec3 pa = p - shape.parameters[0].xyz;
+    const vec3 ba = shape.parameters[1].xyz - shape.parameters[0].xyz;
+    const float h = clamp(dot(pa, ba) / dot(ba, ba), 0.0, 1.0);
+    return length(pa - ba * h) - shape.parameters[0].w;
+  }
+  if (kind == SDF_GYROID) {
+    const float scale = shape.parameters[0].x;
+    const float thickness = shape.parameters[0].y;
+    const float gyroid = abs(dot(sin(p * scale), cos(p.zxy * scale))) / scale - thickness;
+    const vec3 center = (shape.parameters[2].xyz + shape.parameters[3].xyz) * 0.5;
+    const vec3 halfExtents = (shape.parameters[3].xyz - shape.parameters[2].xyz) * 0.5;
+    return max(gyroid, sdfBox(p - center, halfExtents));
+  }
+  if (kind == SDF_TORUS) {
+    const vec3 c = p - shape.parameters[0].xyz;
+    const vec2 q = vec2(length(c.xz) - shape.parameters[0].w, c.y);
+    return length(q) - shape.parameters[1].x;
+  }
+  return 1e20;
+}
+
+vec3 sdfNormal(const uint kind, const vec3 p, const ProceduralShape shape, const float epsilon) {
+  const vec2 e = vec2(1.0, -1.0) * epsilon;
+  return normalize(
+    e.xyy * sdf(kind, p + e.xyy, shape) +
+    e.yyx * sdf(kind, p + e.yyx, shape) +
+    e.yxy * sdf(kind, p + e.yxy, shape) +
+    e.xxx * sdf(kind, p + e.xxx, shape));
+}
+
+// marches the ray through the bounds of the shape
+vec4 hitSdf(const uint kind, const vec3 origin, const vec3 direction, const ProceduralShape shape, const float tMin, const float tMax) {
+  const vec2 bounds = slabs(origin, direction, shape.parameters[2].xyz, shape.parameters[3].xyz);
+  const float tEnd = min(bounds.y, tMax);
+  const float extent = length(shape.parameters[3].xyz - shape.parameters[2].xyz);
+  const float epsilon = 1e-4 * extent;
+  // the gyroid is not an exact distance, so its steps are shortened to avoid overshooting
+  const float stepScale = (kind == SDF_GYROID) ? 0.5 : 1.0;
+  // surfaces already under the origin are skipped so that rays can leave them
+  const float side = sign(sdf(kind, origin + direction * max(bounds.x, tMin), shape));
+  float t = max(bounds.x, tMin);
+  for (int i = 0; i < 256 && t <= tEnd; i++) {
+    const float d = side * sdf(kind, origin + direction * t, shape);
+    if (d < epsilon) {
+      const vec3 normal = sdfNormal(kind, origin + direction * t, shape, epsilon);
+      return vec4(t, normal);
+    }
+    t += max(d * stepScale, epsilon);
+  }
+  return NO_HIT;
+}
+
+vec4 hitShape(const ProceduralShape shape, const vec3 origin, const vec3 direction, const float tMin, const float tMax) {
+  const uint kind = shape.kind.x;
+  if (kind == SHAPE_SPHERE) {
+    return hitSphere(origin, direction, shape.parameters[0], tMin, tMax);
+  }
+  if (kind == SHAPE_BOX) {
+    return hitBox(origin, direction, shape.parameters[0].xyz, shape.parameters[1].xyz, tMin, tMax);
+  }
+  if (kind == SHAPE_CYLINDER || kind == SHAPE_CONE) {
+    return hitCone(origin, direction,
+      shape.parameters[0].xyz, shape.parameters[1].xyz, shape.parameters[0].w, shape.parameters[1].w, tMin, tMax);
+  }
+  if (kind == SHAPE_DISC) {
+    return hitDisc(origin, direction, shape.parameters[0].xyz, shape.parameters[1].xyz, shape.parameters[0].w, tMin, tMax);
+  }
+  if (kind == SHAPE_TORUS) {
+    return hitSdf(SDF_TORUS, origin, direction, shape, tMin, tMax);
+  }
+  if (kind == SHAPE_SDF) {
+    return hitSdf(shape.kind.y, origin, direction, shape, tMin, tMax);
+  }
+  return NO_HIT;
+}
@@ -0,0 +1,163 @@
+#![allow(dead_code)]
+
+use nalgebra_glm as glm;
+
+// laid out as `VkAabbPositionsKHR`
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct AABB {
+    min: glm::Vec3,
+    max: glm::Vec3,
+}
+
+impl AABB {
+    pub fn new(min: &glm::Vec3, max: &glm::Vec3) -> Self {
+        Self {
+            min: *min,
+            max: *max,
+        }
+    }
+
+    #[inline]
+    pub fn min(&self) -> &glm::Vec3 {
+        &self.min
+    }
+
+    #[inline]
+    pub fn max(&self) -> &glm::Vec3 {
+        &self.max
+    }
+
+    // contains nothing until grown
+    pub fn empty() -> Self {
+        Self {
+            min: glm::vec3(f32::MAX, f32::MAX, f32::MAX),
+            max: glm::vec3(f32::MIN, f32::MIN, f32::MIN),
+        }
+    }
+
+    pub fn from_points(points: &[glm::Vec3]) -> Self {
+        let mut aabb = Self::empty();
+        for point in points {
+            aabb.grow(point);
+        }
+        aabb
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn grow(&mut self, point: &glm::Vec3) {
+        self.min = glm::min2(&self.min, point);
+        self.max = glm::max2(&self.max, point);
+    }
+
+    pub fn union(&self, other: &AABB) -> Self {
+        Self {
+            min: glm::min2(&self.min, &other.min),
+            max: glm::max2(&self.max, &other.max),
+        }
+    }
+
+    pub fn center(&self) -> glm::Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn extent(&self) -> glm::Vec3 {
+        self.max - self.min
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        if self.is_empty() {
+            return 0.0
+        }
+        let e = self.extent();
+        2.0 * (e.x * e.y + e.y * e.z + e.z * e.x)
+    }
+
+    // the bounds of the box after the transform, which may be looser than those of its contents
+    pub fn transformed(&self, transform: &glm::Mat4) -> Self {
+        if self.is_empty() {
+            return *self
+        }
+        let mut aabb = Self::empty();
+        for i in 0..8 {
+            let corner = glm::vec3(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z });
+            let corner = transform * glm::vec4(corner.x, corner.y, corner.z, 1.0);
+            aabb.grow(&corner.xyz());
+        }
+        aabb
+    }
+
+    // the entry and exit distances of the ray within `t_min` and `t_max`
+    pub fn intersect(&self, origin: &glm::Vec3, inverse_direction: &glm::Vec3, t_min: f32, t_max: f32) -> Option<(f32, f32)> {
+        let t0 = (self.min - origin).component_mul(inverse_direction);
+        let t1 = (self.max - origin).component_mul(inverse_direction);
+        let near = glm::min2(&t0, &t1);
+        let far = glm::max2(&t0, &t1);
+        let enter = near.x.max(near.y).max(near.z).max(t_min);
+        let exit = far.x.min(far.y).min(far.z).min(t_max);
+        if enter <= exit {
+            Some((enter, exit))
+        } else {
+            None
+        }
+    }
+
+    pub fn sphere(center: &glm::Vec3, radius: f32) -> Self {
+        let radius = glm::vec3(radius, radius, radius);
+        let min = center - radius;
+        let max = center + radius;
+        Self {
+            min,
+            max,
+        }
+    }
+}
+
+pub struct Xorshift64 {
+    x: u64,
+}
+
+impl Xorshift64 {
+    pub fn new() -> Self {
+        Self { x: 88172645463325252, }
+    }
+
+    // the state must not be zero
+    pub fn with_seed(seed: u64) -> Self {
+        Self { x: seed.max(1) }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.x;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.x = x;
+        x
+    }
+
+    // @see http://prng.di.unimi.it/
+    // xoshiro / xoroshiro generators and the PRNG shootout, 
+    // section "Generating uniform doubles in the unit interval"
+    pub fn next_uniform(&mut self) -> f64 {
+        let v = self.next_u64();
+        let v = (v >> 12) | 0x3ff0000000000000u64;
+        f64::from_bits(v) - 1.0
+    }
+
+    pub fn next_uniform_f32(&mut self) -> f32 {
+        self.next_uniform() as f32
+    }
+}
+
+impl Default for Xorshift64 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,11 +1,176 @@
 
 
+use nalgebra_glm as glm;
+
 use crate::vk::{Mat4};
 use crate::cores::InputEvent;
+use crate::base::AABB;
+use super::projection::Projection;
+use super::lens::Lens;
 
 pub trait Camera {
     fn view_inverse(&self) -> Mat4;
-    fn projection_inverse(&self) -> Mat4;
     fn apply(&mut self, inputs: InputEvent, delta_time: f32);
     fn update(&mut self, delta_time: f32);
+    // looks at the bounds from where they fill the view and focuses on their center, keeping the orientation
+    fn frame(&mut self, bounds: &AABB);
+    fn projection(&self) -> &Projection;
+    fn set_projection(&mut self, projection: Projection);
+    fn lens(&self) -> &Lens;
+    fn set_lens(&mut self, lens: Lens);
+    fn sensitivity(&self) -> &CameraSensitivity;
+    fn set_sensitivity(&mut self, sensitivity: CameraSensitivity);
+    // moves the camera at once, without smoothing. `orientation` rotates the camera space into world space.
+    fn set_pose(&mut self, position: &glm::Vec3, orientation: &glm::Quat);
+
+    // the world space position and the rotation from the camera space into world space
+    fn pose(&self) -> (glm::Vec3, glm::Quat) {
+        let view_inverse = glm::Mat4::from(&self.view_inverse());
+        let position = glm::vec3(view_inverse[(0, 3)], view_inverse[(1, 3)], view_inverse[(2, 3)]);
+        (position, glm::to_quat(&view_inverse))
+    }
+
+    fn projection_inverse(&self) -> Mat4 {
+        self.projection().inverse().into()
+    }
+
+    // maps world space to clip space for rasterization
+    fn view_projection(&self) -> Mat4 {
+        let view = glm::inverse(&glm::Mat4::from(&self.view_inverse()));
+        (self.projection().matrix() * view).into()
+    }
+
+    // the vertical field of view in radians. switches orthographic cameras back to perspective.
+    fn set_fov(&mut self, fov: f32) {
+        let projection = self.projection().with_fov(fov);
+        self.set_projection(projection);
+    }
+
+    fn set_clip_planes(&mut self, near: f32, far: f32) {
+        let projection = self.projection().with_clip_planes(near, far);
+        self.set_projection(projection);
+    }
+
+    fn set_viewport(&mut self, width: f32, height: f32) {
+        let projection = self.projection().with_viewport(width, height);
+        self.set_projection(projection);
+    }
+
+    // the vertical extent of the view volume in world units
+    fn set_orthographic(&mut self, height: f32) {
+        let projection = self.projection().with_orthographic(height);
+        self.set_projection(projection);
+    }
+
+    // the radius of the aperture in world units, zero for a pinhole
+    fn set_aperture(&mut self, aperture: f32) {
+        let lens = self.lens().with_aperture(aperture);
+        self.set_lens(lens);
+    }
+
+    fn set_focus_distance(&mut self, focus_distance: f32) {
+        let lens = self.lens().with_focus_distance(focus_distance);
+        self.set_lens(lens);
+    }
+
+    // moves the focal plane through the world space point
+    fn focus_on(&mut self, point: &glm::Vec3) {
+        let view = glm::inverse(&glm::Mat4::from(&self.view_inverse()));
+        let point = view * glm::vec4(point.x, point.y, point.z, 1.0);
+        self.set_focus_distance(-point.z);
+    }
+
+    // the world space origin and direction of the ray `ray.rgen` traces through the pixel
+    fn ray(&self, x: f32, y: f32, width: f32, height: f32) -> (glm::Vec3, glm::Vec3) {
+        let view_inverse = glm::Mat4::from(&self.view_inverse());
+        let projection_inverse = glm::Mat4::from(&self.projection_inverse());
+        let d = glm::vec2((x + 0.5) / width, (y + 0.5) / height) * 2.0 - glm::vec2(1.0, 1.0);
+        unproject_ray(&view_inverse, &projection_inverse, &d)
+    }
+}
+
+// multipliers on how fast the cameras respond to the input, all 1 by default
+#[derive(Clone, Copy, Debug)]
+pub struct CameraSensitivity {
+    // per pixel dragged
+    pub rotation: f32,
+    // per pixel dragged with the middle button or with shift
+    pub pan: f32,
+    // per wheel step
+    pub zoom: f32,
+    // per second a movement key is held
+    pub movement: f32,
+    // of the time the camera takes to settle. zero follows the input at once.
+    pub damping: f32,
+}
+
+impl Default for CameraSensitivity {
+    fn default() -> Self {
+        Self {
+            rotation: 1.0,
+            pan: 1.0,
+            zoom: 1.0,
+            movement: 1.0,
+            damping: 1.0,
+        }
+    }
+}
+
+impl CameraSensitivity {
+    // the fraction of the way to the target the camera covers over the elapsed time, approaching
+    // at `rate` per second scaled by the damping. frame rates then make no difference to the motion.
+    pub(super) fn smoothing(&self, rate: f32, delta_time: f32) -> f32 {
+        if self.damping <= 0.0 {
+            return 1.0
+        }
+        1.0 - (-rate / self.damping * delta_time).exp()
+    }
+}
+
+// the rotations around the upward and the right axes `orbital_quat` takes to look along the
+// orientation, which has no roll
+pub(super) fn look_rotations(orientation: &glm::Quat) -> (f32, f32) {
+    let forward = glm::quat_rotate_vec3(orientation, &glm::vec3(0.0, 0.0, -1.0));
+    let rotation_x = forward.x.atan2(-forward.z);
+    let rotation_y = (-forward.y).clamp(-1.0, 1.0).asin();
+    (rotation_x, rotation_y)
+}
+
+// the ray through the point `d` in normalized device coordinates, starting on the near plane.
+// the rays of orthographic projections are parallel.
+pub fn unproject_ray(view_inverse: &glm::Mat4, projection_inverse: &glm::Mat4, d: &glm::Vec2) -> (glm::Vec3, glm::Vec3) {
+    let (origin, direction) = unproject_view_ray(projection_inverse, d);
+    to_world_ray(view_inverse, &origin, &direction)
+}
+
+// the ray through the point `d` leaving the lens at `sample` as given by `Lens::sample`, which
+// meets the pinhole ray on the focal plane
+pub fn unproject_lens_ray(
+    view_inverse: &glm::Mat4,
+    projection_inverse: &glm::Mat4,
+    d: &glm::Vec2,
+    lens: &Lens,
+    sample: &glm::Vec2,
+) -> (glm::Vec3, glm::Vec3) {
+    let (origin, direction) = unproject_view_ray(projection_inverse, d);
+    if lens.is_pinhole() {
+        return to_world_ray(view_inverse, &origin, &direction)
+    }
+    let focus = origin + direction * ((-lens.focus_distance() - origin.z) / direction.z);
+    let origin = origin + glm::vec3(sample.x, sample.y, 0.0);
+    to_world_ray(view_inverse, &origin, &glm::normalize(&(focus - origin)))
+}
+
+fn unproject_view_ray(projection_inverse: &glm::Mat4, d: &glm::Vec2) -> (glm::Vec3, glm::Vec3) {
+    let near = projection_inverse * glm::vec4(d.x, d.y, -1.0, 1.0);
+    let far = projection_inverse * glm::vec4(d.x, d.y, 1.0, 1.0);
+    let near = near.xyz() / near.w;
+    let far = far.xyz() / far.w;
+    (near, glm::normalize(&(far - near)))
+}
+
+fn to_world_ray(view_inverse: &glm::Mat4, origin: &glm::Vec3, direction: &glm::Vec3) -> (glm::Vec3, glm::Vec3) {
+    let origin = view_inverse * glm::vec4(origin.x, origin.y, origin.z, 1.0);
+    let direction = view_inverse * glm::vec4(direction.x, direction.y, direction.z, 0.0);
+    (origin.xyz(), direction.xyz())
 }
@@ -5,44 +5,63 @@ use nalgebra_glm as glm;
 
 use crate::vk::{Mat4};
 use crate::cores::{InputEvent, InputKeyEvent};
-use super::camera::Camera;
+use crate::base::AABB;
+use super::camera::{Camera, CameraSensitivity, look_rotations};
+use super::projection::Projection;
+use super::lens::Lens;
 
 // uses right-handed coordinate system, that is the same as glTF 2.0. 
 // @see https://github.com/KhronosGroup/glTF/tree/master/specification/2.0#coordinate-system-and-units
 
+// per second, about 0.6 of the way a frame at 60 fps
+const DAMPING_RATE: f32 = 55.0;
+// in world units
+const MOVEMENT_SPEED: f32 = 1.5;
+const PAN_STEP: f32 = 0.01;
+const ZOOM_STEP: f32 = 0.5;
+
 pub struct FreeLookCamera {
     inv_view: glm::Mat4,
-    inv_proj: glm::Mat4,
+    projection: Projection,
+    lens: Lens,
     quat_target: glm::Quat,
     quat_smooth: glm::Quat,
     rotation_x: f32,
     rotation_y: f32,
     position_target: glm::Vec3,
     position_smooth: glm::Vec3,
+    sensitivity: CameraSensitivity,
 }
 
 impl FreeLookCamera {
     pub fn new(width: f32, height: f32) -> Self {
-        let perspective = glm::perspective_fov(glm::radians(&glm::vec1(60.0)).x, width, height, 0.1, 100.0);
-        let inv_proj = glm::inverse(&perspective);
+        let projection = Projection::new(width, height);
         let view: glm::Mat4 = glm::identity();
         let quat = orbital_quat(0.0, 0.0);
         let position = glm::vec3(0.0, 0.0, 0.0);
         Self {
             inv_view: glm::inverse(&view),
-            inv_proj: inv_proj,
+            projection,
+            lens: Lens::pinhole(),
             quat_target: quat,
             quat_smooth: quat,
             rotation_x: 0.0,
             rotation_y: 0.0,
             position_target: position,
             position_smooth: position,
+            sensitivity: CameraSensitivity::default(),
         }
     }
 
-    fn rotate(&mut self, x: f32, y: f32, _delta_time: f32) {
-        let x = (x / 600.0) * glm::pi::<f32>();
-        let y = (y / 600.0) * glm::pi::<f32>();
+    pub fn with_sensitivity(mut self, sensitivity: CameraSensitivity) -> Self {
+        self.sensitivity = sensitivity;
+        self
+    }
+
+    fn rotate(&mut self, x: f32, y: f32) {
+        let sensitivity = self.sensitivity.rotation;
+        let x = (x / 600.0) * glm::pi::<f32>() * sensitivity;
+        let y = (y / 600.0) * glm::pi::<f32>() * sensitivity;
         self.rotation_x += x;
         self.rotation_y += y;
         self.quat_target = orbital_quat(self.rotation_x, self.rotation_y);
@@ -54,15 +73,25 @@ impl FreeLookCamera {
     ) {
         let shift_modifier = if is_shift { 2.0 } else { 1.0 };
         let control_modifier = if is_control { 0.25 } else { 1.0 };
-        let speed = 1.5 * shift_modifier * control_modifier;
-        let forward = glm::vec3(0.0, 0.0, -1.0);
-        let right = glm::vec3(1.0, 0.0, 0.0);
-        let up = glm::vec3(0.0, 1.0, 0.0);
+        let speed = MOVEMENT_SPEED * self.sensitivity.movement * shift_modifier * control_modifier;
+        self.translate(&(glm::vec3(x, z, -y) * delta_time * speed));
+    }
+
+    // slides the camera within the view plane, dragging the scene along with the cursor
+    fn pan(&mut self, x: f32, y: f32) {
+        let step = PAN_STEP * self.sensitivity.pan;
+        self.translate(&(glm::vec3(-x, y, 0.0) * step));
+    }
+
+    fn zoom(&mut self, steps: f32) {
+        let step = ZOOM_STEP * self.sensitivity.zoom;
+        self.translate(&glm::vec3(0.0, 0.0, -steps * step));
+    }
+
+    // by the offset in the camera space
+    fn translate(&mut self, offset: &glm::Vec3) {
         let qi = glm::quat_inverse(&self.quat_target);
-        let forward = glm::quat_rotate_vec3(&qi, &forward) * y * delta_time * speed;
-        let right = glm::quat_rotate_vec3(&qi, &right) * x * delta_time * speed;
-        let upward = glm::quat_rotate_vec3(&qi, &up) * z * delta_time * speed;
-        self.position_target += forward + right + upward;
+        self.position_target += glm::quat_rotate_vec3(&qi, offset);
     }
 }
 
@@ -71,25 +100,71 @@ impl Camera for FreeLookCamera {
         self.inv_view.into()
     }
 
-    fn projection_inverse(&self) -> Mat4 {
-        self.inv_proj.into()
+    fn projection(&self) -> &Projection {
+        &self.projection
+    }
+
+    fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
+    }
+
+    fn lens(&self) -> &Lens {
+        &self.lens
+    }
+
+    fn set_lens(&mut self, lens: Lens) {
+        self.lens = lens;
+    }
+
+    fn sensitivity(&self) -> &CameraSensitivity {
+        &self.sensitivity
+    }
+
+    fn set_sensitivity(&mut self, sensitivity: CameraSensitivity) {
+        self.sensitivity = sensitivity;
     }
 
     fn apply(&mut self, input: InputEvent, delta_time: f32) {
         match input {
-            InputEvent::MoveDelta(x, y) => self.rotate(x, y, delta_time),
+            InputEvent::MoveDelta(x, y) => self.rotate(x, y),
+            InputEvent::PanDelta(x, y) => self.pan(x, y),
+            InputEvent::Zoom(steps) => self.zoom(steps),
             InputEvent::Key(event) => self.forward(event, delta_time),
+            InputEvent::Click(_, _) => (),
+            InputEvent::Action(_) => (),
+            InputEvent::Resize(_, _) => (),
         }
     }
 
-    fn update(&mut self, _delta_time: f32) {
-        // TODO(ogukei): adjust speed by over time
-        let a = 0.6;
+    fn update(&mut self, delta_time: f32) {
+        let a = self.sensitivity.smoothing(DAMPING_RATE, delta_time);
         self.position_smooth = glm::lerp_vec(&self.position_smooth, &self.position_target, &glm::vec3(a, a, a));
         self.quat_smooth = glm::quat_slerp(&self.quat_smooth, &self.quat_target, a);
-        let translate = self.position_smooth * -1.0;
-        let view = glm::quat_to_mat4(&self.quat_smooth) * glm::translation(&translate);
-        self.inv_view = glm::inverse(&view);
+        self.inv_view = glm::inverse(&view(&self.quat_smooth, &self.position_smooth));
+    }
+
+    fn set_pose(&mut self, position: &glm::Vec3, orientation: &glm::Quat) {
+        let (rotation_x, rotation_y) = look_rotations(orientation);
+        self.rotation_x = rotation_x;
+        self.rotation_y = rotation_y;
+        self.quat_target = glm::quat_inverse(orientation);
+        self.quat_smooth = self.quat_target;
+        self.position_target = *position;
+        self.position_smooth = *position;
+        self.inv_view = glm::inverse(&view(&self.quat_smooth, position));
+    }
+
+    fn frame(&mut self, bounds: &AABB) {
+        let (center, distance) = match self.projection.fit(bounds) {
+            Some(v) => v,
+            None => return,
+        };
+        self.lens = self.lens.with_focus_distance(distance);
+        let forward = glm::quat_rotate_vec3(&glm::quat_inverse(&self.quat_target), &glm::vec3(0.0, 0.0, -1.0));
+        self.position_target = center - forward * distance;
+        self.position_smooth = self.position_target;
+        self.quat_smooth = self.quat_target;
+        self.inv_view = glm::inverse(&view(&self.quat_smooth, &self.position_smooth));
     }
 }
 
@@ -101,3 +176,31 @@ fn orbital_quat(rotation_x: f32, rotation_y: f32) -> glm::Quat {
     let quat = glm::quat_rotate(&quat, rotation_x, &upward);
     quat
 }
+
+fn view(quat: &glm::Quat, position: &glm::Vec3) -> glm::Mat4 {
+    glm::quat_to_mat4(quat) * glm::translation(&(position * -1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_moves_the_view_at_once() {
+        let mut camera = FreeLookCamera::new(100.0, 100.0);
+        let bounds = AABB::new(&glm::vec3(9.0, -1.0, -1.0), &glm::vec3(11.0, 1.0, 1.0));
+        camera.frame(&bounds);
+        let (position, _) = camera.pose();
+        let distance = camera.lens().focus_distance();
+        assert!(glm::distance(&position, &glm::vec3(10.0, 0.0, distance)) < 1e-4);
+    }
+
+    #[test]
+    fn view_projection_inverts_the_unprojection() {
+        let mut camera = FreeLookCamera::new(100.0, 50.0);
+        camera.set_pose(&glm::vec3(1.0, 2.0, 3.0), &glm::quat_angle_axis(0.5, &glm::vec3(0.0, 1.0, 0.0)));
+        let unprojection = glm::Mat4::from(&camera.view_inverse()) * glm::Mat4::from(&camera.projection_inverse());
+        let product = glm::Mat4::from(&camera.view_projection()) * unprojection;
+        assert!((product - glm::Mat4::identity()).abs().max() < 1e-4);
+    }
+}
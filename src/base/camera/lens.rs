@@ -0,0 +1,89 @@
+
+use nalgebra_glm as glm;
+
+// the thin lens `ray.rgen` jitters the ray origins on. a zero aperture is a pinhole, keeping
+// everything in focus.
+#[derive(Clone, Copy, Debug)]
+pub struct Lens {
+    // in world units
+    aperture: f32,
+    // along the view direction, in world units
+    focus_distance: f32,
+    // the aperture is a regular polygon of as many blades, or a disk below three
+    blades: u32,
+    // of the first blade, in radians
+    blade_rotation: f32,
+}
+
+impl Lens {
+    pub fn pinhole() -> Self {
+        Self {
+            aperture: 0.0,
+            focus_distance: 1.0,
+            blades: 0,
+            blade_rotation: 0.0,
+        }
+    }
+
+    // the radius of the aperture
+    pub fn with_aperture(mut self, aperture: f32) -> Self {
+        self.aperture = aperture.max(0.0);
+        self
+    }
+
+    pub fn with_focus_distance(mut self, focus_distance: f32) -> Self {
+        self.focus_distance = focus_distance.max(1e-6);
+        self
+    }
+
+    pub fn with_blades(mut self, blades: u32, rotation: f32) -> Self {
+        self.blades = if blades < 3 { 0 } else { blades };
+        self.blade_rotation = rotation;
+        self
+    }
+
+    pub fn aperture(&self) -> f32 {
+        self.aperture
+    }
+
+    pub fn focus_distance(&self) -> f32 {
+        self.focus_distance
+    }
+
+    pub fn blades(&self) -> u32 {
+        self.blades
+    }
+
+    pub fn blade_rotation(&self) -> f32 {
+        self.blade_rotation
+    }
+
+    pub fn is_pinhole(&self) -> bool {
+        self.aperture <= 0.0
+    }
+
+    // maps three uniform numbers in [0, 1) to a point on the aperture, relative to its center,
+    // in the same way as `sampleLens` in `ray.rgen`
+    pub fn sample(&self, r0: f32, r1: f32, r2: f32) -> glm::Vec2 {
+        let pi = glm::pi::<f32>();
+        if self.blades == 0 {
+            let radius = r0.sqrt();
+            let theta = 2.0 * pi * r1;
+            return glm::vec2(theta.cos(), theta.sin()) * radius * self.aperture
+        }
+        // picks one of the triangles fanning out from the center, then a point within it
+        let sector = 2.0 * pi / self.blades as f32;
+        let index = (r2 * self.blades as f32).floor();
+        let a0 = self.blade_rotation + index * sector;
+        let a1 = a0 + sector;
+        let p0 = glm::vec2(a0.cos(), a0.sin());
+        let p1 = glm::vec2(a1.cos(), a1.sin());
+        (p0 * (1.0 - r1) + p1 * r1) * r0.sqrt() * self.aperture
+    }
+}
+
+impl Default for Lens {
+    fn default() -> Self {
+        Self::pinhole()
+    }
+}
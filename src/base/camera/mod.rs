@@ -1,8 +1,14 @@
 
 mod camera;
+mod projection;
+mod lens;
+mod path;
 mod orbital;
 mod freelook;
 
 pub use orbital::*;
 pub use freelook::*;
 pub use camera::*;
+pub use projection::*;
+pub use lens::*;
+pub use path::*;
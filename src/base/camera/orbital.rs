@@ -3,42 +3,102 @@
 use nalgebra_glm as glm;
 
 use crate::vk::{Mat4};
-use crate::cores::InputEvent;
-use super::camera::Camera;
+use crate::cores::{InputEvent, InputKeyEvent};
+use crate::base::AABB;
+use super::camera::{Camera, CameraSensitivity, look_rotations};
+use super::projection::Projection;
+use super::lens::Lens;
+
+// per second, about 0.15 of the way a frame at 60 fps
+const DAMPING_RATE: f32 = 10.0;
+// of the distance kept per wheel step
+const ZOOM_FACTOR: f32 = 0.9;
+// of the distance per pixel
+const PAN_STEP: f32 = 0.0015;
+// per second a key is held, in radians for the rotations and of the distance for the zoom
+const KEY_ROTATION_SPEED: f32 = 1.5;
+const KEY_ZOOM_SPEED: f32 = 1.0;
+const MIN_DISTANCE: f32 = 1e-3;
 
 pub struct OrbitalCamera {
     quat: glm::Quat,
     quat_target: glm::Quat,
     inv_view: glm::Mat4,
-    inv_proj: glm::Mat4,
+    projection: Projection,
+    lens: Lens,
+    sensitivity: CameraSensitivity,
     rotation_x: f32,
     rotation_y: f32,
     distance: f32,
+    distance_target: f32,
+    // the point orbited around
+    target: glm::Vec3,
+    target_goal: glm::Vec3,
 }
 
 impl OrbitalCamera {
     pub fn new(width: f32, height: f32) -> Self {
-        let perspective = glm::perspective_fov(glm::radians(&glm::vec1(60.0)).x, width, height, 0.1, 100.0);
-        let inv_proj = glm::inverse(&perspective);
+        let projection = Projection::new(width, height);
         let rotation_x: f32 = -0.24;
         let rotation_y: f32 = 0.36;
         let distance: f32 = 6.0;
+        let target = glm::vec3(0.0, 0.0, 0.0);
         let quat = orbital_quat(rotation_x, rotation_y);
         Self {
             quat: quat,
             quat_target: quat,
-            inv_view: glm::inverse(&view(&quat, distance)),
-            inv_proj: inv_proj,
+            inv_view: glm::inverse(&view(&quat, distance, &target)),
+            projection,
+            lens: Lens::pinhole(),
+            sensitivity: CameraSensitivity::default(),
             rotation_x,
             rotation_y,
             distance,
+            distance_target: distance,
+            target,
+            target_goal: target,
         }
     }
 
-    fn rotate(&mut self, x: f32, y: f32, _delta_time: f32) {
-        self.rotation_x += (x / 400.0) * glm::pi::<f32>();
-        self.rotation_y += (y / 400.0) * glm::pi::<f32>();
+    pub fn with_sensitivity(mut self, sensitivity: CameraSensitivity) -> Self {
+        self.sensitivity = sensitivity;
+        self
+    }
+
+    fn rotate(&mut self, x: f32, y: f32) {
+        let sensitivity = self.sensitivity.rotation;
+        self.rotation_x += (x / 400.0) * glm::pi::<f32>() * sensitivity;
+        self.rotation_y += (y / 400.0) * glm::pi::<f32>() * sensitivity;
+        self.quat_target = orbital_quat(self.rotation_x, self.rotation_y);
+    }
+
+    // moves the target within the view plane, dragging the scene along with the cursor. the step
+    // grows with the distance so that the scene keeps up with the cursor.
+    fn pan(&mut self, x: f32, y: f32) {
+        let step = PAN_STEP * self.distance_target * self.sensitivity.pan;
+        let qi = glm::quat_inverse(&self.quat_target);
+        self.target_goal += glm::quat_rotate_vec3(&qi, &glm::vec3(-x, y, 0.0)) * step;
+    }
+
+    // towards the target for positive steps
+    fn zoom(&mut self, steps: f32) {
+        let factor = ZOOM_FACTOR.powf(steps * self.sensitivity.zoom);
+        self.distance_target = (self.distance_target * factor).max(MIN_DISTANCE);
+    }
+
+    // A and D orbit around the upward axis, E and Q over and under the target and W and S zoom
+    fn orbit(&mut self,
+        InputKeyEvent { x, y, z, is_shift, is_control }: InputKeyEvent,
+        delta_time: f32
+    ) {
+        let shift_modifier = if is_shift { 2.0 } else { 1.0 };
+        let control_modifier = if is_control { 0.25 } else { 1.0 };
+        let speed = self.sensitivity.movement * shift_modifier * control_modifier * delta_time;
+        self.rotation_x -= x * KEY_ROTATION_SPEED * speed;
+        self.rotation_y += z * KEY_ROTATION_SPEED * speed;
         self.quat_target = orbital_quat(self.rotation_x, self.rotation_y);
+        let factor = (-y * KEY_ZOOM_SPEED * speed).exp();
+        self.distance_target = (self.distance_target * factor).max(MIN_DISTANCE);
     }
 }
 
@@ -47,23 +107,77 @@ impl Camera for OrbitalCamera {
         self.inv_view.into()
     }
 
-    fn projection_inverse(&self) -> Mat4 {
-        self.inv_proj.into()
+    fn projection(&self) -> &Projection {
+        &self.projection
+    }
+
+    fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
+    }
+
+    fn lens(&self) -> &Lens {
+        &self.lens
+    }
+
+    fn set_lens(&mut self, lens: Lens) {
+        self.lens = lens;
+    }
+
+    fn sensitivity(&self) -> &CameraSensitivity {
+        &self.sensitivity
+    }
+
+    fn set_sensitivity(&mut self, sensitivity: CameraSensitivity) {
+        self.sensitivity = sensitivity;
     }
 
     fn apply(&mut self, input: InputEvent, delta_time: f32) {
         match input {
-            InputEvent::MoveDelta(x, y) => self.rotate(x, y, delta_time),
-            InputEvent::Key(_event) => (),
+            InputEvent::MoveDelta(x, y) => self.rotate(x, y),
+            InputEvent::PanDelta(x, y) => self.pan(x, y),
+            InputEvent::Zoom(steps) => self.zoom(steps),
+            InputEvent::Key(event) => self.orbit(event, delta_time),
+            InputEvent::Click(_, _) => (),
+            InputEvent::Action(_) => (),
+            InputEvent::Resize(_, _) => (),
         }
     }
 
-    fn update(&mut self, _delta_time: f32) {
-        // TODO(ogukei): adjust speed by over time
-        self.quat = glm::quat_slerp(&self.quat, &self.quat_target, 0.15);
-        let view = view(&self.quat, self.distance);
+    fn update(&mut self, delta_time: f32) {
+        let a = self.sensitivity.smoothing(DAMPING_RATE, delta_time);
+        self.quat = glm::quat_slerp(&self.quat, &self.quat_target, a);
+        self.distance += (self.distance_target - self.distance) * a;
+        self.target = glm::lerp_vec(&self.target, &self.target_goal, &glm::vec3(a, a, a));
+        let view = view(&self.quat, self.distance, &self.target);
         self.inv_view = glm::inverse(&view);
     }
+
+    // keeps the distance, orbiting around the point as far ahead
+    fn set_pose(&mut self, position: &glm::Vec3, orientation: &glm::Quat) {
+        let (rotation_x, rotation_y) = look_rotations(orientation);
+        self.rotation_x = rotation_x;
+        self.rotation_y = rotation_y;
+        self.quat = glm::quat_inverse(orientation);
+        self.quat_target = self.quat;
+        self.distance = self.distance_target;
+        let forward = glm::quat_rotate_vec3(orientation, &glm::vec3(0.0, 0.0, -1.0));
+        self.target = position + forward * self.distance;
+        self.target_goal = self.target;
+        self.inv_view = glm::inverse(&view(&self.quat, self.distance, &self.target));
+    }
+
+    fn frame(&mut self, bounds: &AABB) {
+        let (center, distance) = match self.projection.fit(bounds) {
+            Some(v) => v,
+            None => return,
+        };
+        self.lens = self.lens.with_focus_distance(distance);
+        self.target = center;
+        self.target_goal = center;
+        self.distance = distance;
+        self.distance_target = distance;
+        self.inv_view = glm::inverse(&view(&self.quat, self.distance, &self.target));
+    }
 }
 
 fn orbital_quat(rotation_x: f32, rotation_y: f32) -> glm::Quat {
@@ -75,10 +189,10 @@ fn orbital_quat(rotation_x: f32, rotation_y: f32) -> glm::Quat {
     quat
 }
 
-fn view(quat: &glm::Quat, radius: f32) -> glm::Mat4 {
+fn view(quat: &glm::Quat, radius: f32, target: &glm::Vec3) -> glm::Mat4 {
     let dir = glm::vec3(0.0, 0.0, -radius);
     let translation = glm::translate(&glm::identity(), &dir);
-    let center = glm::translate(&glm::identity(), &glm::vec3(0.0, 0.0, 0.0));
+    let center = glm::translate(&glm::identity(), &-target);
     let rotation = glm::quat_to_mat4(&quat);
     translation * rotation * center
 }
@@ -0,0 +1,347 @@
+
+use nalgebra_glm as glm;
+
+use std::path::Path;
+
+use crate::vk::Result;
+use crate::vk::*;
+
+use super::camera::Camera;
+use super::projection::{ProjectionMode, DEFAULT_FOV};
+
+const HEADER: &str = "# kaldera camera path\n# time x y z qx qy qz qw fov";
+// between the keyframes appended by `CameraPath::record`, in seconds
+const RECORD_INTERVAL: f32 = 2.0;
+
+#[derive(Clone, Copy, Debug)]
+pub struct CameraKeyframe {
+    // in seconds from the start of the path
+    pub time: f32,
+    pub position: glm::Vec3,
+    // rotates the camera space into world space
+    pub orientation: glm::Quat,
+    // the vertical field of view, in radians
+    pub fov: f32,
+}
+
+impl CameraKeyframe {
+    // orthographic cameras are recorded with the default field of view
+    pub fn from_camera(camera: &dyn Camera, time: f32) -> Self {
+        let (position, orientation) = camera.pose();
+        let fov = match camera.projection().mode() {
+            ProjectionMode::Perspective(fov) => fov,
+            ProjectionMode::Orthographic(_) => DEFAULT_FOV,
+        };
+        Self { time, position, orientation, fov }
+    }
+
+    // the field of view is left alone on orthographic cameras
+    pub fn apply(&self, camera: &mut dyn Camera) {
+        camera.set_pose(&self.position, &self.orientation);
+        if !camera.projection().is_orthographic() {
+            camera.set_fov(self.fov);
+        }
+    }
+}
+
+// keyframes in the order of their times, interpolated with Catmull-Rom splines for the positions
+// and the fields of view and with slerp for the orientations
+#[derive(Clone, Debug, Default)]
+pub struct CameraPath {
+    keyframes: Vec<CameraKeyframe>,
+}
+
+impl CameraPath {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_file<P>(path: P) -> Result<Self> where P: AsRef<Path> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| Error::from(e).with_path(path))?;
+        Self::from_text(&text)
+            .map_err(|e| e.with_path(path))
+    }
+
+    // one keyframe per line as `time x y z qx qy qz qw fov` with the time in seconds and the field
+    // of view in degrees. lines starting with `#` are ignored.
+    pub fn from_text(text: &str) -> Result<Self> {
+        let mut this = Self::new();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+            let values: Vec<f32> = line.split_whitespace()
+                .map(|v| v.parse::<f32>())
+                .collect::<std::result::Result<_, _>>()
+                .map_err(|_| ErrorCode::CameraPathInvalid)?;
+            if values.len() != 9 || values.iter().any(|v| !v.is_finite()) {
+                return Err(ErrorCode::CameraPathInvalid.into())
+            }
+            let orientation = glm::quat(values[4], values[5], values[6], values[7]);
+            if glm::quat_length(&orientation) < 1e-6 {
+                return Err(ErrorCode::CameraPathInvalid.into())
+            }
+            let time = values[0];
+            if this.keyframes.last().map(|v| time < v.time).unwrap_or(false) {
+                return Err(ErrorCode::CameraPathInvalid.into())
+            }
+            this.keyframes.push(CameraKeyframe {
+                time,
+                position: glm::vec3(values[1], values[2], values[3]),
+                orientation: glm::quat_normalize(&orientation),
+                fov: glm::radians(&glm::vec1(values[8])).x,
+            });
+        }
+        Ok(this)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{}\n", HEADER);
+        for keyframe in self.keyframes.iter() {
+            let p = &keyframe.position;
+            let q = &keyframe.orientation.coords;
+            let fov = glm::degrees(&glm::vec1(keyframe.fov)).x;
+            text += &format!("{} {} {} {} {} {} {} {} {}\n", keyframe.time, p.x, p.y, p.z, q.x, q.y, q.z, q.w, fov);
+        }
+        text
+    }
+
+    pub fn save<P>(&self, path: P) -> Result<()> where P: AsRef<Path> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_text())
+            .map_err(|e| Error::from(e).with_path(path))
+    }
+
+    pub fn keyframes(&self) -> &[CameraKeyframe] {
+        &self.keyframes
+    }
+
+    pub fn len(&self) -> usize {
+        self.keyframes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keyframes.is_empty()
+    }
+
+    // the time of the last keyframe
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last()
+            .map(|v| v.time)
+            .unwrap_or(0.0)
+    }
+
+    // appends the current pose of the camera a fixed interval after the last keyframe
+    pub fn record(&mut self, camera: &dyn Camera) -> &CameraKeyframe {
+        let time = self.keyframes.last()
+            .map(|v| v.time + RECORD_INTERVAL)
+            .unwrap_or(0.0);
+        self.keyframes.push(CameraKeyframe::from_camera(camera, time));
+        self.keyframes.last().unwrap()
+    }
+
+    // placed after the keyframes of the same time
+    pub fn insert(&mut self, keyframe: CameraKeyframe) {
+        let index = self.keyframes.iter()
+            .position(|v| v.time > keyframe.time)
+            .unwrap_or(self.keyframes.len());
+        self.keyframes.insert(index, keyframe);
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<CameraKeyframe> {
+        if index < self.keyframes.len() {
+            Some(self.keyframes.remove(index))
+        } else {
+            None
+        }
+    }
+
+    pub fn remove_last(&mut self) -> Option<CameraKeyframe> {
+        self.keyframes.pop()
+    }
+
+    pub fn clear(&mut self) {
+        self.keyframes.clear();
+    }
+
+    // the interpolated keyframe at the time, clamped to the path
+    pub fn sample(&self, time: f32) -> Option<CameraKeyframe> {
+        let keyframes = &self.keyframes;
+        let first = keyframes.first()?;
+        let last = keyframes.last()?;
+        let time = time.max(first.time).min(last.time);
+        if keyframes.len() == 1 {
+            return Some(CameraKeyframe { time, ..*first })
+        }
+        // the segment from `index` to `index + 1` containing the time
+        let index = keyframes.iter()
+            .rposition(|v| v.time <= time)
+            .unwrap_or(0)
+            .min(keyframes.len() - 2);
+        let k1 = &keyframes[index];
+        let k2 = &keyframes[index + 1];
+        // the ends are repeated for the tangents
+        let k0 = &keyframes[index.saturating_sub(1)];
+        let k3 = &keyframes[(index + 2).min(keyframes.len() - 1)];
+        let span = k2.time - k1.time;
+        let u = if span > 0.0 { (time - k1.time) / span } else { 1.0 };
+        // along the shorter arc
+        let orientation = if glm::quat_dot(&k1.orientation, &k2.orientation) < 0.0 {
+            -k2.orientation
+        } else {
+            k2.orientation
+        };
+        Some(CameraKeyframe {
+            time,
+            position: catmull_rom(k0.position, k1.position, k2.position, k3.position, u),
+            orientation: glm::quat_slerp(&k1.orientation, &orientation, u),
+            fov: catmull_rom(k0.fov, k1.fov, k2.fov, k3.fov, u),
+        })
+    }
+
+    // plays the path back in steps of `step` seconds
+    pub fn playback(&self, step: f32) -> CameraPlayback {
+        CameraPlayback {
+            path: self.clone(),
+            step: step.max(1e-6),
+            frame: 0,
+        }
+    }
+}
+
+// yields a keyframe per rendered frame, advancing the path by the fixed step each time regardless
+// of how long the frame took, so that every run goes through the same poses
+pub struct CameraPlayback {
+    path: CameraPath,
+    step: f32,
+    frame: usize,
+}
+
+impl CameraPlayback {
+    pub fn path(&self) -> &CameraPath {
+        &self.path
+    }
+
+    // the number of frames to the end, including the last keyframe
+    pub fn frame_count(&self) -> usize {
+        if self.path.is_empty() {
+            return 0
+        }
+        let span = self.path.duration() - self.path.keyframes[0].time;
+        (span / self.step).ceil() as usize + 1
+    }
+}
+
+impl Iterator for CameraPlayback {
+    type Item = CameraKeyframe;
+
+    fn next(&mut self) -> Option<CameraKeyframe> {
+        if self.frame >= self.frame_count() {
+            return None
+        }
+        // multiplied rather than accumulated, keeping the times free of drift
+        let time = self.path.keyframes[0].time + self.frame as f32 * self.step;
+        self.frame += 1;
+        self.path.sample(time)
+    }
+}
+
+fn catmull_rom<T>(p0: T, p1: T, p2: T, p3: T, u: f32) -> T
+where
+    T: Copy + std::ops::Add<Output = T> + std::ops::Sub<Output = T> + std::ops::Mul<f32, Output = T>
+{
+    let a = p1 * 2.0;
+    let b = p2 - p0;
+    let c = p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3;
+    let d = p1 * 3.0 - p0 - p2 * 3.0 + p3;
+    (a + b * u + c * (u * u) + d * (u * u * u)) * 0.5
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyframe(time: f32, x: f32, yaw: f32, fov: f32) -> CameraKeyframe {
+        CameraKeyframe {
+            time,
+            position: glm::vec3(x, 1.0, 2.0),
+            orientation: glm::quat_angle_axis(yaw, &glm::vec3(0.0, 1.0, 0.0)),
+            fov,
+        }
+    }
+
+    fn path() -> CameraPath {
+        let mut path = CameraPath::new();
+        path.insert(keyframe(0.0, 0.0, 0.0, 0.5));
+        path.insert(keyframe(2.0, 2.0, 1.0, 1.0));
+        path.insert(keyframe(4.0, 4.0, 2.0, 1.5));
+        path
+    }
+
+    fn assert_keyframe_eq(a: &CameraKeyframe, b: &CameraKeyframe) {
+        assert!((a.time - b.time).abs() < 1e-5);
+        assert!(glm::distance(&a.position, &b.position) < 1e-5);
+        // q and -q are the same rotation
+        assert!(glm::quat_dot(&a.orientation, &b.orientation).abs() > 1.0 - 1e-5);
+        assert!((a.fov - b.fov).abs() < 1e-5);
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let path = path();
+        let text = path.to_text();
+        assert!(text.starts_with(HEADER));
+        let loaded = CameraPath::from_text(&text).unwrap();
+        assert_eq!(loaded.len(), path.len());
+        for (a, b) in loaded.keyframes().iter().zip(path.keyframes()) {
+            assert_keyframe_eq(a, b);
+        }
+    }
+
+    #[test]
+    fn invalid_text() {
+        // too few values, an unnumbered value, a zero quaternion and times going backwards
+        assert!(CameraPath::from_text("0 0 0 0 0 0 0 1").is_err());
+        assert!(CameraPath::from_text("0 0 0 0 0 0 0 1 fov").is_err());
+        assert!(CameraPath::from_text("0 0 0 0 0 0 0 0 60").is_err());
+        assert!(CameraPath::from_text("1 0 0 0 0 0 0 1 60\n0 0 0 0 0 0 0 1 60").is_err());
+        assert!(CameraPath::from_text("# comments only\n\n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn sample_at_the_keyframes_and_beyond() {
+        let path = path();
+        for k in path.keyframes() {
+            assert_keyframe_eq(&path.sample(k.time).unwrap(), k);
+        }
+        let before = path.sample(-1.0).unwrap();
+        assert_keyframe_eq(&before, &path.keyframes()[0]);
+        let after = path.sample(10.0).unwrap();
+        assert_keyframe_eq(&after, &path.keyframes()[2]);
+        assert!(CameraPath::new().sample(0.0).is_none());
+    }
+
+    #[test]
+    fn sample_between_the_keyframes() {
+        let mut path = path();
+        path.insert(keyframe(6.0, 6.0, 3.0, 2.0));
+        // the spline follows evenly spaced keyframes on a line away from the ends
+        let k = path.sample(3.0).unwrap();
+        assert!(glm::distance(&k.position, &glm::vec3(3.0, 1.0, 2.0)) < 1e-5);
+        assert!((k.fov - 1.25).abs() < 1e-5);
+        let expected = glm::quat_angle_axis(1.5, &glm::vec3(0.0, 1.0, 0.0));
+        assert!(glm::quat_dot(&k.orientation, &expected).abs() > 1.0 - 1e-5);
+    }
+
+    #[test]
+    fn playback_covers_the_path() {
+        let path = path();
+        let frames: Vec<CameraKeyframe> = path.playback(0.5).collect();
+        assert_eq!(frames.len(), 9);
+        assert_keyframe_eq(&frames[0], &path.keyframes()[0]);
+        assert_keyframe_eq(&frames[8], &path.keyframes()[2]);
+    }
+}
@@ -0,0 +1,131 @@
+
+use nalgebra_glm as glm;
+
+use crate::base::AABB;
+
+// 60°
+pub(super) const DEFAULT_FOV: f32 = std::f32::consts::FRAC_PI_3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProjectionMode {
+    // the vertical field of view, in radians
+    Perspective(f32),
+    // the vertical extent of the view volume, in world units
+    Orthographic(f32),
+}
+
+// the projection of a camera. defaults to a 60° perspective clipped between 0.1 and 100.
+#[derive(Clone, Copy, Debug)]
+pub struct Projection {
+    mode: ProjectionMode,
+    width: f32,
+    height: f32,
+    near: f32,
+    far: f32,
+}
+
+impl Projection {
+    pub fn new(width: f32, height: f32) -> Self {
+        Self {
+            mode: ProjectionMode::Perspective(DEFAULT_FOV),
+            width: width.max(1.0),
+            height: height.max(1.0),
+            near: 0.1,
+            far: 100.0,
+        }
+    }
+
+    // the vertical field of view in radians, switching to a perspective projection
+    pub fn with_fov(mut self, fov: f32) -> Self {
+        let epsilon = 1e-3;
+        self.mode = ProjectionMode::Perspective(fov.max(epsilon).min(glm::pi::<f32>() - epsilon));
+        self
+    }
+
+    // the vertical extent of the view volume, switching to an orthographic projection
+    pub fn with_orthographic(mut self, height: f32) -> Self {
+        self.mode = ProjectionMode::Orthographic(height.max(1e-6));
+        self
+    }
+
+    pub fn with_clip_planes(mut self, near: f32, far: f32) -> Self {
+        self.near = near.max(1e-6);
+        self.far = far.max(self.near * 2.0);
+        self
+    }
+
+    // the size of the image the camera renders to, of which the aspect ratio is taken.
+    // zero sizes such as those of minimized windows are raised to a pixel.
+    pub fn with_viewport(mut self, width: f32, height: f32) -> Self {
+        self.width = width.max(1.0);
+        self.height = height.max(1.0);
+        self
+    }
+
+    pub fn mode(&self) -> ProjectionMode {
+        self.mode
+    }
+
+    pub fn is_orthographic(&self) -> bool {
+        match self.mode {
+            ProjectionMode::Perspective(_) => false,
+            ProjectionMode::Orthographic(_) => true,
+        }
+    }
+
+    pub fn near(&self) -> f32 {
+        self.near
+    }
+
+    pub fn far(&self) -> f32 {
+        self.far
+    }
+
+    pub fn viewport(&self) -> (f32, f32) {
+        (self.width, self.height)
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.width / self.height
+    }
+
+    // maps view space to clip space with the depth in [-1, 1]
+    pub fn matrix(&self) -> glm::Mat4 {
+        match self.mode {
+            ProjectionMode::Perspective(fov) =>
+                glm::perspective_fov(fov, self.width, self.height, self.near, self.far),
+            ProjectionMode::Orthographic(height) => {
+                let top = height * 0.5;
+                let right = top * self.aspect();
+                glm::ortho(-right, right, -top, top, self.near, self.far)
+            },
+        }
+    }
+
+    pub fn inverse(&self) -> glm::Mat4 {
+        glm::inverse(&self.matrix())
+    }
+
+    // returns the center of the bounds and the distance from it at which the bounding sphere fits in
+    // the narrower field of view, moving the clip planes to enclose the sphere with room to move
+    // around it. orthographic projections are resized to the sphere instead. empty bounds are ignored.
+    pub(super) fn fit(&mut self, bounds: &AABB) -> Option<(glm::Vec3, f32)> {
+        if bounds.is_empty() {
+            return None
+        }
+        let radius = (glm::length(&bounds.extent()) * 0.5).max(1e-3);
+        let distance = match self.mode {
+            ProjectionMode::Perspective(fov) => {
+                let horizontal_fov = 2.0 * ((fov * 0.5).tan() * self.aspect()).atan();
+                radius / (fov.min(horizontal_fov) * 0.5).sin()
+            },
+            ProjectionMode::Orthographic(_) => {
+                self.mode = ProjectionMode::Orthographic(2.0 * radius * (1.0 / self.aspect()).max(1.0));
+                radius * 2.0
+            },
+        };
+        self.near = radius * 1e-3;
+        self.far = (distance + radius) * 4.0;
+        Some((bounds.center(), distance))
+    }
+}
@@ -35,3 +35,14 @@ impl From<glm::Mat4x4> for Mat4 {
         }
     }
 }
+
+impl From<&Mat4> for glm::Mat4x4 {
+    fn from(v: &Mat4) -> Self {
+        let c = &v.columns;
+        glm::mat4(
+            c[0].x, c[1].x, c[2].x, c[3].x,
+            c[0].y, c[1].y, c[2].y, c[3].y,
+            c[0].z, c[1].z, c[2].z, c[3].z,
+            c[0].w, c[1].w, c[2].w, c[3].w)
+    }
+}
@@ -3,14 +3,25 @@ use std::sync::Arc;
 use std::sync::Mutex;
 
 use crate::ffi::xcb::*;
-use crate::cores::{InputEvent, InputKeyEvent};
+use crate::cores::{InputEvent, InputKeyEvent, InputAction};
 
 use xcb_event_mask_t::*;
 
+const PRIMARY_BUTTON: xcb_button_t = 1;
+const WHEEL_UP_BUTTON: xcb_button_t = 4;
+const WHEEL_DOWN_BUTTON: xcb_button_t = 5;
+// of the modifier state of the events
+const SHIFT_MASK: u16 = 1;
+// in pixels
+const CLICK_TOLERANCE: i16 = 3;
+
 struct XcbInputInterpreterState {
     x_last: Option<i16>,
     y_last: Option<i16>,
     keys: [bool; 256],
+    // where the primary button went down
+    press: Option<(i16, i16)>,
+    size: Option<(u16, u16)>,
 }
 
 impl Default for XcbInputInterpreterState {
@@ -19,6 +30,8 @@ impl Default for XcbInputInterpreterState {
             x_last: None,
             y_last: None,
             keys: [false; 256],
+            press: None,
+            size: None,
         }
     }
 }
@@ -30,6 +43,10 @@ struct KeyCodes {
     pub d: u8,
     pub e: u8,
     pub q: u8,
+    pub f: u8,
+    pub r: u8,
+    pub z: u8,
+    pub p: u8,
     pub shift_left: u8,
     pub control_left: u8,
 }
@@ -43,6 +60,10 @@ impl KeyCodes {
             d: keymap.code_of_key(XcbKey::D).unwrap_or(0),
             e: keymap.code_of_key(XcbKey::E).unwrap_or(0),
             q: keymap.code_of_key(XcbKey::Q).unwrap_or(0),
+            f: keymap.code_of_key(XcbKey::F).unwrap_or(0),
+            r: keymap.code_of_key(XcbKey::R).unwrap_or(0),
+            z: keymap.code_of_key(XcbKey::Z).unwrap_or(0),
+            p: keymap.code_of_key(XcbKey::P).unwrap_or(0),
             shift_left: keymap.code_of_key(XcbKey::ShiftLeft).unwrap_or(0),
             control_left: keymap.code_of_key(XcbKey::ControlLeft).unwrap_or(0),
         }
@@ -68,9 +89,20 @@ impl XcbInputInterpreter {
 
     pub fn next(&self) -> Option<Vec<InputEvent>> {
         let events = self.window.events();
+        // before the held keys are updated
+        let actions = self.actions(events.as_ref());
         let keys = self.keys(events.as_ref());
         let motions = self.motions(events.as_ref());
-        let events: Vec<InputEvent> = keys.into_iter().chain(motions).collect();
+        let wheels = self.wheels(events.as_ref());
+        let clicks = self.clicks(events.as_ref());
+        let resize = self.resize(events.as_ref());
+        let events: Vec<InputEvent> = resize.into_iter()
+            .chain(keys)
+            .chain(motions)
+            .chain(wheels)
+            .chain(clicks)
+            .chain(actions)
+            .collect();
         if events.is_empty() {
             None
         } else {
@@ -78,6 +110,33 @@ impl XcbInputInterpreter {
         }
     }
 
+    // fires when a key bound to a command goes down
+    fn actions(&self, events: Option<&Vec<XcbEvent>>) -> Vec<InputEvent> {
+        let events = if let Some(events) = events { events } else { return vec![] };
+        let state = self.state.lock().unwrap();
+        let mut keys = state.keys;
+        let mut actions = vec![];
+        for event_type in events.iter().filter_map(|v| v.event_type()) {
+            let (key, press) = match event_type {
+                XcbEventType::KeyPress(event) => (event.detail, true),
+                XcbEventType::KeyRelease(event) => (event.detail, false),
+                _ => continue,
+            };
+            if press && !keys[key as usize] {
+                let action = match key {
+                    key if key == self.key_codes.f => Some(InputAction::ToggleDepthOfField),
+                    key if key == self.key_codes.r => Some(InputAction::RecordKeyframe),
+                    key if key == self.key_codes.z => Some(InputAction::RemoveKeyframe),
+                    key if key == self.key_codes.p => Some(InputAction::TogglePlayback),
+                    _ => None,
+                };
+                actions.extend(action.map(InputEvent::Action));
+            }
+            keys[key as usize] = press;
+        }
+        actions
+    }
+
     fn keys(&self, events: Option<&Vec<XcbEvent>>) -> Option<InputEvent> {
         let event_types: Vec<&XcbEventType> = events.iter()
             .flat_map(|v| v.iter())
@@ -112,41 +171,103 @@ impl XcbInputInterpreter {
         }
     }
 
-    fn motions(&self, events: Option<&Vec<XcbEvent>>) -> Option<InputEvent> {
-        let events = if let Some(events) = events { events } else { return None };
-        let event_types: Vec<&XcbEventType> = events.iter()
-            .filter_map(|v| v.event_type())
-            .collect();
+    // drags with the primary button rotate and the others pan
+    fn motions(&self, events: Option<&Vec<XcbEvent>>) -> Vec<InputEvent> {
+        let events = if let Some(events) = events { events } else { return vec![] };
         let mut state = self.state.lock().unwrap();
-        let motion = event_types.iter()
+        let mut rotation = (0i64, 0i64);
+        let mut pan = (0i64, 0i64);
+        let motions = events.iter()
+            .filter_map(|v| v.event_type())
             .filter_map(|v| match v {
                 XcbEventType::MotionNotify(event) => Some(event),
                 _ => None,
-            })
-            .fold((state.x_last, state.y_last, 0i64, 0i64), |acc, event| {
-                if (event.state & XCB_EVENT_MASK_BUTTON_1_MOTION as u16) == 0 {
-                    return (Some(event.event_x), Some(event.event_y), acc.2, acc.3)
-                }
-                let (x_last, y_last, acc_x, acc_y) = acc;
-                let dx = x_last
-                    .map(|x| event.event_x - x)
-                    .map(|dx| dx as i64)
-                    .map(|dx| dx + acc_x)
-                    .unwrap_or(0);
-                let dy = y_last
-                    .map(|y| event.event_y - y)
-                    .map(|dy| dy as i64)
-                    .map(|dy| dy + acc_y)
-                    .unwrap_or(0);
-                (Some(event.event_x), Some(event.event_y), dx, dy)
             });
-        let (x_last, y_last, dx, dy) = motion;
-        state.x_last = x_last;
-        state.y_last = y_last;
-        if dx == 0 && dy == 0 {
+        for event in motions {
+            let delta = state.x_last.zip(state.y_last)
+                .map(|(x, y)| ((event.event_x - x) as i64, (event.event_y - y) as i64))
+                .unwrap_or((0, 0));
+            state.x_last = Some(event.event_x);
+            state.y_last = Some(event.event_y);
+            let is_primary = (event.state & XCB_EVENT_MASK_BUTTON_1_MOTION as u16) != 0;
+            let is_middle = (event.state & XCB_EVENT_MASK_BUTTON_2_MOTION as u16) != 0;
+            let is_shift = (event.state & SHIFT_MASK) != 0;
+            let accumulated = if is_middle || (is_primary && is_shift) {
+                &mut pan
+            } else if is_primary {
+                &mut rotation
+            } else {
+                continue
+            };
+            accumulated.0 += delta.0;
+            accumulated.1 += delta.1;
+        }
+        let mut events = vec![];
+        if rotation != (0, 0) {
+            events.push(InputEvent::MoveDelta(rotation.0 as f32, rotation.1 as f32));
+        }
+        if pan != (0, 0) {
+            events.push(InputEvent::PanDelta(pan.0 as f32, pan.1 as f32));
+        }
+        events
+    }
+
+    fn wheels(&self, events: Option<&Vec<XcbEvent>>) -> Option<InputEvent> {
+        let events = events?;
+        let steps: i32 = events.iter()
+            .filter_map(|v| v.event_type())
+            .map(|v| match v {
+                XcbEventType::ButtonPress(event) if event.detail == WHEEL_UP_BUTTON => 1,
+                XcbEventType::ButtonPress(event) if event.detail == WHEEL_DOWN_BUTTON => -1,
+                _ => 0,
+            })
+            .sum();
+        if steps == 0 {
             None
         } else {
-            Some(InputEvent::MoveDelta(dx as f32, dy as f32))
+            Some(InputEvent::Zoom(steps as f32))
+        }
+    }
+
+    // a release away from the press is a drag rotating the camera rather than a click
+    fn clicks(&self, events: Option<&Vec<XcbEvent>>) -> Vec<InputEvent> {
+        let events = if let Some(events) = events { events } else { return vec![] };
+        let mut state = self.state.lock().unwrap();
+        let mut clicks = vec![];
+        for event_type in events.iter().filter_map(|v| v.event_type()) {
+            match event_type {
+                XcbEventType::ButtonPress(event) if event.detail == PRIMARY_BUTTON => {
+                    state.press = Some((event.event_x, event.event_y));
+                },
+                XcbEventType::ButtonRelease(event) if event.detail == PRIMARY_BUTTON => {
+                    if let Some((x, y)) = state.press.take() {
+                        let distance = (event.event_x - x).abs().max((event.event_y - y).abs());
+                        if distance <= CLICK_TOLERANCE {
+                            clicks.push(InputEvent::Click(event.event_x as f32, event.event_y as f32));
+                        }
+                    }
+                },
+                _ => (),
+            }
+        }
+        clicks
+    }
+
+    // the last size the window took, ignoring the configurations that only move it
+    fn resize(&self, events: Option<&Vec<XcbEvent>>) -> Option<InputEvent> {
+        let events = if let Some(events) = events { events } else { return None };
+        let mut state = self.state.lock().unwrap();
+        let size = events.iter()
+            .filter_map(|v| v.event_type())
+            .filter_map(|v| match v {
+                XcbEventType::ConfigureNotify(event) => Some((event.width, event.height)),
+                _ => None,
+            })
+            .next_back()?;
+        if state.size == Some(size) {
+            return None
         }
+        state.size = Some(size);
+        Some(InputEvent::Resize(size.0 as f32, size.1 as f32))
     }
 }
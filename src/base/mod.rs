@@ -17,6 +17,11 @@ mod geometry;
 #[cfg(feature = "with-nalgebra")]
 pub use geometry::*;
 
+#[cfg(feature = "with-nalgebra")]
+mod aabb;
+#[cfg(feature = "with-nalgebra")]
+pub use aabb::*;
+
 #[cfg(feature = "with-nalgebra")]
 mod camera;
 #[cfg(feature = "with-nalgebra")]
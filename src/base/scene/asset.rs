@@ -12,16 +12,17 @@ use crate::vk::*;
 
 use crate::base::scene::image as scene_image;
 
-use std::path::{Path, PathBuf};
-use std::fs::File;
-use std::io::BufReader;
+use super::resolver::{UriResolver, FileResolver, read_to_end};
+
+use std::path::Path;
+use std::io::{Cursor, Read};
 use std::sync::Arc;
 
 
 pub struct SceneAsset {
     document: gltf::Document,
     buffers: Vec<gltf::buffer::Data>,
-    base: PathBuf,
+    resolver: Option<Arc<dyn UriResolver>>,
 }
 
 impl SceneAsset {
@@ -29,17 +30,39 @@ impl SceneAsset {
         log_debug!("loading scene asset");
         let path = path.as_ref();
         let base = path.parent().unwrap_or_else(|| Path::new("./"));
-        let file = File::open(path)
-            .map_err(|_| ErrorCode::Io)?;
-        let reader = BufReader::new(file);
-        let Gltf { document, blob } = Gltf::from_reader(reader)
-            .map_err(|_| ErrorCode::Io)?;
-        let buffers = import_buffer_data(&document, Some(base), blob)?;
+        let data = read_to_end(path)?;
+        let asset = Self::load(&data, Some(Arc::new(FileResolver::new(base))))
+            .map_err(|e| e.with_path(path))?;
         log_debug!("loading scene asset complete");
+        Ok(asset)
+    }
+
+    // glTF or GLB in memory. external URIs fail to load without a resolver.
+    pub fn from_slice(data: &[u8]) -> Result<Arc<Self>> {
+        Self::load(data, None)
+    }
+
+    pub fn from_slice_with_resolver(data: &[u8], resolver: Arc<dyn UriResolver>) -> Result<Arc<Self>> {
+        Self::load(data, Some(resolver))
+    }
+
+    pub fn from_reader<R>(reader: R) -> Result<Arc<Self>> where R: Read {
+        let data = read_all(reader)?;
+        Self::load(&data, None)
+    }
+
+    pub fn from_reader_with_resolver<R>(reader: R, resolver: Arc<dyn UriResolver>) -> Result<Arc<Self>> where R: Read {
+        let data = read_all(reader)?;
+        Self::load(&data, Some(resolver))
+    }
+
+    fn load(data: &[u8], resolver: Option<Arc<dyn UriResolver>>) -> Result<Arc<Self>> {
+        let Gltf { document, blob } = Gltf::from_reader(Cursor::new(data))?;
+        let buffers = import_buffer_data(&document, resolver.as_deref(), blob)?;
         let asset = Self {
             document,
             buffers,
-            base: base.to_owned(),
+            resolver,
         };
         Ok(Arc::new(asset))
     }
@@ -55,7 +78,7 @@ impl SceneAsset {
     }
 
     pub fn import_image_data(&self, image_index: usize,) -> Result<scene_image::Data> {
-        import_image_data(&self.document, Some(self.base.as_path()), &self.buffers, image_index)
+        import_image_data(&self.document, self.resolver.as_deref(), &self.buffers, image_index)
     }
 }
 
@@ -63,17 +86,17 @@ impl SceneAsset {
 // @see https://docs.rs/gltf/latest/src/gltf/import.rs.html#234-239
 fn import_buffer_data(
     document: &gltf::Document,
-    base: Option<&Path>,
+    resolver: Option<&dyn UriResolver>,
     mut blob: Option<Vec<u8>>,
 ) -> Result<Vec<gltf::buffer::Data>> {
     let mut buffers = Vec::new();
     for buffer in document.buffers() {
         let mut data = match buffer.source() {
-            gltf::buffer::Source::Uri(uri) => Scheme::read(base, uri),
-            gltf::buffer::Source::Bin => blob.take().ok_or(ErrorCode::Io.into()),
+            gltf::buffer::Source::Uri(uri) => Scheme::read(resolver, uri),
+            gltf::buffer::Source::Bin => blob.take().ok_or(ErrorCode::BufferNotFound.into()),
         }?;
         if data.len() < buffer.length() {
-            return Err(ErrorCode::Io.into());
+            return Err(ErrorCode::BufferNotFound.into());
         }
         while data.len() % 4 != 0 {
             data.push(0);
@@ -124,32 +147,20 @@ impl<'a> Scheme<'a> {
         }
     }
 
-    fn read(base: Option<&Path>, uri: &str) -> Result<Vec<u8>> {
+    fn read(resolver: Option<&dyn UriResolver>, uri: &str) -> Result<Vec<u8>> {
         match Scheme::parse(uri) {
-            // The path may be unused in the Scheme::Data case
+            // The resolver may be unused in the Scheme::Data case
             // Example: "uri" : "data:application/octet-stream;base64,wsVHPgA...."
-            Scheme::Data(_, base64) => base64::decode(&base64).map_err(|_| ErrorCode::Io.into()),
-            Scheme::File(path) if base.is_some() => read_to_end(path),
-            Scheme::Relative if base.is_some() => read_to_end(base.unwrap().join(uri)),
-            Scheme::Unsupported => Err(ErrorCode::Io.into()),
-            _ => Err(ErrorCode::Io.into()),
+            Scheme::Data(_, base64) => base64::decode(base64).map_err(Error::from),
+            Scheme::File(_) | Scheme::Relative if resolver.is_some() => resolver.unwrap().resolve(uri),
+            _ => Err(ErrorCode::UriUnsupported(uri.to_owned()).into()),
         }
     }
 }
 
-fn read_to_end<P>(path: P) -> Result<Vec<u8>>
-where
-    P: AsRef<Path>,
-{
-    use std::io::Read;
-    let file = File::open(path.as_ref()).map_err(|_| ErrorCode::Io)?;
-    // Allocate one extra byte so the buffer doesn't need to grow before the
-    // final `read` call at the end of the file.  Don't worry about `usize`
-    // overflow because reading will fail regardless in that case.
-    let length = file.metadata().map(|x| x.len() + 1).unwrap_or(0);
-    let mut reader = BufReader::new(file);
-    let mut data = Vec::with_capacity(length as usize);
-    reader.read_to_end(&mut data).map_err(|_| ErrorCode::Io)?;
+fn read_all<R>(mut reader: R) -> Result<Vec<u8>> where R: Read {
+    let mut data = vec![];
+    reader.read_to_end(&mut data)?;
     Ok(data)
 }
 
@@ -160,7 +171,7 @@ fn convert_image(image: image_crate::DynamicImage) -> image_crate::DynamicImage
 /// Import the image data referenced by a glTF document.
 pub fn import_image_data(
     document: &gltf::Document,
-    base: Option<&Path>,
+    resolver: Option<&dyn UriResolver>,
     buffer_data: &[gltf::buffer::Data],
     image_index: usize,
 ) -> Result<scene_image::Data> {
@@ -171,55 +182,55 @@ pub fn import_image_data(
     };
     let result_image: scene_image::Data;
     let document_image = document.images().nth(image_index)
-        .ok_or_else(|| ErrorCode::Io)?;
+        .ok_or_else(|| ErrorCode::ImageNotFound)?;
     match document_image.source() {
-        image::Source::Uri { uri, mime_type } if base.is_some() => {
+        image::Source::Uri { uri, mime_type } => {
             match Scheme::parse(uri) {
                 Scheme::Data(Some(annoying_case), base64) => {
-                    let encoded_image = base64::decode(&base64).map_err(|_| ErrorCode::Io)?;
+                    let encoded_image = base64::decode(base64)?;
                     let encoded_format = match annoying_case {
                         "image/png" => Png,
                         "image/jpeg" => Jpeg,
                         _ => match guess_format(&encoded_image) {
                             Some(format) => format,
-                            None => return Err(ErrorCode::Io.into()),
+                            None => return Err(ErrorCode::ImageFormatInvalid.into()),
                         },
                     };
                     let decoded_image = image_crate::load_from_memory_with_format(
                         &encoded_image,
                         encoded_format,
-                    ).map_err(|_| ErrorCode::Io)?;
+                    )?;
                     let image = convert_image(decoded_image);
                     let image = scene_image::Data::new(image)
-                        .ok_or_else(|| ErrorCode::Io)?;
+                        .ok_or_else(|| ErrorCode::ImageFormatInvalid)?;
                     return Ok(image);
                 }
-                Scheme::Unsupported => return Err(ErrorCode::Io.into()),
+                Scheme::Unsupported => return Err(ErrorCode::UriUnsupported(uri.to_owned()).into()),
                 _ => {}
             }
-            let encoded_image = Scheme::read(base, uri)?;
+            let encoded_image = Scheme::read(resolver, uri)?;
             let encoded_format = match mime_type {
                 Some("image/png") => Png,
                 Some("image/jpeg") => Jpeg,
                 Some(_) => match guess_format(&encoded_image) {
                     Some(format) => format,
-                    None => return Err(ErrorCode::Io.into()),
+                    None => return Err(ErrorCode::ImageFormatInvalid.into()),
                 },
                 None => match uri.rsplit('.').next() {
                     Some("png") => Png,
                     Some("jpg") | Some("jpeg") => Jpeg,
                     _ => match guess_format(&encoded_image) {
                         Some(format) => format,
-                        None => return Err(ErrorCode::Io.into()),
+                        None => return Err(ErrorCode::ImageFormatInvalid.into()),
                     },
                 },
             };
             let decoded_image =
                 image_crate::load_from_memory_with_format(&encoded_image, encoded_format)
-                    .map_err(|_| ErrorCode::Io)?;
+                    ?;
             let image = convert_image(decoded_image);
             let image = scene_image::Data::new(image)
-                .ok_or_else(|| ErrorCode::Io)?;
+                .ok_or_else(|| ErrorCode::ImageFormatInvalid)?;
             result_image = image;
         }
         image::Source::View { view, mime_type } => {
@@ -232,18 +243,17 @@ pub fn import_image_data(
                 "image/jpeg" => Jpeg,
                 _ => match guess_format(encoded_image) {
                     Some(format) => format,
-                    None => return Err(ErrorCode::Io.into()),
+                    None => return Err(ErrorCode::ImageFormatInvalid.into()),
                 },
             };
             let decoded_image =
                 image_crate::load_from_memory_with_format(encoded_image, encoded_format)
-                    .map_err(|_| ErrorCode::Io)?;
+                    ?;
             let image = convert_image(decoded_image);
             let image = scene_image::Data::new(image)
-            .ok_or_else(|| ErrorCode::Io)?;
+            .ok_or_else(|| ErrorCode::ImageFormatInvalid)?;
             result_image = image;
         }
-        _ => return Err(ErrorCode::Io.into()),
     }
     Ok(result_image)
 }
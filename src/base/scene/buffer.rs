@@ -15,6 +15,8 @@ pub struct SceneStagingBuffers {
     index_buffer: Arc<DedicatedStagingBuffer>,
     normals_buffer: Arc<DedicatedStagingBuffer>,
     description_buffer: Arc<DedicatedStagingBuffer>,
+    // one per primitive followed by the selection
+    descriptions: Vec<SceneMeshPrimitiveDescription>,
     texcoord_buffer: Arc<DedicatedStagingBuffer>,
     material_description_buffer: Arc<DedicatedStagingBuffer>,
     tangent_buffer: Arc<DedicatedStagingBuffer>,
@@ -25,7 +27,7 @@ impl SceneStagingBuffers {
     pub fn new(command_pool: &Arc<CommandPool>, 
         primitives: &Vec<MeshPrimitive>, 
         material_descriptions: &Vec<SceneMaterialDescription>,
-    ) -> Arc<Self> {
+    ) -> Result<Arc<Self>> {
         let num_indices: usize = primitives.iter()
             .map(|v| v.primitive().indices().count())
             .sum();
@@ -41,7 +43,7 @@ impl SceneStagingBuffers {
                 | VK_BUFFER_USAGE_ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_BIT_KHR as VkBufferUsageFlags,
             VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT as VkMemoryPropertyFlags,
             vertex_buffer_size as VkDeviceSize,
-        ).unwrap();
+        )?;
         let index_buffer_size = std::mem::size_of::<u32>() * num_indices;
         let index_buffer = DedicatedStagingBuffer::new(
             command_pool, 
@@ -51,7 +53,7 @@ impl SceneStagingBuffers {
                 | VK_BUFFER_USAGE_ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_BIT_KHR as VkBufferUsageFlags,
             VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT as VkMemoryPropertyFlags,
             index_buffer_size as VkDeviceSize,
-        ).unwrap();
+        )?;
         let normals_buffer_size = std::mem::size_of::<[f32; 3]>() * num_vertices;
         let normals_buffer = DedicatedStagingBuffer::new(
             command_pool, 
@@ -59,13 +61,14 @@ impl SceneStagingBuffers {
                 | VK_BUFFER_USAGE_SHADER_DEVICE_ADDRESS_BIT as VkBufferUsageFlags,
             VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT as VkMemoryPropertyFlags,
             normals_buffer_size as VkDeviceSize,
-        ).unwrap();
+        )?;
         let descriptions: Vec<SceneMeshPrimitiveDescription> = primitives.iter()
             .map(|v| SceneMeshPrimitiveDescription::new(
                 v.offset().clone(),
                 v.material_index() as u32,
                 v.use_color_multipliers()))
             .collect();
+        let descriptions = with_selection_slot(&descriptions, None);
         let description_buffer_size = std::mem::size_of::<SceneMeshPrimitiveDescription>() * descriptions.len();
         let description_buffer = DedicatedStagingBuffer::new(
             command_pool, 
@@ -73,7 +76,7 @@ impl SceneStagingBuffers {
                 | VK_BUFFER_USAGE_SHADER_DEVICE_ADDRESS_BIT as VkBufferUsageFlags,
             VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT as VkMemoryPropertyFlags,
             description_buffer_size as VkDeviceSize,
-        ).unwrap();
+        )?;
         let texcoord_buffer_size = std::mem::size_of::<[f32; 2]>() * num_vertices;
         let texcoord_buffer = DedicatedStagingBuffer::new(
             command_pool, 
@@ -81,7 +84,7 @@ impl SceneStagingBuffers {
                 | VK_BUFFER_USAGE_SHADER_DEVICE_ADDRESS_BIT as VkBufferUsageFlags,
             VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT as VkMemoryPropertyFlags,
             texcoord_buffer_size as VkDeviceSize,
-        ).unwrap();
+        )?;
         let material_description_buffer_size = std::mem::size_of::<SceneMaterialDescription>() * material_descriptions.len();
         let material_description_buffer = DedicatedStagingBuffer::new(
             command_pool, 
@@ -89,7 +92,7 @@ impl SceneStagingBuffers {
                 | VK_BUFFER_USAGE_SHADER_DEVICE_ADDRESS_BIT as VkBufferUsageFlags,
             VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT as VkMemoryPropertyFlags,
             material_description_buffer_size as VkDeviceSize,
-        ).unwrap();
+        )?;
         let tangent_buffer_size = std::mem::size_of::<[f32; 4]>() * num_vertices;
         let tangent_buffer = DedicatedStagingBuffer::new(
             command_pool, 
@@ -97,7 +100,7 @@ impl SceneStagingBuffers {
                 | VK_BUFFER_USAGE_SHADER_DEVICE_ADDRESS_BIT as VkBufferUsageFlags,
             VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT as VkMemoryPropertyFlags,
             tangent_buffer_size as VkDeviceSize,
-        ).unwrap();
+        )?;
         let has_colors = primitives.iter().any(|v| v.use_color_multipliers());
         let dummy_buffer_size = 256usize;
         assert!(dummy_buffer_size % std::mem::size_of::<f32>() == 0);
@@ -108,7 +111,7 @@ impl SceneStagingBuffers {
                 | VK_BUFFER_USAGE_SHADER_DEVICE_ADDRESS_BIT as VkBufferUsageFlags,
             VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT as VkMemoryPropertyFlags,
             color_buffer_size as VkDeviceSize,
-        ).unwrap();
+        )?;
         unsafe {
             let queue_submit = QueueSubmit::new(command_pool.queue());
             index_buffer.defer_update(&queue_submit, index_buffer_size as VkDeviceSize, |data| {
@@ -195,19 +198,20 @@ impl SceneStagingBuffers {
                 // ignores vertex color multipliers.
                 // assumes the shader does not access the buffer.
             }
-            queue_submit.execute().unwrap();
+            queue_submit.execute()?;
         }
         let buffer = Self {
             vertex_buffer,
             index_buffer,
             normals_buffer,
             description_buffer,
+            descriptions,
             texcoord_buffer,
             material_description_buffer,
             tangent_buffer,
             color_buffer,
         };
-        Arc::new(buffer)
+        Ok(Arc::new(buffer))
     }
 
     #[inline]
@@ -230,6 +234,21 @@ impl SceneStagingBuffers {
         &self.description_buffer
     }
 
+    // the trailing description is the one the selected instance is pointed at, flagged for the
+    // highlight. the device must not be reading the buffer.
+    pub fn write_selection(&self, primitive_index: Option<usize>) {
+        let primitive_count = self.descriptions.len() - 1;
+        let descriptions = with_selection_slot(&self.descriptions[..primitive_count], primitive_index);
+        let size = std::mem::size_of::<SceneMeshPrimitiveDescription>() * descriptions.len();
+        self.description_buffer.write(descriptions.as_ptr() as *const std::ffi::c_void, size);
+    }
+
+    // the index of the description `write_selection` fills in
+    #[inline]
+    pub fn selection_description_index(&self) -> usize {
+        self.descriptions.len() - 1
+    }
+
     #[inline]
     pub fn texcoord_buffer(&self) -> &Arc<DedicatedStagingBuffer> {
         &self.texcoord_buffer
@@ -250,3 +269,14 @@ impl SceneStagingBuffers {
         &self.color_buffer
     }
 }
+
+fn with_selection_slot(descriptions: &[SceneMeshPrimitiveDescription], primitive_index: Option<usize>) -> Vec<SceneMeshPrimitiveDescription> {
+    let selection = primitive_index
+        .and_then(|v| descriptions.get(v))
+        .map(|v| v.selected())
+        .unwrap_or_default();
+    descriptions.iter()
+        .copied()
+        .chain(std::iter::once(selection))
+        .collect()
+}
@@ -0,0 +1,518 @@
+
+use nalgebra_glm as glm;
+
+use super::aabb::AABB;
+
+const BIN_COUNT: usize = 12;
+const MAX_LEAF_PRIMITIVES: usize = 4;
+// relative to intersecting a primitive
+const TRAVERSAL_COST: f32 = 1.0;
+
+// a bounding volume hierarchy over the bounds of arbitrary primitives, built with the binned
+// surface area heuristic. the primitives are referred to by their index in the slice it was
+// built from, and intersected by the callers.
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    // primitive indices, grouped by leaf
+    indices: Vec<u32>,
+}
+
+#[derive(Clone)]
+struct BvhNode {
+    bounds: AABB,
+    // the first index of a leaf, or the left child of an inner node whose right child follows it
+    offset: u32,
+    // zero for inner nodes
+    count: u32,
+}
+
+impl BvhNode {
+    #[inline]
+    fn is_leaf(&self) -> bool {
+        self.count > 0
+    }
+}
+
+struct BvhBin {
+    bounds: AABB,
+    count: usize,
+}
+
+impl Bvh {
+    pub fn new(bounds: &[AABB]) -> Self {
+        let centers: Vec<glm::Vec3> = bounds.iter()
+            .map(|v| v.center())
+            .collect();
+        let mut this = Self {
+            nodes: Vec::with_capacity(bounds.len().max(1) * 2),
+            indices: (0..bounds.len() as u32).collect(),
+        };
+        this.nodes.push(BvhNode {
+            bounds: AABB::empty(),
+            offset: 0,
+            count: bounds.len() as u32,
+        });
+        if !bounds.is_empty() {
+            this.subdivide(0, bounds, &centers);
+        }
+        this
+    }
+
+    pub fn primitive_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn bounds(&self) -> &AABB {
+        &self.nodes[0].bounds
+    }
+
+    // updates the node bounds after the primitives moved, keeping the hierarchy. the traversal slows
+    // down as the primitives drift away from where they were when it was built.
+    pub fn refit(&mut self, bounds: &[AABB]) {
+        assert_eq!(bounds.len(), self.indices.len());
+        // children always come after their parents
+        for index in (0..self.nodes.len()).rev() {
+            let node = &self.nodes[index];
+            let updated = if node.is_leaf() {
+                let range = node.offset as usize..(node.offset + node.count) as usize;
+                self.indices[range].iter()
+                    .fold(AABB::empty(), |acc, &v| acc.union(&bounds[v as usize]))
+            } else {
+                let left = node.offset as usize;
+                self.nodes[left].bounds.union(&self.nodes[left + 1].bounds)
+            };
+            self.nodes[index].bounds = updated;
+        }
+    }
+
+    // finds the closest primitive along the ray. `intersect` returns the distance to the primitive
+    // if the ray hits it within the given maximum.
+    pub fn closest<F>(&self, origin: &glm::Vec3, direction: &glm::Vec3, t_min: f32, t_max: f32, mut intersect: F) -> Option<(usize, f32)>
+    where
+        F: FnMut(usize, f32) -> Option<f32>
+    {
+        let mut closest: Option<(usize, f32)> = None;
+        self.traverse(origin, direction, t_min, t_max, |index, t_max| {
+            match intersect(index, t_max) {
+                Some(t) if t <= t_max => {
+                    closest = Some((index, t));
+                    (false, t)
+                },
+                _ => (false, t_max),
+            }
+        });
+        closest
+    }
+
+    // whether the ray hits any primitive, stopping at the first one
+    pub fn any<F>(&self, origin: &glm::Vec3, direction: &glm::Vec3, t_min: f32, t_max: f32, mut intersect: F) -> bool
+    where
+        F: FnMut(usize, f32) -> bool
+    {
+        let mut hit = false;
+        self.traverse(origin, direction, t_min, t_max, |index, t_max| {
+            hit = intersect(index, t_max);
+            (hit, t_max)
+        });
+        hit
+    }
+
+    // finds the primitive closest to the point. `distance` returns the distance from the point to
+    // the primitive, which the traversal uses to skip nodes farther than the closest one so far.
+    pub fn nearest<F>(&self, point: &glm::Vec3, max_distance: f32, mut distance: F) -> Option<(usize, f32)>
+    where
+        F: FnMut(usize) -> f32
+    {
+        if self.indices.is_empty() {
+            return None
+        }
+        let mut nearest: Option<(usize, f32)> = None;
+        let mut max_distance = max_distance;
+        let mut stack: Vec<usize> = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if distance_to_bounds(&node.bounds, point) > max_distance {
+                continue
+            }
+            if node.is_leaf() {
+                let range = node.offset as usize..(node.offset + node.count) as usize;
+                for &primitive in self.indices[range].iter() {
+                    let d = distance(primitive as usize);
+                    if d <= max_distance {
+                        max_distance = d;
+                        nearest = Some((primitive as usize, d));
+                    }
+                }
+                continue
+            }
+            // visits the nearer child first
+            let left = node.offset as usize;
+            let right = left + 1;
+            let left_distance = distance_to_bounds(&self.nodes[left].bounds, point);
+            let right_distance = distance_to_bounds(&self.nodes[right].bounds, point);
+            if left_distance < right_distance {
+                stack.push(right);
+                stack.push(left);
+            } else {
+                stack.push(left);
+                stack.push(right);
+            }
+        }
+        nearest
+    }
+
+    // `visit` receives the primitives of the leaves the ray enters, returning whether to stop and
+    // the maximum distance from then on
+    fn traverse<F>(&self, origin: &glm::Vec3, direction: &glm::Vec3, t_min: f32, t_max: f32, mut visit: F)
+    where
+        F: FnMut(usize, f32) -> (bool, f32)
+    {
+        if self.indices.is_empty() {
+            return
+        }
+        let inverse_direction = glm::vec3(1.0 / direction.x, 1.0 / direction.y, 1.0 / direction.z);
+        let mut t_max = t_max;
+        let mut stack: Vec<usize> = Vec::with_capacity(64);
+        if self.nodes[0].bounds.intersect(origin, &inverse_direction, t_min, t_max).is_none() {
+            return
+        }
+        stack.push(0);
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.is_leaf() {
+                let range = node.offset as usize..(node.offset + node.count) as usize;
+                for &primitive in self.indices[range].iter() {
+                    let (stop, updated) = visit(primitive as usize, t_max);
+                    if stop {
+                        return
+                    }
+                    t_max = updated;
+                }
+                continue
+            }
+            let left = node.offset as usize;
+            let right = left + 1;
+            let left_hit = self.nodes[left].bounds.intersect(origin, &inverse_direction, t_min, t_max);
+            let right_hit = self.nodes[right].bounds.intersect(origin, &inverse_direction, t_min, t_max);
+            // pushes the farther child first so that the nearer one is visited next
+            match (left_hit, right_hit) {
+                (Some((l, _)), Some((r, _))) => {
+                    if l < r {
+                        stack.push(right);
+                        stack.push(left);
+                    } else {
+                        stack.push(left);
+                        stack.push(right);
+                    }
+                },
+                (Some(_), None) => stack.push(left),
+                (None, Some(_)) => stack.push(right),
+                (None, None) => (),
+            }
+        }
+    }
+
+    fn subdivide(&mut self, index: usize, bounds: &[AABB], centers: &[glm::Vec3]) {
+        let (offset, count) = (self.nodes[index].offset as usize, self.nodes[index].count as usize);
+        let range = offset..offset + count;
+        let node_bounds = self.indices[range.clone()].iter()
+            .fold(AABB::empty(), |acc, &v| acc.union(&bounds[v as usize]));
+        self.nodes[index].bounds = node_bounds;
+        if count <= MAX_LEAF_PRIMITIVES {
+            return
+        }
+        let center_bounds = self.indices[range.clone()].iter()
+            .fold(AABB::empty(), |mut acc, &v| { acc.grow(&centers[v as usize]); acc });
+        let split = match Self::find_split(&self.indices[range.clone()], bounds, centers, &center_bounds) {
+            Some(v) => v,
+            None => return,
+        };
+        let (axis, position, cost) = split;
+        // keeps the primitives together when splitting costs more than intersecting them all
+        let leaf_cost = count as f32;
+        let split_cost = TRAVERSAL_COST + cost / node_bounds.surface_area().max(f32::EPSILON);
+        if split_cost >= leaf_cost {
+            return
+        }
+        // partitions the primitives in place
+        let indices = &mut self.indices[range];
+        let mut i = 0;
+        let mut j = indices.len();
+        while i < j {
+            if centers[indices[i] as usize][axis] < position {
+                i += 1;
+            } else {
+                j -= 1;
+                indices.swap(i, j);
+            }
+        }
+        let left_count = i;
+        if left_count == 0 || left_count == count {
+            return
+        }
+        let left = self.nodes.len();
+        self.nodes.push(BvhNode { bounds: AABB::empty(), offset: offset as u32, count: left_count as u32 });
+        self.nodes.push(BvhNode { bounds: AABB::empty(), offset: (offset + left_count) as u32, count: (count - left_count) as u32 });
+        self.nodes[index].offset = left as u32;
+        self.nodes[index].count = 0;
+        self.subdivide(left, bounds, centers);
+        self.subdivide(left + 1, bounds, centers);
+    }
+
+    // returns the axis, the position and the unnormalized SAH cost of the cheapest bin boundary
+    fn find_split(indices: &[u32], bounds: &[AABB], centers: &[glm::Vec3], center_bounds: &AABB) -> Option<(usize, f32, f32)> {
+        let mut best: Option<(usize, f32, f32)> = None;
+        let axis_bounds = center_bounds.min().iter().zip(center_bounds.max().iter());
+        for (axis, (&min, &max)) in axis_bounds.enumerate() {
+            if max <= min {
+                continue
+            }
+            let scale = BIN_COUNT as f32 / (max - min);
+            let mut bins: Vec<BvhBin> = (0..BIN_COUNT)
+                .map(|_| BvhBin { bounds: AABB::empty(), count: 0 })
+                .collect();
+            for &index in indices.iter() {
+                let bin = (((centers[index as usize][axis] - min) * scale) as usize).min(BIN_COUNT - 1);
+                bins[bin].count += 1;
+                bins[bin].bounds = bins[bin].bounds.union(&bounds[index as usize]);
+            }
+            // sweeps from both sides to evaluate every boundary between the bins
+            let mut right_areas = [0.0f32; BIN_COUNT];
+            let mut right_counts = [0usize; BIN_COUNT];
+            let mut accumulated = AABB::empty();
+            let mut count = 0;
+            for i in (1..BIN_COUNT).rev() {
+                accumulated = accumulated.union(&bins[i].bounds);
+                count += bins[i].count;
+                right_areas[i] = accumulated.surface_area();
+                right_counts[i] = count;
+            }
+            let mut accumulated = AABB::empty();
+            let mut count = 0;
+            for i in 0..BIN_COUNT - 1 {
+                accumulated = accumulated.union(&bins[i].bounds);
+                count += bins[i].count;
+                let cost = accumulated.surface_area() * count as f32
+                    + right_areas[i + 1] * right_counts[i + 1] as f32;
+                if best.map_or(true, |(_, _, v)| cost < v) {
+                    let position = min + (i + 1) as f32 / scale;
+                    best = Some((axis, position, cost));
+                }
+            }
+        }
+        best
+    }
+}
+
+fn distance_to_bounds(bounds: &AABB, point: &glm::Vec3) -> f32 {
+    let clamped = glm::clamp_vec(point, bounds.min(), bounds.max());
+    glm::distance(&clamped, point)
+}
+
+// returns the distance and the barycentrics of the second and third vertices, hitting both faces
+// @see https://en.wikipedia.org/wiki/M%C3%B6ller%E2%80%93Trumbore_intersection_algorithm
+pub(super) fn intersect_triangle(vertices: &[glm::Vec3; 3], origin: &glm::Vec3, direction: &glm::Vec3, t_min: f32, t_max: f32) -> Option<(f32, f32, f32)> {
+    let edge1 = vertices[1] - vertices[0];
+    let edge2 = vertices[2] - vertices[0];
+    let p = glm::cross(direction, &edge2);
+    let determinant = glm::dot(&edge1, &p);
+    if determinant.abs() < 1e-12 {
+        return None
+    }
+    let inverse = 1.0 / determinant;
+    let s = origin - vertices[0];
+    let u = glm::dot(&s, &p) * inverse;
+    if !(0.0..=1.0).contains(&u) {
+        return None
+    }
+    let q = glm::cross(&s, &edge1);
+    let v = glm::dot(direction, &q) * inverse;
+    if v < 0.0 || u + v > 1.0 {
+        return None
+    }
+    let t = glm::dot(&edge2, &q) * inverse;
+    if t < t_min || t > t_max {
+        return None
+    }
+    Some((t, u, v))
+}
+
+// returns the barycentrics of the second and third vertices at the point on the triangle closest
+// to `point`
+// @see Real-Time Collision Detection, 5.1.5 Closest Point on Triangle to Point
+pub(super) fn closest_point_on_triangle(vertices: &[glm::Vec3; 3], point: &glm::Vec3) -> (f32, f32) {
+    let [a, b, c] = vertices;
+    let ab = b - a;
+    let ac = c - a;
+    let ap = point - a;
+    let d1 = glm::dot(&ab, &ap);
+    let d2 = glm::dot(&ac, &ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return (0.0, 0.0)
+    }
+    let bp = point - b;
+    let d3 = glm::dot(&ab, &bp);
+    let d4 = glm::dot(&ac, &bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return (1.0, 0.0)
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return (d1 / (d1 - d3), 0.0)
+    }
+    let cp = point - c;
+    let d5 = glm::dot(&ab, &cp);
+    let d6 = glm::dot(&ac, &cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return (0.0, 1.0)
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return (0.0, d2 / (d2 - d6))
+    }
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+        let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+        return (1.0 - w, w)
+    }
+    let denominator = 1.0 / (va + vb + vc);
+    (vb * denominator, vc * denominator)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::Xorshift64;
+
+    fn triangles(rng: &mut Xorshift64, count: usize) -> Vec<[glm::Vec3; 3]> {
+        let mut point = |scale: f32| glm::vec3(rng.next_uniform_f32(), rng.next_uniform_f32(), rng.next_uniform_f32()) * scale;
+        (0..count)
+            .map(|_| {
+                let center = point(20.0) - glm::vec3(10.0, 10.0, 10.0);
+                [center + point(2.0), center + point(2.0), center + point(2.0)]
+            })
+            .collect()
+    }
+
+    fn bounds(triangles: &[[glm::Vec3; 3]]) -> Vec<AABB> {
+        triangles.iter()
+            .map(|v| AABB::from_points(v))
+            .collect()
+    }
+
+    fn rays(rng: &mut Xorshift64, count: usize) -> Vec<(glm::Vec3, glm::Vec3)> {
+        let mut point = || glm::vec3(rng.next_uniform_f32(), rng.next_uniform_f32(), rng.next_uniform_f32()) * 30.0 - glm::vec3(15.0, 15.0, 15.0);
+        (0..count)
+            .map(|_| {
+                let origin = point();
+                let direction = glm::normalize(&(point() - origin));
+                (origin, direction)
+            })
+            .collect()
+    }
+
+    fn brute_force_closest(triangles: &[[glm::Vec3; 3]], origin: &glm::Vec3, direction: &glm::Vec3) -> Option<(usize, f32)> {
+        triangles.iter()
+            .enumerate()
+            .filter_map(|(index, v)| intersect_triangle(v, origin, direction, 0.0, 100.0).map(|(t, _, _)| (index, t)))
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+    }
+
+    fn assert_closest_matches(bvh: &Bvh, triangles: &[[glm::Vec3; 3]], rays: &[(glm::Vec3, glm::Vec3)]) {
+        let mut hits = 0;
+        for (origin, direction) in rays.iter() {
+            let closest = bvh.closest(origin, direction, 0.0, 100.0, |index, t_max| {
+                intersect_triangle(&triangles[index], origin, direction, 0.0, t_max).map(|(t, _, _)| t)
+            });
+            let expected = brute_force_closest(triangles, origin, direction);
+            assert_eq!(closest.map(|v| v.0), expected.map(|v| v.0));
+            if let (Some((_, t)), Some((_, expected))) = (closest, expected) {
+                assert!((t - expected).abs() < 1e-5);
+                hits += 1;
+            }
+            let any = bvh.any(origin, direction, 0.0, 100.0, |index, t_max| {
+                intersect_triangle(&triangles[index], origin, direction, 0.0, t_max).is_some()
+            });
+            assert_eq!(any, expected.is_some());
+        }
+        // the rays are random, but some must hit for the comparison to mean anything
+        assert!(hits > 0);
+    }
+
+    #[test]
+    fn closest_and_any_match_brute_force() {
+        let mut rng = Xorshift64::with_seed(7);
+        let triangles = triangles(&mut rng, 500);
+        let bvh = Bvh::new(&bounds(&triangles));
+        assert_eq!(bvh.primitive_count(), 500);
+        let rays = rays(&mut rng, 500);
+        assert_closest_matches(&bvh, &triangles, &rays);
+    }
+
+    #[test]
+    fn nearest_matches_brute_force() {
+        let mut rng = Xorshift64::with_seed(11);
+        let triangles = triangles(&mut rng, 300);
+        let bvh = Bvh::new(&bounds(&triangles));
+        let distance = |index: usize, point: &glm::Vec3| {
+            let [a, b, c] = &triangles[index];
+            let (u, v) = closest_point_on_triangle(&triangles[index], point);
+            glm::distance(&(a * (1.0 - u - v) + b * u + c * v), point)
+        };
+        for (point, _) in rays(&mut rng, 200).iter() {
+            let (index, d) = bvh.nearest(point, f32::MAX, |index| distance(index, point)).unwrap();
+            let expected = (0..triangles.len())
+                .map(|index| distance(index, point))
+                .fold(f32::MAX, f32::min);
+            assert!((d - expected).abs() < 1e-5);
+            assert!((distance(index, point) - expected).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn refit_follows_moved_primitives() {
+        let mut rng = Xorshift64::with_seed(13);
+        let mut triangles = triangles(&mut rng, 200);
+        let mut bvh = Bvh::new(&bounds(&triangles));
+        let offset = glm::vec3(3.0, -2.0, 5.0);
+        for (index, triangle) in triangles.iter_mut().enumerate() {
+            // moves every other one so that the hierarchy no longer fits the original layout
+            if index % 2 == 0 {
+                for vertex in triangle.iter_mut() {
+                    *vertex += offset;
+                }
+            }
+        }
+        bvh.refit(&bounds(&triangles));
+        let expected = bounds(&triangles).iter()
+            .fold(AABB::empty(), |acc, v| acc.union(v));
+        assert_eq!(bvh.bounds().min(), expected.min());
+        assert_eq!(bvh.bounds().max(), expected.max());
+        let rays = rays(&mut rng, 500);
+        assert_closest_matches(&bvh, &triangles, &rays);
+    }
+
+    #[test]
+    fn empty_hierarchy_misses() {
+        let bvh = Bvh::new(&[]);
+        let origin = glm::vec3(0.0, 0.0, 0.0);
+        let direction = glm::vec3(0.0, 0.0, -1.0);
+        assert!(bvh.closest(&origin, &direction, 0.0, 100.0, |_, _| Some(1.0)).is_none());
+        assert!(!bvh.any(&origin, &direction, 0.0, 100.0, |_, _| true));
+        assert!(bvh.nearest(&origin, f32::MAX, |_| 0.0).is_none());
+    }
+
+    #[test]
+    fn intersect_triangle_returns_barycentrics() {
+        let vertices = [glm::vec3(0.0, 0.0, -2.0), glm::vec3(1.0, 0.0, -2.0), glm::vec3(0.0, 1.0, -2.0)];
+        let origin = glm::vec3(0.25, 0.5, 0.0);
+        let (t, u, v) = intersect_triangle(&vertices, &origin, &glm::vec3(0.0, 0.0, -1.0), 0.0, 100.0).unwrap();
+        assert!((t - 2.0).abs() < 1e-6);
+        assert!((u - 0.25).abs() < 1e-6);
+        assert!((v - 0.5).abs() < 1e-6);
+        // both faces are hit
+        assert!(intersect_triangle(&vertices, &glm::vec3(0.25, 0.5, -4.0), &glm::vec3(0.0, 0.0, 1.0), 0.0, 100.0).is_some());
+        // and the distance range is respected
+        assert!(intersect_triangle(&vertices, &origin, &glm::vec3(0.0, 0.0, -1.0), 0.0, 1.0).is_none());
+    }
+}
@@ -0,0 +1,992 @@
+
+use nalgebra_glm as glm;
+
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use crate::vk::Result;
+use crate::vk::*;
+use crate::ffi::vk::*;
+
+use super::asset::*;
+use super::custom::{srgb_to_linear, linear_to_srgb};
+use super::image_provider::ImageProvider;
+use super::material::*;
+use super::mesh::*;
+use super::primitive::Primitive;
+use super::scene::{Scene, SceneBuildOptions};
+use super::resolver::referenced_files;
+
+const CACHE_MAGIC: &[u8; 8] = b"KLDRSCN\0";
+// detects caches written on a host with the other byte order
+const CACHE_BYTE_ORDER: u32 = 0x01020304;
+
+// bump whenever the loaders or the layout below change so that stale caches are rebuilt
+pub const SCENE_CACHE_LOADER_VERSION: u32 = 3;
+
+const PRIMITIVE_OPAQUE: u32 = 1;
+const PRIMITIVE_TANGENTS: u32 = 2;
+const PRIMITIVE_COLORS: u32 = 4;
+
+// identifies the asset a cache was made from, together with the external buffers and images
+// it references, so that replacing any of them invalidates the cache.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SceneCacheKey {
+    path: String,
+    modified_secs: u64,
+    modified_nanos: u32,
+    loader_version: u32,
+    external_files: Vec<SceneCacheFile>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct SceneCacheFile {
+    path: String,
+    modified_secs: u64,
+    modified_nanos: u32,
+    size: u64,
+}
+
+impl SceneCacheFile {
+    fn new(path: &Path) -> Result<Self> {
+        let with_path = |e: std::io::Error| Error::from(e).with_path(path);
+        let metadata = std::fs::metadata(path)
+            .map_err(with_path)?;
+        let modified = metadata.modified()
+            .map_err(with_path)?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let file = Self {
+            path: path.to_string_lossy().into_owned(),
+            modified_secs: modified.as_secs(),
+            modified_nanos: modified.subsec_nanos(),
+            size: metadata.len(),
+        };
+        Ok(file)
+    }
+}
+
+impl SceneCacheKey {
+    pub fn new<P>(path: P) -> Result<Self> where P: AsRef<Path> {
+        let path = path.as_ref();
+        let with_path = |e: std::io::Error| Error::from(e).with_path(path);
+        let canonical = std::fs::canonicalize(path)
+            .map_err(with_path)?;
+        let asset_file = SceneCacheFile::new(path)?;
+        // only the document is parsed; the buffers and images are not loaded
+        let data = std::fs::read(path)
+            .map_err(with_path)?;
+        let gltf = gltf::Gltf::from_slice(&data)
+            .map_err(|e| Error::from(e).with_path(path))?;
+        let external_files = referenced_files(path, &gltf.document)
+            .iter()
+            .skip(1)
+            .map(|v| SceneCacheFile::new(v))
+            .collect::<Result<Vec<_>>>()?;
+        let key = Self {
+            path: canonical.to_string_lossy().into_owned(),
+            modified_secs: asset_file.modified_secs,
+            modified_nanos: asset_file.modified_nanos,
+            loader_version: SCENE_CACHE_LOADER_VERSION,
+            external_files,
+        };
+        Ok(key)
+    }
+}
+
+// the packed streams, descriptions and mip levels of a glTF scene.
+// loading it skips parsing, attribute conversion, image decoding and mipmap generation.
+pub struct SceneCache {
+    key: SceneCacheKey,
+    indices: Vec<u32>,
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    texcoords: Vec<[f32; 2]>,
+    tangents: Vec<[f32; 4]>,
+    colors: Vec<[f32; 4]>,
+    primitives: Vec<CachedPrimitive>,
+    instances: Vec<CachedInstance>,
+    materials: Vec<CachedMaterial>,
+    textures: Vec<Arc<CachedTexture>>,
+}
+
+impl SceneCache {
+    pub fn from_asset<P>(path: P, asset: &SceneAsset) -> Result<Self> where P: AsRef<Path> {
+        log_debug!("creating scene cache");
+        let key = SceneCacheKey::new(path)?;
+        let mut cache = Self {
+            key,
+            indices: vec![],
+            positions: vec![],
+            normals: vec![],
+            texcoords: vec![],
+            tangents: vec![],
+            colors: vec![],
+            primitives: vec![],
+            instances: vec![],
+            materials: vec![],
+            textures: vec![],
+        };
+        let table = MeshTable::new(asset)?;
+        for mesh_primitive in table.mesh_primitives().iter() {
+            cache.add_primitive(mesh_primitive);
+        }
+        // the colors are stored only when any primitive has them, like the staging buffers
+        if !cache.colors.is_empty() {
+            cache.colors.resize(cache.positions.len(), [0.0; 4]);
+        }
+        let scene = asset.document()
+            .default_scene()
+            .or_else(|| asset.document().scenes().nth(0))
+            .ok_or(ErrorCode::SceneNotFound)?;
+        cache.instances = scene.nodes()
+            .flat_map(FlattenNode::flatten)
+            .filter_map(|v| MeshNode::new(v, &table))
+            .flatten()
+            .map(|v| {
+                let mut transform = [0.0f32; 16];
+                transform.copy_from_slice(v.transform().as_slice());
+                CachedInstance {
+                    primitive_index: v.primitive().index() as u32,
+                    transform,
+                    name: v.name().map(|v| v.to_owned()),
+                }
+            })
+            .collect();
+        let image_provider = ImageProvider::new(asset);
+        let mut textures: HashMap<(usize, bool), Arc<CachedTexture>> = HashMap::new();
+        for material in asset.document().materials() {
+            let sources = MaterialImageSources::new(&material);
+            let mut texture = |image_index: Option<usize>, srgb: bool| -> Result<Option<Arc<CachedTexture>>> {
+                let image_index = match image_index {
+                    Some(v) => v,
+                    None => return Ok(None),
+                };
+                if let Some(texture) = textures.get(&(image_index, srgb)) {
+                    return Ok(Some(Arc::clone(texture)));
+                }
+                let data = image_provider.image(image_index)?
+                    .ok_or_else(|| Error::from(ErrorCode::ImageNotFound).with_image(image_index))?;
+                let pixels = MaterialImagePixels::new(&data)
+                    .ok_or_else(|| Error::from(ErrorCode::ImageFormatInvalid).with_image(image_index))?;
+                let texture = Arc::new(CachedTexture::new(pixels.pixels(), data.width, data.height, srgb));
+                textures.insert((image_index, srgb), Arc::clone(&texture));
+                Ok(Some(texture))
+            };
+            let material = CachedMaterial {
+                name: material.name().map(|v| v.to_owned()),
+                color_texture: texture(sources.color_image_index, true)?,
+                normal_texture: texture(sources.normal_image_index, false)?,
+            };
+            cache.materials.push(material);
+        }
+        // sorted so that the same asset always produces the same file
+        let mut textures: Vec<_> = textures.into_iter().collect();
+        textures.sort_by_key(|(key, _)| *key);
+        cache.textures = textures.into_iter()
+            .map(|(_, v)| v)
+            .collect();
+        log_debug!("creating scene cache complete");
+        Ok(cache)
+    }
+
+    // returns `None` when the cache does not exist or was made from another version of the asset
+    pub fn load<P, Q>(cache_path: P, asset_path: Q) -> Result<Option<Self>>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let cache_path = cache_path.as_ref();
+        let key = SceneCacheKey::new(asset_path)?;
+        let data = match std::fs::read(cache_path) {
+            Ok(v) => v,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Error::from(e).with_path(cache_path)),
+        };
+        log_debug!("loading scene cache");
+        let cache = CacheReader::new(&data).read_cache(&key)
+            .map_err(|e| e.with_path(cache_path))?;
+        log_debug!("loading scene cache complete");
+        Ok(cache)
+    }
+
+    // rebuilds the cache from the asset when it is missing, stale or broken.
+    // failing to write the cache does not fail the load.
+    pub fn load_or_create<P, Q>(asset_path: P, cache_path: Q) -> Result<Self>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let asset_path = asset_path.as_ref();
+        let cache_path = cache_path.as_ref();
+        match Self::load(cache_path, asset_path) {
+            Ok(Some(cache)) => return Ok(cache),
+            Ok(None) => {
+                log_debug!("scene cache not found {}", cache_path.display());
+            },
+            Err(e) => {
+                log_debug!("scene cache discarded: {}", error_chain(&e));
+            },
+        }
+        let asset = SceneAsset::new(asset_path)?;
+        let cache = Self::from_asset(asset_path, &asset)?;
+        if let Err(e) = cache.save(cache_path) {
+            log_debug!("scene cache not saved: {}", error_chain(&e));
+        }
+        Ok(cache)
+    }
+
+    // a file in the temporary directory named after the asset path
+    pub fn default_path<P>(asset_path: P) -> PathBuf where P: AsRef<Path> {
+        let path = asset_path.as_ref();
+        let path = std::fs::canonicalize(path)
+            .unwrap_or_else(|_| path.to_owned());
+        let mut hasher = DefaultHasher::new();
+        path.hash(&mut hasher);
+        std::env::temp_dir()
+            .join("kaldera")
+            .join(format!("{:016x}.scene", hasher.finish()))
+    }
+
+    // writes into a temporary file first so that readers never see a partial cache
+    pub fn save<P>(&self, path: P) -> Result<()> where P: AsRef<Path> {
+        let path = path.as_ref();
+        let with_path = |e: std::io::Error| Error::from(e).with_path(path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(with_path)?;
+        }
+        let temporary_path = path.with_extension("partial");
+        {
+            let file = File::create(&temporary_path)
+                .map_err(with_path)?;
+            let mut writer = BufWriter::new(file);
+            self.write(&mut writer)
+                .and_then(|_| writer.flush())
+                .map_err(with_path)?;
+        }
+        std::fs::rename(&temporary_path, path)
+            .map_err(with_path)?;
+        Ok(())
+    }
+
+    pub fn key(&self) -> &SceneCacheKey {
+        &self.key
+    }
+
+    pub(super) fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    pub(super) fn positions(&self) -> &[[f32; 3]] {
+        &self.positions
+    }
+
+    pub(super) fn normals(&self) -> &[[f32; 3]] {
+        &self.normals
+    }
+
+    pub(super) fn texcoords(&self) -> &[[f32; 2]] {
+        &self.texcoords
+    }
+
+    pub(super) fn tangents(&self) -> &[[f32; 4]] {
+        &self.tangents
+    }
+
+    pub(super) fn colors(&self) -> &[[f32; 4]] {
+        &self.colors
+    }
+
+    pub(super) fn primitives(&self) -> &[CachedPrimitive] {
+        &self.primitives
+    }
+
+    pub(super) fn instances(&self) -> &[CachedInstance] {
+        &self.instances
+    }
+
+    pub(super) fn materials(&self) -> &[CachedMaterial] {
+        &self.materials
+    }
+
+    pub fn build(&self, command_pool: &Arc<CommandPool>) -> Result<Scene> {
+        self.build_with_options(command_pool, &SceneBuildOptions::new())
+    }
+
+    pub fn build_with_options(&self, command_pool: &Arc<CommandPool>, options: &SceneBuildOptions) -> Result<Scene> {
+        log_debug!("start scene cache builder");
+        let primitives: Vec<_> = self.primitives.iter()
+            .map(|v| {
+                let vertices = v.vertex_offset as usize..(v.vertex_offset + v.vertex_count) as usize;
+                let indices = v.index_offset as usize..(v.index_offset + v.index_count) as usize;
+                let tangents = if v.flags & PRIMITIVE_TANGENTS != 0 {
+                    Some(&self.tangents[vertices.clone()])
+                } else {
+                    None
+                };
+                let colors = if v.flags & PRIMITIVE_COLORS != 0 {
+                    Some(&self.colors[vertices.clone()])
+                } else {
+                    None
+                };
+                let material_index = if v.material_index >= 0 {
+                    Some(v.material_index as usize)
+                } else {
+                    None
+                };
+                let primitive = Primitive::from_slices(
+                    &self.indices[indices],
+                    &self.positions[vertices.clone()],
+                    &self.normals[vertices.clone()],
+                    &self.texcoords[vertices],
+                    material_index,
+                    v.flags & PRIMITIVE_OPAQUE != 0)
+                    .with_tangent_slice(tangents)
+                    .with_color_slice(colors);
+                (v.mesh_index as usize, primitive)
+            })
+            .collect();
+        let table = MeshTable::from_primitives(primitives);
+        let nodes: Vec<_> = self.instances.iter()
+            .filter_map(|v| MeshNode::from_primitive(v.primitive_index as usize, &glm::make_mat4(&v.transform), &table)
+                .map(|node| node.with_name(v.name.clone())))
+            .collect();
+        let materials: Vec<_> = self.materials.iter()
+            .map(Material::cached)
+            .collect();
+        let image_provider = ImageProvider::empty();
+        Scene::new(&[], &table, &nodes, &materials, &image_provider, options, command_pool)
+    }
+
+    fn add_primitive(&mut self, mesh_primitive: &MeshPrimitive) {
+        let primitive = mesh_primitive.primitive();
+        let vertex_offset = self.positions.len();
+        let index_offset = self.indices.len();
+        let vertex_count = primitive.positions().count();
+        let mut flags = 0u32;
+        if primitive.is_opaque() {
+            flags |= PRIMITIVE_OPAQUE;
+        }
+        unsafe {
+            extend_from_raw(&mut self.indices, primitive.indices().data(), primitive.indices().count());
+            extend_from_raw(&mut self.positions, primitive.positions().data(), vertex_count);
+            extend_from_raw(&mut self.normals, primitive.normals().data(), vertex_count);
+            extend_from_raw(&mut self.texcoords, primitive.texcoords().data(), vertex_count);
+            match primitive.tangents() {
+                Some(tangents) => {
+                    flags |= PRIMITIVE_TANGENTS;
+                    extend_from_raw(&mut self.tangents, tangents.data(), vertex_count);
+                },
+                None => self.tangents.resize(vertex_offset + vertex_count, [0.0; 4]),
+            }
+            if let Some(colors) = primitive.colors() {
+                flags |= PRIMITIVE_COLORS;
+                self.colors.resize(vertex_offset, [0.0; 4]);
+                extend_from_raw(&mut self.colors, colors.data(), vertex_count);
+            }
+        }
+        let cached = CachedPrimitive {
+            mesh_index: mesh_primitive.mesh_index() as u32,
+            vertex_offset: vertex_offset as u32,
+            vertex_count: vertex_count as u32,
+            index_offset: index_offset as u32,
+            index_count: primitive.indices().count() as u32,
+            material_index: primitive.material_index().map_or(-1, |v| v as i32),
+            flags,
+        };
+        self.primitives.push(cached);
+    }
+
+    fn write(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        writer.write_all(CACHE_MAGIC)?;
+        write_u32(writer, CACHE_BYTE_ORDER)?;
+        write_u32(writer, self.key.loader_version)?;
+        write_string(writer, &self.key.path)?;
+        write_u64(writer, self.key.modified_secs)?;
+        write_u32(writer, self.key.modified_nanos)?;
+        write_u32(writer, self.key.external_files.len() as u32)?;
+        for v in self.key.external_files.iter() {
+            write_string(writer, &v.path)?;
+            write_u64(writer, v.modified_secs)?;
+            write_u32(writer, v.modified_nanos)?;
+            write_u64(writer, v.size)?;
+        }
+        write_slice(writer, &self.indices)?;
+        write_slice(writer, &self.positions)?;
+        write_slice(writer, &self.normals)?;
+        write_slice(writer, &self.texcoords)?;
+        write_slice(writer, &self.tangents)?;
+        write_slice(writer, &self.colors)?;
+        write_u32(writer, self.primitives.len() as u32)?;
+        for v in self.primitives.iter() {
+            write_u32(writer, v.mesh_index)?;
+            write_u32(writer, v.vertex_offset)?;
+            write_u32(writer, v.vertex_count)?;
+            write_u32(writer, v.index_offset)?;
+            write_u32(writer, v.index_count)?;
+            write_u32(writer, v.material_index as u32)?;
+            write_u32(writer, v.flags)?;
+        }
+        write_u32(writer, self.instances.len() as u32)?;
+        for v in self.instances.iter() {
+            write_u32(writer, v.primitive_index)?;
+            write_slice(writer, &v.transform)?;
+            write_string(writer, v.name.as_deref().unwrap_or(""))?;
+        }
+        write_u32(writer, self.textures.len() as u32)?;
+        for v in self.textures.iter() {
+            write_u32(writer, v.width)?;
+            write_u32(writer, v.height)?;
+            write_u32(writer, v.srgb as u32)?;
+            write_slice(writer, &v.levels)?;
+        }
+        let texture_index = |texture: &Option<Arc<CachedTexture>>| -> u32 {
+            texture.as_ref()
+                .and_then(|texture| self.textures.iter().position(|v| Arc::ptr_eq(v, texture)))
+                .map_or(u32::MAX, |v| v as u32)
+        };
+        write_u32(writer, self.materials.len() as u32)?;
+        for v in self.materials.iter() {
+            write_string(writer, v.name.as_deref().unwrap_or(""))?;
+            write_u32(writer, texture_index(&v.color_texture))?;
+            write_u32(writer, texture_index(&v.normal_texture))?;
+        }
+        Ok(())
+    }
+}
+
+pub(super) struct CachedPrimitive {
+    mesh_index: u32,
+    pub(super) vertex_offset: u32,
+    pub(super) vertex_count: u32,
+    pub(super) index_offset: u32,
+    pub(super) index_count: u32,
+    pub(super) material_index: i32,
+    flags: u32,
+}
+
+impl CachedPrimitive {
+    pub(super) fn is_opaque(&self) -> bool {
+        self.flags & PRIMITIVE_OPAQUE != 0
+    }
+
+    pub(super) fn has_tangents(&self) -> bool {
+        self.flags & PRIMITIVE_TANGENTS != 0
+    }
+
+    pub(super) fn has_colors(&self) -> bool {
+        self.flags & PRIMITIVE_COLORS != 0
+    }
+}
+
+pub(super) struct CachedInstance {
+    pub(super) primitive_index: u32,
+    // column-major
+    pub(super) transform: [f32; 16],
+    // of the node
+    pub(super) name: Option<String>,
+}
+
+pub struct CachedMaterial {
+    name: Option<String>,
+    color_texture: Option<Arc<CachedTexture>>,
+    normal_texture: Option<Arc<CachedTexture>>,
+}
+
+impl CachedMaterial {
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn color_texture(&self) -> Option<&Arc<CachedTexture>> {
+        self.color_texture.as_ref()
+    }
+
+    pub fn normal_texture(&self) -> Option<&Arc<CachedTexture>> {
+        self.normal_texture.as_ref()
+    }
+}
+
+// RGBA8 texels of every mip level that `TextureImage::new_premipped` expects
+pub struct CachedTexture {
+    width: u32,
+    height: u32,
+    srgb: bool,
+    levels: Vec<u8>,
+}
+
+impl CachedTexture {
+    pub(super) fn new(pixels: &[u8], width: u32, height: u32, srgb: bool) -> Self {
+        let extent = VkExtent3D {
+            width,
+            height,
+            depth: 1,
+        };
+        let mip_levels = TextureImage::mip_levels_for(extent);
+        let mut levels = pixels.to_vec();
+        let mut offset = 0;
+        for level in 1..mip_levels {
+            let src_width = (width >> (level - 1)).max(1);
+            let src_height = (height >> (level - 1)).max(1);
+            let src_size = src_width as usize * src_height as usize * 4;
+            let dst = downsample(&levels[offset..offset + src_size], src_width, src_height, srgb);
+            offset += src_size;
+            levels.extend_from_slice(&dst);
+        }
+        Self {
+            width,
+            height,
+            srgb,
+            levels,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn levels(&self) -> &Vec<u8> {
+        &self.levels
+    }
+
+    pub fn is_srgb(&self) -> bool {
+        self.srgb
+    }
+}
+
+// averages 2x2 texels. sRGB colors are averaged in linear space like the device blit does.
+fn downsample(src: &[u8], width: u32, height: u32, srgb: bool) -> Vec<u8> {
+    let dst_width = (width >> 1).max(1) as usize;
+    let dst_height = (height >> 1).max(1) as usize;
+    let width = width as usize;
+    let height = height as usize;
+    let to_linear: Vec<f32> = (0..256)
+        .map(|v| {
+            let v = v as f32 / 255.0;
+            if srgb { srgb_to_linear(v) } else { v }
+        })
+        .collect();
+    let from_linear = |v: f32| -> u8 {
+        let v = if srgb { linear_to_srgb(v) } else { v };
+        (v.clamp(0.0, 1.0) * 255.0 + 0.5) as u8
+    };
+    let mut dst = Vec::with_capacity(dst_width * dst_height * 4);
+    for y in 0..dst_height {
+        let y0 = (y * 2).min(height - 1);
+        let y1 = (y * 2 + 1).min(height - 1);
+        for x in 0..dst_width {
+            let x0 = (x * 2).min(width - 1);
+            let x1 = (x * 2 + 1).min(width - 1);
+            let texels = [(x0, y0), (x1, y0), (x0, y1), (x1, y1)];
+            for channel in 0..4 {
+                let sum: f32 = texels.iter()
+                    .map(|&(x, y)| src[(y * width + x) * 4 + channel])
+                    .map(|v| if channel == 3 { v as f32 / 255.0 } else { to_linear[v as usize] })
+                    .sum();
+                let average = sum / 4.0;
+                if channel == 3 {
+                    dst.push((average.clamp(0.0, 1.0) * 255.0 + 0.5) as u8);
+                } else {
+                    dst.push(from_linear(average));
+                }
+            }
+        }
+    }
+    dst
+}
+
+// copies without assuming the alignment of the source such as glTF buffer views
+unsafe fn extend_from_raw<T: Copy>(values: &mut Vec<T>, data: *const u8, count: usize) {
+    values.reserve(count);
+    let len = values.len();
+    std::ptr::copy_nonoverlapping(data, values.as_mut_ptr().add(len) as *mut u8, count * std::mem::size_of::<T>());
+    values.set_len(len + count);
+}
+
+fn write_u32(writer: &mut impl Write, value: u32) -> std::io::Result<()> {
+    writer.write_all(&value.to_ne_bytes())
+}
+
+fn write_u64(writer: &mut impl Write, value: u64) -> std::io::Result<()> {
+    writer.write_all(&value.to_ne_bytes())
+}
+
+fn write_string(writer: &mut impl Write, value: &str) -> std::io::Result<()> {
+    write_slice(writer, value.as_bytes())
+}
+
+// the element count followed by the bytes as they are in memory
+fn write_slice<T: Copy>(writer: &mut impl Write, values: &[T]) -> std::io::Result<()> {
+    write_u64(writer, values.len() as u64)?;
+    let bytes = unsafe {
+        std::slice::from_raw_parts(values.as_ptr() as *const u8, std::mem::size_of_val(values))
+    };
+    writer.write_all(bytes)
+}
+
+struct CacheReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> CacheReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            offset: 0,
+        }
+    }
+
+    fn read_cache(&mut self, key: &SceneCacheKey) -> Result<Option<SceneCache>> {
+        if self.read_bytes(CACHE_MAGIC.len())? != CACHE_MAGIC
+            || self.read_u32()? != CACHE_BYTE_ORDER
+            || self.read_u32()? != key.loader_version {
+            return Ok(None);
+        }
+        let cached_key = SceneCacheKey {
+            loader_version: key.loader_version,
+            path: self.read_string()?,
+            modified_secs: self.read_u64()?,
+            modified_nanos: self.read_u32()?,
+            external_files: self.read_external_files()?,
+        };
+        if &cached_key != key {
+            return Ok(None);
+        }
+        let indices = self.read_vec()?;
+        let positions: Vec<[f32; 3]> = self.read_vec()?;
+        let normals = self.read_vec()?;
+        let texcoords = self.read_vec()?;
+        let tangents = self.read_vec()?;
+        let colors = self.read_vec()?;
+        let num_primitives = self.read_u32()?;
+        let primitives = (0..num_primitives)
+            .map(|_| {
+                let primitive = CachedPrimitive {
+                    mesh_index: self.read_u32()?,
+                    vertex_offset: self.read_u32()?,
+                    vertex_count: self.read_u32()?,
+                    index_offset: self.read_u32()?,
+                    index_count: self.read_u32()?,
+                    material_index: self.read_u32()? as i32,
+                    flags: self.read_u32()?,
+                };
+                Ok(primitive)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let num_instances = self.read_u32()?;
+        let instances = (0..num_instances)
+            .map(|_| {
+                let primitive_index = self.read_u32()?;
+                let values: Vec<f32> = self.read_vec()?;
+                if values.len() != 16 {
+                    return Err(ErrorCode::CacheInvalid.into());
+                }
+                let mut transform = [0.0f32; 16];
+                transform.copy_from_slice(&values);
+                let name = self.read_string()?;
+                let name = if name.is_empty() { None } else { Some(name) };
+                Ok(CachedInstance { primitive_index, transform, name })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let num_textures = self.read_u32()?;
+        let textures = (0..num_textures)
+            .map(|_| {
+                let texture = CachedTexture {
+                    width: self.read_u32()?,
+                    height: self.read_u32()?,
+                    srgb: self.read_u32()? != 0,
+                    levels: self.read_vec()?,
+                };
+                Ok(Arc::new(texture))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let num_materials = self.read_u32()?;
+        let materials = (0..num_materials)
+            .map(|_| {
+                let name = self.read_string()?;
+                let mut texture = || -> Result<Option<Arc<CachedTexture>>> {
+                    match self.read_u32()? {
+                        u32::MAX => Ok(None),
+                        index => textures.get(index as usize)
+                            .map(|v| Some(Arc::clone(v)))
+                            .ok_or_else(|| ErrorCode::CacheInvalid.into()),
+                    }
+                };
+                let material = CachedMaterial {
+                    name: if name.is_empty() { None } else { Some(name) },
+                    color_texture: texture()?,
+                    normal_texture: texture()?,
+                };
+                Ok(material)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let cache = SceneCache {
+            key: cached_key,
+            indices,
+            positions,
+            normals,
+            texcoords,
+            tangents,
+            colors,
+            primitives,
+            instances,
+            materials,
+            textures,
+        };
+        validate(&cache)?;
+        Ok(Some(cache))
+    }
+
+    fn read_external_files(&mut self) -> Result<Vec<SceneCacheFile>> {
+        let num_files = self.read_u32()?;
+        (0..num_files)
+            .map(|_| {
+                let file = SceneCacheFile {
+                    path: self.read_string()?,
+                    modified_secs: self.read_u64()?,
+                    modified_nanos: self.read_u32()?,
+                    size: self.read_u64()?,
+                };
+                Ok(file)
+            })
+            .collect()
+    }
+
+    fn read_bytes(&mut self, size: usize) -> Result<&'a [u8]> {
+        let end = self.offset.checked_add(size)
+            .filter(|&v| v <= self.data.len())
+            .ok_or(ErrorCode::CacheInvalid)?;
+        let bytes = &self.data[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        let b = self.read_bytes(4)?;
+        Ok(u32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn read_u64(&mut self) -> Result<u64> {
+        let b = self.read_bytes(8)?;
+        Ok(u64::from_ne_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+    }
+
+    fn read_string(&mut self) -> Result<String> {
+        let bytes: Vec<u8> = self.read_vec()?;
+        String::from_utf8(bytes)
+            .map_err(|_| ErrorCode::CacheInvalid.into())
+    }
+
+    fn read_vec<T: Copy>(&mut self) -> Result<Vec<T>> {
+        let count = self.read_u64()? as usize;
+        let size = count.checked_mul(std::mem::size_of::<T>())
+            .ok_or(ErrorCode::CacheInvalid)?;
+        let bytes = self.read_bytes(size)?;
+        let mut values: Vec<T> = Vec::with_capacity(count);
+        unsafe {
+            extend_from_raw(&mut values, bytes.as_ptr(), count);
+        }
+        Ok(values)
+    }
+}
+
+// the ranges are checked once here so that building the scene can slice the streams freely
+fn validate(cache: &SceneCache) -> Result<()> {
+    let num_vertices = cache.positions.len();
+    let streams_valid = cache.normals.len() == num_vertices
+        && cache.texcoords.len() == num_vertices
+        && cache.tangents.len() == num_vertices
+        && (cache.colors.is_empty() || cache.colors.len() == num_vertices);
+    if !streams_valid {
+        return Err(ErrorCode::CacheInvalid.into());
+    }
+    for v in cache.primitives.iter() {
+        let vertex_end = v.vertex_offset as usize + v.vertex_count as usize;
+        let index_end = v.index_offset as usize + v.index_count as usize;
+        let has_colors = v.flags & PRIMITIVE_COLORS != 0;
+        if vertex_end > num_vertices
+            || index_end > cache.indices.len()
+            || (has_colors && cache.colors.is_empty()) {
+            return Err(ErrorCode::CacheInvalid.into());
+        }
+        // the indices count from the first vertex of the primitive, and -1 stands for no material
+        let indices = &cache.indices[v.index_offset as usize..index_end];
+        let material_valid = v.material_index == -1
+            || (v.material_index >= 0 && (v.material_index as usize) < cache.materials.len());
+        if indices.iter().any(|&index| index >= v.vertex_count) || !material_valid {
+            return Err(ErrorCode::CacheInvalid.into());
+        }
+    }
+    if cache.instances.iter().any(|v| v.primitive_index as usize >= cache.primitives.len()) {
+        return Err(ErrorCode::CacheInvalid.into());
+    }
+    for v in cache.textures.iter() {
+        let extent = VkExtent3D {
+            width: v.width,
+            height: v.height,
+            depth: 1,
+        };
+        let size: usize = (0..TextureImage::mip_levels_for(extent))
+            .map(|level| (v.width >> level).max(1) as usize * (v.height >> level).max(1) as usize * 4)
+            .sum();
+        if v.levels.len() != size {
+            return Err(ErrorCode::CacheInvalid.into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key() -> SceneCacheKey {
+        SceneCacheKey {
+            path: "/assets/triangle.gltf".to_owned(),
+            modified_secs: 1,
+            modified_nanos: 2,
+            loader_version: SCENE_CACHE_LOADER_VERSION,
+            external_files: vec![SceneCacheFile {
+                path: "/assets/triangle.bin".to_owned(),
+                modified_secs: 3,
+                modified_nanos: 4,
+                size: 36,
+            }],
+        }
+    }
+
+    // a textured triangle placed once
+    fn triangle() -> SceneCache {
+        let texture = Arc::new(CachedTexture::new(&[255; 2 * 2 * 4], 2, 2, true));
+        SceneCache {
+            key: key(),
+            indices: vec![0, 1, 2],
+            positions: vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+            normals: vec![[0.0, 0.0, 1.0]; 3],
+            texcoords: vec![[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]],
+            tangents: vec![[0.0; 4]; 3],
+            colors: vec![],
+            primitives: vec![CachedPrimitive {
+                mesh_index: 0,
+                vertex_offset: 0,
+                vertex_count: 3,
+                index_offset: 0,
+                index_count: 3,
+                material_index: 0,
+                flags: PRIMITIVE_OPAQUE,
+            }],
+            instances: vec![CachedInstance {
+                primitive_index: 0,
+                transform: [1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0],
+                name: Some("triangle".to_owned()),
+            }],
+            materials: vec![CachedMaterial {
+                name: Some("white".to_owned()),
+                color_texture: Some(Arc::clone(&texture)),
+                normal_texture: None,
+            }],
+            textures: vec![texture],
+        }
+    }
+
+    fn bytes(cache: &SceneCache) -> Vec<u8> {
+        let mut data = vec![];
+        cache.write(&mut data).unwrap();
+        data
+    }
+
+    fn read(data: &[u8]) -> Result<Option<SceneCache>> {
+        CacheReader::new(data).read_cache(&key())
+    }
+
+    #[test]
+    fn round_trip() {
+        let cache = triangle();
+        let loaded = read(&bytes(&cache)).unwrap().unwrap();
+        assert_eq!(loaded.key(), cache.key());
+        assert_eq!(loaded.indices(), cache.indices());
+        assert_eq!(loaded.positions(), cache.positions());
+        assert_eq!(loaded.texcoords(), cache.texcoords());
+        assert_eq!(loaded.primitives().len(), 1);
+        assert_eq!(loaded.primitives()[0].material_index, 0);
+        assert!(loaded.primitives()[0].is_opaque());
+        assert_eq!(loaded.instances()[0].name.as_deref(), Some("triangle"));
+        let material = &loaded.materials()[0];
+        assert_eq!(material.name(), Some("white"));
+        assert!(material.normal_texture().is_none());
+        let texture = material.color_texture().unwrap();
+        assert_eq!((texture.width(), texture.height(), texture.is_srgb()), (2, 2, true));
+        assert_eq!(texture.levels(), cache.textures[0].levels());
+    }
+
+    #[test]
+    fn stale_key_is_missing() {
+        let mut cache = triangle();
+        cache.key.modified_secs += 1;
+        assert!(read(&bytes(&cache)).unwrap().is_none());
+    }
+
+    #[test]
+    fn replaced_external_file_is_missing() {
+        let mut cache = triangle();
+        cache.key.external_files[0].size += 1;
+        assert!(read(&bytes(&cache)).unwrap().is_none());
+    }
+
+    #[test]
+    fn key_covers_the_external_buffers() {
+        let directory = std::env::temp_dir().join(format!("kaldera-cache-test-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("scene.gltf");
+        std::fs::write(&path, r#"{"asset":{"version":"2.0"},"buffers":[{"uri":"scene.bin","byteLength":4}]}"#).unwrap();
+        std::fs::write(directory.join("scene.bin"), [0u8; 4]).unwrap();
+        let before = SceneCacheKey::new(&path);
+        std::fs::write(directory.join("scene.bin"), [0u8; 8]).unwrap();
+        let after = SceneCacheKey::new(&path);
+        std::fs::remove_dir_all(&directory).unwrap();
+        let (before, after) = (before.unwrap(), after.unwrap());
+        assert_eq!(before.external_files.len(), 1);
+        assert_ne!(before, after);
+    }
+
+    #[test]
+    fn truncated_data_is_invalid() {
+        let data = bytes(&triangle());
+        assert!(read(&data[..data.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn index_beyond_the_primitive_is_invalid() {
+        let mut cache = triangle();
+        cache.indices[2] = 3;
+        assert!(read(&bytes(&cache)).is_err());
+    }
+
+    #[test]
+    fn missing_material_is_invalid() {
+        let mut cache = triangle();
+        cache.primitives[0].material_index = 1;
+        assert!(read(&bytes(&cache)).is_err());
+        cache.primitives[0].material_index = -1;
+        assert!(read(&bytes(&cache)).unwrap().is_some());
+    }
+
+    #[test]
+    fn missing_primitive_is_invalid() {
+        let mut cache = triangle();
+        cache.instances[0].primitive_index = 1;
+        assert!(read(&bytes(&cache)).is_err());
+    }
+}
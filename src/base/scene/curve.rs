@@ -0,0 +1,119 @@
+
+use nalgebra_glm as glm;
+
+use crate::vk::Result;
+use crate::vk::*;
+
+use super::aabb::AABB;
+use super::procedural::*;
+use super::shape::ProceduralShapeDescription;
+
+// a tube segment whose radius varies linearly from `a` to `b`
+#[derive(Clone, Copy, Debug)]
+pub enum CurveSegment {
+    Linear { a: glm::Vec3, b: glm::Vec3, radius_a: f32, radius_b: f32 },
+    // passes through the first and the last point
+    CubicBezier { points: [glm::Vec3; 4], radius_a: f32, radius_b: f32 },
+}
+
+impl CurveSegment {
+    // the control points bound the curve
+    pub fn aabb(&self) -> AABB {
+        let (points, radius): (&[glm::Vec3], f32) = match self {
+            Self::Linear { a, b, radius_a, radius_b } => (&[*a, *b][..], radius_a.max(*radius_b)),
+            Self::CubicBezier { points, radius_a, radius_b } => (&points[..], radius_a.max(*radius_b)),
+        };
+        let min = points.iter().fold(points[0], |acc, v| glm::min2(&acc, v));
+        let max = points.iter().fold(points[0], |acc, v| glm::max2(&acc, v));
+        AABB::new(&min.add_scalar(-radius), &max.add_scalar(radius))
+    }
+}
+
+// strands of tube segments intersected by `ray.curve.rint` through
+// `RayTracingGraphicsPipeline::CURVE_HIT_GROUP`, such as hair, wires and streamlines
+pub struct Curves {
+    primitives: Vec<ProceduralPrimitive>,
+}
+
+impl Curves {
+    pub fn new() -> Self {
+        Self { primitives: vec![] }
+    }
+
+    pub fn add_segment(&mut self, segment: &CurveSegment, material: ProceduralMaterial) {
+        let primitive = ProceduralPrimitive::with_description(
+            segment.aabb(),
+            ProceduralShapeDescription::curve(segment),
+            material);
+        self.primitives.push(primitive);
+    }
+
+    // connects the points with straight segments. `radii` has a radius for every point.
+    pub fn add_polyline(&mut self, points: &[[f32; 3]], radii: &[f32], material: ProceduralMaterial) -> Result<()> {
+        Self::validate(points, radii)?;
+        for i in 0..points.len() - 1 {
+            let segment = CurveSegment::Linear {
+                a: glm::make_vec3(&points[i]),
+                b: glm::make_vec3(&points[i + 1]),
+                radius_a: radii[i],
+                radius_b: radii[i + 1],
+            };
+            self.add_segment(&segment, material);
+        }
+        Ok(())
+    }
+
+    // passes a Catmull-Rom spline through the points, converted to cubic Bezier segments
+    pub fn add_smooth_polyline(&mut self, points: &[[f32; 3]], radii: &[f32], material: ProceduralMaterial) -> Result<()> {
+        Self::validate(points, radii)?;
+        let points: Vec<glm::Vec3> = points.iter()
+            .map(|v| glm::make_vec3(v))
+            .collect();
+        let last = points.len() - 1;
+        for i in 0..last {
+            // the end points are repeated to keep the tangents defined
+            let p0 = points[i.saturating_sub(1)];
+            let p1 = points[i];
+            let p2 = points[i + 1];
+            let p3 = points[(i + 2).min(last)];
+            let segment = CurveSegment::CubicBezier {
+                points: [p1, p1 + (p2 - p0) / 6.0, p2 - (p3 - p1) / 6.0, p2],
+                radius_a: radii[i],
+                radius_b: radii[i + 1],
+            };
+            self.add_segment(&segment, material);
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.primitives.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.primitives.is_empty()
+    }
+
+    // added to a scene with `Scene::add_procedural_geometry`
+    pub fn geometry(&self) -> Result<ProceduralGeometry> {
+        if self.primitives.is_empty() {
+            return Err(ErrorCode::MeshNotFound.into())
+        }
+        let geometry = ProceduralGeometry::new(self.primitives.clone())
+            .with_builtin_hit_group(RayTracingGraphicsPipeline::CURVE_HIT_GROUP);
+        Ok(geometry)
+    }
+
+    fn validate(points: &[[f32; 3]], radii: &[f32]) -> Result<()> {
+        if points.len() < 2 || points.len() != radii.len() {
+            return Err(ErrorCode::MeshFormatInvalid.into())
+        }
+        Ok(())
+    }
+}
+
+impl Default for Curves {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,432 @@
+
+use nalgebra_glm as glm;
+
+use image_crate::DynamicImage;
+
+use std::sync::Arc;
+
+use crate::vk::Result;
+use crate::vk::*;
+
+use super::image as scene_image;
+use super::image_provider::ImageProvider;
+use super::material::Material;
+use super::mesh::*;
+use super::primitive::Primitive;
+use super::scene::{Scene, SceneBuildOptions};
+
+// constructs a scene from meshes, materials and instances given by the application
+// instead of a glTF document. produces the same staging buffers and structures as `SceneBuilder`.
+pub struct CustomSceneBuilder {
+    meshes: Vec<CustomMesh>,
+    materials: Vec<CustomMaterial>,
+    instances: Vec<CustomInstance>,
+    options: SceneBuildOptions,
+}
+
+impl CustomSceneBuilder {
+    pub fn new() -> Self {
+        Self {
+            meshes: vec![],
+            materials: vec![],
+            instances: vec![],
+            options: SceneBuildOptions::new(),
+        }
+    }
+
+    pub fn with_options(mut self, options: SceneBuildOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    // returns the material index to be referenced by meshes
+    pub fn add_material(&mut self, material: CustomMaterial) -> usize {
+        self.materials.push(material);
+        self.materials.len() - 1
+    }
+
+    // returns the mesh index to be referenced by instances
+    pub fn add_mesh(&mut self, mesh: CustomMesh) -> Result<usize> {
+        if let Some(material_index) = mesh.material_index {
+            if material_index >= self.materials.len() {
+                return Err(ErrorCode::MaterialNotFound.into());
+            }
+        }
+        let mut mesh = mesh.complete()?;
+        // normal mapping reads tangents which are derived from texture coordinates
+        let has_normal_image = self.materials.get(mesh.material_index.unwrap_or(0))
+            .map_or(false, |v| v.normal_image.is_some());
+        if has_normal_image && mesh.tangents.is_none() {
+            mesh.tangents = Some(generate_tangents(
+                &mesh.positions,
+                mesh.normals.as_ref().unwrap(),
+                mesh.texcoords.as_ref().unwrap(),
+                &mesh.indices));
+        }
+        self.meshes.push(mesh);
+        Ok(self.meshes.len() - 1)
+    }
+
+    pub fn add_instance(&mut self, mesh_index: usize, transform: glm::Mat4) -> Result<()> {
+        if mesh_index >= self.meshes.len() {
+            return Err(ErrorCode::MeshNotFound.into());
+        }
+        let instance = CustomInstance {
+            mesh_index,
+            transform,
+        };
+        self.instances.push(instance);
+        Ok(())
+    }
+
+    pub fn build(mut self, command_pool: &Arc<CommandPool>) -> Result<Scene> {
+        log_debug!("start custom scene builder");
+        // meshes without any material refer to the first one
+        if self.materials.is_empty() {
+            self.materials.push(CustomMaterial::new());
+        }
+        let materials = &self.materials;
+        let primitives: Vec<_> = self.meshes.iter()
+            .enumerate()
+            .map(|(mesh_index, mesh)| (mesh_index, mesh.primitive(materials)))
+            .collect();
+        let table = MeshTable::from_primitives(primitives);
+        let nodes: Vec<_> = self.instances.iter()
+            .flat_map(|v| MeshNode::from_mesh(v.mesh_index, &v.transform, &table))
+            .collect();
+        let materials: Vec<_> = materials.iter()
+            .map(Material::custom)
+            .collect();
+        let image_provider = ImageProvider::empty();
+        Scene::new(&[], &table, &nodes, &materials, &image_provider, &self.options, command_pool)
+    }
+
+    pub(super) fn meshes(&self) -> &[CustomMesh] {
+        &self.meshes
+    }
+
+    pub(super) fn materials(&self) -> &[CustomMaterial] {
+        &self.materials
+    }
+
+    pub(super) fn instances(&self) -> &[CustomInstance] {
+        &self.instances
+    }
+}
+
+impl Default for CustomSceneBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub(super) struct CustomInstance {
+    pub(super) mesh_index: usize,
+    pub(super) transform: glm::Mat4,
+}
+
+// a triangle list mesh. normals are computed from the triangles if not given.
+pub struct CustomMesh {
+    positions: Vec<[f32; 3]>,
+    indices: Vec<u32>,
+    normals: Option<Vec<[f32; 3]>>,
+    texcoords: Option<Vec<[f32; 2]>>,
+    tangents: Option<Vec<[f32; 4]>>,
+    colors: Option<Vec<[f32; 4]>>,
+    material_index: Option<usize>,
+}
+
+impl CustomMesh {
+    pub fn new(positions: Vec<[f32; 3]>, indices: Vec<u32>) -> Self {
+        Self {
+            positions,
+            indices,
+            normals: None,
+            texcoords: None,
+            tangents: None,
+            colors: None,
+            material_index: None,
+        }
+    }
+
+    pub fn with_normals(mut self, normals: Vec<[f32; 3]>) -> Self {
+        self.normals = Some(normals);
+        self
+    }
+
+    pub fn with_texcoords(mut self, texcoords: Vec<[f32; 2]>) -> Self {
+        self.texcoords = Some(texcoords);
+        self
+    }
+
+    pub fn with_tangents(mut self, tangents: Vec<[f32; 4]>) -> Self {
+        self.tangents = Some(tangents);
+        self
+    }
+
+    // per-vertex color multipliers in linear RGBA
+    pub fn with_colors(mut self, colors: Vec<[f32; 4]>) -> Self {
+        self.colors = Some(colors);
+        self
+    }
+
+    pub fn with_material(mut self, material_index: usize) -> Self {
+        self.material_index = Some(material_index);
+        self
+    }
+
+    pub fn positions(&self) -> &Vec<[f32; 3]> {
+        &self.positions
+    }
+
+    pub fn indices(&self) -> &Vec<u32> {
+        &self.indices
+    }
+
+    // filled by `complete` once the mesh is added to a builder
+    pub(super) fn normals(&self) -> &[[f32; 3]] {
+        self.normals.as_ref().unwrap()
+    }
+
+    pub(super) fn texcoords(&self) -> &[[f32; 2]] {
+        self.texcoords.as_ref().unwrap()
+    }
+
+    pub(super) fn tangents(&self) -> Option<&[[f32; 4]]> {
+        self.tangents.as_deref()
+    }
+
+    pub(super) fn colors(&self) -> Option<&[[f32; 4]]> {
+        self.colors.as_deref()
+    }
+
+    pub(super) fn material_index(&self) -> usize {
+        self.material_index.unwrap_or(0)
+    }
+
+    // validates attributes then fills the ones the shaders always read
+    fn complete(mut self) -> Result<Self> {
+        let num_vertices = self.positions.len();
+        let is_valid = !self.indices.is_empty()
+            && self.indices.len() % 3 == 0
+            && self.indices.iter().all(|&v| (v as usize) < num_vertices)
+            && self.normals.as_ref().map_or(true, |v| v.len() == num_vertices)
+            && self.texcoords.as_ref().map_or(true, |v| v.len() == num_vertices)
+            && self.tangents.as_ref().map_or(true, |v| v.len() == num_vertices)
+            && self.colors.as_ref().map_or(true, |v| v.len() == num_vertices);
+        if !is_valid {
+            return Err(ErrorCode::MeshFormatInvalid.into());
+        }
+        if self.normals.is_none() {
+            self.normals = Some(smooth_normals(&self.positions, &self.indices));
+        }
+        if self.texcoords.is_none() {
+            self.texcoords = Some(vec![[0.0, 0.0]; num_vertices]);
+        }
+        Ok(self)
+    }
+
+    fn primitive<'a>(&'a self, materials: &[CustomMaterial]) -> Primitive<'a> {
+        let material_index = self.material_index.unwrap_or(0);
+        let is_opaque = materials.get(material_index)
+            .map_or(true, |v| v.is_opaque());
+        Primitive::from_slices(
+            &self.indices,
+            &self.positions,
+            self.normals.as_ref().unwrap(),
+            self.texcoords.as_ref().unwrap(),
+            Some(material_index),
+            is_opaque,
+        )
+        .with_tangent_slice(self.tangents.as_deref())
+        .with_color_slice(self.colors.as_deref())
+    }
+}
+
+// area weighted vertex normals shared by all the triangles referring to the same vertex
+pub fn smooth_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut normals = vec![glm::vec3(0.0, 0.0, 0.0); positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let v0 = glm::make_vec3(&positions[triangle[0] as usize]);
+        let v1 = glm::make_vec3(&positions[triangle[1] as usize]);
+        let v2 = glm::make_vec3(&positions[triangle[2] as usize]);
+        // the length of the cross product is twice the triangle area
+        let normal = glm::cross(&(v1 - v0), &(v2 - v0));
+        for &index in triangle {
+            normals[index as usize] += normal;
+        }
+    }
+    normals.into_iter()
+        .map(|v| {
+            if glm::length(&v) > 0.0 {
+                let v = glm::normalize(&v);
+                [v.x, v.y, v.z]
+            } else {
+                [0.0, 1.0, 0.0]
+            }
+        })
+        .collect()
+}
+
+// per-vertex tangents with the bitangent sign in w, following the glTF 2.0 convention
+pub fn generate_tangents(
+    positions: &[[f32; 3]],
+    normals: &[[f32; 3]],
+    texcoords: &[[f32; 2]],
+    indices: &[u32],
+) -> Vec<[f32; 4]> {
+    let mut tangents = vec![glm::vec3(0.0, 0.0, 0.0); positions.len()];
+    let mut bitangents = vec![glm::vec3(0.0, 0.0, 0.0); positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let (i0, i1, i2) = (triangle[0] as usize, triangle[1] as usize, triangle[2] as usize);
+        let e1 = glm::make_vec3(&positions[i1]) - glm::make_vec3(&positions[i0]);
+        let e2 = glm::make_vec3(&positions[i2]) - glm::make_vec3(&positions[i0]);
+        let d1 = glm::make_vec2(&texcoords[i1]) - glm::make_vec2(&texcoords[i0]);
+        let d2 = glm::make_vec2(&texcoords[i2]) - glm::make_vec2(&texcoords[i0]);
+        let det = d1.x * d2.y - d2.x * d1.y;
+        if det.abs() < f32::EPSILON {
+            continue;
+        }
+        let r = 1.0 / det;
+        let tangent = (e1 * d2.y - e2 * d1.y) * r;
+        let bitangent = (e2 * d1.x - e1 * d2.x) * r;
+        for &index in triangle {
+            tangents[index as usize] += tangent;
+            bitangents[index as usize] += bitangent;
+        }
+    }
+    tangents.into_iter()
+        .zip(bitangents)
+        .zip(normals.iter())
+        .map(|((t, b), n)| {
+            let n = glm::make_vec3(n);
+            // Gram-Schmidt orthogonalization
+            let t = t - n * glm::dot(&n, &t);
+            let t = if glm::length(&t) > 0.0 {
+                glm::normalize(&t)
+            } else {
+                // any vector perpendicular to the normal
+                let axis = if n.x.abs() < 0.9 { glm::vec3(1.0, 0.0, 0.0) } else { glm::vec3(0.0, 1.0, 0.0) };
+                glm::normalize(&glm::cross(&n, &axis))
+            };
+            let w = if glm::dot(&glm::cross(&n, &t), &b) < 0.0 { -1.0 } else { 1.0 };
+            [t.x, t.y, t.z, w]
+        })
+        .collect()
+}
+
+// a material evaluated by the triangle hit shaders.
+// the base color multiplies the color image, or is used as a plain color without it.
+pub struct CustomMaterial {
+    name: Option<String>,
+    base_color: [f32; 4],
+    color_image: Option<DynamicImage>,
+    normal_image: Option<DynamicImage>,
+    is_opaque: bool,
+}
+
+impl CustomMaterial {
+    pub fn new() -> Self {
+        Self {
+            name: None,
+            base_color: [1.0, 1.0, 1.0, 1.0],
+            color_image: None,
+            normal_image: None,
+            is_opaque: true,
+        }
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    // linear RGBA
+    pub fn with_base_color(mut self, base_color: [f32; 4]) -> Self {
+        self.base_color = base_color;
+        self
+    }
+
+    // sRGB encoded color texture
+    pub fn with_color_image(mut self, image: DynamicImage) -> Self {
+        self.color_image = Some(image);
+        self
+    }
+
+    // tangent space normal map, requires mesh tangents
+    pub fn with_normal_image(mut self, image: DynamicImage) -> Self {
+        self.normal_image = Some(image);
+        self
+    }
+
+    // discards the texels whose alpha is below the half in the any-hit shader
+    pub fn with_alpha_mask(mut self) -> Self {
+        self.is_opaque = false;
+        self
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn is_opaque(&self) -> bool {
+        self.is_opaque
+    }
+
+    pub(super) fn color_image_data(&self) -> Option<scene_image::Data> {
+        let factor = self.base_color;
+        let image = match &self.color_image {
+            Some(image) => {
+                let mut image = image.to_rgba8();
+                if factor != [1.0, 1.0, 1.0, 1.0] {
+                    for pixel in image.pixels_mut() {
+                        for (channel, factor) in pixel.0.iter_mut().zip(&factor).take(3) {
+                            let linear = srgb_to_linear(*channel as f32 / 255.0) * factor;
+                            *channel = (linear_to_srgb(linear) * 255.0).round() as u8;
+                        }
+                        pixel.0[3] = (pixel.0[3] as f32 * factor[3]).round() as u8;
+                    }
+                }
+                image
+            },
+            None => {
+                let mut image = image_crate::RgbaImage::new(1, 1);
+                let pixel = image.get_pixel_mut(0, 0);
+                for (channel, factor) in pixel.0.iter_mut().zip(&factor).take(3) {
+                    *channel = (linear_to_srgb(*factor) * 255.0).round() as u8;
+                }
+                pixel.0[3] = (factor[3].clamp(0.0, 1.0) * 255.0).round() as u8;
+                image
+            },
+        };
+        scene_image::Data::new(DynamicImage::ImageRgba8(image))
+    }
+
+    pub(super) fn normal_image_data(&self) -> Option<scene_image::Data> {
+        let image = self.normal_image.as_ref()?;
+        scene_image::Data::new(DynamicImage::ImageRgba8(image.to_rgba8()))
+    }
+}
+
+impl Default for CustomMaterial {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub(super) fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub(super) fn linear_to_srgb(v: f32) -> f32 {
+    let v = v.clamp(0.0, 1.0);
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
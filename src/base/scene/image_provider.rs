@@ -2,15 +2,32 @@
 use super::SceneAsset;
 use super::image as scene_image;
 
+use crate::vk::Result;
+
 pub struct ImageProvider<'a> {
-    asset: &'a SceneAsset,
+    assets: Vec<&'a SceneAsset>,
 }
 
 impl<'a> ImageProvider<'a> {
-    pub fn new(asset: &'a SceneAsset) -> Self { Self { asset } }
+    pub fn new(asset: &'a SceneAsset) -> Self { Self::from_assets(&[asset]) }
+
+    // the images of each asset are numbered after those of the previous ones
+    pub fn from_assets(assets: &[&'a SceneAsset]) -> Self { Self { assets: assets.to_vec() } }
+
+    // provides no images for scenes without any asset
+    pub fn empty() -> Self { Self { assets: vec![] } }
 
-    pub fn image(&self, index: usize) -> Option<scene_image::Data> {
-        let image = self.asset.import_image_data(index).unwrap();
-        Some(image)
+    pub fn image(&self, index: usize) -> Result<Option<scene_image::Data>> {
+        let mut local_index = index;
+        for asset in self.assets.iter() {
+            let count = asset.document().images().count();
+            if local_index < count {
+                let image = asset.import_image_data(local_index)
+                    .map_err(|e| e.with_image(index))?;
+                return Ok(Some(image))
+            }
+            local_index -= count;
+        }
+        Ok(None)
     }
 }
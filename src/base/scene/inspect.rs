@@ -0,0 +1,629 @@
+
+use gltf;
+use gltf::accessor::{DataType, Dimensions};
+use gltf::mesh::Mode;
+use gltf::Semantic;
+
+use std::fmt;
+use std::sync::Arc;
+
+use crate::vk::*;
+
+use super::asset::*;
+use super::image::Format;
+use super::material::*;
+use super::mesh::*;
+use super::primitive::*;
+
+// describes a glTF asset without creating any Vulkan objects.
+// the checks follow what `SceneBuilder` and the shaders expect so that
+// assets can be triaged on machines without GPUs.
+pub struct SceneReport {
+    num_nodes: usize,
+    num_instances: usize,
+    num_instanced_triangles: usize,
+    primitives: Vec<PrimitiveReport>,
+    materials: Vec<MaterialReport>,
+    images: Vec<ImageReport>,
+    memory: MemoryEstimate,
+    rejections: Vec<String>,
+    warnings: Vec<String>,
+}
+
+impl SceneReport {
+    pub fn new(asset: &Arc<SceneAsset>) -> Self {
+        let document = asset.document();
+        let mut rejections: Vec<String> = vec![];
+        let mut warnings: Vec<String> = vec![];
+        // images are decoded up front because the materials refer to their sizes
+        let images: Vec<ImageReport> = document.images()
+            .map(|v| ImageReport::new(asset, v))
+            .collect();
+        let materials: Vec<MaterialReport> = document.materials()
+            .filter_map(|v| v.index().map(|index| (index, v)))
+            .map(|(index, v)| MaterialReport::new(index, &v))
+            .collect();
+        for material in materials.iter() {
+            for image_index in material.image_indices() {
+                match images.get(image_index) {
+                    Some(image) => if let Some(error) = image.rejection() {
+                        rejections.push(format!("material {} image {}: {}", material.index, image_index, error));
+                    },
+                    None => rejections.push(format!("material {} refers to missing image {}", material.index, image_index)),
+                }
+            }
+        }
+        for image in images.iter() {
+            let is_used = materials.iter()
+                .any(|v| v.image_indices().any(|index| index == image.index));
+            if !is_used {
+                warnings.push(format!("image {} is not used by any material", image.index));
+            }
+        }
+        // loads every primitive on its own so that a single broken one does not hide the others
+        let mut primitives: Vec<PrimitiveReport> = vec![];
+        let mut loaded: Vec<(usize, Primitive)> = vec![];
+        for mesh in document.meshes() {
+            for primitive in mesh.primitives() {
+                let (report, primitive) = PrimitiveReport::new(&mesh, &primitive, asset, &materials);
+                rejections.extend(report.rejections.iter()
+                    .map(|v| format!("mesh {} primitive {}: {}", report.mesh_index, report.primitive_index, v)));
+                warnings.extend(report.warnings.iter()
+                    .map(|v| format!("mesh {} primitive {}: {}", report.mesh_index, report.primitive_index, v)));
+                if let Some(primitive) = primitive {
+                    loaded.push((report.mesh_index, primitive));
+                }
+                primitives.push(report);
+            }
+        }
+        let table = MeshTable::from_primitives(loaded);
+        let scene = document.default_scene()
+            .or_else(|| document.scenes().nth(0));
+        let nodes: Vec<_> = match scene {
+            Some(scene) => scene.nodes()
+                .flat_map(FlattenNode::flatten)
+                .filter_map(|v| MeshNode::new(v, &table))
+                .flatten()
+                .collect(),
+            None => {
+                rejections.push(format!("{}", Error::from(ErrorCode::SceneNotFound)));
+                vec![]
+            },
+        };
+        let num_instanced_triangles = nodes.iter()
+            .map(|v| v.primitive().primitive().indices().count() / 3)
+            .sum();
+        for mesh in document.meshes() {
+            let is_instanced = nodes.iter()
+                .any(|v| v.primitive().mesh_index() == mesh.index());
+            if !is_instanced {
+                warnings.push(format!("mesh {} is not instanced by the scene", mesh.index()));
+            }
+        }
+        let memory = MemoryEstimate::new(&table, &materials, &images);
+        let report = Self {
+            num_nodes: document.nodes().count(),
+            num_instances: nodes.len(),
+            num_instanced_triangles,
+            primitives,
+            materials,
+            images,
+            memory,
+            rejections,
+            warnings,
+        };
+        report
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.num_nodes
+    }
+
+    pub fn num_instances(&self) -> usize {
+        self.num_instances
+    }
+
+    pub fn num_triangles(&self) -> usize {
+        self.primitives.iter()
+            .map(|v| v.num_triangles)
+            .sum()
+    }
+
+    pub fn primitives(&self) -> &Vec<PrimitiveReport> {
+        &self.primitives
+    }
+
+    pub fn materials(&self) -> &Vec<MaterialReport> {
+        &self.materials
+    }
+
+    pub fn images(&self) -> &Vec<ImageReport> {
+        &self.images
+    }
+
+    pub fn memory(&self) -> &MemoryEstimate {
+        &self.memory
+    }
+
+    // problems that make the renderer fail or read out of bounds
+    pub fn rejections(&self) -> &Vec<String> {
+        &self.rejections
+    }
+
+    pub fn warnings(&self) -> &Vec<String> {
+        &self.warnings
+    }
+
+    pub fn is_renderable(&self) -> bool {
+        self.rejections.is_empty()
+    }
+}
+
+impl fmt::Display for SceneReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "scene")?;
+        writeln!(f, "  nodes: {}", self.num_nodes)?;
+        writeln!(f, "  primitives: {}", self.primitives.len())?;
+        writeln!(f, "  instances: {}", self.num_instances)?;
+        writeln!(f, "  triangles: {} ({} instanced)", self.num_triangles(), self.num_instanced_triangles)?;
+        writeln!(f, "primitives")?;
+        for primitive in self.primitives.iter() {
+            write!(f, "{}", primitive)?;
+        }
+        writeln!(f, "materials")?;
+        for material in self.materials.iter() {
+            writeln!(f, "{}", material)?;
+        }
+        writeln!(f, "images")?;
+        for image in self.images.iter() {
+            writeln!(f, "{}", image)?;
+        }
+        write!(f, "{}", self.memory)?;
+        writeln!(f, "rejections: {}", self.rejections.len())?;
+        for rejection in self.rejections.iter() {
+            writeln!(f, "  {}", rejection)?;
+        }
+        writeln!(f, "warnings: {}", self.warnings.len())?;
+        for warning in self.warnings.iter() {
+            writeln!(f, "  {}", warning)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttributePath {
+    // the accessor bytes are copied into the staging buffers as they are
+    Reference,
+    // the values are converted through the gltf reader into a `Vec`
+    Converted,
+    // the renderer does not read the attribute
+    Ignored,
+    // the primitive failed to load
+    Unknown,
+}
+
+pub struct AttributeReport {
+    pub semantic: String,
+    pub data_type: DataType,
+    pub dimensions: Dimensions,
+    pub normalized: bool,
+    pub count: usize,
+    pub path: AttributePath,
+}
+
+impl fmt::Display for AttributeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let normalized = if self.normalized { " normalized" } else { "" };
+        let path = match self.path {
+            AttributePath::Reference => "zero-copy",
+            AttributePath::Converted => "converted",
+            AttributePath::Ignored => "ignored",
+            AttributePath::Unknown => "-",
+        };
+        write!(f, "{} {:?} {:?}{} x{} ({})", self.semantic, self.dimensions, self.data_type, normalized, self.count, path)
+    }
+}
+
+pub struct PrimitiveReport {
+    pub mesh_index: usize,
+    pub mesh_name: Option<String>,
+    pub primitive_index: usize,
+    pub mode: Mode,
+    pub num_vertices: usize,
+    pub num_triangles: usize,
+    pub material_index: Option<usize>,
+    pub indices: Option<AttributeReport>,
+    pub attributes: Vec<AttributeReport>,
+    pub rejections: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl PrimitiveReport {
+    fn new<'a>(
+        mesh: &gltf::Mesh<'a>,
+        primitive: &gltf::Primitive<'a>,
+        asset: &'a SceneAsset,
+        materials: &[MaterialReport],
+    ) -> (Self, Option<Primitive<'a>>) {
+        let mut rejections: Vec<String> = vec![];
+        let mut warnings: Vec<String> = vec![];
+        let loaded = match Primitive::new(primitive.clone(), asset.buffers()) {
+            Ok(v) => Some(v),
+            Err(error) => {
+                let missing: Vec<_> = [Semantic::Positions, Semantic::Normals, Semantic::TexCoords(0)].iter()
+                    .filter(|v| primitive.get(v).is_none())
+                    .map(|v| v.to_string())
+                    .chain(primitive.indices().map_or(Some("indices".to_owned()), |_| None))
+                    .collect();
+                if missing.is_empty() {
+                    rejections.push(error_chain(&error));
+                } else {
+                    rejections.push(format!("missing {}", missing.join(", ")));
+                }
+                None
+            },
+        };
+        if primitive.mode() != Mode::Triangles {
+            rejections.push(format!("mode {:?} is rendered as a triangle list", primitive.mode()));
+        }
+        let indices = primitive.indices()
+            .map(|accessor| AttributeReport {
+                semantic: "indices".to_owned(),
+                data_type: accessor.data_type(),
+                dimensions: accessor.dimensions(),
+                normalized: accessor.normalized(),
+                count: accessor.count(),
+                path: match loaded.as_ref().map(|v| v.indices()) {
+                    Some(Indices::Accessor(_)) => AttributePath::Reference,
+                    Some(Indices::Vector(_)) => AttributePath::Converted,
+                    None => AttributePath::Unknown,
+                },
+            });
+        let attributes: Vec<AttributeReport> = primitive.attributes()
+            .map(|(semantic, accessor)| AttributeReport {
+                path: attribute_path(&semantic, loaded.as_ref()),
+                semantic: semantic.to_string(),
+                data_type: accessor.data_type(),
+                dimensions: accessor.dimensions(),
+                normalized: accessor.normalized(),
+                count: accessor.count(),
+            })
+            .collect();
+        let mut num_vertices = 0;
+        let mut num_triangles = 0;
+        if let Some(loaded) = loaded.as_ref() {
+            num_vertices = loaded.positions().count();
+            num_triangles = loaded.indices().count() / 3;
+            let counts = [
+                ("NORMAL", Some(loaded.normals().count())),
+                ("TEXCOORD_0", Some(loaded.texcoords().count())),
+                ("TANGENT", loaded.tangents().map(|v| v.count())),
+                ("COLOR_0", loaded.colors().map(|v| v.count())),
+            ];
+            for (semantic, count) in counts.iter() {
+                match count {
+                    Some(count) if *count != num_vertices => {
+                        rejections.push(format!("{} has {} elements for {} vertices", semantic, count, num_vertices));
+                    },
+                    _ => {},
+                }
+            }
+            if loaded.indices().count() % 3 != 0 {
+                rejections.push(format!("{} indices do not form triangles", loaded.indices().count()));
+            }
+            let max_index = max_index(loaded.indices());
+            if let Some(max_index) = max_index {
+                if max_index as usize >= num_vertices {
+                    rejections.push(format!("index {} is out of {} vertices", max_index, num_vertices));
+                }
+            }
+        }
+        let material_index = primitive.material().index();
+        match material_index {
+            Some(index) => {
+                let has_normal_image = materials.get(index)
+                    .map_or(false, |v| v.normal_image_index.is_some());
+                let has_tangents = primitive.get(&Semantic::Tangents).is_some();
+                if has_normal_image && !has_tangents {
+                    warnings.push(format!("material {} has a normal texture but TANGENT is missing", index));
+                }
+            },
+            None if materials.is_empty() => {
+                rejections.push("no material while the document defines none".to_owned());
+            },
+            None => {
+                warnings.push("no material, material 0 is used instead of the default".to_owned());
+            },
+        }
+        let report = Self {
+            mesh_index: mesh.index(),
+            mesh_name: mesh.name().map(|v| v.to_owned()),
+            primitive_index: primitive.index(),
+            mode: primitive.mode(),
+            num_vertices,
+            num_triangles,
+            material_index,
+            indices,
+            attributes,
+            rejections,
+            warnings,
+        };
+        (report, loaded)
+    }
+}
+
+impl fmt::Display for PrimitiveReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "  mesh {} primitive {}", self.mesh_index, self.primitive_index)?;
+        if let Some(name) = self.mesh_name.as_ref() {
+            write!(f, " \"{}\"", name)?;
+        }
+        writeln!(f, ": {:?}, {} vertices, {} triangles", self.mode, self.num_vertices, self.num_triangles)?;
+        match self.material_index {
+            Some(index) => writeln!(f, "    material: {}", index)?,
+            None => writeln!(f, "    material: none")?,
+        }
+        for attribute in self.indices.iter().chain(self.attributes.iter()) {
+            writeln!(f, "    {}", attribute)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct MaterialReport {
+    pub index: usize,
+    pub name: Option<String>,
+    pub alpha_mode: gltf::material::AlphaMode,
+    pub color_image_index: Option<usize>,
+    pub normal_image_index: Option<usize>,
+}
+
+impl MaterialReport {
+    fn new(index: usize, material: &gltf::material::Material) -> Self {
+        let sources = MaterialImageSources::new(material);
+        Self {
+            index,
+            name: material.name().map(|v| v.to_owned()),
+            alpha_mode: material.alpha_mode(),
+            color_image_index: sources.color_image_index,
+            normal_image_index: sources.normal_image_index,
+        }
+    }
+
+    fn image_indices(&self) -> impl Iterator<Item = usize> {
+        self.color_image_index.into_iter()
+            .chain(self.normal_image_index)
+    }
+}
+
+impl fmt::Display for MaterialReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "  material {}", self.index)?;
+        if let Some(name) = self.name.as_ref() {
+            write!(f, " \"{}\"", name)?;
+        }
+        write!(f, ": {:?}", self.alpha_mode)?;
+        if let Some(index) = self.color_image_index {
+            write!(f, ", color image {}", index)?;
+        }
+        if let Some(index) = self.normal_image_index {
+            write!(f, ", normal image {}", index)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct ImageReport {
+    pub index: usize,
+    pub source: String,
+    pub mime_type: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    pub format: Option<Format>,
+    pub error: Option<String>,
+}
+
+impl ImageReport {
+    fn new(asset: &SceneAsset, image: gltf::Image) -> Self {
+        let (source, mime_type) = match image.source() {
+            gltf::image::Source::View { view, mime_type } => (format!("buffer view {}", view.index()), Some(mime_type)),
+            gltf::image::Source::Uri { uri, mime_type } => {
+                // embedded images would flood the report
+                let source = if uri.starts_with("data:") { "data URI".to_owned() } else { uri.to_owned() };
+                (source, mime_type)
+            },
+        };
+        let mut report = Self {
+            index: image.index(),
+            source,
+            mime_type: mime_type.map(|v| v.to_owned()),
+            width: 0,
+            height: 0,
+            format: None,
+            error: None,
+        };
+        match asset.import_image_data(image.index()) {
+            Ok(data) => {
+                report.width = data.width;
+                report.height = data.height;
+                report.format = Some(data.format);
+            },
+            Err(error) => report.error = Some(error_chain(&error)),
+        }
+        report
+    }
+
+    // textures are uploaded as 8-bit RGBA
+    fn rejection(&self) -> Option<String> {
+        if let Some(error) = self.error.as_ref() {
+            return Some(error.clone());
+        }
+        match self.format {
+            Some(Format::R8G8B8) | Some(Format::R8G8B8A8) => None,
+            Some(format) => Some(format!("unsupported image format {:?}", format)),
+            None => None,
+        }
+    }
+}
+
+impl fmt::Display for ImageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "  image {}: {}", self.index, self.source)?;
+        if let Some(mime_type) = self.mime_type.as_ref() {
+            write!(f, " ({})", mime_type)?;
+        }
+        match (self.format, self.error.as_ref()) {
+            (Some(format), _) => write!(f, ", {}x{} {:?}", self.width, self.height, format),
+            (None, Some(error)) => write!(f, ", {}", error),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+// device local memory that `SceneBuilder` would allocate.
+// every staging buffer keeps a host visible copy of the same size.
+#[derive(Default)]
+pub struct MemoryEstimate {
+    pub vertex_buffer: u64,
+    pub index_buffer: u64,
+    pub normal_buffer: u64,
+    pub description_buffer: u64,
+    pub texcoord_buffer: u64,
+    pub material_description_buffer: u64,
+    pub tangent_buffer: u64,
+    pub color_buffer: u64,
+    pub textures: u64,
+    pub num_textures: usize,
+}
+
+impl MemoryEstimate {
+    fn new(table: &MeshTable, materials: &[MaterialReport], images: &[ImageReport]) -> Self {
+        let primitives = table.mesh_primitives();
+        let num_indices: usize = primitives.iter()
+            .map(|v| v.primitive().indices().count())
+            .sum();
+        let num_vertices: usize = primitives.iter()
+            .map(|v| v.primitive().positions().count())
+            .sum();
+        let has_colors = primitives.iter().any(|v| v.use_color_multipliers());
+        // mirrors `SceneStagingBuffers::new`
+        let size = |count: usize, element: usize| (count * element) as u64;
+        let mut estimate = Self {
+            vertex_buffer: size(num_vertices, std::mem::size_of::<[f32; 3]>()),
+            index_buffer: size(num_indices, std::mem::size_of::<u32>()),
+            normal_buffer: size(num_vertices, std::mem::size_of::<[f32; 3]>()),
+            description_buffer: size(primitives.len(), std::mem::size_of::<SceneMeshPrimitiveDescription>()),
+            texcoord_buffer: size(num_vertices, std::mem::size_of::<[f32; 2]>()),
+            material_description_buffer: size(materials.len(), std::mem::size_of::<SceneMaterialDescription>()),
+            tangent_buffer: size(num_vertices, std::mem::size_of::<[f32; 4]>()),
+            color_buffer: if has_colors { size(num_vertices, std::mem::size_of::<[f32; 4]>()) } else { 256 },
+            textures: 0,
+            num_textures: 0,
+        };
+        // each material uploads its own textures even when they share an image
+        for image_index in materials.iter().flat_map(|v| v.image_indices()) {
+            if let Some(image) = images.get(image_index).filter(|v| v.format.is_some()) {
+                estimate.textures += texture_size(image.width, image.height);
+                estimate.num_textures += 1;
+            }
+        }
+        estimate
+    }
+
+    pub fn staging_buffers(&self) -> u64 {
+        self.vertex_buffer
+            + self.index_buffer
+            + self.normal_buffer
+            + self.description_buffer
+            + self.texcoord_buffer
+            + self.material_description_buffer
+            + self.tangent_buffer
+            + self.color_buffer
+    }
+
+    pub fn total(&self) -> u64 {
+        self.staging_buffers() + self.textures
+    }
+}
+
+impl fmt::Display for MemoryEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "estimated gpu memory")?;
+        writeln!(f, "  vertex buffer: {}", format_bytes(self.vertex_buffer))?;
+        writeln!(f, "  index buffer: {}", format_bytes(self.index_buffer))?;
+        writeln!(f, "  normal buffer: {}", format_bytes(self.normal_buffer))?;
+        writeln!(f, "  description buffer: {}", format_bytes(self.description_buffer))?;
+        writeln!(f, "  texcoord buffer: {}", format_bytes(self.texcoord_buffer))?;
+        writeln!(f, "  material description buffer: {}", format_bytes(self.material_description_buffer))?;
+        writeln!(f, "  tangent buffer: {}", format_bytes(self.tangent_buffer))?;
+        writeln!(f, "  color buffer: {}", format_bytes(self.color_buffer))?;
+        writeln!(f, "  staging buffers: {} (plus the same amount of host memory)", format_bytes(self.staging_buffers()))?;
+        writeln!(f, "  textures: {} in {} textures", format_bytes(self.textures), self.num_textures)?;
+        writeln!(f, "  total: {} excluding acceleration structures", format_bytes(self.total()))
+    }
+}
+
+fn attribute_path(semantic: &Semantic, primitive: Option<&Primitive>) -> AttributePath {
+    let primitive = match primitive {
+        Some(v) => v,
+        None => return AttributePath::Unknown,
+    };
+    let is_reference = match semantic {
+        Semantic::Positions => matches!(primitive.positions(), Positions::Accessor(_)),
+        Semantic::Normals => matches!(primitive.normals(), Normals::Accessor(_)),
+        Semantic::TexCoords(0) => matches!(primitive.texcoords(), Texcoords::Accessor(_)),
+        Semantic::Tangents => matches!(primitive.tangents(), Some(Tangents::Accessor(_))),
+        Semantic::Colors(0) => matches!(primitive.colors(), Some(Colors::Accessor(_))),
+        _ => return AttributePath::Ignored,
+    };
+    if is_reference {
+        AttributePath::Reference
+    } else {
+        AttributePath::Converted
+    }
+}
+
+fn max_index(indices: &Indices) -> Option<u32> {
+    match indices {
+        Indices::Accessor(_) => {
+            // the accessor holds tightly packed u32 values that may not be aligned
+            let bytes = unsafe { std::slice::from_raw_parts(indices.data(), indices.count() * 4) };
+            bytes.chunks_exact(4)
+                .map(|v| u32::from_le_bytes([v[0], v[1], v[2], v[3]]))
+                .max()
+        },
+        Indices::Vector(v) => v.iter().copied().max(),
+    }
+}
+
+// RGBA8 with the mip levels `TextureImage` allocates
+fn texture_size(width: u32, height: u32) -> u64 {
+    let mip_levels = width
+        .max(height)
+        .next_power_of_two()
+        .trailing_zeros()
+        .max(1);
+    (0..mip_levels)
+        .map(|level| {
+            let width = (width >> level).max(1) as u64;
+            let height = (height >> level).max(1) as u64;
+            width * height * 4
+        })
+        .sum()
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[unit])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
@@ -9,25 +9,36 @@ use crate::vk::*;
 use super::image as scene_image;
 
 use super::mesh::*;
+use super::custom::CustomMaterial;
+use super::cache::CachedMaterial;
 use super::image_provider::ImageProvider;
 
-pub struct Material<'a> {
-    material: gltf::material::Material<'a>,
+pub enum Material<'a> {
+    // `image_offset` is where the images of the material's asset start in the `ImageProvider`
+    Document { material: gltf::material::Material<'a>, image_offset: usize },
+    Custom(&'a CustomMaterial),
+    Cached(&'a CachedMaterial),
 }
 
 impl<'a> Material<'a> {
-    pub fn new(material: gltf::material::Material<'a>) -> Self {
-        Self {
-            material,
-        }
+    pub fn with_image_offset(material: gltf::material::Material<'a>, image_offset: usize) -> Self {
+        Self::Document { material, image_offset }
     }
 
-    pub fn name(&self) -> Option<&'a str> {
-        self.material.name()
+    pub fn custom(material: &'a CustomMaterial) -> Self {
+        Self::Custom(material)
     }
 
-    fn material(&self) -> &gltf::material::Material<'a> {
-        &self.material
+    pub fn cached(material: &'a CachedMaterial) -> Self {
+        Self::Cached(material)
+    }
+
+    pub fn name(&self) -> Option<&'a str> {
+        match self {
+            Self::Document { material, .. } => material.name(),
+            Self::Custom(material) => material.name(),
+            Self::Cached(material) => material.name(),
+        }
     }
 }
 
@@ -37,7 +48,7 @@ pub struct MaterialImageSources {
 }
 
 impl MaterialImageSources {
-    fn new(material: &gltf::material::Material) -> Self { 
+    pub(super) fn new(material: &gltf::material::Material) -> Self { 
         let color_image_index = Self::color_image_index(material);
         let normal_image_index = Self::normal_image_index(material);
         Self { color_image_index, normal_image_index }
@@ -70,14 +81,26 @@ pub struct MaterialImageData {
 
 impl MaterialImageData {
     pub fn new(material: &Material, image_provider: &ImageProvider) -> Result<Self> { 
-        let sources = MaterialImageSources::new(material.material());
+        let (material, image_offset) = match material {
+            Material::Document { material, image_offset } => (material, *image_offset),
+            Material::Custom(material) => {
+                let color_image = material.color_image_data();
+                let normal_image = material.normal_image_data();
+                return Ok(Self { color_image, normal_image });
+            },
+            // cached materials carry their decoded mip levels instead
+            Material::Cached(_) => return Ok(Self { color_image: None, normal_image: None }),
+        };
+        let sources = MaterialImageSources::new(material);
+        let image = |index: usize| -> Result<scene_image::Data> {
+            image_provider.image(index)?
+                .ok_or_else(|| Error::from(ErrorCode::ImageNotFound).with_image(index))
+        };
         let color_image = sources.color_image_index
-            .map(|index| image_provider.image(index)
-                .ok_or_else(|| ErrorCode::ImageNotFound))
+            .map(|v| image(image_offset + v))
             .transpose()?;
         let normal_image = sources.normal_image_index
-            .map(|index| image_provider.image(index)
-                .ok_or_else(|| ErrorCode::ImageNotFound))
+            .map(|v| image(image_offset + v))
             .transpose()?;
         let this = Self { color_image, normal_image };
         Ok(this)
@@ -161,7 +184,7 @@ pub enum MaterialImagePixels<'a> {
 }
 
 impl<'a> MaterialImagePixels<'a> {
-    fn new(image: &'a scene_image::Data) -> Option<Self> {
+    pub(super) fn new(image: &'a scene_image::Data) -> Option<Self> {
         use scene_image::Format;
         match image.format {
             Format::R8G8B8 => {
@@ -193,6 +216,8 @@ impl<'a> MaterialImagePixels<'a> {
 pub struct SceneMaterialDescription {
     color_texture_index: i32,
     normal_texture_index: i32,
+    // selects the callable shader evaluating the material
+    material_type: u32,
 }
 
 pub struct MaterialDescriptionsTextures {
@@ -202,13 +227,13 @@ pub struct MaterialDescriptionsTextures {
 }
 
 impl MaterialDescriptionsTextures {
-    pub fn new(materials: &[Material], image_provider: &ImageProvider, command_pool: &Arc<CommandPool>) -> Self {
+    pub fn new(materials: &[Material], image_provider: &ImageProvider, command_pool: &Arc<CommandPool>) -> Result<Self> {
         let queue_submit = QueueSubmit::new(command_pool.queue());
         let materials: Vec<_> = materials.iter()
             .map(|v| SceneMeshMaterial::new(v, image_provider, command_pool, &queue_submit))
             //.map(|v| SceneMeshMaterial::new_placeholder(command_pool, &queue_submit))
-            .collect();
-        queue_submit.execute().unwrap();
+            .collect::<Result<_>>()?;
+        queue_submit.execute()?;
         let mut descriptions: Vec<SceneMaterialDescription> = vec![];
         let mut textures: Vec<Arc<Texture>> = vec![];
         for material in materials.iter() {
@@ -231,18 +256,20 @@ impl MaterialDescriptionsTextures {
             let desc = SceneMaterialDescription {
                 color_texture_index,
                 normal_texture_index,
+                material_type: RayTracingGraphicsPipeline::BUILTIN_MATERIAL_TYPE,
             };
             descriptions.push(desc);
         }
-        Self {
+        let this = Self {
             descriptions,
             textures,
             materials,
-        }
+        };
+        Ok(this)
     }
 
-    pub fn replace_material(&mut self, command_pool: &Arc<CommandPool>, queue_submit: &Arc<QueueSubmit>, image_provider: &ImageProvider, material: &Material, material_index: usize) {
-        let mesh_material = SceneMeshMaterial::new(material, image_provider, command_pool, queue_submit);
+    pub fn replace_material(&mut self, command_pool: &Arc<CommandPool>, queue_submit: &Arc<QueueSubmit>, image_provider: &ImageProvider, material: &Material, material_index: usize) -> Result<()> {
+        let mesh_material = SceneMeshMaterial::new(material, image_provider, command_pool, queue_submit)?;
         // color
         let color_texture_index: i32;
         if let Some(color_texture) = mesh_material.color_texture() {
@@ -262,7 +289,20 @@ impl MaterialDescriptionsTextures {
         let desc = SceneMaterialDescription {
             color_texture_index,
             normal_texture_index,
+            material_type: self.descriptions[material_index].material_type,
         };
         self.descriptions[material_index] = desc;
+        Ok(())
+    }
+
+    // `material_type_count` of the pipeline evaluating the materials
+    pub fn set_material_type(&mut self, material_index: usize, material_type: u32, material_type_count: u32) -> Result<()> {
+        if material_type >= material_type_count {
+            return Err(ErrorCode::MaterialTypeNotFound.into())
+        }
+        let desc = self.descriptions.get_mut(material_index)
+            .ok_or(ErrorCode::MaterialNotFound)?;
+        desc.material_type = material_type;
+        Ok(())
     }
 }
@@ -1,6 +1,7 @@
 
 use std::sync::{Arc, Mutex, MutexGuard};
 
+use crate::vk::Result;
 use crate::vk::*;
 
 use super::image_provider::ImageProvider;
@@ -47,6 +48,10 @@ impl<'a> MaterialRepositoryStateRef<'a> {
         &self.guard.descriptions
     }
 
+    pub fn set_material_type(&mut self, material_index: usize, material_type: u32, material_type_count: u32) -> Result<()> {
+        self.guard.set_material_type(material_index, material_type, material_type_count)
+    }
+
     #[allow(dead_code)]
     pub fn replace_material(
         &mut self,
@@ -54,7 +59,7 @@ impl<'a> MaterialRepositoryStateRef<'a> {
         queue_submit: &Arc<QueueSubmit>,
         image_provider: &ImageProvider,
         material: &Material,
-        material_index: usize) 
+        material_index: usize) -> Result<()>
     {
         self.guard.replace_material(command_pool, queue_submit, image_provider, material, material_index)
     }
@@ -15,6 +15,7 @@ use super::image_provider::ImageProvider;
 use super::material::*;
 use super::buffer::*;
 use super::primitive::*;
+use super::cache::*;
 
 pub struct SceneMeshMaterial {
     color_texture: Option<Arc<Texture>>,
@@ -22,10 +23,13 @@ pub struct SceneMeshMaterial {
 }
 
 impl SceneMeshMaterial {
-    pub fn new(material: &Material, image_provider: &ImageProvider, command_pool: &Arc<CommandPool>, queue_submit: &Arc<QueueSubmit>) -> Arc<Self> {
+    pub fn new(material: &Material, image_provider: &ImageProvider, command_pool: &Arc<CommandPool>, queue_submit: &Arc<QueueSubmit>) -> Result<Arc<Self>> {
         log_debug!("loading material {}", material.name().unwrap_or(""));
-        let image_data = MaterialImageData::new(material, image_provider).unwrap();
-        let images = MaterialImages::new(&image_data).unwrap();
+        if let Material::Cached(material) = material {
+            return Self::from_cached(material, command_pool, queue_submit);
+        }
+        let image_data = MaterialImageData::new(material, image_provider)?;
+        let images = MaterialImages::new(&image_data)?;
         let color_texture = images.color_image()
             .map(|image| {
                 let pixels = image.pixels().pixels();
@@ -38,10 +42,10 @@ impl SceneMeshMaterial {
                 };
                 let device = command_pool.queue().device();
                 let mipmaps = true;
-                let texture_image = TextureImage::new(device, extent, VkFormat::VK_FORMAT_R8G8B8A8_SRGB, mipmaps).unwrap();
-                let texture = Texture::new(command_pool, queue_submit, &texture_image, data, data_size).unwrap();
-                texture
-            });
+                let texture_image = TextureImage::new(device, extent, VkFormat::VK_FORMAT_R8G8B8A8_SRGB, mipmaps)?;
+                Texture::new(command_pool, queue_submit, &texture_image, data, data_size)
+            })
+            .transpose()?;
         let normal_texture = images.normal_image()
             .map(|image| {
                 let pixels = image.pixels().pixels();
@@ -54,19 +58,45 @@ impl SceneMeshMaterial {
                 };
                 let device = command_pool.queue().device();
                 let mipmaps = true;
-                let texture_image = TextureImage::new(device, extent, VkFormat::VK_FORMAT_R8G8B8A8_UNORM, mipmaps).unwrap();
-                let texture = Texture::new(command_pool, queue_submit, &texture_image, data, data_size).unwrap();
-                texture
-            });
+                let texture_image = TextureImage::new(device, extent, VkFormat::VK_FORMAT_R8G8B8A8_UNORM, mipmaps)?;
+                Texture::new(command_pool, queue_submit, &texture_image, data, data_size)
+            })
+            .transpose()?;
+        let mesh_material = Self {
+            color_texture,
+            normal_texture,
+        };
+        Ok(Arc::new(mesh_material))
+    }
+
+    // uploads the mip levels as they are without generating them on the device
+    fn from_cached(material: &CachedMaterial, command_pool: &Arc<CommandPool>, queue_submit: &Arc<QueueSubmit>) -> Result<Arc<Self>> {
+        let texture = |texture: &Arc<CachedTexture>, format: VkFormat| -> Result<Arc<Texture>> {
+            let levels = texture.levels();
+            let extent = VkExtent3D {
+                width: texture.width(),
+                height: texture.height(),
+                depth: 1,
+            };
+            let device = command_pool.queue().device();
+            let texture_image = TextureImage::new_premipped(device, extent, format)?;
+            Texture::new(command_pool, queue_submit, &texture_image, levels.as_ptr() as *const c_void, levels.len())
+        };
+        let color_texture = material.color_texture()
+            .map(|v| texture(v, VkFormat::VK_FORMAT_R8G8B8A8_SRGB))
+            .transpose()?;
+        let normal_texture = material.normal_texture()
+            .map(|v| texture(v, VkFormat::VK_FORMAT_R8G8B8A8_UNORM))
+            .transpose()?;
         let mesh_material = Self {
             color_texture,
             normal_texture,
         };
-        Arc::new(mesh_material)
+        Ok(Arc::new(mesh_material))
     }
 
     #[allow(dead_code)]
-    pub fn new_placeholder(command_pool: &Arc<CommandPool>, queue_submit: &Arc<QueueSubmit>) -> Arc<Self> {
+    pub fn new_placeholder(command_pool: &Arc<CommandPool>, queue_submit: &Arc<QueueSubmit>) -> Result<Arc<Self>> {
         let extent = VkExtent3D {
             width: 1,
             height: 1,
@@ -76,13 +106,13 @@ impl SceneMeshMaterial {
         data.resize(4, 255u8);
         let device = command_pool.queue().device();
         let mipmaps = false;
-        let texture_image = TextureImage::new(device, extent, VkFormat::VK_FORMAT_R8G8B8A8_SRGB, mipmaps).unwrap();
-        let texture = Texture::new(command_pool, queue_submit, &texture_image, data.as_ptr() as *const c_void, data.len()).unwrap();
+        let texture_image = TextureImage::new(device, extent, VkFormat::VK_FORMAT_R8G8B8A8_SRGB, mipmaps)?;
+        let texture = Texture::new(command_pool, queue_submit, &texture_image, data.as_ptr() as *const c_void, data.len())?;
         let mesh_material = Self {
             color_texture: Some(texture),
             normal_texture: None,
         };
-        Arc::new(mesh_material)
+        Ok(Arc::new(mesh_material))
     }
 
     pub fn color_texture(&self) -> Option<&Arc<Texture>> {
@@ -95,7 +125,7 @@ impl SceneMeshMaterial {
 }
 
 #[repr(C)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Default)]
 pub struct SceneMeshPrimitiveDescription {
     vertex_offset: u32,
     index_offset: u32,
@@ -117,11 +147,19 @@ impl SceneMeshPrimitiveDescription {
             flags,
         }
     }
+
+    pub fn selected(&self) -> Self {
+        Self {
+            flags: self.flags | SceneMeshPrimitiveDescriptionFlags::Selected as u32,
+            ..*self
+        }
+    }
 }
 
 #[repr(C)]
 pub enum SceneMeshPrimitiveDescriptionFlags {
     UseColorMultipliers = 1,
+    Selected = 2,
 }
 
 pub struct MeshTable<'a> {
@@ -130,18 +168,38 @@ pub struct MeshTable<'a> {
 }
 
 impl<'a> MeshTable<'a> {
-    pub fn new(asset: &'a SceneAsset) -> Self {
-        log_debug!("iterating meshes");
-        let meshes = asset.document().meshes()
-            .map(|v| Mesh::new(v, asset.buffers()));
-        log_debug!("constructing mesh primitives");
-        let primitives: Vec<MeshPrimitive> = meshes
-            .flat_map(|mesh| {
-                let mesh_index = mesh.index();
-                mesh.into_primitives()
-                    .into_iter()
-                    .map(move |mesh| (mesh_index, mesh))
-            })
+    pub fn new(asset: &'a SceneAsset) -> Result<Self> {
+        Self::from_assets(&[asset])
+    }
+
+    // the meshes and materials of each asset are numbered after those of the previous ones
+    pub fn from_assets(assets: &[&'a SceneAsset]) -> Result<Self> {
+        let mut primitives: Vec<(usize, Primitive<'a>)> = vec![];
+        let mut mesh_offset: usize = 0;
+        let mut material_offset: usize = 0;
+        for asset in assets {
+            log_debug!("iterating meshes");
+            let meshes = asset.document().meshes()
+                .map(|v| Mesh::new(v, asset.buffers()))
+                .collect::<Result<Vec<_>>>()?;
+            log_debug!("constructing mesh primitives");
+            let asset_primitives = meshes.into_iter()
+                .flat_map(|mesh| {
+                    let mesh_index = mesh_offset + mesh.index();
+                    mesh.into_primitives()
+                        .into_iter()
+                        .map(move |mesh| (mesh_index, mesh.with_material_offset(material_offset)))
+                });
+            primitives.extend(asset_primitives);
+            mesh_offset += asset.document().meshes().count();
+            material_offset += asset.document().materials().count();
+        }
+        Ok(Self::from_primitives(primitives))
+    }
+
+    // takes pairs of a mesh index and its primitive then lays them out in the shared buffers
+    pub fn from_primitives(primitives: Vec<(usize, Primitive<'a>)>) -> Self {
+        let primitives: Vec<MeshPrimitive> = primitives.into_iter()
             .scan(MeshPrimitiveOffset::default(), |state, item| {
                 let (mesh_index, primitive) = item;
                 let offset = state.clone();
@@ -167,8 +225,9 @@ impl<'a> MeshTable<'a> {
     }
 
     fn get(&self, mesh_index: usize) -> Vec<&MeshPrimitive<'a>> {
-        self.mesh_table.get(&mesh_index).unwrap()
+        self.mesh_table.get(&mesh_index)
             .into_iter()
+            .flatten()
             .filter_map(|&v| self.primitives.get(v))
             .collect()
     }
@@ -181,19 +240,42 @@ impl<'a> MeshTable<'a> {
 pub struct MeshNode<'a, 'b: 'a> {
     primitive: &'b MeshPrimitive<'a>,
     transform: glm::Mat4,
+    name: Option<String>,
 }
 
 impl<'a, 'b: 'a> MeshNode<'a, 'b> {
     pub fn new(node: FlattenNode<'a>, mesh_table: &'b MeshTable<'a>) -> Option<Vec<Self>> {
+        Self::with_offset(node, 0, &glm::identity(), mesh_table)
+    }
+
+    // places a node of an asset whose meshes start at `mesh_offset` in the table
+    pub fn with_offset(node: FlattenNode<'a>, mesh_offset: usize, root_transform: &glm::Mat4, mesh_table: &'b MeshTable<'a>) -> Option<Vec<Self>> {
         let mesh = node.node().mesh()?;
-        let transform = node.transform();
-        let primitives = mesh_table.get(mesh.index());
-        let nodes = primitives.into_iter()
-            .map(|primitive| MeshNode { primitive, transform: transform.clone() })
+        let transform = root_transform * node.transform();
+        let name = node.node().name().map(|v| v.to_owned());
+        let nodes = Self::from_mesh(mesh_offset + mesh.index(), &transform, mesh_table)
+            .into_iter()
+            .map(|v| v.with_name(name.clone()))
             .collect();
         Some(nodes)
     }
 
+    pub fn from_mesh(mesh_index: usize, transform: &glm::Mat4, mesh_table: &'b MeshTable<'a>) -> Vec<Self> {
+        mesh_table.get(mesh_index).into_iter()
+            .map(|primitive| MeshNode { primitive, transform: *transform, name: None })
+            .collect()
+    }
+
+    pub fn from_primitive(primitive_index: usize, transform: &glm::Mat4, mesh_table: &'b MeshTable<'a>) -> Option<Self> {
+        let primitive = mesh_table.mesh_primitives().get(primitive_index)?;
+        Some(MeshNode { primitive, transform: *transform, name: None })
+    }
+
+    pub fn with_name(mut self, name: Option<String>) -> Self {
+        self.name = name;
+        self
+    }
+
     pub fn primitive(&self) -> &'b MeshPrimitive<'a> {
         self.primitive
     }
@@ -201,6 +283,10 @@ impl<'a, 'b: 'a> MeshNode<'a, 'b> {
     pub fn transform(&self) -> &glm::Mat4 {
         &self.transform
     }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
 }
 
 #[allow(dead_code)]
@@ -221,6 +307,10 @@ impl SceneMeshPrimitive {
     pub fn bottom_level_acceleration_structure(&self) -> &Arc<BottomLevelAccelerationStructure> {
         &self.structure
     }
+
+    pub fn material_index(&self) -> Option<usize> {
+        self.geometry.material_index
+    }
 }
 
 #[allow(dead_code)]
@@ -233,11 +323,13 @@ pub struct SceneMeshPrimitiveGeometry {
 }
 
 impl SceneMeshPrimitiveGeometry {
-    pub fn new(mesh_primitive: &MeshPrimitive, staging_buffers: &Arc<SceneStagingBuffers>, _command_pool: &Arc<CommandPool>) -> Arc<Self> {
+    pub fn new(mesh_primitive: &MeshPrimitive, staging_buffers: &Arc<SceneStagingBuffers>, _command_pool: &Arc<CommandPool>) -> Result<Arc<Self>> {
         let vertex_stride = std::mem::size_of::<[f32; 3]>();
         let num_vertices = mesh_primitive.primitive().positions().count();
         let num_indices = mesh_primitive.primitive().indices().count();
-        assert_eq!(mesh_primitive.primitive().positions().count(), mesh_primitive.primitive.normals().count());
+        if mesh_primitive.primitive().positions().count() != mesh_primitive.primitive.normals().count() {
+            return Err(ErrorCode::MeshFormatInvalid.into());
+        }
         let structure_geometry = BottomLevelAccelerationStructureGeometry::triangles(
             num_vertices as u32, 
             vertex_stride as VkDeviceSize,
@@ -255,7 +347,7 @@ impl SceneMeshPrimitiveGeometry {
             structure_geometry,
             material_index: mesh_primitive.primitive.material_index(),
         };
-        Arc::new(v)
+        Ok(Arc::new(v))
     }
 
     pub fn structure_geometry(&self) -> &Arc<BottomLevelAccelerationStructureGeometry> {
@@ -327,15 +419,16 @@ struct Mesh<'a> {
 }
 
 impl<'a> Mesh<'a> {
-    fn new(mesh: gltf::Mesh<'a>, buffers: &'a Vec<gltf::buffer::Data>) -> Self {
+    fn new(mesh: gltf::Mesh<'a>, buffers: &'a [gltf::buffer::Data]) -> Result<Self> {
         let index = mesh.index();
         let primitives = mesh.primitives()
             .map(|v| Primitive::new(v, buffers))
-            .collect();
-        Self {
+            .collect::<Result<Vec<_>>>()?;
+        let mesh = Self {
             index,
             primitives,
-        }
+        };
+        Ok(mesh)
     }
 
     fn into_primitives(self) -> Vec<Primitive<'a>> {
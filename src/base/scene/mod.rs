@@ -1,8 +1,12 @@
 
 mod asset;
+mod resolver;
 mod material;
-mod aabb;
+use super::aabb;
 mod procedural;
+mod shape;
+mod point_cloud;
+mod curve;
 mod primitive;
 mod mesh;
 mod buffer;
@@ -10,7 +14,32 @@ mod image;
 mod image_provider;
 mod material_repository;
 mod scene;
+mod custom;
+mod obj;
+mod ply;
+mod stl;
+mod inspect;
+mod cache;
+mod bvh;
+mod reference;
+mod query;
+mod watcher;
 
-pub use scene::Scene;
+pub use scene::{Scene, SceneInstance, SceneMeshSummary};
 pub use asset::SceneAsset;
-pub use scene::SceneBuilder;
+pub use resolver::{UriResolver, FileResolver, MemoryResolver};
+pub use scene::{SceneBuilder, SceneBuildOptions};
+pub use custom::{CustomSceneBuilder, CustomMesh, CustomMaterial};
+pub use obj::{ObjAsset, ObjMesh, ObjMaterial};
+pub use ply::PlyAsset;
+pub use stl::StlAsset;
+pub use cache::{SceneCache, SceneCacheKey, SCENE_CACHE_LOADER_VERSION};
+pub use watcher::SceneWatcher;
+pub use procedural::{ProceduralGeometry, ProceduralPrimitive, ProceduralMaterial, SphereGenerator};
+pub use curve::{Curves, CurveSegment};
+pub use point_cloud::{PointCloud, PointCloudSplat};
+pub use shape::{ProceduralShape, ProceduralSdf, ProceduralShapeDescription};
+pub use bvh::Bvh;
+pub use query::{SceneQuery, SceneHit};
+pub use reference::{ReferenceScene, ReferenceRenderer, ReferenceImage};
+pub use inspect::{SceneReport, PrimitiveReport, MaterialReport, ImageReport, AttributeReport, AttributePath, MemoryEstimate};
@@ -0,0 +1,849 @@
+
+use nalgebra_glm as glm;
+
+use image_crate::DynamicImage;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use crate::vk::Result;
+use crate::vk::*;
+
+use super::custom::*;
+
+// Wavefront OBJ with MTL material libraries.
+// polygons are triangulated, and normals are generated per smoothing group when absent.
+// @see http://paulbourke.net/dataformats/obj/
+// @see http://paulbourke.net/dataformats/mtl/
+pub struct ObjAsset {
+    meshes: Vec<ObjMesh>,
+    materials: Vec<ObjMaterial>,
+}
+
+impl ObjAsset {
+    pub fn new<P>(path: P) -> Result<Self> where P: AsRef<Path> {
+        log_debug!("loading obj asset");
+        let path = path.as_ref();
+        let base = path.parent().unwrap_or_else(|| Path::new("./"));
+        let file = File::open(path)
+            .map_err(|e| Error::from(e).with_path(path))?;
+        let mut parser = ObjParser::new(base);
+        let lines = logical_lines(BufReader::new(file))
+            .map_err(|e| e.with_path(path))?;
+        for line in lines {
+            parser.parse_line(&line)
+                .map_err(|e| e.with_path(path))?;
+        }
+        let (meshes, materials) = parser.complete();
+        log_debug!("loading obj asset complete");
+        let asset = Self {
+            meshes,
+            materials,
+        };
+        Ok(asset)
+    }
+
+    pub fn meshes(&self) -> &Vec<ObjMesh> {
+        &self.meshes
+    }
+
+    pub fn materials(&self) -> &Vec<ObjMaterial> {
+        &self.materials
+    }
+
+    // places every mesh at the origin
+    pub fn into_builder(self) -> Result<CustomSceneBuilder> {
+        let mut builder = CustomSceneBuilder::new();
+        let material_indices: Vec<usize> = self.materials.into_iter()
+            .map(|v| builder.add_material(v.into_custom_material()))
+            .collect();
+        for mesh in self.meshes.into_iter() {
+            let material_index = mesh.material_index
+                .and_then(|v| material_indices.get(v).copied());
+            let mut custom_mesh = CustomMesh::new(mesh.positions, mesh.indices)
+                .with_normals(mesh.normals)
+                .with_texcoords(mesh.texcoords);
+            if let Some(material_index) = material_index {
+                custom_mesh = custom_mesh.with_material(material_index);
+            }
+            let mesh_index = builder.add_mesh(custom_mesh)?;
+            builder.add_instance(mesh_index, glm::identity())?;
+        }
+        Ok(builder)
+    }
+}
+
+// triangles sharing an object or group name and a material
+pub struct ObjMesh {
+    name: String,
+    material_index: Option<usize>,
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    texcoords: Vec<[f32; 2]>,
+    indices: Vec<u32>,
+}
+
+impl ObjMesh {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn material_index(&self) -> Option<usize> {
+        self.material_index
+    }
+
+    pub fn num_vertices(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn num_triangles(&self) -> usize {
+        self.indices.len() / 3
+    }
+}
+
+pub struct ObjMaterial {
+    pub name: String,
+    pub diffuse: [f32; 3],
+    pub specular: [f32; 3],
+    pub shininess: f32,
+    pub dissolve: f32,
+    pub emission: [f32; 3],
+    pub diffuse_image: Option<DynamicImage>,
+    pub bump_image: Option<DynamicImage>,
+    pub bump_multiplier: f32,
+    pub normal_image: Option<DynamicImage>,
+    pub dissolve_image: Option<DynamicImage>,
+}
+
+impl ObjMaterial {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            diffuse: [0.8, 0.8, 0.8],
+            specular: [0.0, 0.0, 0.0],
+            shininess: 0.0,
+            dissolve: 1.0,
+            emission: [0.0, 0.0, 0.0],
+            diffuse_image: None,
+            bump_image: None,
+            bump_multiplier: 1.0,
+            normal_image: None,
+            dissolve_image: None,
+        }
+    }
+
+    // the triangle hit shader evaluates the diffuse term, the alpha mask and the normal map.
+    // specular, shininess and emission are kept in `ObjMaterial` for the applications.
+    fn into_custom_material(self) -> CustomMaterial {
+        let [r, g, b] = self.diffuse;
+        let mut material = CustomMaterial::new()
+            .with_name(self.name)
+            .with_base_color([r, g, b, self.dissolve]);
+        let has_dissolve_image = self.dissolve_image.is_some();
+        let color_image = match (self.diffuse_image, self.dissolve_image) {
+            (color, None) => color,
+            (color, Some(dissolve)) => Some(merge_alpha(color, &dissolve)),
+        };
+        if let Some(image) = color_image {
+            material = material.with_color_image(image);
+        }
+        if has_dissolve_image {
+            material = material.with_alpha_mask();
+        }
+        let bump_multiplier = self.bump_multiplier;
+        let bump_image = self.bump_image;
+        let normal_image = self.normal_image
+            .or_else(|| bump_image.map(|v| bump_to_normal(&v, bump_multiplier)));
+        if let Some(image) = normal_image {
+            material = material.with_normal_image(image);
+        }
+        material
+    }
+}
+
+// joins lines continued by a trailing backslash
+fn logical_lines(reader: impl BufRead) -> Result<Vec<String>> {
+    let mut lines = vec![];
+    let mut pending = String::new();
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim_end();
+        if let Some(line) = line.strip_suffix('\\') {
+            pending.push_str(line);
+            pending.push(' ');
+            continue;
+        }
+        pending.push_str(line);
+        lines.push(std::mem::take(&mut pending));
+    }
+    if !pending.is_empty() {
+        lines.push(pending);
+    }
+    Ok(lines)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum NormalKey {
+    Explicit(usize),
+    // shares the generated normal with the faces in the same smoothing group
+    Smooth(u32),
+    // every face has its own vertices
+    Flat(usize),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct VertexKey {
+    position: usize,
+    texcoord: Option<usize>,
+    normal: NormalKey,
+}
+
+struct ObjMeshBuilder {
+    name: String,
+    material_index: Option<usize>,
+    vertices: HashMap<VertexKey, u32>,
+    positions: Vec<[f32; 3]>,
+    normals: Vec<Option<[f32; 3]>>,
+    texcoords: Vec<[f32; 2]>,
+    indices: Vec<u32>,
+}
+
+impl ObjMeshBuilder {
+    fn new(name: &str, material_index: Option<usize>) -> Self {
+        Self {
+            name: name.to_owned(),
+            material_index,
+            vertices: HashMap::new(),
+            positions: vec![],
+            normals: vec![],
+            texcoords: vec![],
+            indices: vec![],
+        }
+    }
+
+    fn vertex(&mut self, key: VertexKey, parser: &ObjParser) -> u32 {
+        if let Some(&index) = self.vertices.get(&key) {
+            return index;
+        }
+        let index = self.positions.len() as u32;
+        self.positions.push(parser.positions[key.position]);
+        self.texcoords.push(key.texcoord.map_or([0.0, 0.0], |v| parser.texcoords[v]));
+        self.normals.push(match key.normal {
+            NormalKey::Explicit(v) => Some(parser.normals[v]),
+            _ => None,
+        });
+        self.vertices.insert(key, index);
+        index
+    }
+
+    fn complete(self) -> ObjMesh {
+        // generated normals are area weighted over the faces sharing a vertex
+        let smooth = smooth_normals(&self.positions, &self.indices);
+        let normals = self.normals.into_iter()
+            .zip(smooth)
+            .map(|(explicit, smooth)| explicit.unwrap_or(smooth))
+            .collect();
+        ObjMesh {
+            name: self.name,
+            material_index: self.material_index,
+            positions: self.positions,
+            normals,
+            texcoords: self.texcoords,
+            indices: self.indices,
+        }
+    }
+}
+
+struct ObjParser {
+    base: PathBuf,
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    texcoords: Vec<[f32; 2]>,
+    materials: Vec<ObjMaterial>,
+    material_table: HashMap<String, usize>,
+    images: HashMap<PathBuf, DynamicImage>,
+    meshes: Vec<ObjMesh>,
+    current: Option<ObjMeshBuilder>,
+    name: String,
+    material_index: Option<usize>,
+    smoothing_group: u32,
+    num_faces: usize,
+}
+
+impl ObjParser {
+    fn new(base: &Path) -> Self {
+        Self {
+            base: base.to_owned(),
+            positions: vec![],
+            normals: vec![],
+            texcoords: vec![],
+            materials: vec![],
+            material_table: HashMap::new(),
+            images: HashMap::new(),
+            meshes: vec![],
+            current: None,
+            name: String::new(),
+            material_index: None,
+            smoothing_group: 0,
+            num_faces: 0,
+        }
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<()> {
+        let line = line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+        match keyword {
+            "v" => {
+                let v = parse_floats::<3>(tokens)?;
+                self.positions.push(v);
+            },
+            "vn" => {
+                let v = parse_floats::<3>(tokens)?;
+                self.normals.push(v);
+            },
+            "vt" => {
+                // flips vertically since OBJ places the origin at the bottom left
+                let [u, v] = parse_floats::<2>(tokens)?;
+                self.texcoords.push([u, 1.0 - v]);
+            },
+            "f" => {
+                let corners: Vec<&str> = tokens.collect();
+                self.parse_face(&corners)?;
+            },
+            "o" | "g" => {
+                let name = rest_of_line(line, keyword);
+                if name != self.name {
+                    self.flush();
+                    self.name = name.to_owned();
+                }
+            },
+            "usemtl" => {
+                let name = rest_of_line(line, keyword);
+                let material_index = self.material_table.get(name).copied();
+                if material_index != self.material_index {
+                    self.flush();
+                    self.material_index = material_index;
+                }
+            },
+            "s" => {
+                self.smoothing_group = match tokens.next() {
+                    Some("off") | None => 0,
+                    Some(v) => v.parse().unwrap_or(0),
+                };
+            },
+            "mtllib" => {
+                // file names may contain spaces, which is ambiguous with multiple libraries.
+                // the whole name is tried first, then each name separated by whitespace.
+                let name = rest_of_line(line, keyword);
+                let path = self.base.join(normalize_path(name));
+                if path.is_file() {
+                    self.load_material_library(&path)?;
+                } else {
+                    for name in tokens {
+                        let path = self.base.join(normalize_path(name));
+                        self.load_material_library(&path)?;
+                    }
+                }
+            },
+            _ => {},
+        }
+        Ok(())
+    }
+
+    fn parse_face(&mut self, corners: &[&str]) -> Result<()> {
+        if corners.len() < 3 {
+            return Err(ErrorCode::MeshFormatInvalid.into());
+        }
+        let face_index = self.num_faces;
+        self.num_faces += 1;
+        let keys = corners.iter()
+            .map(|corner| self.parse_corner(corner, face_index))
+            .collect::<Result<Vec<VertexKey>>>()?;
+        let polygon: Vec<[f32; 3]> = keys.iter()
+            .map(|v| self.positions[v.position])
+            .collect();
+        let triangles = triangulate(&polygon);
+        let mut current = match self.current.take() {
+            Some(v) => v,
+            None => ObjMeshBuilder::new(&self.name, self.material_index),
+        };
+        let indices: Vec<u32> = keys.iter()
+            .map(|&key| current.vertex(key, self))
+            .collect();
+        for triangle in triangles {
+            current.indices.extend(triangle.iter().map(|&v| indices[v]));
+        }
+        self.current = Some(current);
+        Ok(())
+    }
+
+    // `v`, `v/vt`, `v//vn` or `v/vt/vn` with 1-based or negative relative indices
+    fn parse_corner(&self, corner: &str, face_index: usize) -> Result<VertexKey> {
+        let mut it = corner.split('/');
+        let position = it.next()
+            .and_then(|v| resolve_index(v, self.positions.len()))
+            .ok_or(ErrorCode::MeshFormatInvalid)?;
+        let texcoord = match it.next() {
+            Some(v) if !v.is_empty() => Some(resolve_index(v, self.texcoords.len())
+                .ok_or(ErrorCode::MeshFormatInvalid)?),
+            _ => None,
+        };
+        let normal = match it.next() {
+            Some(v) if !v.is_empty() => NormalKey::Explicit(resolve_index(v, self.normals.len())
+                .ok_or(ErrorCode::MeshFormatInvalid)?),
+            _ if self.smoothing_group != 0 => NormalKey::Smooth(self.smoothing_group),
+            _ => NormalKey::Flat(face_index),
+        };
+        let key = VertexKey {
+            position,
+            texcoord,
+            normal,
+        };
+        Ok(key)
+    }
+
+    fn flush(&mut self) {
+        if let Some(current) = self.current.take() {
+            if !current.indices.is_empty() {
+                self.meshes.push(current.complete());
+            }
+        }
+    }
+
+    fn complete(mut self) -> (Vec<ObjMesh>, Vec<ObjMaterial>) {
+        self.flush();
+        (self.meshes, self.materials)
+    }
+
+    fn load_material_library(&mut self, path: &Path) -> Result<()> {
+        let file = match File::open(path) {
+            Ok(v) => v,
+            Err(_) => {
+                // renders with the default material like other viewers do
+                log_debug!("material library not found {}", path.display());
+                return Ok(());
+            },
+        };
+        let base = path.parent().unwrap_or_else(|| Path::new("./")).to_owned();
+        let mut material: Option<ObjMaterial> = None;
+        for line in logical_lines(BufReader::new(file))? {
+            let line = line.split('#').next().unwrap_or("");
+            let mut tokens = line.split_whitespace();
+            let keyword = match tokens.next() {
+                Some(v) => v,
+                None => continue,
+            };
+            if keyword == "newmtl" {
+                if let Some(material) = material.take() {
+                    self.add_material(material);
+                }
+                material = Some(ObjMaterial::new(rest_of_line(line, keyword)));
+                continue;
+            }
+            let current = match material.as_mut() {
+                Some(v) => v,
+                None => continue,
+            };
+            match keyword {
+                "Kd" => current.diffuse = parse_floats::<3>(tokens)?,
+                "Ks" => current.specular = parse_floats::<3>(tokens)?,
+                "Ke" => current.emission = parse_floats::<3>(tokens)?,
+                "Ns" => current.shininess = parse_floats::<1>(tokens)?[0],
+                "d" => current.dissolve = parse_floats::<1>(tokens)?[0],
+                "Tr" => current.dissolve = 1.0 - parse_floats::<1>(tokens)?[0],
+                "map_Kd" => {
+                    let (name, _) = parse_texture_map(tokens);
+                    current.diffuse_image = self.load_image(&base, name);
+                },
+                "map_Bump" | "map_bump" | "bump" => {
+                    let (name, options) = parse_texture_map(tokens);
+                    current.bump_image = self.load_image(&base, name);
+                    if let Some(multiplier) = options.get("-bm").and_then(|v| v.first()) {
+                        current.bump_multiplier = multiplier.parse().unwrap_or(1.0);
+                    }
+                },
+                "norm" => {
+                    let (name, _) = parse_texture_map(tokens);
+                    current.normal_image = self.load_image(&base, name);
+                },
+                "map_d" => {
+                    let (name, _) = parse_texture_map(tokens);
+                    current.dissolve_image = self.load_image(&base, name);
+                },
+                _ => {},
+            }
+        }
+        if let Some(material) = material.take() {
+            self.add_material(material);
+        }
+        Ok(())
+    }
+
+    fn add_material(&mut self, material: ObjMaterial) {
+        self.material_table.insert(material.name.clone(), self.materials.len());
+        self.materials.push(material);
+    }
+
+    // textures referenced by several materials are decoded once
+    fn load_image(&mut self, base: &Path, name: Option<String>) -> Option<DynamicImage> {
+        let path = base.join(normalize_path(&name?));
+        if let Some(image) = self.images.get(&path) {
+            return Some(image.clone());
+        }
+        log_debug!("loading texture {}", path.display());
+        match image_crate::open(&path) {
+            Ok(image) => {
+                self.images.insert(path, image.clone());
+                Some(image)
+            },
+            Err(_) => {
+                log_debug!("texture not loaded {}", path.display());
+                None
+            },
+        }
+    }
+}
+
+fn parse_floats<'a, const N: usize>(tokens: impl Iterator<Item = &'a str>) -> Result<[f32; N]> {
+    let mut values = [0.0f32; N];
+    let mut tokens = tokens;
+    for value in values.iter_mut() {
+        *value = tokens.next()
+            .and_then(|v| v.parse().ok())
+            .ok_or(ErrorCode::MeshFormatInvalid)?;
+    }
+    Ok(values)
+}
+
+fn rest_of_line<'a>(line: &'a str, keyword: &str) -> &'a str {
+    line.trim_start()[keyword.len()..].trim()
+}
+
+// exporters on Windows write backslashes as separators
+fn normalize_path(name: &str) -> PathBuf {
+    PathBuf::from(name.replace('\\', "/"))
+}
+
+// 1-based, or negative ones relative to the end of the list
+fn resolve_index(token: &str, len: usize) -> Option<usize> {
+    let index: i64 = token.parse().ok()?;
+    let index = if index < 0 {
+        len as i64 + index
+    } else {
+        index - 1
+    };
+    if index >= 0 && (index as usize) < len {
+        Some(index as usize)
+    } else {
+        None
+    }
+}
+
+// splits the options such as `-bm 0.5` from the file name
+fn parse_texture_map<'a>(tokens: impl Iterator<Item = &'a str>) -> (Option<String>, HashMap<&'a str, Vec<&'a str>>) {
+    let tokens: Vec<&str> = tokens.collect();
+    let mut options = HashMap::new();
+    let mut i = 0;
+    while i < tokens.len() && tokens[i].starts_with('-') {
+        let option = tokens[i];
+        let num_args = match option {
+            "-o" | "-s" | "-t" => {
+                // takes up to three numbers
+                tokens[i + 1..].iter()
+                    .take(3)
+                    .take_while(|v| v.parse::<f32>().is_ok())
+                    .count()
+            },
+            "-mm" => 2,
+            _ => 1,
+        };
+        let end = (i + 1 + num_args).min(tokens.len());
+        options.insert(option, tokens[i + 1..end].to_vec());
+        i = end;
+    }
+    let name = if i < tokens.len() {
+        Some(tokens[i..].join(" "))
+    } else {
+        None
+    };
+    (name, options)
+}
+
+// ear clipping on the plane of the polygon, which also handles concave polygons.
+// returns indices into the given polygon.
+pub(super) fn triangulate(polygon: &[[f32; 3]]) -> Vec<[usize; 3]> {
+    let n = polygon.len();
+    if n == 3 {
+        return vec![[0, 1, 2]];
+    }
+    let fan = || (1..n - 1).map(|i| [0, i, i + 1]).collect();
+    // Newell's method
+    let mut normal = glm::vec3(0.0, 0.0, 0.0);
+    for i in 0..n {
+        let a = glm::make_vec3(&polygon[i]);
+        let b = glm::make_vec3(&polygon[(i + 1) % n]);
+        normal += glm::vec3(
+            (a.y - b.y) * (a.z + b.z),
+            (a.z - b.z) * (a.x + b.x),
+            (a.x - b.x) * (a.y + b.y));
+    }
+    if glm::length(&normal) <= 0.0 {
+        return fan();
+    }
+    let normal = glm::normalize(&normal);
+    let is_convex = |a: usize, b: usize, c: usize| {
+        let a = glm::make_vec3(&polygon[a]);
+        let b = glm::make_vec3(&polygon[b]);
+        let c = glm::make_vec3(&polygon[c]);
+        glm::dot(&glm::cross(&(b - a), &(c - b)), &normal) > 0.0
+    };
+    let contains = |a: usize, b: usize, c: usize, p: usize| {
+        let a = glm::make_vec3(&polygon[a]);
+        let b = glm::make_vec3(&polygon[b]);
+        let c = glm::make_vec3(&polygon[c]);
+        let p = glm::make_vec3(&polygon[p]);
+        let ab = glm::dot(&glm::cross(&(b - a), &(p - a)), &normal);
+        let bc = glm::dot(&glm::cross(&(c - b), &(p - b)), &normal);
+        let ca = glm::dot(&glm::cross(&(a - c), &(p - c)), &normal);
+        ab >= 0.0 && bc >= 0.0 && ca >= 0.0
+    };
+    let mut remaining: Vec<usize> = (0..n).collect();
+    let mut triangles = Vec::with_capacity(n - 2);
+    while remaining.len() > 3 {
+        let m = remaining.len();
+        let ear = (0..m).find(|&i| {
+            let a = remaining[(i + m - 1) % m];
+            let b = remaining[i];
+            let c = remaining[(i + 1) % m];
+            is_convex(a, b, c) && !remaining.iter()
+                .filter(|&&p| p != a && p != b && p != c)
+                .any(|&p| contains(a, b, c, p))
+        });
+        let i = match ear {
+            Some(v) => v,
+            // degenerate or self-intersecting polygons
+            None => return fan(),
+        };
+        let a = remaining[(i + m - 1) % m];
+        let b = remaining[i];
+        let c = remaining[(i + 1) % m];
+        triangles.push([a, b, c]);
+        remaining.remove(i);
+    }
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    triangles
+}
+
+// replaces the alpha channel of the color image with the luminance of the dissolve image
+fn merge_alpha(color: Option<DynamicImage>, dissolve: &DynamicImage) -> DynamicImage {
+    use image_crate::imageops::FilterType;
+    let dissolve = dissolve.to_luma8();
+    let mut color = match color {
+        Some(v) => v.to_rgba8(),
+        None => image_crate::RgbaImage::from_pixel(dissolve.width(), dissolve.height(), image_crate::Rgba([255, 255, 255, 255])),
+    };
+    let dissolve = if dissolve.dimensions() == color.dimensions() {
+        dissolve
+    } else {
+        image_crate::imageops::resize(&dissolve, color.width(), color.height(), FilterType::Triangle)
+    };
+    for (pixel, alpha) in color.pixels_mut().zip(dissolve.pixels()) {
+        pixel.0[3] = alpha.0[0];
+    }
+    DynamicImage::ImageRgba8(color)
+}
+
+// derives a tangent space normal map from a height map by central differences.
+// colored images are regarded as normal maps already.
+fn bump_to_normal(image: &DynamicImage, multiplier: f32) -> DynamicImage {
+    let rgba = image.to_rgba8();
+    let is_grayscale = rgba.pixels()
+        .all(|v| v.0[0] == v.0[1] && v.0[1] == v.0[2]);
+    if !is_grayscale {
+        return DynamicImage::ImageRgba8(rgba);
+    }
+    let height = image.to_luma8();
+    let (w, h) = height.dimensions();
+    let at = |x: i64, y: i64| {
+        let x = x.rem_euclid(w as i64) as u32;
+        let y = y.rem_euclid(h as i64) as u32;
+        height.get_pixel(x, y).0[0] as f32 / 255.0
+    };
+    let strength = 2.0 * multiplier;
+    let normal = image_crate::RgbaImage::from_fn(w, h, |x, y| {
+        let (x, y) = (x as i64, y as i64);
+        let dx = (at(x + 1, y) - at(x - 1, y)) * strength;
+        let dy = (at(x, y + 1) - at(x, y - 1)) * strength;
+        // texture rows grow downward while the bitangent points toward +v in OBJ
+        let n = glm::normalize(&glm::vec3(-dx, dy, 1.0));
+        let encode = |v: f32| ((v * 0.5 + 0.5) * 255.0).round() as u8;
+        image_crate::Rgba([encode(n.x), encode(n.y), encode(n.z), 255])
+    });
+    DynamicImage::ImageRgba8(normal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<(Vec<ObjMesh>, Vec<ObjMaterial>)> {
+        let mut parser = ObjParser::new(Path::new("./"));
+        for line in logical_lines(text.as_bytes())? {
+            parser.parse_line(&line)?;
+        }
+        Ok(parser.complete())
+    }
+
+    fn area(positions: &[[f32; 3]], indices: &[u32]) -> f32 {
+        indices.chunks(3)
+            .map(|v| {
+                let a = glm::make_vec3(&positions[v[0] as usize]);
+                let b = glm::make_vec3(&positions[v[1] as usize]);
+                let c = glm::make_vec3(&positions[v[2] as usize]);
+                glm::length(&glm::cross(&(b - a), &(c - a))) * 0.5
+            })
+            .sum()
+    }
+
+    #[test]
+    fn quad_with_all_attributes() {
+        let (meshes, _) = parse("
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            vt 0 0
+            vt 1 0
+            vt 1 1
+            vt 0 1
+            vn 0 0 1
+            f 1/1/1 2/2/1 3/3/1 4/4/1
+        ").unwrap();
+        assert_eq!(meshes.len(), 1);
+        let mesh = &meshes[0];
+        assert_eq!(mesh.num_vertices(), 4);
+        assert_eq!(mesh.num_triangles(), 2);
+        assert_eq!(mesh.normals, vec![[0.0, 0.0, 1.0]; 4]);
+        // flipped to the top left origin
+        assert_eq!(mesh.texcoords[0], [0.0, 1.0]);
+        assert_eq!(mesh.texcoords[2], [1.0, 0.0]);
+        assert!((area(&mesh.positions, &mesh.indices) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn relative_indices_and_continued_lines() {
+        let (meshes, _) = parse("
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            f -3 -2 \\
+              -1
+        ").unwrap();
+        assert_eq!(meshes[0].indices, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn generated_normals_face_the_winding() {
+        let (meshes, _) = parse("
+            v 0 0 0
+            v 0 1 0
+            v 1 0 0
+            f 1 2 3
+        ").unwrap();
+        for normal in meshes[0].normals.iter() {
+            assert!(glm::distance(&glm::make_vec3(normal), &glm::vec3(0.0, 0.0, -1.0)) < 1e-6);
+        }
+    }
+
+    #[test]
+    fn groups_split_the_meshes() {
+        let (meshes, _) = parse("
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            o first
+            f 1 2 3
+            o second
+            f 1 2 3
+            f 3 2 1
+        ").unwrap();
+        let names: Vec<&str> = meshes.iter().map(|v| v.name()).collect();
+        assert_eq!(names, vec!["first", "second"]);
+        assert_eq!(meshes[1].num_triangles(), 2);
+    }
+
+    #[test]
+    fn index_out_of_range_is_invalid() {
+        assert!(parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4").is_err());
+        assert!(parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 -4").is_err());
+        assert!(parse("v 0 0 0\nv 1 0 0\nf 1 2").is_err());
+    }
+
+    #[test]
+    fn materials_from_the_library() {
+        let directory = std::env::temp_dir().join(format!("kaldera-obj-test-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("scene.mtl"), "newmtl red\nKd 1 0 0\nd 0.5\nnewmtl blue\nKd 0 0 1\n").unwrap();
+        let path = directory.join("scene.obj");
+        std::fs::write(&path, "mtllib scene.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl blue\nf 1 2 3\nusemtl red\nf 1 2 3\n").unwrap();
+        let asset = ObjAsset::new(&path);
+        std::fs::remove_dir_all(&directory).unwrap();
+        let asset = asset.unwrap();
+        let materials = asset.materials();
+        assert_eq!(materials.len(), 2);
+        assert_eq!(materials[0].name, "red");
+        assert_eq!(materials[0].diffuse, [1.0, 0.0, 0.0]);
+        assert_eq!(materials[0].dissolve, 0.5);
+        let material_indices: Vec<Option<usize>> = asset.meshes().iter().map(|v| v.material_index()).collect();
+        assert_eq!(material_indices, vec![Some(1), Some(0)]);
+    }
+
+    #[test]
+    fn materials_from_several_libraries() {
+        let directory = std::env::temp_dir().join(format!("kaldera-obj-libraries-test-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("a.mtl"), "newmtl red\nKd 1 0 0\n").unwrap();
+        std::fs::write(directory.join("b.mtl"), "newmtl blue\nKd 0 0 1\n").unwrap();
+        let path = directory.join("scene.obj");
+        std::fs::write(&path, "mtllib a.mtl b.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl blue\nf 1 2 3\n").unwrap();
+        let asset = ObjAsset::new(&path);
+        std::fs::remove_dir_all(&directory).unwrap();
+        let asset = asset.unwrap();
+        let names: Vec<&str> = asset.materials().iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, vec!["red", "blue"]);
+        assert_eq!(asset.meshes()[0].material_index(), Some(1));
+    }
+
+    #[test]
+    fn library_names_may_contain_spaces() {
+        let directory = std::env::temp_dir().join(format!("kaldera-obj-spaces-test-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("my scene.mtl"), "newmtl red\nKd 1 0 0\n").unwrap();
+        let path = directory.join("scene.obj");
+        std::fs::write(&path, "mtllib my scene.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl red\nf 1 2 3\n").unwrap();
+        let asset = ObjAsset::new(&path);
+        std::fs::remove_dir_all(&directory).unwrap();
+        let asset = asset.unwrap();
+        assert_eq!(asset.materials().len(), 1);
+        assert_eq!(asset.meshes()[0].material_index(), Some(0));
+    }
+
+    #[test]
+    fn concave_polygons_keep_their_area() {
+        // a U shape, which a fan from the first corner would cover wrongly
+        let polygon = [
+            [0.0, 0.0, 0.0], [3.0, 0.0, 0.0], [3.0, 3.0, 0.0], [2.0, 3.0, 0.0],
+            [2.0, 1.0, 0.0], [1.0, 1.0, 0.0], [1.0, 3.0, 0.0], [0.0, 3.0, 0.0],
+        ];
+        let triangles = triangulate(&polygon);
+        assert_eq!(triangles.len(), 6);
+        let indices: Vec<u32> = triangles.iter().flatten().map(|&v| v as u32).collect();
+        assert!((area(&polygon, &indices) - 7.0).abs() < 1e-5);
+    }
+}
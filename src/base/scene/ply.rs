@@ -0,0 +1,532 @@
+
+use nalgebra_glm as glm;
+
+use std::path::Path;
+
+use crate::vk::Result;
+use crate::vk::*;
+
+use super::custom::*;
+use super::obj::triangulate;
+
+// Polygon File Format in ASCII or binary encoding.
+// reads the positions, normals, texture coordinates and colors of the vertex element
+// and the polygons of the face element. the other elements are skipped.
+// @see http://paulbourke.net/dataformats/ply/
+pub struct PlyAsset {
+    positions: Vec<[f32; 3]>,
+    normals: Option<Vec<[f32; 3]>>,
+    texcoords: Option<Vec<[f32; 2]>>,
+    colors: Option<Vec<[f32; 4]>>,
+    indices: Vec<u32>,
+}
+
+impl PlyAsset {
+    pub fn new<P>(path: P) -> Result<Self> where P: AsRef<Path> {
+        log_debug!("loading ply asset");
+        let path = path.as_ref();
+        let data = std::fs::read(path)
+            .map_err(|e| Error::from(e).with_path(path))?;
+        let asset = Self::from_slice(&data)
+            .map_err(|e| e.with_path(path))?;
+        log_debug!("loading ply asset complete");
+        Ok(asset)
+    }
+
+    pub fn from_slice(data: &[u8]) -> Result<Self> {
+        let (header, body) = PlyHeader::parse(data)?;
+        let mut reader = match header.format {
+            PlyFormat::Ascii => {
+                let text = std::str::from_utf8(body)
+                    .map_err(|_| ErrorCode::MeshFormatInvalid)?;
+                PlyReader::Ascii(text.split_whitespace())
+            },
+            PlyFormat::BinaryLittleEndian => PlyReader::Binary { data: body, offset: 0, is_big_endian: false },
+            PlyFormat::BinaryBigEndian => PlyReader::Binary { data: body, offset: 0, is_big_endian: true },
+        };
+        let mut asset = Self {
+            positions: vec![],
+            normals: None,
+            texcoords: None,
+            colors: None,
+            indices: vec![],
+        };
+        for element in header.elements.iter() {
+            match element.name.as_str() {
+                "vertex" => asset.read_vertices(element, &mut reader)?,
+                "face" => asset.read_faces(element, &mut reader)?,
+                _ => element.skip(&mut reader)?,
+            }
+        }
+        Ok(asset)
+    }
+
+    pub fn positions(&self) -> &Vec<[f32; 3]> {
+        &self.positions
+    }
+
+    pub fn normals(&self) -> Option<&Vec<[f32; 3]>> {
+        self.normals.as_ref()
+    }
+
+    pub fn colors(&self) -> Option<&Vec<[f32; 4]>> {
+        self.colors.as_ref()
+    }
+
+    pub fn indices(&self) -> &Vec<u32> {
+        &self.indices
+    }
+
+    // a single mesh at the origin with the default material.
+    // vertex colors multiply the material color.
+    pub fn into_builder(self) -> Result<CustomSceneBuilder> {
+        if self.indices.is_empty() {
+            return Err(ErrorCode::MeshNotFound.into());
+        }
+        let mut mesh = CustomMesh::new(self.positions, self.indices);
+        if let Some(normals) = self.normals {
+            mesh = mesh.with_normals(normals);
+        }
+        if let Some(texcoords) = self.texcoords {
+            mesh = mesh.with_texcoords(texcoords);
+        }
+        if let Some(colors) = self.colors {
+            mesh = mesh.with_colors(colors);
+        }
+        let mut builder = CustomSceneBuilder::new();
+        let mesh_index = builder.add_mesh(mesh)?;
+        builder.add_instance(mesh_index, glm::identity())?;
+        Ok(builder)
+    }
+
+    fn read_vertices(&mut self, element: &PlyElement, reader: &mut PlyReader) -> Result<()> {
+        let find = |names: &[&str]| element.properties.iter()
+            .position(|v| v.list_type.is_none() && names.contains(&v.name.as_str()));
+        let position_indices = [find(&["x"]), find(&["y"]), find(&["z"])];
+        let normal_indices = [find(&["nx"]), find(&["ny"]), find(&["nz"])];
+        let texcoord_indices = [
+            find(&["u", "s", "texture_u", "texture_s"]),
+            find(&["v", "t", "texture_v", "texture_t"]),
+        ];
+        let color_indices = [
+            find(&["red", "diffuse_red"]),
+            find(&["green", "diffuse_green"]),
+            find(&["blue", "diffuse_blue"]),
+            find(&["alpha", "diffuse_alpha"]),
+        ];
+        if position_indices.iter().any(|v| v.is_none()) {
+            return Err(ErrorCode::MeshFormatInvalid.into());
+        }
+        let has_normals = normal_indices.iter().all(|v| v.is_some());
+        let has_texcoords = texcoord_indices.iter().all(|v| v.is_some());
+        let has_colors = color_indices[..3].iter().all(|v| v.is_some());
+        let mut values = vec![0.0f64; element.properties.len()];
+        let mut positions = Vec::with_capacity(element.count);
+        let mut normals = Vec::with_capacity(if has_normals { element.count } else { 0 });
+        let mut texcoords = Vec::with_capacity(if has_texcoords { element.count } else { 0 });
+        let mut colors = Vec::with_capacity(if has_colors { element.count } else { 0 });
+        for _ in 0..element.count {
+            for (value, property) in values.iter_mut().zip(element.properties.iter()) {
+                match property.list_type {
+                    Some(count_type) => {
+                        let count = reader.read(count_type)? as usize;
+                        for _ in 0..count {
+                            reader.read(property.value_type)?;
+                        }
+                    },
+                    None => *value = reader.read(property.value_type)?,
+                }
+            }
+            let at = |index: Option<usize>| index.map_or(0.0, |v| values[v] as f32);
+            positions.push([at(position_indices[0]), at(position_indices[1]), at(position_indices[2])]);
+            if has_normals {
+                normals.push([at(normal_indices[0]), at(normal_indices[1]), at(normal_indices[2])]);
+            }
+            if has_texcoords {
+                // flips vertically like OBJ
+                texcoords.push([at(texcoord_indices[0]), 1.0 - at(texcoord_indices[1])]);
+            }
+            if has_colors {
+                // integer colors are sRGB encoded while the shaders multiply linear ones
+                let channel = |index: Option<usize>| {
+                    let property = &element.properties[index.unwrap()];
+                    match property.value_type.normalizer() {
+                        Some(max) => srgb_to_linear(values[index.unwrap()] as f32 / max),
+                        None => values[index.unwrap()] as f32,
+                    }
+                };
+                let alpha = color_indices[3].map_or(1.0, |index| {
+                    let property = &element.properties[index];
+                    let max = property.value_type.normalizer().unwrap_or(1.0);
+                    values[index] as f32 / max
+                });
+                colors.push([channel(color_indices[0]), channel(color_indices[1]), channel(color_indices[2]), alpha]);
+            }
+        }
+        self.positions = positions;
+        self.normals = if has_normals { Some(normals) } else { None };
+        self.texcoords = if has_texcoords { Some(texcoords) } else { None };
+        self.colors = if has_colors { Some(colors) } else { None };
+        Ok(())
+    }
+
+    fn read_faces(&mut self, element: &PlyElement, reader: &mut PlyReader) -> Result<()> {
+        let list_index = element.properties.iter()
+            .position(|v| v.list_type.is_some() && (v.name == "vertex_indices" || v.name == "vertex_index"))
+            .ok_or(ErrorCode::MeshFormatInvalid)?;
+        self.indices.reserve(element.count * 3);
+        let mut polygon = vec![];
+        for _ in 0..element.count {
+            for (i, property) in element.properties.iter().enumerate() {
+                let count_type = match property.list_type {
+                    Some(v) => v,
+                    None => {
+                        reader.read(property.value_type)?;
+                        continue;
+                    },
+                };
+                let count = reader.read(count_type)? as usize;
+                polygon.clear();
+                for _ in 0..count {
+                    let index = reader.read(property.value_type)?;
+                    polygon.push(index as u32);
+                }
+                if i != list_index || count < 3 {
+                    continue;
+                }
+                if polygon.iter().any(|&v| v as usize >= self.positions.len()) {
+                    return Err(ErrorCode::MeshFormatInvalid.into());
+                }
+                if count == 3 {
+                    self.indices.extend_from_slice(&polygon);
+                    continue;
+                }
+                let points: Vec<[f32; 3]> = polygon.iter()
+                    .map(|&v| self.positions[v as usize])
+                    .collect();
+                for triangle in triangulate(&points) {
+                    self.indices.extend(triangle.iter().map(|&v| polygon[v]));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy)]
+enum PlyType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl PlyType {
+    fn parse(name: &str) -> Option<Self> {
+        let ty = match name {
+            "char" | "int8" => Self::Int8,
+            "uchar" | "uint8" => Self::UInt8,
+            "short" | "int16" => Self::Int16,
+            "ushort" | "uint16" => Self::UInt16,
+            "int" | "int32" => Self::Int32,
+            "uint" | "uint32" => Self::UInt32,
+            "float" | "float32" => Self::Float32,
+            "double" | "float64" => Self::Float64,
+            _ => return None,
+        };
+        Some(ty)
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Self::Int8 | Self::UInt8 => 1,
+            Self::Int16 | Self::UInt16 => 2,
+            Self::Int32 | Self::UInt32 | Self::Float32 => 4,
+            Self::Float64 => 8,
+        }
+    }
+
+    // the maximum of the unsigned types used to normalize colors
+    fn normalizer(&self) -> Option<f32> {
+        match self {
+            Self::UInt8 => Some(255.0),
+            Self::UInt16 => Some(65535.0),
+            _ => None,
+        }
+    }
+}
+
+struct PlyProperty {
+    name: String,
+    value_type: PlyType,
+    // the type of the item count for list properties
+    list_type: Option<PlyType>,
+}
+
+struct PlyElement {
+    name: String,
+    count: usize,
+    properties: Vec<PlyProperty>,
+}
+
+impl PlyElement {
+    fn skip(&self, reader: &mut PlyReader) -> Result<()> {
+        for _ in 0..self.count {
+            for property in self.properties.iter() {
+                let count = match property.list_type {
+                    Some(v) => reader.read(v)? as usize,
+                    None => 1,
+                };
+                for _ in 0..count {
+                    reader.read(property.value_type)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+struct PlyHeader {
+    format: PlyFormat,
+    elements: Vec<PlyElement>,
+}
+
+impl PlyHeader {
+    // returns the header and the body following `end_header`
+    fn parse(data: &[u8]) -> Result<(Self, &[u8])> {
+        const END_HEADER: &[u8] = b"end_header";
+        let end = data.windows(END_HEADER.len())
+            .position(|v| v == END_HEADER)
+            .ok_or(ErrorCode::MeshFormatInvalid)?;
+        // the body starts after the line feed of `end_header`
+        let body_offset = data[end..].iter()
+            .position(|&v| v == b'\n')
+            .map_or(data.len(), |v| end + v + 1);
+        let text = std::str::from_utf8(&data[..end])
+            .map_err(|_| ErrorCode::MeshFormatInvalid)?;
+        let mut lines = text.lines().map(|v| v.trim());
+        if lines.next() != Some("ply") {
+            return Err(ErrorCode::MeshFormatInvalid.into());
+        }
+        let mut format = None;
+        let mut elements: Vec<PlyElement> = vec![];
+        for line in lines {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            match tokens.as_slice() {
+                ["format", name, ..] => {
+                    format = match *name {
+                        "ascii" => Some(PlyFormat::Ascii),
+                        "binary_little_endian" => Some(PlyFormat::BinaryLittleEndian),
+                        "binary_big_endian" => Some(PlyFormat::BinaryBigEndian),
+                        _ => return Err(ErrorCode::MeshFormatInvalid.into()),
+                    };
+                },
+                ["element", name, count] => {
+                    let count = count.parse()
+                        .map_err(|_| ErrorCode::MeshFormatInvalid)?;
+                    elements.push(PlyElement {
+                        name: name.to_string(),
+                        count,
+                        properties: vec![],
+                    });
+                },
+                ["property", "list", count_type, value_type, name] => {
+                    let property = PlyProperty {
+                        name: name.to_string(),
+                        value_type: PlyType::parse(value_type).ok_or(ErrorCode::MeshFormatInvalid)?,
+                        list_type: Some(PlyType::parse(count_type).ok_or(ErrorCode::MeshFormatInvalid)?),
+                    };
+                    elements.last_mut()
+                        .ok_or(ErrorCode::MeshFormatInvalid)?
+                        .properties.push(property);
+                },
+                ["property", value_type, name] => {
+                    let property = PlyProperty {
+                        name: name.to_string(),
+                        value_type: PlyType::parse(value_type).ok_or(ErrorCode::MeshFormatInvalid)?,
+                        list_type: None,
+                    };
+                    elements.last_mut()
+                        .ok_or(ErrorCode::MeshFormatInvalid)?
+                        .properties.push(property);
+                },
+                _ => {},
+            }
+        }
+        let header = Self {
+            format: format.ok_or(ErrorCode::MeshFormatInvalid)?,
+            elements,
+        };
+        Ok((header, &data[body_offset..]))
+    }
+}
+
+enum PlyReader<'a> {
+    Ascii(std::str::SplitWhitespace<'a>),
+    Binary {
+        data: &'a [u8],
+        offset: usize,
+        is_big_endian: bool,
+    },
+}
+
+impl<'a> PlyReader<'a> {
+    fn read(&mut self, ty: PlyType) -> Result<f64> {
+        match self {
+            Self::Ascii(tokens) => {
+                tokens.next()
+                    .and_then(|v| v.parse().ok())
+                    .ok_or_else(|| ErrorCode::MeshFormatInvalid.into())
+            },
+            Self::Binary { data, offset, is_big_endian } => {
+                let size = ty.size();
+                let bytes = data.get(*offset..*offset + size)
+                    .ok_or(ErrorCode::MeshFormatInvalid)?;
+                *offset += size;
+                let mut buf = [0u8; 8];
+                buf[..size].copy_from_slice(bytes);
+                if *is_big_endian {
+                    buf[..size].reverse();
+                }
+                let value = match ty {
+                    PlyType::Int8 => buf[0] as i8 as f64,
+                    PlyType::UInt8 => buf[0] as f64,
+                    PlyType::Int16 => i16::from_le_bytes([buf[0], buf[1]]) as f64,
+                    PlyType::UInt16 => u16::from_le_bytes([buf[0], buf[1]]) as f64,
+                    PlyType::Int32 => i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+                    PlyType::UInt32 => u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+                    PlyType::Float32 => f32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+                    PlyType::Float64 => f64::from_le_bytes(buf),
+                };
+                Ok(value)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUAD_HEADER: &str = "ply
+format {} 1.0
+comment a unit quad
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+element edge 1
+property int vertex1
+property int vertex2
+end_header
+";
+
+    const QUAD_POSITIONS: [[f32; 3]; 4] = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]];
+
+    // the quad with the colors and the edge in the binary encoding
+    fn binary_quad(is_big_endian: bool) -> Vec<u8> {
+        let format = if is_big_endian { "binary_big_endian" } else { "binary_little_endian" };
+        let mut data = QUAD_HEADER.replace("{}", format).into_bytes();
+        let mut push = |bytes: &[u8]| {
+            if is_big_endian {
+                data.extend(bytes.iter().rev());
+            } else {
+                data.extend_from_slice(bytes);
+            }
+        };
+        for p in QUAD_POSITIONS.iter() {
+            for v in p.iter() {
+                push(&v.to_le_bytes());
+            }
+            push(&[255]);
+            push(&[0]);
+            push(&[0]);
+        }
+        push(&[4]);
+        for i in 0..4i32 {
+            push(&i.to_le_bytes());
+        }
+        push(&0i32.to_le_bytes());
+        push(&1i32.to_le_bytes());
+        data
+    }
+
+    fn ascii_quad() -> Vec<u8> {
+        let body = "0 0 0 255 0 0\n1 0 0 255 0 0\n1 1 0 255 0 0\n0 1 0 255 0 0\n4 0 1 2 3\n0 1\n";
+        (QUAD_HEADER.replace("{}", "ascii") + body).into_bytes()
+    }
+
+    fn assert_quad(asset: &PlyAsset) {
+        assert_eq!(asset.positions(), &QUAD_POSITIONS.to_vec());
+        assert_eq!(asset.indices().len(), 6);
+        assert!(asset.normals().is_none());
+        let colors = asset.colors().unwrap();
+        assert_eq!(colors, &vec![[1.0, 0.0, 0.0, 1.0]; 4]);
+    }
+
+    #[test]
+    fn ascii() {
+        assert_quad(&PlyAsset::from_slice(&ascii_quad()).unwrap());
+    }
+
+    #[test]
+    fn binary_in_both_byte_orders() {
+        assert_quad(&PlyAsset::from_slice(&binary_quad(false)).unwrap());
+        assert_quad(&PlyAsset::from_slice(&binary_quad(true)).unwrap());
+    }
+
+    #[test]
+    fn normals_and_texcoords() {
+        let data = "ply
+format ascii 1.0
+element vertex 3
+property double x
+property double y
+property double z
+property float nx
+property float ny
+property float nz
+property float s
+property float t
+element face 1
+property list uchar uint vertex_index
+end_header
+0 0 0 0 0 1 0 0
+1 0 0 0 0 1 1 0
+0 1 0 0 0 1 0 1
+3 0 1 2
+";
+        let asset = PlyAsset::from_slice(data.as_bytes()).unwrap();
+        assert_eq!(asset.indices(), &vec![0, 1, 2]);
+        assert_eq!(asset.normals().unwrap(), &vec![[0.0, 0.0, 1.0]; 3]);
+        // flipped to the top left origin
+        assert_eq!(asset.texcoords.as_ref().unwrap()[2], [0.0, 0.0]);
+    }
+
+    #[test]
+    fn invalid_files() {
+        let truncated = binary_quad(false);
+        assert!(PlyAsset::from_slice(&truncated[..truncated.len() - 12]).is_err());
+        let out_of_range = String::from_utf8(ascii_quad()).unwrap().replace("4 0 1 2 3", "4 0 1 2 4");
+        assert!(PlyAsset::from_slice(out_of_range.as_bytes()).is_err());
+        let unknown_format = String::from_utf8(ascii_quad()).unwrap().replace("ascii", "utf16");
+        assert!(PlyAsset::from_slice(unknown_format.as_bytes()).is_err());
+        assert!(PlyAsset::from_slice(b"solid cube\nendsolid cube\n").is_err());
+    }
+}
@@ -0,0 +1,171 @@
+
+use nalgebra_glm as glm;
+
+use std::path::Path;
+
+use crate::vk::Result;
+use crate::vk::*;
+
+use super::custom::srgb_to_linear;
+use super::ply::PlyAsset;
+use super::procedural::*;
+use super::shape::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PointCloudSplat {
+    Sphere,
+    // oriented by the point normals. points without normals are splatted as spheres.
+    Disc,
+}
+
+// points splatted with a radius and rendered through the procedural geometry path. the colors end
+// up in the material buffer looked up by the procedural closest hit shader.
+pub struct PointCloud {
+    positions: Vec<[f32; 3]>,
+    normals: Option<Vec<[f32; 3]>>,
+    colors: Option<Vec<[f32; 4]>>,
+    radius: f32,
+    splat: PointCloudSplat,
+}
+
+impl PointCloud {
+    pub fn new(positions: Vec<[f32; 3]>) -> Self {
+        Self {
+            positions,
+            normals: None,
+            colors: None,
+            radius: 0.01,
+            splat: PointCloudSplat::Sphere,
+        }
+    }
+
+    // linear RGBA
+    pub fn with_colors(mut self, colors: Vec<[f32; 4]>) -> Self {
+        self.colors = Some(colors);
+        self
+    }
+
+    pub fn with_normals(mut self, normals: Vec<[f32; 3]>) -> Self {
+        self.normals = Some(normals);
+        self
+    }
+
+    pub fn with_radius(mut self, radius: f32) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    pub fn with_splat(mut self, splat: PointCloudSplat) -> Self {
+        self.splat = splat;
+        self
+    }
+
+    // the vertices of the asset regardless of its faces
+    pub fn from_ply(asset: &PlyAsset) -> Self {
+        let mut this = Self::new(asset.positions().clone());
+        this.normals = asset.normals().cloned();
+        this.colors = asset.colors().cloned();
+        this
+    }
+
+    pub fn from_xyz_file<P>(path: P) -> Result<Self> where P: AsRef<Path> {
+        log_debug!("loading xyz point cloud");
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| Error::from(e).with_path(path))?;
+        let this = Self::from_xyz(&text)
+            .map_err(|e| e.with_path(path))?;
+        log_debug!("loading xyz point cloud complete");
+        Ok(this)
+    }
+
+    // one point per line as `x y z`, `x y z r g b` or `x y z r g b nx ny nz`, where integer
+    // colors are sRGB encoded in 0-255. lines starting with `#` are ignored.
+    pub fn from_xyz(text: &str) -> Result<Self> {
+        let mut positions = vec![];
+        let mut colors = vec![];
+        let mut normals = vec![];
+        let mut columns: Option<usize> = None;
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+            let values: Vec<f32> = line.split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|v| !v.is_empty())
+                .map(|v| v.parse::<f32>())
+                .collect::<std::result::Result<_, _>>()
+                .map_err(|_| ErrorCode::MeshFormatInvalid)?;
+            // every line has to agree with the first one
+            let count = *columns.get_or_insert(values.len());
+            if values.len() != count || !(count == 3 || count == 6 || count == 9) {
+                return Err(ErrorCode::MeshFormatInvalid.into())
+            }
+            positions.push([values[0], values[1], values[2]]);
+            if count >= 6 {
+                colors.push([values[3], values[4], values[5]]);
+            }
+            if count == 9 {
+                normals.push([values[6], values[7], values[8]]);
+            }
+        }
+        if positions.is_empty() {
+            return Err(ErrorCode::MeshNotFound.into())
+        }
+        let mut this = Self::new(positions);
+        if !colors.is_empty() {
+            let is_integer = colors.iter().flatten().any(|v| *v > 1.0);
+            let colors = colors.into_iter()
+                .map(|[r, g, b]| if is_integer {
+                    [srgb_to_linear(r / 255.0), srgb_to_linear(g / 255.0), srgb_to_linear(b / 255.0), 1.0]
+                } else {
+                    [r, g, b, 1.0]
+                })
+                .collect();
+            this = this.with_colors(colors);
+        }
+        if !normals.is_empty() {
+            this = this.with_normals(normals);
+        }
+        Ok(this)
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    // added to a scene with `Scene::add_procedural_geometry`
+    pub fn geometry(&self) -> Result<ProceduralGeometry> {
+        if self.positions.is_empty() {
+            return Err(ErrorCode::MeshNotFound.into())
+        }
+        let count = self.positions.len();
+        let is_consistent = self.normals.as_ref().map_or(true, |v| v.len() == count)
+            && self.colors.as_ref().map_or(true, |v| v.len() == count);
+        if !is_consistent {
+            return Err(ErrorCode::MeshFormatInvalid.into())
+        }
+        let primitives = self.positions.iter()
+            .enumerate()
+            .map(|(index, position)| {
+                let center = glm::make_vec3(position);
+                let normal = self.normals.as_ref()
+                    .map(|v| glm::make_vec3(&v[index]))
+                    .filter(|v| glm::length(v) > f32::EPSILON);
+                let shape = match (self.splat, normal) {
+                    (PointCloudSplat::Disc, Some(normal)) =>
+                        ProceduralShape::Disc { center, normal, radius: self.radius },
+                    _ => ProceduralShape::Sphere { center, radius: self.radius },
+                };
+                let [r, g, b, _] = self.colors.as_ref()
+                    .map_or([1.0, 1.0, 1.0, 1.0], |v| v[index]);
+                ProceduralPrimitive::with_shape(&shape, ProceduralMaterial::lambertian(r, g, b))
+            })
+            .collect();
+        Ok(ProceduralGeometry::new(primitives))
+    }
+}
@@ -5,6 +5,9 @@ use gltf::accessor::DataType;
 use gltf::accessor::Dimensions;
 use gltf::Semantic;
 
+use crate::vk::Result;
+use crate::vk::*;
+
 pub struct Primitive<'a> {
     indices: Indices<'a>,
     positions: Positions<'a>,
@@ -17,22 +20,62 @@ pub struct Primitive<'a> {
 }
 
 impl<'a> Primitive<'a> {
-    pub fn new(primitive: gltf::Primitive<'a>, buffers: &'a Vec<gltf::buffer::Data>) -> Self {
+    pub fn new(primitive: gltf::Primitive<'a>, buffers: &'a [gltf::buffer::Data]) -> Result<Self> {
         let material_index = primitive.material().index();
         let is_opaque = primitive.material().alpha_mode() == gltf::material::AlphaMode::Opaque;
-        Self {
-            indices: Indices::new(&primitive, buffers),
-            positions: Positions::new(&primitive, buffers),
-            normals: Normals::new(&primitive, buffers),
+        let primitive = Self {
+            indices: Indices::new(&primitive, buffers)?,
+            positions: Positions::new(&primitive, buffers)?,
+            normals: Normals::new(&primitive, buffers)?,
             // TODO(ogukei): support default TEXCOORD_0
-            texcoords: Texcoords::new(&primitive, buffers).unwrap(),
-            tangents: Tangents::new(&primitive, buffers),
-            colors: Colors::new(&primitive, buffers),
+            texcoords: Texcoords::new(&primitive, buffers)?
+                .ok_or(ErrorCode::MeshFormatInvalid)?,
+            tangents: Tangents::new(&primitive, buffers)?,
+            colors: Colors::new(&primitive, buffers)?,
+            material_index,
+            is_opaque,
+        };
+        Ok(primitive)
+    }
+
+    // builds a primitive borrowing vertex attributes owned by the caller without copying them
+    pub fn from_slices(
+        indices: &'a [u32],
+        positions: &'a [[f32; 3]],
+        normals: &'a [[f32; 3]],
+        texcoords: &'a [[f32; 2]],
+        material_index: Option<usize>,
+        is_opaque: bool,
+    ) -> Self {
+        Self {
+            indices: Indices::Accessor(AccessorIndicesU32::from_slice(indices)),
+            positions: Positions::Accessor(AccessorPositions::from_slice(positions)),
+            normals: Normals::Accessor(AccessorNormals::from_slice(normals)),
+            texcoords: Texcoords::Accessor(AccessorTexcoords::from_slice(texcoords)),
+            tangents: None,
+            colors: None,
             material_index,
             is_opaque,
         }
     }
 
+    pub fn with_tangent_slice(mut self, tangents: Option<&'a [[f32; 4]]>) -> Self {
+        self.tangents = tangents.map(|v| Tangents::Accessor(AccessorTangents::from_slice(v)));
+        self
+    }
+
+    pub fn with_color_slice(mut self, colors: Option<&'a [[f32; 4]]>) -> Self {
+        self.colors = colors.map(|v| Colors::Accessor(AccessorColors::from_slice(v)));
+        self
+    }
+
+    // numbers the material after those of the assets placed before this one.
+    // primitives without a material fall back to the first material of their asset.
+    pub fn with_material_offset(mut self, material_offset: usize) -> Self {
+        self.material_index = Some(self.material_index.unwrap_or(0) + material_offset);
+        self
+    }
+
     #[inline]
     pub fn is_opaque(&self) -> bool {
         self.is_opaque
@@ -80,20 +123,23 @@ pub enum Indices<'a> {
 }
 
 impl<'a> Indices<'a> {
-    fn new(primitive: &gltf::Primitive<'a>, buffers: &'a Vec<gltf::buffer::Data>) -> Self {
-        let indices = primitive.indices().unwrap();
-        let view = indices.view().unwrap();
-        let use_reference = view.stride() == None
+    fn new(primitive: &gltf::Primitive<'a>, buffers: &'a [gltf::buffer::Data]) -> Result<Self> {
+        let indices = primitive.indices()
+            .ok_or(ErrorCode::MeshFormatInvalid)?;
+        let use_reference = is_tightly_packed(&indices)
             && indices.data_type() == DataType::U32
             && indices.dimensions() == Dimensions::Scalar;
         if use_reference {
-            Self::Accessor(AccessorIndicesU32::new(primitive, buffers))
+            let accessor = AccessorIndicesU32::new(&indices, buffers)
+                .map_err(|e| e.with_accessor(indices.index()))?;
+            Ok(Self::Accessor(accessor))
         } else {
-            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
-            let indices = reader.read_indices().unwrap()
+            let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|v| &v[..]));
+            let values = reader.read_indices()
+                .ok_or_else(|| Error::from(ErrorCode::AccessorInvalid).with_accessor(indices.index()))?
                 .into_u32()
                 .collect();
-            Self::Vector(indices)
+            Ok(Self::Vector(values))
         }
     }
 
@@ -112,6 +158,17 @@ impl<'a> Indices<'a> {
             &Self::Vector(v) => v.as_ptr() as *const _ as *const u8,
         }
     }
+
+    // copied out since the accessor bytes may be unaligned
+    pub fn to_vec(&self) -> Vec<u32> {
+        match self {
+            Self::Accessor(indices) => indices.slice.chunks_exact(4)
+                .take(indices.count)
+                .map(|v| u32::from_le_bytes([v[0], v[1], v[2], v[3]]))
+                .collect(),
+            Self::Vector(v) => v.clone(),
+        }
+    }
 }
 
 pub struct AccessorIndicesU32<'a> {
@@ -120,15 +177,18 @@ pub struct AccessorIndicesU32<'a> {
 }
 
 impl<'a> AccessorIndicesU32<'a> {
-    fn new(primitive: &gltf::Primitive<'a>, buffers: &'a Vec<gltf::buffer::Data>) -> Self {
-        let indices = primitive.indices().unwrap();
-        let view = indices.view().unwrap();
-        let buffer = buffers.get(view.buffer().index()).unwrap();
-        let offset = view.offset() + indices.offset();
-        let slice = &buffer[offset..offset + view.length()];
-        Self {
-            slice,
+    fn new(indices: &gltf::Accessor<'a>, buffers: &'a [gltf::buffer::Data]) -> Result<Self> {
+        let accessor = Self {
+            slice: accessor_slice(indices, buffers)?,
             count: indices.count(),
+        };
+        Ok(accessor)
+    }
+
+    fn from_slice(values: &'a [u32]) -> Self {
+        Self {
+            slice: as_bytes(values),
+            count: values.len(),
         }
     }
 }
@@ -139,7 +199,7 @@ pub enum Positions<'a> {
 }
 
 impl<'a> Positions<'a> {
-    fn new(primitive: &gltf::Primitive<'a>, buffers: &'a Vec<gltf::buffer::Data>) -> Self {
+    fn new(primitive: &gltf::Primitive<'a>, buffers: &'a [gltf::buffer::Data]) -> Result<Self> {
         let positions = primitive.attributes()
             .find_map(|(semantic, accessor)| 
                 match semantic { 
@@ -147,17 +207,19 @@ impl<'a> Positions<'a> {
                     _ => None,
                 }
             )
-            .unwrap();
-        let view = positions.view().unwrap();
-        let use_reference = view.stride() == None
+            .ok_or(ErrorCode::MeshFormatInvalid)?;
+        let use_reference = is_tightly_packed(&positions)
             && positions.data_type() == DataType::F32 
             && positions.dimensions() == Dimensions::Vec3;
         if use_reference {
-            Self::Accessor(AccessorPositions::new(&positions, buffers))
+            let accessor = AccessorPositions::new(&positions, buffers)
+                .map_err(|e| e.with_accessor(positions.index()))?;
+            Ok(Self::Accessor(accessor))
         } else {
-            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
-            let positions = reader.read_positions().unwrap();
-            Self::Vector(positions.collect())
+            let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|v| &v[..]));
+            let values = reader.read_positions()
+                .ok_or_else(|| Error::from(ErrorCode::AccessorInvalid).with_accessor(positions.index()))?;
+            Ok(Self::Vector(values.collect()))
         }
     }
 
@@ -176,6 +238,19 @@ impl<'a> Positions<'a> {
             &Self::Vector(v) => v.as_ptr() as *const _ as *const u8,
         }
     }
+
+    pub fn to_vec(&self) -> Vec<[f32; 3]> {
+        match self {
+            Self::Accessor(positions) => positions.slice.chunks_exact(12)
+                .take(positions.count)
+                .map(|v| {
+                    let f = |i: usize| f32::from_le_bytes([v[i], v[i + 1], v[i + 2], v[i + 3]]);
+                    [f(0), f(4), f(8)]
+                })
+                .collect(),
+            Self::Vector(v) => v.clone(),
+        }
+    }
 }
 
 pub struct AccessorPositions<'a> {
@@ -184,14 +259,18 @@ pub struct AccessorPositions<'a> {
 }
 
 impl<'a> AccessorPositions<'a> {
-    fn new(positions: &gltf::Accessor<'a>, buffers: &'a Vec<gltf::buffer::Data>) -> Self {
-        let view = positions.view().unwrap();
-        let buffer = buffers.get(view.buffer().index()).unwrap();
-        let offset = view.offset() + positions.offset();
-        let slice = &buffer[offset..offset + view.length()];
-        Self {
-            slice,
+    fn new(positions: &gltf::Accessor<'a>, buffers: &'a [gltf::buffer::Data]) -> Result<Self> {
+        let accessor = Self {
+            slice: accessor_slice(positions, buffers)?,
             count: positions.count(),
+        };
+        Ok(accessor)
+    }
+
+    fn from_slice(values: &'a [[f32; 3]]) -> Self {
+        Self {
+            slice: as_bytes(values),
+            count: values.len(),
         }
     }
 }
@@ -202,7 +281,7 @@ pub enum Normals<'a> {
 }
 
 impl<'a> Normals<'a> {
-    fn new(primitive: &gltf::Primitive<'a>, buffers: &'a Vec<gltf::buffer::Data>) -> Self {
+    fn new(primitive: &gltf::Primitive<'a>, buffers: &'a [gltf::buffer::Data]) -> Result<Self> {
         let normals = primitive.attributes()
             .find_map(|(semantic, accessor)| 
                 match semantic { 
@@ -210,17 +289,19 @@ impl<'a> Normals<'a> {
                     _ => None,
                 }
             )
-            .unwrap();
-        let view = normals.view().unwrap();
-        let use_reference = view.stride() == None
+            .ok_or(ErrorCode::MeshFormatInvalid)?;
+        let use_reference = is_tightly_packed(&normals)
             && normals.data_type() == DataType::F32 
             && normals.dimensions() == Dimensions::Vec3;
         if use_reference {
-            Self::Accessor(AccessorNormals::new(&normals, buffers))
+            let accessor = AccessorNormals::new(&normals, buffers)
+                .map_err(|e| e.with_accessor(normals.index()))?;
+            Ok(Self::Accessor(accessor))
         } else {
-            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
-            let normals = reader.read_normals().unwrap();
-            Self::Vector(normals.collect())
+            let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|v| &v[..]));
+            let values = reader.read_normals()
+                .ok_or_else(|| Error::from(ErrorCode::AccessorInvalid).with_accessor(normals.index()))?;
+            Ok(Self::Vector(values.collect()))
         }
     }
 
@@ -247,14 +328,18 @@ pub struct AccessorNormals<'a> {
 }
 
 impl<'a> AccessorNormals<'a> {
-    fn new(normals: &gltf::Accessor<'a>, buffers: &'a Vec<gltf::buffer::Data>) -> Self {
-        let view = normals.view().unwrap();
-        let buffer = buffers.get(view.buffer().index()).unwrap();
-        let offset = view.offset() + normals.offset();
-        let slice = &buffer[offset..offset + view.length()];
-        Self {
-            slice,
+    fn new(normals: &gltf::Accessor<'a>, buffers: &'a [gltf::buffer::Data]) -> Result<Self> {
+        let accessor = Self {
+            slice: accessor_slice(normals, buffers)?,
             count: normals.count(),
+        };
+        Ok(accessor)
+    }
+
+    fn from_slice(values: &'a [[f32; 3]]) -> Self {
+        Self {
+            slice: as_bytes(values),
+            count: values.len(),
         }
     }
 }
@@ -265,24 +350,31 @@ pub enum Texcoords<'a> {
 }
 
 impl<'a> Texcoords<'a> {
-    fn new(primitive: &gltf::Primitive<'a>, buffers: &'a Vec<gltf::buffer::Data>) -> Option<Self> {
+    fn new(primitive: &gltf::Primitive<'a>, buffers: &'a [gltf::buffer::Data]) -> Result<Option<Self>> {
         let texcoords = primitive.attributes()
             .find_map(|(semantic, accessor)| 
                 match semantic { 
                     Semantic::TexCoords(0) => Some(accessor),
                     _ => None,
                 }
-            )?;
-        let view = texcoords.view().unwrap();
-        let use_reference = view.stride() == None
+            );
+        let texcoords = match texcoords {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        let use_reference = is_tightly_packed(&texcoords)
             && texcoords.data_type() == DataType::F32 
             && texcoords.dimensions() == Dimensions::Vec2;
         if use_reference {
-            Some(Self::Accessor(AccessorTexcoords::new(&texcoords, buffers)))
+            let accessor = AccessorTexcoords::new(&texcoords, buffers)
+                .map_err(|e| e.with_accessor(texcoords.index()))?;
+            Ok(Some(Self::Accessor(accessor)))
         } else {
-            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
-            let texcoords = reader.read_tex_coords(0).unwrap().into_f32();
-            Some(Self::Vector(texcoords.collect()))
+            let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|v| &v[..]));
+            let values = reader.read_tex_coords(0)
+                .ok_or_else(|| Error::from(ErrorCode::AccessorInvalid).with_accessor(texcoords.index()))?
+                .into_f32();
+            Ok(Some(Self::Vector(values.collect())))
         }
     }
 
@@ -309,14 +401,18 @@ pub struct AccessorTexcoords<'a> {
 }
 
 impl<'a> AccessorTexcoords<'a> {
-    fn new(texcoords: &gltf::Accessor<'a>, buffers: &'a Vec<gltf::buffer::Data>) -> Self {
-        let view = texcoords.view().unwrap();
-        let buffer = buffers.get(view.buffer().index()).unwrap();
-        let offset = view.offset() + texcoords.offset();
-        let slice = &buffer[offset..offset + view.length()];
-        Self {
-            slice,
+    fn new(texcoords: &gltf::Accessor<'a>, buffers: &'a [gltf::buffer::Data]) -> Result<Self> {
+        let accessor = Self {
+            slice: accessor_slice(texcoords, buffers)?,
             count: texcoords.count(),
+        };
+        Ok(accessor)
+    }
+
+    fn from_slice(values: &'a [[f32; 2]]) -> Self {
+        Self {
+            slice: as_bytes(values),
+            count: values.len(),
         }
     }
 }
@@ -327,26 +423,32 @@ pub enum Tangents<'a> {
 }
 
 impl<'a> Tangents<'a> {
-    fn new(primitive: &gltf::Primitive<'a>, buffers: &'a Vec<gltf::buffer::Data>) -> Option<Self> {
+    fn new(primitive: &gltf::Primitive<'a>, buffers: &'a [gltf::buffer::Data]) -> Result<Option<Self>> {
         let tangents = primitive.attributes()
             .find_map(|(semantic, accessor)| 
                 match semantic { 
                     Semantic::Tangents => Some(accessor),
                     _ => None,
                 }
-            )?;
-        let view = tangents.view().unwrap();
-        let use_reference = view.stride() == None
+            );
+        let tangents = match tangents {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        let use_reference = is_tightly_packed(&tangents)
             && tangents.data_type() == DataType::F32 
             && tangents.dimensions() == Dimensions::Vec4;
-        let tangents = if use_reference {
-            Self::Accessor(AccessorTangents::new(&tangents, buffers))
+        let values = if use_reference {
+            let accessor = AccessorTangents::new(&tangents, buffers)
+                .map_err(|e| e.with_accessor(tangents.index()))?;
+            Self::Accessor(accessor)
         } else {
-            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
-            let tangents = reader.read_tangents().unwrap();
-            Self::Vector(tangents.collect())
+            let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|v| &v[..]));
+            let values = reader.read_tangents()
+                .ok_or_else(|| Error::from(ErrorCode::AccessorInvalid).with_accessor(tangents.index()))?;
+            Self::Vector(values.collect())
         };
-        Some(tangents)
+        Ok(Some(values))
     }
 
     #[inline]
@@ -372,14 +474,18 @@ pub struct AccessorTangents<'a> {
 }
 
 impl<'a> AccessorTangents<'a> {
-    fn new(tangents: &gltf::Accessor<'a>, buffers: &'a Vec<gltf::buffer::Data>) -> Self {
-        let view = tangents.view().unwrap();
-        let buffer = buffers.get(view.buffer().index()).unwrap();
-        let offset = view.offset() + tangents.offset();
-        let slice = &buffer[offset..offset + view.length()];
-        Self {
-            slice,
+    fn new(tangents: &gltf::Accessor<'a>, buffers: &'a [gltf::buffer::Data]) -> Result<Self> {
+        let accessor = Self {
+            slice: accessor_slice(tangents, buffers)?,
             count: tangents.count(),
+        };
+        Ok(accessor)
+    }
+
+    fn from_slice(values: &'a [[f32; 4]]) -> Self {
+        Self {
+            slice: as_bytes(values),
+            count: values.len(),
         }
     }
 }
@@ -390,26 +496,32 @@ pub enum Colors<'a> {
 }
 
 impl<'a> Colors<'a> {
-    fn new(primitive: &gltf::Primitive<'a>, buffers: &'a Vec<gltf::buffer::Data>) -> Option<Self> {
+    fn new(primitive: &gltf::Primitive<'a>, buffers: &'a [gltf::buffer::Data]) -> Result<Option<Self>> {
         let colors= primitive.attributes()
             .find_map(|(semantic, accessor)| 
                 match semantic { 
                     Semantic::Colors(set) => if set == 0 { Some(accessor) } else { None },
                     _ => None,
                 }
-            )?;
-        let view = colors.view().unwrap();
-        let use_reference = view.stride() == None
+            );
+        let colors = match colors {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        let use_reference = is_tightly_packed(&colors)
             && colors.data_type() == DataType::F32
             && colors.dimensions() == Dimensions::Vec4;
-        let colors = if use_reference {
-            Self::Accessor(AccessorColors::new(&colors, buffers))
+        let values = if use_reference {
+            let accessor = AccessorColors::new(&colors, buffers)
+                .map_err(|e| e.with_accessor(colors.index()))?;
+            Self::Accessor(accessor)
         } else {
-            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
-            let colors = reader.read_colors(0).unwrap();
-            Self::Vector(colors.into_rgba_f32().collect())
+            let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|v| &v[..]));
+            let values = reader.read_colors(0)
+                .ok_or_else(|| Error::from(ErrorCode::AccessorInvalid).with_accessor(colors.index()))?;
+            Self::Vector(values.into_rgba_f32().collect())
         };
-        Some(colors)
+        Ok(Some(values))
     }
 
     #[inline]
@@ -435,14 +547,43 @@ pub struct AccessorColors<'a> {
 }
 
 impl<'a> AccessorColors<'a> {
-    fn new(colors: &gltf::Accessor<'a>, buffers: &'a Vec<gltf::buffer::Data>) -> Self {
-        let view = colors.view().unwrap();
-        let buffer = buffers.get(view.buffer().index()).unwrap();
-        let offset = view.offset() + colors.offset();
-        let slice = &buffer[offset..offset + view.length()];
-        Self {
-            slice,
+    fn new(colors: &gltf::Accessor<'a>, buffers: &'a [gltf::buffer::Data]) -> Result<Self> {
+        let accessor = Self {
+            slice: accessor_slice(colors, buffers)?,
             count: colors.count(),
+        };
+        Ok(accessor)
+    }
+
+    fn from_slice(values: &'a [[f32; 4]]) -> Self {
+        Self {
+            slice: as_bytes(values),
+            count: values.len(),
         }
     }
 }
+
+// zero-copy access requires elements laid out contiguously in a buffer view
+fn is_tightly_packed(accessor: &gltf::Accessor) -> bool {
+    accessor.sparse().is_none()
+        && accessor.view().map_or(false, |view| view.stride().map_or(true, |v| v == accessor.size()))
+}
+
+fn accessor_slice<'a>(accessor: &gltf::Accessor<'a>, buffers: &'a [gltf::buffer::Data]) -> Result<&'a [u8]> {
+    let view = accessor.view()
+        .ok_or(ErrorCode::AccessorInvalid)?;
+    let buffer = buffers.get(view.buffer().index())
+        .ok_or(ErrorCode::BufferNotFound)?;
+    let offset = view.offset() + accessor.offset();
+    let length = accessor.count() * accessor.size();
+    if accessor.offset() + length > view.length() {
+        return Err(ErrorCode::AccessorInvalid.into());
+    }
+    buffer.get(offset..offset + length)
+        .ok_or_else(|| ErrorCode::AccessorInvalid.into())
+}
+
+fn as_bytes<T>(values: &[T]) -> &[u8] {
+    let size = std::mem::size_of_val(values);
+    unsafe { std::slice::from_raw_parts(values.as_ptr() as *const u8, size) }
+}
@@ -1,144 +1,122 @@
 
-/*
-use gltf;
 use nalgebra_glm as glm;
 
-use std::collections::HashMap;
 use std::sync::Arc;
 
 use crate::vk::Result;
 use crate::vk::*;
 use crate::ffi::vk::*;
 
-use libc::c_void;
-
-use gltf::accessor::DataType;
-use gltf::accessor::Dimensions;
-use gltf::Semantic;
-
 use VkMemoryPropertyFlagBits::*;
 use VkBufferUsageFlagBits::*;
 
 use super::aabb::*;
-use super::material::*;
+use super::shape::*;
 
-pub struct SceneProceduralGeometry {
-    aabb_buffer: Arc<DedicatedStagingBuffer>,
-    sphere_buffer: Arc<DedicatedStagingBuffer>,
-    structures: Vec<Arc<BottomLevelAccelerationStructure>>,
-    sphere_material_buffer: Arc<DedicatedStagingBuffer>,
+// procedural primitives intersected by the same hit group. every geometry gets a structure of its
+// own and shows up through the instances added with its mesh index.
+#[derive(Clone)]
+pub struct ProceduralGeometry {
+    primitives: Vec<ProceduralPrimitive>,
+    hit_group: u32,
 }
 
-impl SceneProceduralGeometry {
-    pub fn new(aabbs: &Vec<AABB>, spheres: &Vec<glm::Vec4>, materials: &Vec<SphereMaterial>, command_pool: &Arc<CommandPool>) -> Result<Arc<Self>> {
-        let device = command_pool.queue().device();
-        let num_aabb = aabbs.len();
-        let aabb_buffer_size = num_aabb * std::mem::size_of::<AABB>();
-        let aabb_buffer = DedicatedStagingBuffer::new(command_pool, 
-                VK_BUFFER_USAGE_STORAGE_BUFFER_BIT as  VkBufferUsageFlags
-                | VK_BUFFER_USAGE_SHADER_DEVICE_ADDRESS_BIT as VkBufferUsageFlags,
-            VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT as VkMemoryPropertyFlags,
-            aabb_buffer_size as VkDeviceSize,
-        )?;
-        let sphere_buffer_size = spheres.len() * std::mem::size_of::<[f32; 4]>();
-        let sphere_buffer = DedicatedStagingBuffer::new(command_pool, 
-                VK_BUFFER_USAGE_STORAGE_BUFFER_BIT as  VkBufferUsageFlags
-                | VK_BUFFER_USAGE_SHADER_DEVICE_ADDRESS_BIT as VkBufferUsageFlags,
-            VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT as VkMemoryPropertyFlags,
-            sphere_buffer_size as VkDeviceSize,
-        )?;
-        let sphere_material_buffer_size = spheres.len() * std::mem::size_of::<SphereMaterial>();
-        let sphere_material_buffer = DedicatedStagingBuffer::new(command_pool, 
-                VK_BUFFER_USAGE_STORAGE_BUFFER_BIT as  VkBufferUsageFlags
-                | VK_BUFFER_USAGE_SHADER_DEVICE_ADDRESS_BIT as VkBufferUsageFlags,
-            VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT as VkMemoryPropertyFlags,
-            sphere_material_buffer_size as VkDeviceSize,
-        )?;
-        unsafe {
-            aabb_buffer.update(aabb_buffer_size as VkDeviceSize, |data| {
-                let dst = data as *mut u8;
-                std::ptr::copy_nonoverlapping(aabbs.as_ptr() as *const u8, dst, aabb_buffer_size);
-            });
-            sphere_buffer.update(sphere_buffer_size as VkDeviceSize, |data| {
-                let dst = data as *mut u8;
-                std::ptr::copy_nonoverlapping(spheres.as_ptr() as *const u8, dst, sphere_buffer_size);
-            });
-            sphere_material_buffer.update(sphere_material_buffer_size as VkDeviceSize, |data| {
-                let dst = data as *mut u8;
-                std::ptr::copy_nonoverlapping(materials.as_ptr() as *const u8, dst, sphere_material_buffer_size);
-            });
+impl ProceduralGeometry {
+    // intersected as `ProceduralShape`s by the built-in procedural hit group
+    pub fn new(primitives: Vec<ProceduralPrimitive>) -> Self {
+        Self {
+            primitives,
+            hit_group: RayTracingGraphicsPipeline::PROCEDURAL_HIT_GROUP,
         }
-        assert_eq!(std::mem::size_of::<AABB>(), std::mem::size_of::<[f32; 6]>());
-        let structure_geometry = BottomLevelAccelerationStructureAABBsGeometry::new(
-            num_aabb as u32, 
-            aabb_buffer.device_buffer_memory())?;
-        let builder = BottomLevelAccelerationStructuresBuilder::new(command_pool, std::slice::from_ref(&structure_geometry));
-        let structures = builder.build()?;
-        let geometry = Self {
-            aabb_buffer,
-            structures,
-            sphere_buffer,
-            sphere_material_buffer,
-        };
-        Ok(Arc::new(geometry))
     }
 
-    pub fn structures(&self) -> &Vec<Arc<BottomLevelAccelerationStructure>> {
-        &self.structures
+    // intersects the primitives with a hit group registered with `RayTracingGraphicsPipeline::with_hit_groups`
+    pub fn with_hit_group(mut self, hit_group: u32, pipeline: &RayTracingGraphicsPipeline) -> Result<Self> {
+        if hit_group >= pipeline.hit_group_count() {
+            return Err(ErrorCode::HitGroupNotFound.into())
+        }
+        self.hit_group = hit_group;
+        Ok(self)
     }
 
-    pub fn sphere_buffer(&self) -> &Arc<DedicatedStagingBuffer> {
-        &self.sphere_buffer
+    // one of the hit groups every pipeline has
+    pub(super) fn with_builtin_hit_group(mut self, hit_group: u32) -> Self {
+        debug_assert!(hit_group < RayTracingGraphicsPipeline::BUILTIN_HIT_GROUP_COUNT);
+        self.hit_group = hit_group;
+        self
     }
 
-    pub fn sphere_material_buffer(&self) -> &Arc<DedicatedStagingBuffer> {
-        &self.sphere_material_buffer
+    pub fn primitives(&self) -> &[ProceduralPrimitive] {
+        &self.primitives
+    }
+
+    pub fn hit_group(&self) -> u32 {
+        self.hit_group
     }
 }
 
-pub struct SphereGenerator {
-    rng: Xorshift64,
+#[derive(Clone)]
+pub struct ProceduralPrimitive {
+    aabb: AABB,
+    description: ProceduralShapeDescription,
+    material: ProceduralMaterial,
 }
 
-impl SphereGenerator {
-    pub fn new() -> Self {
-        Self { rng: Xorshift64::new() }
+impl ProceduralPrimitive {
+    // `parameters` are handed to the intersection shader of a custom hit group as the first vec4 of
+    // the record at `gl_InstanceCustomIndexEXT + gl_PrimitiveID` of the primitive buffer. rays only
+    // reach the shader within `aabb`.
+    pub fn new(aabb: AABB, parameters: &glm::Vec4, material: ProceduralMaterial) -> Self {
+        Self {
+            description: ProceduralShapeDescription::custom(parameters),
+            aabb,
+            material,
+        }
     }
 
-    fn gen_vec4(&mut self, x: isize, z: isize) -> glm::Vec4 {
-        let x = x as f32 + 0.9 * self.rng.next_uniform_f32();
-        let y = 0.2;
-        let z = z as f32 + 0.9 * self.rng.next_uniform_f32();
-        let v = glm::vec4(x, y, z, 0.2);
-        v
+    pub fn with_shape(shape: &ProceduralShape, material: ProceduralMaterial) -> Self {
+        Self {
+            aabb: shape.aabb(),
+            description: shape.description(),
+            material,
+        }
     }
 
-    pub fn generate(&mut self, spheres: &mut Vec<glm::Vec4>, sphere_materials: &mut Vec<SphereMaterial>) {
-        let mut rng = Xorshift64::new();
-        for x in -7..7isize {
-            for z in -7..7isize {
-                let mut v: glm::Vec4;
-                loop {
-                    v = self.gen_vec4(x, z);
-                    let intersects = spheres.iter()
-                        .any(|s| glm::distance(&s.xyz(), &v.xyz()) < (s.w + v.w));
-                    if !intersects {
-                        break;
-                    }
-                }
-                spheres.push(v);
-                sphere_materials.push(SphereMaterial::random(&mut rng));
-            }
+    pub(super) fn with_description(aabb: AABB, description: ProceduralShapeDescription, material: ProceduralMaterial) -> Self {
+        Self {
+            aabb,
+            description,
+            material,
         }
     }
+
+    pub fn sphere(center: &glm::Vec3, radius: f32, material: ProceduralMaterial) -> Self {
+        let shape = ProceduralShape::Sphere { center: *center, radius };
+        Self::with_shape(&shape, material)
+    }
+
+    pub fn aabb(&self) -> &AABB {
+        &self.aabb
+    }
+
+    pub fn description(&self) -> &ProceduralShapeDescription {
+        &self.description
+    }
+
+    pub fn material(&self) -> &ProceduralMaterial {
+        &self.material
+    }
 }
 
-pub struct SphereMaterial {
+// laid out as `Material` in `ray.common.glsl`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct ProceduralMaterial {
     albedo: glm::Vec4,
     tp: glm::UVec4,
 }
 
-impl SphereMaterial {
+impl ProceduralMaterial {
     pub fn lambertian(x: f32, y: f32, z: f32) -> Self {
         let albedo = glm::vec4(x, y, z, 0.0);
         let tp = glm::vec4(0u32, 0, 0, 0);
@@ -148,6 +126,7 @@ impl SphereMaterial {
         }
     }
 
+    // `w` is the fuzziness of the reflection
     pub fn metal(x: f32, y: f32, z: f32, w: f32) -> Self {
         let albedo = glm::vec4(x, y, z, w);
         let tp = glm::vec4(1u32, 0, 0, 0);
@@ -170,8 +149,8 @@ impl SphereMaterial {
         let choose = rng.next_uniform();
         if choose < 0.8 {
             Self::lambertian(
-                rng.next_uniform_f32() * rng.next_uniform_f32(), 
-                rng.next_uniform_f32() * rng.next_uniform_f32(), 
+                rng.next_uniform_f32() * rng.next_uniform_f32(),
+                rng.next_uniform_f32() * rng.next_uniform_f32(),
                 rng.next_uniform_f32() * rng.next_uniform_f32())
         } else if choose < 0.98 {
             let x = rng.next_uniform_f32();
@@ -185,4 +164,210 @@ impl SphereMaterial {
     }
 }
 
-*/
\ No newline at end of file
+// scatters small non-overlapping spheres with random materials over a 14x14 grid
+pub struct SphereGenerator {
+    rng: Xorshift64,
+}
+
+impl SphereGenerator {
+    pub fn new() -> Self {
+        Self { rng: Xorshift64::new() }
+    }
+
+    fn gen_vec4(&mut self, x: isize, z: isize) -> glm::Vec4 {
+        let x = x as f32 + 0.9 * self.rng.next_uniform_f32();
+        let y = 0.2;
+        let z = z as f32 + 0.9 * self.rng.next_uniform_f32();
+        glm::vec4(x, y, z, 0.2)
+    }
+
+    pub fn generate(&mut self) -> ProceduralGeometry {
+        let mut rng = Xorshift64::new();
+        let mut spheres: Vec<glm::Vec4> = vec![];
+        let mut primitives: Vec<ProceduralPrimitive> = vec![];
+        for x in -7..7isize {
+            for z in -7..7isize {
+                let mut v: glm::Vec4;
+                loop {
+                    v = self.gen_vec4(x, z);
+                    let intersects = spheres.iter()
+                        .any(|s| glm::distance(&s.xyz(), &v.xyz()) < (s.w + v.w));
+                    if !intersects {
+                        break;
+                    }
+                }
+                spheres.push(v);
+                primitives.push(ProceduralPrimitive::sphere(&v.xyz(), v.w, ProceduralMaterial::random(&mut rng)));
+            }
+        }
+        ProceduralGeometry::new(primitives)
+    }
+}
+
+impl Default for SphereGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// the procedural geometries of a scene packed into shared buffers
+#[allow(dead_code)]
+pub struct SceneProceduralGeometries {
+    // the boxes of the geometries built by the latest call only
+    aabb_buffer: Arc<DedicatedStagingBuffer>,
+    primitive_buffer: Arc<DedicatedStagingBuffer>,
+    material_buffer: Arc<DedicatedStagingBuffer>,
+    geometries: Vec<SceneProceduralGeometry>,
+}
+
+#[derive(Clone)]
+pub struct SceneProceduralGeometry {
+    // the index of the first primitive within the shared buffers
+    offset: usize,
+    hit_group: u32,
+    structure: Arc<BottomLevelAccelerationStructure>,
+}
+
+impl SceneProceduralGeometry {
+    #[inline]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    #[inline]
+    pub fn hit_group(&self) -> u32 {
+        self.hit_group
+    }
+
+    #[inline]
+    pub fn bottom_level_acceleration_structure(&self) -> &Arc<BottomLevelAccelerationStructure> {
+        &self.structure
+    }
+}
+
+impl SceneProceduralGeometries {
+    pub fn new(geometries: &[ProceduralGeometry], command_pool: &Arc<CommandPool>) -> Result<Arc<Self>> {
+        Self::with_built(&[], geometries, command_pool)
+    }
+
+    // `geometries` starts with those uploaded to `self`, whose structures are kept. only the ones
+    // after them are built. the primitive and material buffers are shared by all the geometries and
+    // written again.
+    pub fn append(&self, geometries: &[ProceduralGeometry], command_pool: &Arc<CommandPool>) -> Result<Arc<Self>> {
+        if geometries.len() < self.geometries.len() {
+            return Err(ErrorCode::MeshNotFound.into())
+        }
+        Self::with_built(&self.geometries, geometries, command_pool)
+    }
+
+    fn with_built(built: &[SceneProceduralGeometry], geometries: &[ProceduralGeometry], command_pool: &Arc<CommandPool>) -> Result<Arc<Self>> {
+        // geometries without primitives cannot be built
+        if geometries.iter().any(|v| v.primitives().is_empty()) {
+            return Err(ErrorCode::MeshFormatInvalid.into())
+        }
+        let mut offsets: Vec<usize> = vec![];
+        let mut offset: usize = 0;
+        for geometry in geometries.iter() {
+            // the offset becomes the custom index of the instances
+            if offset > TopLevelAccelerationStructureInstance::MAX_CUSTOM_INDEX as usize {
+                return Err(ErrorCode::InstanceCustomIndexOutOfRange.into())
+            }
+            offsets.push(offset);
+            offset += geometry.primitives().len();
+        }
+        let primitives: Vec<&ProceduralPrimitive> = geometries.iter()
+            .flat_map(|v| v.primitives().iter())
+            .collect();
+        let added = &geometries[built.len()..];
+        let added_offsets = &offsets[built.len()..];
+        // the structures only read the boxes of the primitives they are built from
+        let aabb_offset = added_offsets.first().copied().unwrap_or(0);
+        let aabbs: Vec<AABB> = added.iter()
+            .flat_map(|v| v.primitives().iter())
+            .map(|v| *v.aabb())
+            .collect();
+        let descriptions: Vec<ProceduralShapeDescription> = primitives.iter()
+            .map(|v| *v.description())
+            .collect();
+        let materials: Vec<ProceduralMaterial> = primitives.iter()
+            .map(|v| *v.material())
+            .collect();
+        assert_eq!(std::mem::size_of::<AABB>(), std::mem::size_of::<[f32; 6]>());
+        let aabb_buffer = Self::staging_buffer(&aabbs,
+            VK_BUFFER_USAGE_STORAGE_BUFFER_BIT as VkBufferUsageFlags
+                | VK_BUFFER_USAGE_SHADER_DEVICE_ADDRESS_BIT as VkBufferUsageFlags
+                | VK_BUFFER_USAGE_ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_BIT_KHR as VkBufferUsageFlags,
+            command_pool)?;
+        let primitive_buffer = Self::staging_buffer(&descriptions,
+            VK_BUFFER_USAGE_STORAGE_BUFFER_BIT as VkBufferUsageFlags
+                | VK_BUFFER_USAGE_SHADER_DEVICE_ADDRESS_BIT as VkBufferUsageFlags,
+            command_pool)?;
+        let material_buffer = Self::staging_buffer(&materials,
+            VK_BUFFER_USAGE_STORAGE_BUFFER_BIT as VkBufferUsageFlags
+                | VK_BUFFER_USAGE_SHADER_DEVICE_ADDRESS_BIT as VkBufferUsageFlags,
+            command_pool)?;
+        let structures = if added.is_empty() {
+            vec![]
+        } else {
+            let queries: Vec<_> = added.iter()
+                .zip(added_offsets.iter())
+                .map(|(geometry, offset)| BottomLevelAccelerationStructureGeometry::aabbs(
+                    geometry.primitives().len() as u32,
+                    (offset - aabb_offset) as u32,
+                    aabb_buffer.device_buffer_memory(),
+                    true,
+                ))
+                .map(|v| BottomLevelAccelerationStructureBuildQuery::new(vec![v]))
+                .collect();
+            BottomLevelAccelerationStructuresBuilder::new(command_pool, queries).build()?
+        };
+        let added = added.iter()
+            .zip(added_offsets.iter())
+            .zip(structures)
+            .map(|((geometry, &offset), structure)| SceneProceduralGeometry {
+                offset,
+                hit_group: geometry.hit_group(),
+                structure,
+            });
+        let geometries = built.iter()
+            .cloned()
+            .chain(added)
+            .collect();
+        let this = Self {
+            aabb_buffer,
+            primitive_buffer,
+            material_buffer,
+            geometries,
+        };
+        Ok(Arc::new(this))
+    }
+
+    // bound in place of the sphere buffer of the descriptor sets, laid out as `ProceduralShape`s
+    pub fn primitive_buffer(&self) -> &Arc<DedicatedStagingBuffer> {
+        &self.primitive_buffer
+    }
+
+    pub fn material_buffer(&self) -> &Arc<DedicatedStagingBuffer> {
+        &self.material_buffer
+    }
+
+    pub fn geometries(&self) -> &[SceneProceduralGeometry] {
+        &self.geometries
+    }
+
+    // the buffers are never empty so that they can be bound before any primitive exists
+    fn staging_buffer<T>(data: &[T], usage: VkBufferUsageFlags, command_pool: &Arc<CommandPool>) -> Result<Arc<DedicatedStagingBuffer>> {
+        let dummy_buffer_size = 256usize;
+        let size = std::mem::size_of_val(data);
+        let buffer = DedicatedStagingBuffer::new(
+            command_pool,
+            usage,
+            VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT as VkMemoryPropertyFlags,
+            size.max(dummy_buffer_size) as VkDeviceSize,
+        )?;
+        if size > 0 {
+            buffer.write(data.as_ptr() as *const libc::c_void, size);
+        }
+        Ok(buffer)
+    }
+}
@@ -0,0 +1,416 @@
+
+use nalgebra_glm as glm;
+
+use std::sync::Arc;
+
+use crate::vk::Result;
+use crate::vk::*;
+
+use super::aabb::AABB;
+use super::bvh::*;
+use super::mesh::MeshPrimitive;
+use super::scene::{Scene, SceneInstanceEntry};
+
+// object space copies of the triangle meshes of a scene, kept for the queries on the CPU
+pub(super) struct SceneGeometry {
+    primitives: Vec<SceneGeometryPrimitive>,
+}
+
+struct SceneGeometryPrimitive {
+    positions: Vec<glm::Vec3>,
+    indices: Vec<u32>,
+    material_index: Option<usize>,
+    // of the asset the primitive belongs to
+    mesh_index: usize,
+    // in object space
+    bounds: AABB,
+}
+
+impl SceneGeometry {
+    pub(super) fn new(mesh_primitives: &[MeshPrimitive]) -> Result<Self> {
+        let primitives = mesh_primitives.iter()
+            .map(|v| {
+                let primitive = v.primitive();
+                let positions: Vec<glm::Vec3> = primitive.positions().to_vec()
+                    .iter()
+                    .map(|v| glm::make_vec3(v))
+                    .collect();
+                let indices = primitive.indices().to_vec();
+                if indices.iter().any(|&v| v as usize >= positions.len()) {
+                    return Err(ErrorCode::MeshFormatInvalid.into())
+                }
+                Ok(SceneGeometryPrimitive {
+                    indices,
+                    material_index: primitive.material_index(),
+                    mesh_index: v.mesh_index(),
+                    bounds: AABB::from_points(&positions),
+                    positions,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { primitives })
+    }
+
+    pub(super) fn primitive_bounds(&self, primitive_index: usize) -> Option<AABB> {
+        self.primitives.get(primitive_index)
+            .map(|v| v.bounds)
+    }
+
+    // returns the mesh index of the asset, the material index and the triangle count
+    pub(super) fn primitive_summary(&self, primitive_index: usize) -> Option<(usize, Option<usize>, usize)> {
+        self.primitives.get(primitive_index)
+            .map(|v| (v.mesh_index, v.material_index, v.indices.len() / 3))
+    }
+}
+
+// a triangle found by `SceneQuery`
+#[derive(Clone, Debug)]
+pub struct SceneHit {
+    // the id of the `SceneInstance`
+    pub instance: usize,
+    // the mesh index the instance was placed with
+    pub primitive: usize,
+    // the triangle within the mesh
+    pub triangle: usize,
+    // of the second and third vertices of the triangle
+    pub barycentrics: glm::Vec2,
+    // the distance along the ray, or from the point for `SceneQuery::closest_point`
+    pub t: f32,
+    pub material: Option<usize>,
+    pub position: glm::Vec3,
+    // the world space geometric normal facing the side the triangle was hit from
+    pub normal: glm::Vec3,
+}
+
+// a bounding volume hierarchy over the world space triangles of the instances of a scene, answering
+// ray casts and closest point queries on the CPU. procedural geometries are not included and
+// hidden instances are skipped. alpha testing is not applied.
+pub struct SceneQuery {
+    geometry: Arc<SceneGeometry>,
+    instances: Vec<SceneQueryInstance>,
+    triangles: Vec<SceneQueryTriangle>,
+    bvh: Bvh,
+}
+
+struct SceneQueryInstance {
+    id: usize,
+    primitive_index: usize,
+    visible: bool,
+    // the vertices of the primitive in world space
+    positions: Vec<glm::Vec3>,
+}
+
+struct SceneQueryTriangle {
+    instance_index: u32,
+    triangle_index: u32,
+}
+
+impl SceneQuery {
+    pub(super) fn new(geometry: &Arc<SceneGeometry>, entries: &[(usize, SceneInstanceEntry)]) -> Self {
+        log_debug!("building scene query");
+        let mut instances: Vec<SceneQueryInstance> = vec![];
+        let mut triangles: Vec<SceneQueryTriangle> = vec![];
+        for (id, entry) in entries.iter() {
+            let primitive = match geometry.primitives.get(entry.primitive_index) {
+                Some(v) => v,
+                None => continue,
+            };
+            let instance_index = instances.len() as u32;
+            let triangle_count = primitive.indices.len() / 3;
+            triangles.extend((0..triangle_count as u32)
+                .map(|triangle_index| SceneQueryTriangle { instance_index, triangle_index }));
+            instances.push(SceneQueryInstance {
+                id: *id,
+                primitive_index: entry.primitive_index,
+                visible: entry.visible,
+                positions: transform_positions(&primitive.positions, &entry.transform),
+            });
+        }
+        let mut this = Self {
+            geometry: Arc::clone(geometry),
+            instances,
+            triangles,
+            bvh: Bvh::new(&[]),
+        };
+        this.bvh = Bvh::new(&this.triangle_bounds());
+        log_debug!("building scene query complete {} triangles", this.triangles.len());
+        this
+    }
+
+    // catches up with the instances of the scene. moved, shown and hidden instances only refit the
+    // hierarchy while added and removed instances rebuild it.
+    pub fn update(&mut self, scene: &Scene) {
+        self.update_entries(scene.instance_entries());
+    }
+
+    fn update_entries(&mut self, entries: Vec<(usize, SceneInstanceEntry)>) {
+        let entries: Vec<_> = entries.into_iter()
+            .filter(|(_, v)| v.primitive_index < self.geometry.primitives.len())
+            .collect();
+        let is_same_layout = entries.len() == self.instances.len()
+            && entries.iter()
+                .zip(self.instances.iter())
+                .all(|((id, entry), instance)| *id == instance.id && entry.primitive_index == instance.primitive_index);
+        if !is_same_layout {
+            *self = Self::new(&self.geometry, &entries);
+            return
+        }
+        for ((_, entry), instance) in entries.iter().zip(self.instances.iter_mut()) {
+            let primitive = &self.geometry.primitives[instance.primitive_index];
+            instance.visible = entry.visible;
+            instance.positions = transform_positions(&primitive.positions, &entry.transform);
+        }
+        let bounds = self.triangle_bounds();
+        self.bvh.refit(&bounds);
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    // the bounds of the triangles at the last update, hidden instances included
+    pub fn bounds(&self) -> &AABB {
+        self.bvh.bounds()
+    }
+
+    // the closest triangle along the ray. `direction` need not be normalized, in which case `t` is
+    // measured in its length.
+    pub fn raycast(&self, origin: &glm::Vec3, direction: &glm::Vec3) -> Option<SceneHit> {
+        self.raycast_within(origin, direction, 0.0, f32::INFINITY)
+    }
+
+    pub fn raycast_within(&self, origin: &glm::Vec3, direction: &glm::Vec3, t_min: f32, t_max: f32) -> Option<SceneHit> {
+        let mut barycentrics = glm::vec2(0.0, 0.0);
+        let (index, t) = self.bvh.closest(origin, direction, t_min, t_max, |index, t_max| {
+            let vertices = self.visible_vertices(index)?;
+            let (t, u, v) = intersect_triangle(&vertices, origin, direction, t_min, t_max)?;
+            barycentrics = glm::vec2(u, v);
+            Some(t)
+        })?;
+        let mut hit = self.hit(index, barycentrics, t);
+        if glm::dot(&hit.normal, direction) > 0.0 {
+            hit.normal = -hit.normal;
+        }
+        Some(hit)
+    }
+
+    // whether nothing lies on the segment between the points
+    pub fn is_visible(&self, from: &glm::Vec3, to: &glm::Vec3) -> bool {
+        let direction = to - from;
+        !self.bvh.any(from, &direction, 0.0, 1.0, |index, t_max| {
+            self.visible_vertices(index)
+                .and_then(|vertices| intersect_triangle(&vertices, from, &direction, 0.0, t_max))
+                .is_some()
+        })
+    }
+
+    // the point on the triangles closest to `point` within `max_distance`
+    pub fn closest_point(&self, point: &glm::Vec3, max_distance: f32) -> Option<SceneHit> {
+        let (index, distance) = self.bvh.nearest(point, max_distance, |index| {
+            match self.visible_vertices(index) {
+                Some(vertices) => {
+                    let (u, v) = closest_point_on_triangle(&vertices, point);
+                    glm::distance(&interpolate(&vertices, u, v), point)
+                },
+                None => f32::INFINITY,
+            }
+        })?;
+        let vertices = self.vertices(index);
+        let (u, v) = closest_point_on_triangle(&vertices, point);
+        let mut hit = self.hit(index, glm::vec2(u, v), distance);
+        if glm::dot(&hit.normal, &(point - hit.position)) < 0.0 {
+            hit.normal = -hit.normal;
+        }
+        Some(hit)
+    }
+
+    fn hit(&self, index: usize, barycentrics: glm::Vec2, t: f32) -> SceneHit {
+        let triangle = &self.triangles[index];
+        let instance = &self.instances[triangle.instance_index as usize];
+        let primitive = &self.geometry.primitives[instance.primitive_index];
+        let vertices = self.vertices(index);
+        let normal = glm::cross(&(vertices[1] - vertices[0]), &(vertices[2] - vertices[0]));
+        SceneHit {
+            instance: instance.id,
+            primitive: instance.primitive_index,
+            triangle: triangle.triangle_index as usize,
+            barycentrics,
+            t,
+            material: primitive.material_index,
+            position: interpolate(&vertices, barycentrics.x, barycentrics.y),
+            normal: glm::normalize(&normal),
+        }
+    }
+
+    fn vertices(&self, index: usize) -> [glm::Vec3; 3] {
+        let triangle = &self.triangles[index];
+        let instance = &self.instances[triangle.instance_index as usize];
+        let indices = &self.geometry.primitives[instance.primitive_index].indices;
+        let offset = triangle.triangle_index as usize * 3;
+        [
+            instance.positions[indices[offset] as usize],
+            instance.positions[indices[offset + 1] as usize],
+            instance.positions[indices[offset + 2] as usize],
+        ]
+    }
+
+    fn visible_vertices(&self, index: usize) -> Option<[glm::Vec3; 3]> {
+        let instance = &self.instances[self.triangles[index].instance_index as usize];
+        if instance.visible {
+            Some(self.vertices(index))
+        } else {
+            None
+        }
+    }
+
+    fn triangle_bounds(&self) -> Vec<AABB> {
+        (0..self.triangles.len())
+            .map(|index| AABB::from_points(&self.vertices(index)))
+            .collect()
+    }
+}
+
+fn transform_positions(positions: &[glm::Vec3], transform: &glm::Mat4) -> Vec<glm::Vec3> {
+    positions.iter()
+        .map(|v| (transform * glm::vec4(v.x, v.y, v.z, 1.0)).xyz())
+        .collect()
+}
+
+fn interpolate(vertices: &[glm::Vec3; 3], u: f32, v: f32) -> glm::Vec3 {
+    vertices[0] * (1.0 - u - v) + vertices[1] * u + vertices[2] * v
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::mesh::MeshTable;
+    use super::super::primitive::Primitive;
+
+    const POSITIONS: [[f32; 3]; 3] = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+    const NORMALS: [[f32; 3]; 3] = [[0.0, 0.0, 1.0]; 3];
+    const TEXCOORDS: [[f32; 2]; 3] = [[0.0, 0.0]; 3];
+    const INDICES: [u32; 3] = [0, 1, 2];
+
+    // a unit triangle in the xy plane facing +z
+    fn geometry() -> Arc<SceneGeometry> {
+        let primitive = Primitive::from_slices(&INDICES, &POSITIONS, &NORMALS, &TEXCOORDS, Some(3), true);
+        let table = MeshTable::from_primitives(vec![(0, primitive)]);
+        Arc::new(SceneGeometry::new(table.mesh_primitives()).unwrap())
+    }
+
+    fn entry(transform: glm::Mat4) -> SceneInstanceEntry {
+        SceneInstanceEntry {
+            primitive_index: 0,
+            transform,
+            visible: true,
+            name: None,
+        }
+    }
+
+    fn translation(x: f32, y: f32, z: f32) -> glm::Mat4 {
+        glm::translation(&glm::vec3(x, y, z))
+    }
+
+    fn down(x: f32, y: f32) -> (glm::Vec3, glm::Vec3) {
+        (glm::vec3(x, y, 5.0), glm::vec3(0.0, 0.0, -1.0))
+    }
+
+    #[test]
+    fn raycast_hits_the_unit_triangle() {
+        let query = SceneQuery::new(&geometry(), &[(4, entry(glm::identity()))]);
+        assert_eq!(query.triangle_count(), 1);
+        let (origin, direction) = down(0.25, 0.5);
+        let hit = query.raycast(&origin, &direction).unwrap();
+        assert_eq!(hit.instance, 4);
+        assert_eq!(hit.primitive, 0);
+        assert_eq!(hit.triangle, 0);
+        assert_eq!(hit.material, Some(3));
+        assert!((hit.t - 5.0).abs() < 1e-5);
+        assert!(glm::distance(&hit.barycentrics, &glm::vec2(0.25, 0.5)) < 1e-5);
+        assert!(glm::distance(&hit.position, &glm::vec3(0.25, 0.5, 0.0)) < 1e-5);
+        assert!(glm::distance(&hit.normal, &glm::vec3(0.0, 0.0, 1.0)) < 1e-5);
+        // the normal faces the side the ray came from
+        let hit = query.raycast(&glm::vec3(0.25, 0.5, -5.0), &glm::vec3(0.0, 0.0, 1.0)).unwrap();
+        assert!(glm::distance(&hit.normal, &glm::vec3(0.0, 0.0, -1.0)) < 1e-5);
+        let (origin, direction) = down(0.75, 0.75);
+        assert!(query.raycast(&origin, &direction).is_none());
+        let (origin, direction) = down(0.25, 0.5);
+        assert!(query.raycast_within(&origin, &direction, 0.0, 4.0).is_none());
+    }
+
+    #[test]
+    fn hidden_instances_are_skipped() {
+        let mut hidden = entry(translation(0.0, 0.0, 1.0));
+        hidden.visible = false;
+        let query = SceneQuery::new(&geometry(), &[(0, hidden), (1, entry(glm::identity()))]);
+        let (origin, direction) = down(0.25, 0.25);
+        let hit = query.raycast(&origin, &direction).unwrap();
+        assert_eq!(hit.instance, 1);
+        assert!((hit.t - 5.0).abs() < 1e-5);
+        let hit = query.closest_point(&glm::vec3(0.25, 0.25, 2.0), 10.0).unwrap();
+        assert_eq!(hit.instance, 1);
+        assert!(query.is_visible(&glm::vec3(0.25, 0.25, 5.0), &glm::vec3(0.25, 0.25, 0.5)));
+    }
+
+    #[test]
+    fn update_refits_moved_instances() {
+        let mut query = SceneQuery::new(&geometry(), &[(0, entry(glm::identity()))]);
+        query.update_entries(vec![(0, entry(translation(10.0, 0.0, 0.0)))]);
+        assert_eq!(query.triangle_count(), 1);
+        assert_eq!(query.bounds().min(), &glm::vec3(10.0, 0.0, 0.0));
+        assert_eq!(query.bounds().max(), &glm::vec3(11.0, 1.0, 0.0));
+        let (origin, direction) = down(0.25, 0.25);
+        assert!(query.raycast(&origin, &direction).is_none());
+        let (origin, direction) = down(10.25, 0.25);
+        let hit = query.raycast(&origin, &direction).unwrap();
+        assert!(glm::distance(&hit.position, &glm::vec3(10.25, 0.25, 0.0)) < 1e-5);
+        // hiding does not need a rebuild either
+        let mut hidden = entry(translation(10.0, 0.0, 0.0));
+        hidden.visible = false;
+        query.update_entries(vec![(0, hidden)]);
+        assert!(query.raycast(&origin, &direction).is_none());
+    }
+
+    #[test]
+    fn update_rebuilds_after_adding_and_removing() {
+        let mut query = SceneQuery::new(&geometry(), &[(0, entry(glm::identity()))]);
+        query.update_entries(vec![(0, entry(glm::identity())), (1, entry(translation(5.0, 0.0, 0.0)))]);
+        assert_eq!(query.triangle_count(), 2);
+        let (origin, direction) = down(5.25, 0.25);
+        assert_eq!(query.raycast(&origin, &direction).unwrap().instance, 1);
+        query.update_entries(vec![(1, entry(translation(5.0, 0.0, 0.0)))]);
+        assert_eq!(query.triangle_count(), 1);
+        let (origin, direction) = down(0.25, 0.25);
+        assert!(query.raycast(&origin, &direction).is_none());
+        // instances of meshes the geometry does not know are left out
+        let mut unknown = entry(glm::identity());
+        unknown.primitive_index = 1;
+        query.update_entries(vec![(1, entry(translation(5.0, 0.0, 0.0))), (2, unknown)]);
+        assert_eq!(query.triangle_count(), 1);
+    }
+
+    #[test]
+    fn is_visible_checks_the_segment() {
+        let query = SceneQuery::new(&geometry(), &[(0, entry(glm::identity()))]);
+        let above = glm::vec3(0.25, 0.25, 1.0);
+        let below = glm::vec3(0.25, 0.25, -1.0);
+        assert!(!query.is_visible(&above, &below));
+        assert!(query.is_visible(&above, &glm::vec3(0.25, 0.25, 0.5)));
+        assert!(query.is_visible(&glm::vec3(2.0, 2.0, 1.0), &glm::vec3(2.0, 2.0, -1.0)));
+    }
+
+    #[test]
+    fn closest_point_on_the_triangle() {
+        let query = SceneQuery::new(&geometry(), &[(0, entry(glm::identity()))]);
+        // above the face
+        let hit = query.closest_point(&glm::vec3(0.25, 0.25, -2.0), 10.0).unwrap();
+        assert!((hit.t - 2.0).abs() < 1e-5);
+        assert!(glm::distance(&hit.position, &glm::vec3(0.25, 0.25, 0.0)) < 1e-5);
+        assert!(glm::distance(&hit.normal, &glm::vec3(0.0, 0.0, -1.0)) < 1e-5);
+        // beyond a vertex
+        let hit = query.closest_point(&glm::vec3(3.0, -1.0, 0.0), 10.0).unwrap();
+        assert!(glm::distance(&hit.position, &glm::vec3(1.0, 0.0, 0.0)) < 1e-5);
+        assert!(glm::distance(&hit.barycentrics, &glm::vec2(1.0, 0.0)) < 1e-5);
+        assert!(query.closest_point(&glm::vec3(3.0, -1.0, 0.0), 1.0).is_none());
+    }
+}
@@ -0,0 +1,640 @@
+
+use nalgebra_glm as glm;
+
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::vk::Result;
+use crate::vk::*;
+use crate::base::{Camera, Lens, unproject_lens_ray};
+
+use super::aabb::*;
+use super::bvh::{Bvh, intersect_triangle};
+use super::cache::{SceneCache, CachedTexture};
+use super::custom::{CustomSceneBuilder, srgb_to_linear, linear_to_srgb};
+use super::image as scene_image;
+
+// mirrors `ray.rgen`, `ray.triangles.rchit`, `ray.triangles.rahit` and `ray.rmiss`
+const LIGHT_POSITION: [f32; 3] = [9.0, 20.0, 8.0];
+const MIN_LIGHT_DIFFUSE: f32 = 0.1;
+const SHADOW_ATTENUATION: f32 = 0.3;
+const ALPHA_CUTOFF: f32 = 0.5;
+const T_MIN: f32 = 0.001;
+const T_MAX: f32 = 10000.0;
+const LENS_SAMPLES: usize = 8;
+
+const TILE_SIZE: usize = 32;
+
+// the triangle meshes, materials and textures of a scene laid out for tracing on the CPU.
+// procedural geometries and callable materials are not supported; every material is shaded
+// with the built-in model.
+pub struct ReferenceScene {
+    normals: Vec<[f32; 3]>,
+    texcoords: Vec<[f32; 2]>,
+    tangents: Vec<[f32; 4]>,
+    colors: Vec<[f32; 4]>,
+    primitives: Vec<ReferencePrimitive>,
+    materials: Vec<ReferenceMaterial>,
+    instances: Vec<ReferenceInstance>,
+    triangles: Vec<ReferenceTriangle>,
+    bvh: Bvh,
+}
+
+struct ReferencePrimitive {
+    vertex_offset: usize,
+    vertex_count: usize,
+    index_offset: usize,
+    index_count: usize,
+    material_index: usize,
+    is_opaque: bool,
+    has_tangents: bool,
+    has_colors: bool,
+}
+
+struct ReferenceMaterial {
+    color_texture: Option<Arc<CachedTexture>>,
+    normal_texture: Option<Arc<CachedTexture>>,
+}
+
+struct ReferenceInstance {
+    primitive_index: usize,
+    // transforms normals from the space of the instance to the world
+    normal_transform: glm::Mat3,
+}
+
+struct ReferenceTriangle {
+    // in world space
+    vertices: [glm::Vec3; 3],
+    // the vertex indices within the primitive
+    indices: [usize; 3],
+    instance_index: usize,
+}
+
+impl ReferenceScene {
+    pub fn new(cache: &SceneCache) -> Result<Self> {
+        log_debug!("creating reference scene");
+        let primitives: Vec<ReferencePrimitive> = cache.primitives().iter()
+            .map(|v| ReferencePrimitive {
+                vertex_offset: v.vertex_offset as usize,
+                vertex_count: v.vertex_count as usize,
+                index_offset: v.index_offset as usize,
+                index_count: v.index_count as usize,
+                // the staging buffers fall back to the first material likewise
+                material_index: if v.material_index >= 0 { v.material_index as usize } else { 0 },
+                is_opaque: v.is_opaque(),
+                has_tangents: v.has_tangents(),
+                has_colors: v.has_colors(),
+            })
+            .collect();
+        let materials: Vec<ReferenceMaterial> = cache.materials().iter()
+            .map(|v| ReferenceMaterial {
+                color_texture: v.color_texture().cloned(),
+                normal_texture: v.normal_texture().cloned(),
+            })
+            .collect();
+        let instances: Vec<(usize, glm::Mat4)> = cache.instances().iter()
+            .map(|v| (v.primitive_index as usize, glm::make_mat4(&v.transform)))
+            .collect();
+        let this = Self {
+            normals: cache.normals().to_vec(),
+            texcoords: cache.texcoords().to_vec(),
+            tangents: cache.tangents().to_vec(),
+            colors: cache.colors().to_vec(),
+            primitives,
+            materials,
+            instances: vec![],
+            triangles: vec![],
+            bvh: Bvh::new(&[]),
+        };
+        this.with_instances(cache.positions(), cache.indices(), &instances)
+    }
+
+    // the meshes of a custom, OBJ, PLY or STL scene. the textures are converted the way
+    // `CustomSceneBuilder::build` uploads them.
+    pub fn from_builder(builder: &CustomSceneBuilder) -> Result<Self> {
+        log_debug!("creating reference scene from builder");
+        let mut positions: Vec<[f32; 3]> = vec![];
+        let mut indices: Vec<u32> = vec![];
+        let mut normals: Vec<[f32; 3]> = vec![];
+        let mut texcoords: Vec<[f32; 2]> = vec![];
+        let mut tangents: Vec<[f32; 4]> = vec![];
+        let mut colors: Vec<[f32; 4]> = vec![];
+        let mut primitives: Vec<ReferencePrimitive> = vec![];
+        for mesh in builder.meshes().iter() {
+            let vertex_offset = positions.len();
+            let vertex_count = mesh.positions().len();
+            primitives.push(ReferencePrimitive {
+                vertex_offset,
+                vertex_count,
+                index_offset: indices.len(),
+                index_count: mesh.indices().len(),
+                material_index: mesh.material_index(),
+                is_opaque: builder.materials().get(mesh.material_index()).map_or(true, |v| v.is_opaque()),
+                has_tangents: mesh.tangents().is_some(),
+                has_colors: mesh.colors().is_some(),
+            });
+            positions.extend_from_slice(mesh.positions());
+            indices.extend_from_slice(mesh.indices());
+            normals.extend_from_slice(mesh.normals());
+            texcoords.extend_from_slice(mesh.texcoords());
+            tangents.extend_from_slice(mesh.tangents().unwrap_or(&vec![[0.0; 4]; vertex_count]));
+            colors.extend_from_slice(mesh.colors().unwrap_or(&vec![[0.0; 4]; vertex_count]));
+        }
+        let texture = |data: Option<scene_image::Data>, srgb: bool| {
+            data.map(|v| Arc::new(CachedTexture::new(&v.pixels, v.width, v.height, srgb)))
+        };
+        let materials: Vec<ReferenceMaterial> = builder.materials().iter()
+            .map(|v| ReferenceMaterial {
+                color_texture: texture(v.color_image_data(), true),
+                normal_texture: texture(v.normal_image_data(), false),
+            })
+            .collect();
+        let instances: Vec<(usize, glm::Mat4)> = builder.instances().iter()
+            .map(|v| (v.mesh_index, v.transform))
+            .collect();
+        let this = Self {
+            normals,
+            texcoords,
+            tangents,
+            colors,
+            primitives,
+            materials,
+            instances: vec![],
+            triangles: vec![],
+            bvh: Bvh::new(&[]),
+        };
+        this.with_instances(&positions, &indices, &instances)
+    }
+
+    // places the triangles of the primitives in the world and builds the hierarchy over them
+    fn with_instances(mut self, positions: &[[f32; 3]], indices: &[u32], instances: &[(usize, glm::Mat4)]) -> Result<Self> {
+        for (primitive_index, transform) in instances.iter() {
+            let primitive = self.primitives.get(*primitive_index)
+                .ok_or(ErrorCode::MeshNotFound)?;
+            let normal_transform = glm::transpose(&glm::inverse(&glm::mat4_to_mat3(transform)));
+            let primitive_indices = indices
+                .get(primitive.index_offset..primitive.index_offset + primitive.index_count)
+                .ok_or(ErrorCode::MeshFormatInvalid)?;
+            let primitive_positions = positions
+                .get(primitive.vertex_offset..primitive.vertex_offset + primitive.vertex_count)
+                .ok_or(ErrorCode::MeshFormatInvalid)?;
+            let world_positions: Vec<glm::Vec3> = primitive_positions.iter()
+                .map(|v| (transform * glm::vec4(v[0], v[1], v[2], 1.0)).xyz())
+                .collect();
+            let instance_index = self.instances.len();
+            for triangle in primitive_indices.chunks_exact(3) {
+                let indices = [triangle[0] as usize, triangle[1] as usize, triangle[2] as usize];
+                if indices.iter().any(|&v| v >= world_positions.len()) {
+                    return Err(ErrorCode::MeshFormatInvalid.into())
+                }
+                self.triangles.push(ReferenceTriangle {
+                    vertices: [world_positions[indices[0]], world_positions[indices[1]], world_positions[indices[2]]],
+                    indices,
+                    instance_index,
+                });
+            }
+            self.instances.push(ReferenceInstance {
+                primitive_index: *primitive_index,
+                normal_transform,
+            });
+        }
+        log_debug!("building reference bvh over {} triangles", self.triangles.len());
+        let bounds: Vec<AABB> = self.triangles.iter()
+            .map(|v| AABB::from_points(&v.vertices))
+            .collect();
+        self.bvh = Bvh::new(&bounds);
+        log_debug!("creating reference scene complete");
+        Ok(self)
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    pub fn bounds(&self) -> &AABB {
+        self.bvh.bounds()
+    }
+
+    // the closest hit, skipping the alpha tested texels like `ray.triangles.rahit`
+    fn closest_hit(&self, origin: &glm::Vec3, direction: &glm::Vec3, t_min: f32, t_max: f32) -> Option<ReferenceHit> {
+        let mut barycentrics = glm::vec2(0.0, 0.0);
+        let (index, t) = self.bvh.closest(origin, direction, t_min, t_max, |index, t_max| {
+            let (t, u, v) = intersect_triangle(&self.triangles[index].vertices, origin, direction, t_min, t_max)?;
+            if !self.passes_alpha_test(index, u, v) {
+                return None
+            }
+            barycentrics = glm::vec2(u, v);
+            Some(t)
+        })?;
+        Some(ReferenceHit { triangle_index: index, t, barycentrics })
+    }
+
+    fn is_occluded(&self, origin: &glm::Vec3, direction: &glm::Vec3, t_min: f32, t_max: f32) -> bool {
+        self.bvh.any(origin, direction, t_min, t_max, |index, t_max| {
+            match intersect_triangle(&self.triangles[index].vertices, origin, direction, t_min, t_max) {
+                Some((_, u, v)) => self.passes_alpha_test(index, u, v),
+                None => false,
+            }
+        })
+    }
+
+    fn passes_alpha_test(&self, triangle_index: usize, u: f32, v: f32) -> bool {
+        let triangle = &self.triangles[triangle_index];
+        let primitive = self.primitive(triangle);
+        if primitive.is_opaque {
+            return true
+        }
+        let texture = match self.materials.get(primitive.material_index).and_then(|v| v.color_texture.as_ref()) {
+            Some(v) => v,
+            None => return true,
+        };
+        let barycentrics = glm::vec3(1.0 - u - v, u, v);
+        let texcoord = self.interpolate_texcoord(primitive, triangle, &barycentrics);
+        sample(texture, &texcoord).w >= ALPHA_CUTOFF
+    }
+
+    fn primitive(&self, triangle: &ReferenceTriangle) -> &ReferencePrimitive {
+        &self.primitives[self.instances[triangle.instance_index].primitive_index]
+    }
+
+    fn interpolate_texcoord(&self, primitive: &ReferencePrimitive, triangle: &ReferenceTriangle, barycentrics: &glm::Vec3) -> glm::Vec2 {
+        let at = |i: usize| glm::make_vec2(&self.texcoords[primitive.vertex_offset + triangle.indices[i]]);
+        at(0) * barycentrics.x + at(1) * barycentrics.y + at(2) * barycentrics.z
+    }
+
+    // `ray.triangles.rchit` for the built-in material type
+    fn shade(&self, hit: &ReferenceHit, origin: &glm::Vec3, direction: &glm::Vec3) -> glm::Vec3 {
+        let triangle = &self.triangles[hit.triangle_index];
+        let instance = &self.instances[triangle.instance_index];
+        let primitive = &self.primitives[instance.primitive_index];
+        let barycentrics = glm::vec3(1.0 - hit.barycentrics.x - hit.barycentrics.y, hit.barycentrics.x, hit.barycentrics.y);
+        let vertex = |i: usize| primitive.vertex_offset + triangle.indices[i];
+        let world_position = origin + direction * hit.t;
+        // normal of the triangle
+        let normal_at = |i: usize| glm::make_vec3(&self.normals[vertex(i)]);
+        let object_normal = glm::normalize(&(normal_at(0) * barycentrics.x + normal_at(1) * barycentrics.y + normal_at(2) * barycentrics.z));
+        let mut world_normal = glm::normalize(&(instance.normal_transform * object_normal));
+        let [v0, v1, v2] = &triangle.vertices;
+        let mut world_geometric_normal = glm::normalize(&glm::cross(&(v1 - v0), &(v2 - v0)));
+        // incident ray geometric normal
+        if glm::dot(&world_geometric_normal, direction) > 0.0 {
+            world_geometric_normal = -world_geometric_normal;
+        }
+        // texture
+        let texcoord = self.interpolate_texcoord(primitive, triangle, &barycentrics);
+        let material = self.materials.get(primitive.material_index);
+        let texture_diffuse = material.and_then(|v| v.color_texture.as_ref())
+            .map_or(glm::vec3(1.0, 1.0, 1.0), |v| sample(v, &texcoord).xyz());
+        // normal mapping
+        if let Some(normal_texture) = material.and_then(|v| v.normal_texture.as_ref()) {
+            let tangent_at = |i: usize| if primitive.has_tangents {
+                glm::make_vec4(&self.tangents[vertex(i)])
+            } else {
+                glm::vec4(0.0, 0.0, 0.0, 0.0)
+            };
+            let tangent = tangent_at(0) * barycentrics.x + tangent_at(1) * barycentrics.y + tangent_at(2) * barycentrics.z;
+            let bitangent = glm::normalize(&glm::cross(&object_normal, &tangent.xyz())) * tangent.w;
+            let texture_normal = sample(normal_texture, &texcoord).xyz();
+            let tangent_normal = texture_normal * 2.0 - glm::vec3(1.0, 1.0, 1.0);
+            let tbn = glm::mat3(
+                tangent.x, bitangent.x, object_normal.x,
+                tangent.y, bitangent.y, object_normal.y,
+                tangent.z, bitangent.z, object_normal.z);
+            let object_normal = glm::normalize(&(tbn * tangent_normal));
+            world_normal = glm::normalize(&(instance.normal_transform * object_normal));
+        }
+        // double sided back-face should have reversed normals
+        if glm::dot(&world_normal, &world_geometric_normal) <= 0.0 {
+            world_normal = -world_normal;
+        }
+        // colors
+        let color_multiplier = if primitive.has_colors {
+            let color_at = |i: usize| glm::make_vec4(&self.colors[vertex(i)]);
+            (color_at(0) * barycentrics.x + color_at(1) * barycentrics.y + color_at(2) * barycentrics.z).xyz()
+        } else {
+            glm::vec3(1.0, 1.0, 1.0)
+        };
+        // diffuse
+        let light_position = glm::make_vec3(&LIGHT_POSITION);
+        let to_light = light_position - world_position;
+        let light_direction = glm::normalize(&to_light);
+        let light = glm::dot(&world_normal, &light_direction).max(MIN_LIGHT_DIFFUSE);
+        // shadow
+        let is_shadowed = self.is_occluded(&world_position, &light_direction, T_MIN, glm::length(&to_light));
+        let attenuation = if is_shadowed { SHADOW_ATTENUATION } else { 1.0 };
+        texture_diffuse.component_mul(&color_multiplier) * light * attenuation
+    }
+
+    // `ray.rmiss`
+    fn sky(direction: &glm::Vec3) -> glm::Vec3 {
+        let direction = glm::normalize(direction);
+        let t = 0.5 * (direction.y + 1.0);
+        let sky_color = glm::vec3(0.5, 0.7, 1.0);
+        let bottom_color = glm::vec3(1.0, 1.0, 1.0);
+        glm::mix(&bottom_color, &sky_color, t)
+    }
+
+    fn trace(&self, origin: &glm::Vec3, direction: &glm::Vec3) -> glm::Vec3 {
+        match self.closest_hit(origin, direction, T_MIN, T_MAX) {
+            Some(hit) => self.shade(&hit, origin, direction),
+            None => Self::sky(direction),
+        }
+    }
+}
+
+struct ReferenceHit {
+    triangle_index: usize,
+    t: f32,
+    barycentrics: glm::Vec2,
+}
+
+// renders a `ReferenceScene` on the CPU the way the ray tracing pipeline does, split into tiles
+// shared by a thread pool
+pub struct ReferenceRenderer {
+    width: usize,
+    height: usize,
+    samples: usize,
+    threads: usize,
+}
+
+impl ReferenceRenderer {
+    pub fn new(width: usize, height: usize) -> Self {
+        let threads = std::thread::available_parallelism()
+            .map(|v| v.get())
+            .unwrap_or(1);
+        Self {
+            width,
+            height,
+            samples: 1,
+            threads,
+        }
+    }
+
+    // a single sample goes through the pixel center like `ray.rgen`. more samples are jittered.
+    pub fn with_samples(mut self, samples: usize) -> Self {
+        self.samples = samples.max(1);
+        self
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn render(&self, scene: &ReferenceScene, camera: &dyn Camera) -> ReferenceImage {
+        let view_inverse = glm::Mat4::from(&camera.view_inverse());
+        let projection_inverse = glm::Mat4::from(&camera.projection_inverse());
+        let lens = *camera.lens();
+        let tiles_x = (self.width + TILE_SIZE - 1) / TILE_SIZE;
+        let tiles_y = (self.height + TILE_SIZE - 1) / TILE_SIZE;
+        let tile_count = tiles_x * tiles_y;
+        let next_tile = AtomicUsize::new(0);
+        let pixels = Mutex::new(vec![glm::vec3(0.0, 0.0, 0.0); self.width * self.height]);
+        std::thread::scope(|scope| {
+            for _ in 0..self.threads.min(tile_count.max(1)) {
+                scope.spawn(|| loop {
+                    let tile = next_tile.fetch_add(1, Ordering::Relaxed);
+                    if tile >= tile_count {
+                        break
+                    }
+                    let x0 = (tile % tiles_x) * TILE_SIZE;
+                    let y0 = (tile / tiles_x) * TILE_SIZE;
+                    let x1 = (x0 + TILE_SIZE).min(self.width);
+                    let y1 = (y0 + TILE_SIZE).min(self.height);
+                    let mut colors = Vec::with_capacity((x1 - x0) * (y1 - y0));
+                    for y in y0..y1 {
+                        for x in x0..x1 {
+                            colors.push(self.render_pixel(scene, &view_inverse, &projection_inverse, &lens, x, y));
+                        }
+                    }
+                    let mut pixels = pixels.lock().unwrap();
+                    let mut colors = colors.into_iter();
+                    for y in y0..y1 {
+                        for x in x0..x1 {
+                            pixels[y * self.width + x] = colors.next().unwrap();
+                        }
+                    }
+                });
+            }
+        });
+        ReferenceImage {
+            width: self.width,
+            height: self.height,
+            pixels: pixels.into_inner().unwrap(),
+        }
+    }
+
+    fn render_pixel(
+        &self,
+        scene: &ReferenceScene,
+        view_inverse: &glm::Mat4,
+        projection_inverse: &glm::Mat4,
+        lens: &Lens,
+        x: usize,
+        y: usize,
+    ) -> glm::Vec3 {
+        let mut rng = Xorshift64::with_seed((y * self.width + x) as u64 + 1);
+        let mut color = glm::vec3(0.0, 0.0, 0.0);
+        // `ray.rgen` takes several lens samples through the pixel center
+        let samples = if lens.is_pinhole() { self.samples } else { self.samples.max(LENS_SAMPLES) };
+        for _ in 0..samples {
+            let jitter = if self.samples > 1 {
+                glm::vec2(rng.next_uniform_f32(), rng.next_uniform_f32())
+            } else {
+                glm::vec2(0.5, 0.5)
+            };
+            let pixel_center = glm::vec2(x as f32, y as f32) + jitter;
+            let uv = glm::vec2(pixel_center.x / self.width as f32, pixel_center.y / self.height as f32);
+            let d = uv * 2.0 - glm::vec2(1.0, 1.0);
+            let sample = lens.sample(rng.next_uniform_f32(), rng.next_uniform_f32(), rng.next_uniform_f32());
+            let (origin, direction) = unproject_lens_ray(view_inverse, projection_inverse, &d, lens, &sample);
+            color += scene.trace(&origin, &direction);
+        }
+        color / samples as f32
+    }
+}
+
+// linear colors as written to the storage image by `ray.rgen`
+pub struct ReferenceImage {
+    width: usize,
+    height: usize,
+    pixels: Vec<glm::Vec3>,
+}
+
+impl ReferenceImage {
+    // reads back an image stored as RGBA8 UNORM, such as the ray tracing output
+    pub fn from_rgba8(width: usize, height: usize, data: &[u8]) -> Option<Self> {
+        if data.len() != width * height * 4 {
+            return None
+        }
+        let pixels = data.chunks_exact(4)
+            .map(|v| glm::vec3(v[0] as f32, v[1] as f32, v[2] as f32) / 255.0)
+            .collect();
+        Some(Self { width, height, pixels })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> &glm::Vec3 {
+        &self.pixels[y * self.width + x]
+    }
+
+    // the colors clamped and quantized like the storage image
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        self.pixels.iter()
+            .flat_map(|v| {
+                let quantize = |v: f32| (v.clamp(0.0, 1.0) * 255.0 + 0.5) as u8;
+                vec![quantize(v.x), quantize(v.y), quantize(v.z), 255u8]
+            })
+            .collect()
+    }
+
+    // the root mean square difference of the channels, or `None` when the sizes differ
+    pub fn difference(&self, other: &ReferenceImage) -> Option<f32> {
+        if self.width != other.width || self.height != other.height {
+            return None
+        }
+        if self.pixels.is_empty() {
+            return Some(0.0)
+        }
+        let sum: f32 = self.pixels.iter()
+            .zip(other.pixels.iter())
+            .map(|(a, b)| {
+                let clamp = |v: &glm::Vec3| glm::clamp(v, 0.0, 1.0);
+                glm::length2(&(clamp(a) - clamp(b)))
+            })
+            .sum();
+        Some((sum / (self.pixels.len() * 3) as f32).sqrt())
+    }
+
+    // encoded to sRGB the way the swapchain presents the storage image
+    pub fn save<P>(&self, path: P) -> Result<()> where P: AsRef<Path> {
+        let path = path.as_ref();
+        let data: Vec<u8> = self.to_rgba8()
+            .chunks_exact(4)
+            .flat_map(|v| {
+                let encode = |v: u8| (linear_to_srgb(v as f32 / 255.0) * 255.0 + 0.5) as u8;
+                vec![encode(v[0]), encode(v[1]), encode(v[2]), v[3]]
+            })
+            .collect();
+        image_crate::save_buffer(path, &data, self.width as u32, self.height as u32, image_crate::ColorType::Rgba8)
+            .map_err(|e| Error::from(e).with_path(path))
+    }
+}
+
+// bilinear filtering of the first mip level with repeating texture coordinates, like the texture
+// sampler. sRGB textures are decoded to linear colors.
+fn sample(texture: &CachedTexture, texcoord: &glm::Vec2) -> glm::Vec4 {
+    let width = texture.width() as usize;
+    let height = texture.height() as usize;
+    let levels = texture.levels();
+    let texel = |x: isize, y: isize| -> glm::Vec4 {
+        let x = x.rem_euclid(width as isize) as usize;
+        let y = y.rem_euclid(height as isize) as usize;
+        let offset = (y * width + x) * 4;
+        let v = &levels[offset..offset + 4];
+        let channel = |v: u8| v as f32 / 255.0;
+        if texture.is_srgb() {
+            glm::vec4(srgb_to_linear(channel(v[0])), srgb_to_linear(channel(v[1])), srgb_to_linear(channel(v[2])), channel(v[3]))
+        } else {
+            glm::vec4(channel(v[0]), channel(v[1]), channel(v[2]), channel(v[3]))
+        }
+    };
+    let x = texcoord.x * width as f32 - 0.5;
+    let y = texcoord.y * height as f32 - 0.5;
+    let x0 = x.floor();
+    let y0 = y.floor();
+    let fx = x - x0;
+    let fy = y - y0;
+    let (x0, y0) = (x0 as isize, y0 as isize);
+    let top = glm::mix(&texel(x0, y0), &texel(x0 + 1, y0), fx);
+    let bottom = glm::mix(&texel(x0, y0 + 1), &texel(x0 + 1, y0 + 1), fx);
+    glm::mix(&top, &bottom, fy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::{CustomMesh, CustomMaterial, FreeLookCamera};
+
+    // a wall facing the camera at the origin, which looks down -z
+    fn wall(base_color: [f32; 4]) -> ReferenceScene {
+        let mut builder = CustomSceneBuilder::new();
+        let material = builder.add_material(CustomMaterial::new().with_base_color(base_color));
+        let positions = vec![[-10.0, -10.0, -5.0], [10.0, -10.0, -5.0], [10.0, 10.0, -5.0], [-10.0, 10.0, -5.0]];
+        let mesh = CustomMesh::new(positions, vec![0, 1, 2, 0, 2, 3])
+            .with_material(material);
+        let mesh = builder.add_mesh(mesh).unwrap();
+        builder.add_instance(mesh, glm::identity()).unwrap();
+        ReferenceScene::from_builder(&builder).unwrap()
+    }
+
+    #[test]
+    fn from_builder_places_the_instances() {
+        let scene = wall([1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(scene.triangle_count(), 2);
+        assert_eq!(scene.bounds().min(), &glm::vec3(-10.0, -10.0, -5.0));
+        assert_eq!(scene.bounds().max(), &glm::vec3(10.0, 10.0, -5.0));
+    }
+
+    #[test]
+    fn center_pixel_is_lit_by_the_light() {
+        let scene = wall([0.5, 0.25, 1.0, 1.0]);
+        let camera = FreeLookCamera::new(3.0, 3.0);
+        // the center of the middle pixel looks straight ahead, hitting the wall at (0, 0, -5)
+        let image = ReferenceRenderer::new(3, 3)
+            .with_threads(1)
+            .render(&scene, &camera);
+        let to_light = glm::make_vec3(&LIGHT_POSITION) - glm::vec3(0.0, 0.0, -5.0);
+        let light = glm::dot(&glm::vec3(0.0, 0.0, 1.0), &glm::normalize(&to_light));
+        let expected = glm::vec3(0.5, 0.25, 1.0) * light;
+        // the base color goes through an sRGB texel like on the device
+        assert!(glm::distance(image.pixel(1, 1), &expected) < 0.01, "{:?}", image.pixel(1, 1));
+    }
+
+    #[test]
+    fn misses_show_the_sky() {
+        let scene = ReferenceScene::from_builder(&CustomSceneBuilder::new()).unwrap();
+        let camera = FreeLookCamera::new(3.0, 3.0);
+        let image = ReferenceRenderer::new(3, 3)
+            .render(&scene, &camera);
+        let expected = ReferenceScene::sky(&glm::vec3(0.0, 0.0, -1.0));
+        assert!(glm::distance(image.pixel(1, 1), &expected) < 1e-6);
+    }
+
+    #[test]
+    fn render_is_deterministic_across_thread_counts() {
+        let scene = wall([0.8, 0.6, 0.4, 1.0]);
+        let mut camera = FreeLookCamera::new(80.0, 48.0);
+        camera.set_aperture(0.2);
+        // several tiles with jittered samples and lens samples
+        let render = |threads: usize| ReferenceRenderer::new(80, 48)
+            .with_samples(2)
+            .with_threads(threads)
+            .render(&scene, &camera);
+        let single = render(1);
+        for &threads in [2, 3, 8].iter() {
+            let image = render(threads);
+            assert_eq!(image.to_rgba8(), single.to_rgba8());
+            assert_eq!(image.difference(&single), Some(0.0));
+        }
+    }
+
+    #[test]
+    fn rgba8_round_trip() {
+        let data: Vec<u8> = (0..2 * 2 * 4).map(|v| if v % 4 == 3 { 255 } else { v as u8 * 10 }).collect();
+        let image = ReferenceImage::from_rgba8(2, 2, &data).unwrap();
+        assert_eq!(image.to_rgba8(), data);
+        assert!(ReferenceImage::from_rgba8(2, 2, &data[4..]).is_none());
+        let other = ReferenceImage::from_rgba8(1, 4, &data).unwrap();
+        assert!(image.difference(&other).is_none());
+    }
+}
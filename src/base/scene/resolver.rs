@@ -0,0 +1,120 @@
+
+use crate::vk::Result;
+use crate::vk::*;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+// loads the external buffers and images referenced by glTF URIs.
+// `data:` URIs are decoded by the asset itself and never reach the resolvers.
+pub trait UriResolver: Send + Sync {
+    // `uri` is either relative to the asset or a `file:` URI
+    fn resolve(&self, uri: &str) -> Result<Vec<u8>>;
+}
+
+// resolves relative URIs against a directory
+pub struct FileResolver {
+    base: PathBuf,
+}
+
+impl FileResolver {
+    pub fn new<P>(base: P) -> Self where P: AsRef<Path> {
+        Self {
+            base: base.as_ref().to_owned(),
+        }
+    }
+}
+
+impl UriResolver for FileResolver {
+    fn resolve(&self, uri: &str) -> Result<Vec<u8>> {
+        if let Some(path) = uri.strip_prefix("file://").or_else(|| uri.strip_prefix("file:")) {
+            return read_to_end(path);
+        }
+        read_to_end(self.base.join(uri))
+    }
+}
+
+// serves the files registered beforehand, such as the ones embedded with `include_bytes!`
+// or extracted from archives
+pub struct MemoryResolver {
+    files: HashMap<String, Vec<u8>>,
+}
+
+impl MemoryResolver {
+    pub fn new() -> Self {
+        Self {
+            files: HashMap::new(),
+        }
+    }
+
+    pub fn with_file(mut self, uri: impl Into<String>, data: impl Into<Vec<u8>>) -> Self {
+        self.files.insert(uri.into(), data.into());
+        self
+    }
+
+    pub fn insert(&mut self, uri: impl Into<String>, data: impl Into<Vec<u8>>) {
+        self.files.insert(uri.into(), data.into());
+    }
+}
+
+impl Default for MemoryResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UriResolver for MemoryResolver {
+    fn resolve(&self, uri: &str) -> Result<Vec<u8>> {
+        let uri = uri.strip_prefix("./").unwrap_or(uri);
+        self.files.get(uri)
+            .cloned()
+            .ok_or_else(|| Error::from(std::io::Error::from(std::io::ErrorKind::NotFound)).with_path(uri))
+    }
+}
+
+// the asset itself followed by the external files resolved the way `FileResolver` does
+pub(super) fn referenced_files(asset_path: &Path, document: &gltf::Document) -> Vec<PathBuf> {
+    let base = asset_path.parent().unwrap_or_else(|| Path::new("./"));
+    let buffer_uris = document.buffers()
+        .filter_map(|v| match v.source() {
+            gltf::buffer::Source::Uri(uri) => Some(uri),
+            gltf::buffer::Source::Bin => None,
+        });
+    let image_uris = document.images()
+        .filter_map(|v| match v.source() {
+            gltf::image::Source::Uri { uri, .. } => Some(uri),
+            gltf::image::Source::View { .. } => None,
+        });
+    let mut files = vec![asset_path.to_owned()];
+    for uri in buffer_uris.chain(image_uris) {
+        if uri.starts_with("data:") {
+            continue
+        }
+        let path = match uri.strip_prefix("file://").or_else(|| uri.strip_prefix("file:")) {
+            Some(path) => PathBuf::from(path),
+            None => base.join(uri),
+        };
+        files.push(path);
+    }
+    files
+}
+
+pub(super) fn read_to_end<P>(path: P) -> Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let file = File::open(path)
+        .map_err(|e| Error::from(e).with_path(path))?;
+    // Allocate one extra byte so the buffer doesn't need to grow before the
+    // final `read` call at the end of the file.  Don't worry about `usize`
+    // overflow because reading will fail regardless in that case.
+    let length = file.metadata().map(|x| x.len() + 1).unwrap_or(0);
+    let mut reader = BufReader::new(file);
+    let mut data = Vec::with_capacity(length as usize);
+    reader.read_to_end(&mut data)
+        .map_err(|e| Error::from(e).with_path(path))?;
+    Ok(data)
+}
@@ -1,6 +1,7 @@
 
 use nalgebra_glm as glm;
 
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::sync::Mutex;
 
@@ -13,83 +14,173 @@ use super::asset::*;
 use super::mesh::*;
 use super::buffer::*;
 use super::material_repository::*;
+use super::procedural::*;
+use super::query::*;
+use super::aabb::AABB;
 
 pub struct SceneBuilder {
-    asset: Arc<SceneAsset>,
+    assets: Vec<(Arc<SceneAsset>, glm::Mat4)>,
+    options: SceneBuildOptions,
 }
 
 impl SceneBuilder {
     pub fn new(asset: &Arc<SceneAsset>) -> Self {
         Self {
-            asset: Arc::clone(asset),
+            assets: vec![],
+            options: SceneBuildOptions::new(),
         }
+        .with_asset(asset, &glm::identity())
     }
 
-    pub fn build(self, command_pool: &Arc<CommandPool>) -> Scene {
-        let asset = &self.asset;
-        log_debug!("start scene builder");
-        let table = MeshTable::new(asset);
-        log_debug!("iterating nodes");
-        let scene = asset.document()
-            .scenes()
-            .nth(0)
-            .unwrap();
-        let nodes: Vec<_> = scene.nodes()
-            .map(|v| FlattenNode::flatten(v))
-            .flatten()
-            .map(|v| MeshNode::new(v, &table))
-            .filter_map(|v| v)
-            .flatten()
+    pub fn with_options(mut self, options: SceneBuildOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    // places another asset in the same scene with `transform` applied on top of its node hierarchy.
+    // its geometry, materials and images are appended after those of the assets added before.
+    pub fn with_asset(mut self, asset: &Arc<SceneAsset>, transform: &glm::Mat4) -> Self {
+        self.assets.push((Arc::clone(asset), *transform));
+        self
+    }
+
+    pub fn build(self, command_pool: &Arc<CommandPool>) -> Result<Scene> {
+        let assets: Vec<&SceneAsset> = self.assets.iter()
+            .map(|(asset, _)| asset.as_ref())
             .collect();
-        log_debug!("iterating nodes complete");
-        log_debug!("iterating materials");
-        let materials: Vec<_> = asset.document().materials()
-            .into_iter()
-            .map(|v| Material::new(v))
+        log_debug!("start scene builder");
+        let table = MeshTable::from_assets(&assets)?;
+        let mut nodes: Vec<MeshNode> = vec![];
+        let mut materials: Vec<Material> = vec![];
+        let mut mesh_offset: usize = 0;
+        let mut image_offset: usize = 0;
+        for (asset, transform) in self.assets.iter() {
+            log_debug!("iterating nodes");
+            let scene = asset.document()
+                .default_scene()
+                .or_else(|| asset.document().scenes().nth(0))
+                .ok_or(ErrorCode::SceneNotFound)?;
+            let asset_nodes = scene.nodes()
+                .map(|v| FlattenNode::flatten(v))
+                .flatten()
+                .map(|v| MeshNode::with_offset(v, mesh_offset, transform, &table))
+                .filter_map(|v| v)
+                .flatten();
+            nodes.extend(asset_nodes);
+            log_debug!("iterating nodes complete");
+            log_debug!("iterating materials");
+            let asset_materials = asset.document().materials()
+                .map(|v| Material::with_image_offset(v, image_offset));
+            materials.extend(asset_materials);
+            log_debug!("iterating materials complete");
+            mesh_offset += asset.document().meshes().count();
+            image_offset += asset.document().images().count();
+        }
+        let image_provider = ImageProvider::from_assets(&assets);
+        let assets: Vec<_> = self.assets.iter()
+            .map(|(asset, _)| Arc::clone(asset))
             .collect();
-        log_debug!("iterating materials complete");
-        Scene::new(asset, &table, &nodes, &materials, command_pool)
+        Scene::new(&assets, &table, &nodes, &materials, &image_provider, &self.options, command_pool)
+    }
+}
+
+// how the acceleration structures of a scene are built. the top level structure allows updates by
+// default so that moving instances refits it instead of rebuilding it.
+#[derive(Clone, Copy, Debug)]
+pub struct SceneBuildOptions {
+    bottom_level: AccelerationStructureBuildOptions,
+    top_level: AccelerationStructureBuildOptions,
+}
+
+impl SceneBuildOptions {
+    pub fn new() -> Self {
+        Self {
+            bottom_level: AccelerationStructureBuildOptions::new(),
+            top_level: AccelerationStructureBuildOptions::new()
+                .with_update(true),
+        }
+    }
+
+    // applied to the structure of every triangle mesh. allowing updates lets `Scene::refit_mesh`
+    // refit them.
+    pub fn with_bottom_level(mut self, options: AccelerationStructureBuildOptions) -> Self {
+        self.bottom_level = options;
+        self
+    }
+
+    // compaction is ignored since the structure is rebuilt in place
+    pub fn with_top_level(mut self, options: AccelerationStructureBuildOptions) -> Self {
+        self.top_level = options;
+        self
+    }
+
+    pub fn bottom_level(&self) -> AccelerationStructureBuildOptions {
+        self.bottom_level
+    }
+
+    pub fn top_level(&self) -> AccelerationStructureBuildOptions {
+        self.top_level
+    }
+}
+
+impl Default for SceneBuildOptions {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 #[allow(dead_code)]
 pub struct Scene {
-    asset: Arc<SceneAsset>,
+    assets: Vec<Arc<SceneAsset>>,
     command_pool: Arc<CommandPool>,
     primitives: Vec<Arc<SceneMeshPrimitive>>,
     staging_buffers: Arc<SceneStagingBuffers>,
-    top_level_acceleration_structure: Arc<TopLevelAccelerationStructure>,
+    top_level_acceleration_structure: Mutex<Arc<TopLevelAccelerationStructure>>,
     material_repository: Arc<MaterialRepository>,
+    instances: Arc<Mutex<SceneInstances>>,
+    procedurals: Mutex<SceneProcedurals>,
+    geometry: Arc<SceneGeometry>,
+    material_names: Vec<Option<String>>,
+    options: SceneBuildOptions,
     state: Mutex<SceneState>,
 }
 
 impl Scene {
-    fn new(asset: &Arc<SceneAsset>, table: &MeshTable, nodes: &[MeshNode], materials: &[Material], command_pool: &Arc<CommandPool>) -> Self {
+    pub(super) fn new(
+        assets: &[Arc<SceneAsset>],
+        table: &MeshTable,
+        nodes: &[MeshNode],
+        materials: &[Material],
+        image_provider: &ImageProvider,
+        options: &SceneBuildOptions,
+        command_pool: &Arc<CommandPool>,
+    ) -> Result<Self> {
         let primitives = table.mesh_primitives();
+        let geometry = SceneGeometry::new(primitives)?;
         log_debug!("creating material images");
-        let image_provider = ImageProvider::new(asset);
         let descriptions_textures = MaterialDescriptionsTextures::new(
             materials,
-            &image_provider,
-            command_pool);
+            image_provider,
+            command_pool)?;
         let material_repository = MaterialRepository::new(descriptions_textures);
         log_debug!("creating material images complete");
         log_debug!("creating staging buffers");
-        let staging_buffers = SceneStagingBuffers::new(command_pool, primitives, material_repository.state().descriptions());
+        let staging_buffers = SceneStagingBuffers::new(command_pool, primitives, material_repository.state().descriptions())?;
         log_debug!("creating staging buffers complete");
         log_debug!("building blas");
         let scene_mesh_primitive_geometries: Vec<_> = table.mesh_primitives().iter()
             .map(|v| SceneMeshPrimitiveGeometry::new(v, &staging_buffers, command_pool))
-            .collect();
+            .collect::<Result<_>>()?;
         let geometries: Vec<_> = scene_mesh_primitive_geometries.iter()
             .map(|v| v.structure_geometry())
             .map(Arc::clone)
             .collect();
         let queries: Vec<_> = geometries.into_iter()
-            .map(|v| BottomLevelAccelerationStructureBuildQuery::new(vec![v]))
+            .map(|v| BottomLevelAccelerationStructureBuildQuery::new(vec![v])
+                .with_options(options.bottom_level()))
             .collect();
         let builder = BottomLevelAccelerationStructuresBuilder::new(command_pool, queries);
-        let structures = builder.build();
+        let structures = builder.build()?;
         let scene_mesh_primitives: Vec<_> = structures.into_iter()
             .zip(scene_mesh_primitive_geometries.into_iter())
             .map(|(structure, geometry)| SceneMeshPrimitive::new(geometry, structure))
@@ -99,45 +190,240 @@ impl Scene {
         let node_scale: f32 = 1.0;
         let node_scale = glm::scaling(&glm::vec3(node_scale, node_scale, node_scale));
         let node_translate = glm::translation(&glm::vec3(0.0, 0.0, 0.0));
-        let node_instances = nodes.into_iter()
-            .map(|node| {
-                let index = node.primitive().index();
-                let mesh_primitive = scene_mesh_primitives.get(index).unwrap();
-                let transform = node_translate * node_scale * node.transform();
-                let transform = VkTransformMatrixKHR {
-                    matrix: [
-                        [transform.m11, transform.m12, transform.m13, transform.m14],
-                        [transform.m21, transform.m22, transform.m23, transform.m24],
-                        [transform.m31, transform.m32, transform.m33, transform.m34],
-                    ]
-                };
-                let instance = TopLevelAccelerationStructureInstance::new(
-                    index as u32,
-                    transform,
-                    0,
-                    mesh_primitive.bottom_level_acceleration_structure(),
-                ).unwrap();
-                instance
-            });
-        let instances = node_instances
+        let entries = nodes.iter()
+            .map(|node| SceneInstanceEntry {
+                primitive_index: node.primitive().index(),
+                transform: node_translate * node_scale * node.transform(),
+                visible: true,
+                name: node.name().map(|v| v.to_owned()),
+            })
+            .map(Some)
             .collect();
-        let top_level_acceleration_structure = TopLevelAccelerationStructure::new(command_pool, instances)
-            .unwrap();
+        let instances = SceneInstances {
+            entries,
+            material_hit_groups: HashMap::new(),
+            selected: None,
+            dirty: false,
+            selection_dirty: false,
+        };
+        let procedural_geometries = SceneProceduralGeometries::new(&[], command_pool)?;
+        let structure_instances = instances.structure_instances(&scene_mesh_primitives, &procedural_geometries, &staging_buffers)?;
+        let capacity = structure_instances.len();
+        let top_level_acceleration_structure = TopLevelAccelerationStructure::with_options(command_pool, structure_instances, capacity, options.top_level())?;
         log_debug!("building tlas complete");
         log_debug!("scene building complete");
-        Self {
-            asset: Arc::clone(asset),
+        let scene = Self {
+            assets: assets.to_vec(),
             command_pool: Arc::clone(command_pool),
             primitives: scene_mesh_primitives,
             staging_buffers,
-            top_level_acceleration_structure,
+            top_level_acceleration_structure: Mutex::new(top_level_acceleration_structure),
             material_repository,
+            instances: Arc::new(Mutex::new(instances)),
+            procedurals: Mutex::new(SceneProcedurals {
+                geometries: vec![],
+                uploaded: procedural_geometries,
+                dirty: false,
+            }),
+            geometry: Arc::new(geometry),
+            material_names: materials.iter()
+                .map(|v| v.name().map(|v| v.to_owned()))
+                .collect(),
+            options: *options,
             state: Mutex::new(SceneState::new())
+        };
+        Ok(scene)
+    }
+
+    // the structure is replaced when instances are added beyond its capacity
+    pub fn top_level_acceleration_structure(&self) -> Arc<TopLevelAccelerationStructure> {
+        Arc::clone(&self.top_level_acceleration_structure.lock().unwrap())
+    }
+
+    // one per mesh, in the order of `add_instance` mesh indices
+    pub fn bottom_level_acceleration_structure_stats(&self) -> Vec<AccelerationStructureStats> {
+        self.primitives.iter()
+            .map(|v| v.bottom_level_acceleration_structure().stats())
+            .collect()
+    }
+
+    pub fn top_level_acceleration_structure_stats(&self) -> AccelerationStructureStats {
+        self.top_level_acceleration_structure().stats()
+    }
+
+    // refits the structure of a triangle mesh after its vertices were rewritten through
+    // `vertex_staging_buffer`, then the top level structure. both have to allow updates with
+    // `SceneBuildOptions`. `query` keeps the vertices the scene was built with.
+    pub fn refit_mesh(&self, mesh_index: usize) -> Result<()> {
+        let primitive = self.primitives.get(mesh_index)
+            .ok_or(ErrorCode::MeshNotFound)?;
+        // the frames in flight may still be tracing against the structures
+        self.command_pool.queue().wait_idle()?;
+        primitive.bottom_level_acceleration_structure().update(&self.command_pool)?;
+        self.top_level_acceleration_structure().refit(&self.command_pool)
+    }
+
+    // the number of meshes `add_instance` can place. each glTF primitive counts as a mesh,
+    // followed by the procedural geometries.
+    pub fn mesh_count(&self) -> usize {
+        self.primitives.len() + self.procedurals.lock().unwrap().geometries.len()
+    }
+
+    // returns the mesh index to place instances of the geometry with. the geometry is uploaded by
+    // the next `update`, which then requires the frames to be recorded again.
+    pub fn add_procedural_geometry(&self, geometry: ProceduralGeometry) -> Result<usize> {
+        if geometry.primitives().is_empty() {
+            return Err(ErrorCode::MeshFormatInvalid.into())
+        }
+        let mut procedurals = self.procedurals.lock().unwrap();
+        procedurals.geometries.push(geometry);
+        procedurals.dirty = true;
+        Ok(self.primitives.len() + procedurals.geometries.len() - 1)
+    }
+
+    pub fn instances(&self) -> Vec<SceneInstance> {
+        let instances = self.instances.lock().unwrap();
+        instances.entries.iter()
+            .enumerate()
+            .filter(|(_, v)| v.is_some())
+            .map(|(id, _)| SceneInstance::new(id, &self.instances))
+            .collect()
+    }
+
+    // builds a hierarchy over the world space triangles of the instances for ray casts and closest
+    // point queries on the CPU. `SceneQuery::update` follows later changes to the instances.
+    pub fn query(&self) -> SceneQuery {
+        SceneQuery::new(&self.geometry, &self.instance_entries())
+    }
+
+    pub(super) fn instance_entries(&self) -> Vec<(usize, SceneInstanceEntry)> {
+        let instances = self.instances.lock().unwrap();
+        instances.entries.iter()
+            .enumerate()
+            .filter_map(|(id, v)| v.as_ref().map(|v| (id, v.clone())))
+            .collect()
+    }
+
+    // the instance shows up once `update` runs
+    pub fn add_instance(&self, mesh_index: usize, transform: &glm::Mat4) -> Result<SceneInstance> {
+        if mesh_index >= self.mesh_count() {
+            return Err(ErrorCode::MeshNotFound.into())
+        }
+        let mut instances = self.instances.lock().unwrap();
+        let entry = SceneInstanceEntry {
+            primitive_index: mesh_index,
+            transform: *transform,
+            visible: true,
+            name: None,
+        };
+        let id = instances.entries.len();
+        instances.entries.push(Some(entry));
+        instances.dirty = true;
+        Ok(SceneInstance::new(id, &self.instances))
+    }
+
+    // highlights the instance in the ray traced image from the next `update`. a single instance is
+    // selected at a time.
+    pub fn set_selected_instance(&self, instance: Option<&SceneInstance>) -> Result<()> {
+        let mut instances = self.instances.lock().unwrap();
+        let selected = match instance {
+            Some(instance) => {
+                instances.entries.get(instance.id())
+                    .and_then(|v| v.as_ref())
+                    .ok_or(ErrorCode::InstanceNotFound)?;
+                Some(instance.id())
+            },
+            None => None,
+        };
+        if instances.selected != selected {
+            instances.selected = selected;
+            instances.dirty = true;
+            instances.selection_dirty = true;
+        }
+        Ok(())
+    }
+
+    pub fn selected_instance(&self) -> Option<SceneInstance> {
+        let instances = self.instances.lock().unwrap();
+        instances.selected
+            .filter(|&id| instances.entries.get(id).map_or(false, |v| v.is_some()))
+            .map(|id| SceneInstance::new(id, &self.instances))
+    }
+
+    // the object space bounds of a mesh `add_instance` takes, computed from the vertices or the
+    // procedural primitives
+    pub fn mesh_bounds(&self, mesh_index: usize) -> Option<AABB> {
+        if let Some(bounds) = self.geometry.primitive_bounds(mesh_index) {
+            return Some(bounds)
         }
+        let procedurals = self.procedurals.lock().unwrap();
+        let geometry = procedurals.geometries.get(mesh_index.checked_sub(self.primitives.len())?)?;
+        let bounds = geometry.primitives().iter()
+            .fold(AABB::empty(), |acc, v| acc.union(v.aabb()));
+        Some(bounds)
+    }
+
+    // the world space bounds of the mesh of the instance
+    pub fn instance_bounds(&self, instance: &SceneInstance) -> Result<AABB> {
+        let mesh_index = instance.mesh_index()?;
+        let bounds = self.mesh_bounds(mesh_index)
+            .ok_or(ErrorCode::MeshNotFound)?;
+        Ok(bounds.transformed(&instance.transform()?))
+    }
+
+    // the world space bounds of the visible instances, empty when there are none
+    pub fn bounds(&self) -> AABB {
+        self.instance_entries().iter()
+            .filter(|(_, entry)| entry.visible)
+            .filter_map(|(_, entry)| self.mesh_bounds(entry.primitive_index)
+                .map(|v| v.transformed(&entry.transform)))
+            .fold(AABB::empty(), |acc, v| acc.union(&v))
     }
 
-    pub fn top_level_acceleration_structure(&self) -> &Arc<TopLevelAccelerationStructure> {
-        &self.top_level_acceleration_structure
+    // describes a triangle mesh `add_instance` takes. procedural geometries have no summary.
+    pub fn mesh_summary(&self, mesh_index: usize) -> Option<SceneMeshSummary> {
+        let (source_mesh_index, material_index, triangle_count) = self.geometry.primitive_summary(mesh_index)?;
+        let material_name = material_index
+            .and_then(|v| self.material_names.get(v))
+            .cloned()
+            .flatten();
+        let summary = SceneMeshSummary {
+            source_mesh_index,
+            material_index,
+            material_name,
+            triangle_count,
+        };
+        Some(summary)
+    }
+
+    // routes the instances of the meshes using the material through another hit group of the
+    // pipeline, such as one registered with `RayTracingGraphicsPipeline::with_hit_groups`
+    pub fn set_material_hit_group(&self, material_index: usize, hit_group: u32, pipeline: &RayTracingGraphicsPipeline) -> Result<()> {
+        if material_index >= self.material_repository.state().descriptions().len() {
+            return Err(ErrorCode::MaterialNotFound.into())
+        }
+        if hit_group >= pipeline.hit_group_count() {
+            return Err(ErrorCode::HitGroupNotFound.into())
+        }
+        let mut instances = self.instances.lock().unwrap();
+        instances.material_hit_groups.insert(material_index, hit_group);
+        instances.dirty = true;
+        Ok(())
+    }
+
+    // evaluates the material through the callable shader registered for `material_type` with
+    // `RayTracingGraphicsPipeline::with_shaders`, or the built-in shading for
+    // `RayTracingGraphicsPipeline::BUILTIN_MATERIAL_TYPE`
+    pub fn set_material_type(&self, material_index: usize, material_type: u32, pipeline: &RayTracingGraphicsPipeline) -> Result<()> {
+        let mut state = self.material_repository.state();
+        state.set_material_type(material_index, material_type, pipeline.material_type_count())?;
+        // the frames in flight may still be reading the descriptions
+        self.command_pool.queue().wait_idle()?;
+        let descriptions = state.descriptions();
+        let size = std::mem::size_of::<SceneMaterialDescription>() * descriptions.len();
+        self.staging_buffers.material_description_buffer()
+            .write(descriptions.as_ptr() as *const std::ffi::c_void, size);
+        Ok(())
     }
 
     pub fn index_staging_buffer(&self) -> &Arc<DedicatedStagingBuffer> {
@@ -172,6 +458,15 @@ impl Scene {
         &self.staging_buffers.color_buffer()
     }
 
+    // bound as the sphere buffer of the descriptor sets. replaced when procedural geometries are added.
+    pub fn procedural_staging_buffer(&self) -> Arc<DedicatedStagingBuffer> {
+        Arc::clone(self.procedurals.lock().unwrap().uploaded.primitive_buffer())
+    }
+
+    pub fn procedural_material_staging_buffer(&self) -> Arc<DedicatedStagingBuffer> {
+        Arc::clone(self.procedurals.lock().unwrap().uploaded.material_buffer())
+    }
+
     pub fn textures(&self) -> Vec<Arc<Texture>> {
         // copying Vec for some convenience
         let state = self.material_repository.state();
@@ -181,8 +476,11 @@ impl Scene {
             .collect()
     }
 
-    pub fn update(&self, delta_time: f32, descriptor_sets: &Arc<RayTracingDescriptorSets>) {
-        self.state.lock().unwrap().update(self, delta_time, descriptor_sets);
+    // applies the procedural geometry and instance changes made since the previous call. returns true
+    // when buffers bound to the descriptor sets were reallocated, in which case the frames have to be
+    // recorded again.
+    pub fn update(&self, delta_time: f32, descriptor_sets: &Arc<RayTracingDescriptorSets>) -> Result<bool> {
+        self.state.lock().unwrap().update(self, delta_time, descriptor_sets)
     }
 }
 
@@ -196,7 +494,221 @@ impl SceneState {
         } 
     }
 
-    fn update(&mut self, _scene: &Scene, _delta_time: f32, _descriptor_sets: &Arc<RayTracingDescriptorSets>) {
+    fn update(&mut self, scene: &Scene, _delta_time: f32, descriptor_sets: &Arc<RayTracingDescriptorSets>) -> Result<bool> {
+        let mut procedurals = scene.procedurals.lock().unwrap();
+        let mut reallocated = false;
+        if procedurals.dirty {
+            procedurals.dirty = false;
+            // the frames in flight may still be reading the buffers
+            scene.command_pool.queue().wait_idle()?;
+            let uploaded = procedurals.uploaded.append(&procedurals.geometries, &scene.command_pool)?;
+            descriptor_sets.update_procedural_buffers(uploaded.primitive_buffer(), uploaded.material_buffer())?;
+            procedurals.uploaded = uploaded;
+            reallocated = true;
+        }
+        let (instances, selection) = {
+            let mut instances = scene.instances.lock().unwrap();
+            if !instances.dirty {
+                return Ok(reallocated)
+            }
+            instances.dirty = false;
+            // the primitive whose description is copied to the selection slot
+            let selection = if instances.selection_dirty {
+                instances.selection_dirty = false;
+                let primitive_index = instances.selected
+                    .and_then(|id| instances.entries.get(id))
+                    .and_then(|v| v.as_ref())
+                    .map(|v| v.primitive_index)
+                    .filter(|&v| v < scene.primitives.len());
+                Some(primitive_index)
+            } else {
+                None
+            };
+            (instances.structure_instances(&scene.primitives, &procedurals.uploaded, &scene.staging_buffers)?, selection)
+        };
+        // the frames in flight may still be tracing against the structure
+        scene.command_pool.queue().wait_idle()?;
+        if let Some(primitive_index) = selection {
+            scene.staging_buffers.write_selection(primitive_index);
+        }
+        let mut structure = scene.top_level_acceleration_structure.lock().unwrap();
+        if instances.len() <= structure.capacity() {
+            structure.update(&scene.command_pool, instances)?;
+            return Ok(reallocated)
+        }
+        // grows geometrically so that adding instances one at a time seldom reallocates
+        let capacity = instances.len() * 2;
+        let replaced = TopLevelAccelerationStructure::with_options(&scene.command_pool, instances, capacity, scene.options.top_level())?;
+        descriptor_sets.update_acceleration_structure(&replaced)?;
+        *structure = replaced;
+        Ok(true)
+    }
+}
+
+// a TLAS instance of a scene. changes take effect on the next `Scene::update`.
+#[derive(Clone)]
+pub struct SceneInstance {
+    id: usize,
+    instances: Arc<Mutex<SceneInstances>>,
+}
+
+impl SceneInstance {
+    fn new(id: usize, instances: &Arc<Mutex<SceneInstances>>) -> Self {
+        Self {
+            id,
+            instances: Arc::clone(instances),
+        }
+    }
+
+    #[inline]
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn mesh_index(&self) -> Result<usize> {
+        self.with_entry(|entry| entry.primitive_index)
+    }
+
+    pub fn transform(&self) -> Result<glm::Mat4> {
+        self.with_entry(|entry| entry.transform)
+    }
+
+    pub fn is_visible(&self) -> Result<bool> {
+        self.with_entry(|entry| entry.visible)
+    }
+
+    // the name of the node the instance was placed from
+    pub fn name(&self) -> Result<Option<String>> {
+        self.with_entry(|entry| entry.name.clone())
+    }
+
+    pub fn set_transform(&self, transform: &glm::Mat4) -> Result<()> {
+        self.modify_entry(|entry| entry.transform = *transform)
+    }
+
+    // hidden instances stay in the structure with an empty mask, so toggling only refits it
+    pub fn set_visible(&self, visible: bool) -> Result<()> {
+        self.modify_entry(|entry| entry.visible = visible)
+    }
+
+    pub fn remove(self) -> Result<()> {
+        let mut instances = self.instances.lock().unwrap();
+        instances.entries.get_mut(self.id)
+            .and_then(|v| v.take())
+            .ok_or(ErrorCode::InstanceNotFound)?;
+        if instances.selected == Some(self.id) {
+            instances.selected = None;
+            instances.selection_dirty = true;
+        }
+        instances.dirty = true;
+        Ok(())
+    }
+
+    fn with_entry<F, T>(&self, op: F) -> Result<T> where F: FnOnce(&SceneInstanceEntry) -> T {
+        let instances = self.instances.lock().unwrap();
+        let entry = instances.entries.get(self.id)
+            .and_then(|v| v.as_ref())
+            .ok_or(ErrorCode::InstanceNotFound)?;
+        Ok(op(entry))
+    }
+
+    fn modify_entry<F>(&self, op: F) -> Result<()> where F: FnOnce(&mut SceneInstanceEntry) {
+        let mut instances = self.instances.lock().unwrap();
+        let entry = instances.entries.get_mut(self.id)
+            .and_then(|v| v.as_mut())
+            .ok_or(ErrorCode::InstanceNotFound)?;
+        op(entry);
+        instances.dirty = true;
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub(super) struct SceneInstanceEntry {
+    pub(super) primitive_index: usize,
+    pub(super) transform: glm::Mat4,
+    pub(super) visible: bool,
+    pub(super) name: Option<String>,
+}
 
+// what `Scene::mesh_summary` reports of a mesh
+#[derive(Clone, Debug)]
+pub struct SceneMeshSummary {
+    // the glTF mesh the primitive belongs to
+    pub source_mesh_index: usize,
+    pub material_index: Option<usize>,
+    pub material_name: Option<String>,
+    pub triangle_count: usize,
+}
+
+struct SceneProcedurals {
+    geometries: Vec<ProceduralGeometry>,
+    uploaded: Arc<SceneProceduralGeometries>,
+    dirty: bool,
+}
+
+// removed instances leave their slot empty so that the ids of the others stay valid
+struct SceneInstances {
+    entries: Vec<Option<SceneInstanceEntry>>,
+    // materials not listed use the built-in triangle hit group
+    material_hit_groups: HashMap<usize, u32>,
+    selected: Option<usize>,
+    dirty: bool,
+    // the selection slot of the description buffer has to be written
+    selection_dirty: bool,
+}
+
+impl SceneInstances {
+    // procedural geometries are indexed after the mesh primitives
+    fn structure_instances(
+        &self, 
+        primitives: &[Arc<SceneMeshPrimitive>], 
+        procedurals: &SceneProceduralGeometries,
+        staging_buffers: &SceneStagingBuffers,
+    ) -> Result<Vec<Arc<TopLevelAccelerationStructureInstance>>> {
+        self.entries.iter()
+            .enumerate()
+            .filter_map(|(id, v)| v.as_ref().map(|v| (id, v)))
+            .map(|(id, entry)| {
+                let (custom_index, hit_group, structure) = match primitives.get(entry.primitive_index) {
+                    Some(mesh_primitive) => {
+                        let hit_group = mesh_primitive.material_index()
+                            .and_then(|v| self.material_hit_groups.get(&v))
+                            .cloned()
+                            .unwrap_or(RayTracingGraphicsPipeline::TRIANGLES_HIT_GROUP);
+                        // the selected instance reads the flagged copy of its description
+                        let custom_index = if self.selected == Some(id) {
+                            staging_buffers.selection_description_index()
+                        } else {
+                            entry.primitive_index
+                        };
+                        (custom_index, hit_group, mesh_primitive.bottom_level_acceleration_structure())
+                    },
+                    None => {
+                        let geometry = procedurals.geometries()
+                            .get(entry.primitive_index - primitives.len())
+                            .ok_or(ErrorCode::MeshNotFound)?;
+                        // locates the primitives of the geometry within the shared buffers
+                        (geometry.offset(), geometry.hit_group(), geometry.bottom_level_acceleration_structure())
+                    },
+                };
+                let transform = &entry.transform;
+                let transform = VkTransformMatrixKHR {
+                    matrix: [
+                        [transform.m11, transform.m12, transform.m13, transform.m14],
+                        [transform.m21, transform.m22, transform.m23, transform.m24],
+                        [transform.m31, transform.m32, transform.m33, transform.m34],
+                    ]
+                };
+                let mask = if entry.visible { 0xff } else { 0x00 };
+                TopLevelAccelerationStructureInstance::with_mask(
+                    custom_index as u32,
+                    transform,
+                    hit_group,
+                    mask,
+                    structure,
+                )
+            })
+            .collect()
     }
 }
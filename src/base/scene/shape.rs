@@ -0,0 +1,174 @@
+
+use nalgebra_glm as glm;
+
+use super::aabb::AABB;
+use super::curve::CurveSegment;
+
+// analytic primitives intersected by `ray.procedural.rint`. they are defined in the space of the
+// instances placing them.
+#[derive(Clone, Copy, Debug)]
+pub enum ProceduralShape {
+    Sphere { center: glm::Vec3, radius: f32 },
+    Box { min: glm::Vec3, max: glm::Vec3 },
+    // capped at both ends
+    Cylinder { a: glm::Vec3, b: glm::Vec3, radius: f32 },
+    // capped at both ends with the radii at `a` and `b`
+    Cone { a: glm::Vec3, b: glm::Vec3, radius_a: f32, radius_b: f32 },
+    Disc { center: glm::Vec3, normal: glm::Vec3, radius: f32 },
+    // around the y axis
+    Torus { center: glm::Vec3, major_radius: f32, minor_radius: f32 },
+    Sdf(ProceduralSdf),
+}
+
+// surfaces found by sphere tracing a signed distance function within their bounds
+#[derive(Clone, Copy, Debug)]
+pub enum ProceduralSdf {
+    RoundedBox { center: glm::Vec3, half_extents: glm::Vec3, radius: f32 },
+    Capsule { a: glm::Vec3, b: glm::Vec3, radius: f32 },
+    // a triply periodic isosurface clipped to the box
+    Gyroid { min: glm::Vec3, max: glm::Vec3, scale: f32, thickness: f32 },
+}
+
+// the values of `SHAPE_*` and `SDF_*` in `ray.common.shape.glsl`
+const SHAPE_SPHERE: u32 = 0;
+const SHAPE_BOX: u32 = 1;
+const SHAPE_CYLINDER: u32 = 2;
+const SHAPE_CONE: u32 = 3;
+const SHAPE_DISC: u32 = 4;
+const SHAPE_TORUS: u32 = 5;
+const SHAPE_SDF: u32 = 6;
+// intersected by `ray.curve.rint` instead
+const SHAPE_CURVE_LINEAR: u32 = 7;
+const SHAPE_CURVE_CUBIC: u32 = 8;
+// intersected by a user hit group, which interprets the parameters on its own
+const SHAPE_CUSTOM: u32 = 0xffffffff;
+
+const SDF_ROUNDED_BOX: u32 = 0;
+const SDF_CAPSULE: u32 = 1;
+const SDF_GYROID: u32 = 2;
+
+// laid out as `ProceduralShape` in `ray.common.shape.glsl`. the shapes traced within their bounds
+// keep them in the last two parameters.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct ProceduralShapeDescription {
+    parameters: [glm::Vec4; 4],
+    kind: glm::UVec4,
+}
+
+impl ProceduralShapeDescription {
+    pub(super) fn custom(parameters: &glm::Vec4) -> Self {
+        Self::new(SHAPE_CUSTOM, 0, [*parameters, glm::zero(), glm::zero(), glm::zero()])
+    }
+
+    pub(super) fn curve(segment: &CurveSegment) -> Self {
+        match segment {
+            CurveSegment::Linear { a, b, radius_a, radius_b } => Self::new(SHAPE_CURVE_LINEAR, 0, [
+                glm::vec4(a.x, a.y, a.z, *radius_a),
+                glm::vec4(b.x, b.y, b.z, *radius_b),
+                glm::zero(),
+                glm::zero(),
+            ]),
+            CurveSegment::CubicBezier { points, radius_a, radius_b } => Self::new(SHAPE_CURVE_CUBIC, 0, [
+                glm::vec4(points[0].x, points[0].y, points[0].z, *radius_a),
+                glm::vec3_to_vec4(&points[1]),
+                glm::vec3_to_vec4(&points[2]),
+                glm::vec4(points[3].x, points[3].y, points[3].z, *radius_b),
+            ]),
+        }
+    }
+
+    fn new(shape: u32, sdf: u32, parameters: [glm::Vec4; 4]) -> Self {
+        Self {
+            parameters,
+            kind: glm::vec4(shape, sdf, 0, 0),
+        }
+    }
+
+    fn traced(shape: u32, sdf: u32, parameters0: glm::Vec4, parameters1: glm::Vec4, aabb: &AABB) -> Self {
+        let bounds_min = glm::vec3_to_vec4(aabb.min());
+        let bounds_max = glm::vec3_to_vec4(aabb.max());
+        Self::new(shape, sdf, [parameters0, parameters1, bounds_min, bounds_max])
+    }
+}
+
+impl ProceduralShape {
+    pub fn aabb(&self) -> AABB {
+        match self {
+            Self::Sphere { center, radius } => AABB::sphere(center, *radius),
+            Self::Box { min, max } => AABB::new(&glm::min2(min, max), &glm::max2(min, max)),
+            Self::Cylinder { a, b, radius } => Self::cone_aabb(a, b, *radius, *radius),
+            Self::Cone { a, b, radius_a, radius_b } => Self::cone_aabb(a, b, *radius_a, *radius_b),
+            Self::Disc { center, normal, radius } => {
+                let extent = Self::disc_extent(normal, *radius);
+                AABB::new(&(center - extent), &(center + extent))
+            },
+            Self::Torus { center, major_radius, minor_radius } => {
+                let horizontal = major_radius + minor_radius;
+                let extent = glm::vec3(horizontal, *minor_radius, horizontal);
+                AABB::new(&(center - extent), &(center + extent))
+            },
+            Self::Sdf(sdf) => sdf.aabb(),
+        }
+    }
+
+    pub fn description(&self) -> ProceduralShapeDescription {
+        type Description = ProceduralShapeDescription;
+        match self {
+            Self::Sphere { center, radius } =>
+                Description::new(SHAPE_SPHERE, 0, [glm::vec4(center.x, center.y, center.z, *radius), glm::zero(), glm::zero(), glm::zero()]),
+            Self::Box { min, max } =>
+                Description::new(SHAPE_BOX, 0, [glm::vec3_to_vec4(&glm::min2(min, max)), glm::vec3_to_vec4(&glm::max2(min, max)), glm::zero(), glm::zero()]),
+            Self::Cylinder { a, b, radius } =>
+                Description::new(SHAPE_CYLINDER, 0, [glm::vec4(a.x, a.y, a.z, *radius), glm::vec4(b.x, b.y, b.z, *radius), glm::zero(), glm::zero()]),
+            Self::Cone { a, b, radius_a, radius_b } =>
+                Description::new(SHAPE_CONE, 0, [glm::vec4(a.x, a.y, a.z, *radius_a), glm::vec4(b.x, b.y, b.z, *radius_b), glm::zero(), glm::zero()]),
+            Self::Disc { center, normal, radius } => {
+                let normal = glm::normalize(normal);
+                Description::new(SHAPE_DISC, 0, [glm::vec4(center.x, center.y, center.z, *radius), glm::vec3_to_vec4(&normal), glm::zero(), glm::zero()])
+            },
+            Self::Torus { center, major_radius, minor_radius } =>
+                Description::traced(SHAPE_TORUS, 0, glm::vec4(center.x, center.y, center.z, *major_radius), glm::vec4(*minor_radius, 0.0, 0.0, 0.0), &self.aabb()),
+            Self::Sdf(ProceduralSdf::RoundedBox { center, half_extents, radius }) =>
+                Description::traced(SHAPE_SDF, SDF_ROUNDED_BOX, glm::vec4(center.x, center.y, center.z, *radius), glm::vec3_to_vec4(half_extents), &self.aabb()),
+            Self::Sdf(ProceduralSdf::Capsule { a, b, radius }) =>
+                Description::traced(SHAPE_SDF, SDF_CAPSULE, glm::vec4(a.x, a.y, a.z, *radius), glm::vec3_to_vec4(b), &self.aabb()),
+            Self::Sdf(ProceduralSdf::Gyroid { scale, thickness, .. }) =>
+                Description::traced(SHAPE_SDF, SDF_GYROID, glm::vec4(*scale, *thickness, 0.0, 0.0), glm::zero(), &self.aabb()),
+        }
+    }
+
+    // @see https://iquilezles.org/articles/diskbbox/
+    fn cone_aabb(a: &glm::Vec3, b: &glm::Vec3, radius_a: f32, radius_b: f32) -> AABB {
+        let axis = b - a;
+        let length2 = glm::dot(&axis, &axis).max(f32::EPSILON);
+        let e = glm::vec3(1.0, 1.0, 1.0) - axis.component_mul(&axis) / length2;
+        let e = e.map(|v| v.max(0.0).sqrt());
+        let min = glm::min2(&(a - e * radius_a), &(b - e * radius_b));
+        let max = glm::max2(&(a + e * radius_a), &(b + e * radius_b));
+        AABB::new(&min, &max)
+    }
+
+    fn disc_extent(normal: &glm::Vec3, radius: f32) -> glm::Vec3 {
+        let normal = glm::normalize(normal);
+        let e = glm::vec3(1.0, 1.0, 1.0) - normal.component_mul(&normal);
+        e.map(|v| v.max(0.0).sqrt()) * radius
+    }
+}
+
+impl ProceduralSdf {
+    pub fn aabb(&self) -> AABB {
+        match self {
+            Self::RoundedBox { center, half_extents, radius } => {
+                let extent = half_extents.abs().add_scalar(*radius);
+                AABB::new(&(center - extent), &(center + extent))
+            },
+            Self::Capsule { a, b, radius } => {
+                let min = glm::min2(a, b).add_scalar(-radius);
+                let max = glm::max2(a, b).add_scalar(*radius);
+                AABB::new(&min, &max)
+            },
+            Self::Gyroid { min, max, .. } => AABB::new(&glm::min2(min, max), &glm::max2(min, max)),
+        }
+    }
+}
@@ -0,0 +1,212 @@
+
+use nalgebra_glm as glm;
+
+use std::path::Path;
+
+use crate::vk::Result;
+use crate::vk::*;
+
+use super::custom::*;
+
+// STereoLithography in ASCII or binary encoding.
+// facets keep their own vertices so that the hard edges of CAD parts stay flat shaded.
+pub struct StlAsset {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+}
+
+impl StlAsset {
+    pub fn new<P>(path: P) -> Result<Self> where P: AsRef<Path> {
+        log_debug!("loading stl asset");
+        let path = path.as_ref();
+        let data = std::fs::read(path)
+            .map_err(|e| Error::from(e).with_path(path))?;
+        let asset = Self::from_slice(&data)
+            .map_err(|e| e.with_path(path))?;
+        log_debug!("loading stl asset complete");
+        Ok(asset)
+    }
+
+    pub fn from_slice(data: &[u8]) -> Result<Self> {
+        // binary files may also begin with `solid`, so the size is checked first
+        let is_binary = data.len() >= 84 && {
+            let count = u32::from_le_bytes([data[80], data[81], data[82], data[83]]) as usize;
+            data.len() == 84 + count * 50
+        };
+        let mut asset = Self {
+            positions: vec![],
+            normals: vec![],
+        };
+        if is_binary {
+            asset.read_binary(data);
+        } else if data.starts_with(b"solid") {
+            asset.read_ascii(data)?;
+        } else {
+            return Err(ErrorCode::MeshFormatInvalid.into());
+        }
+        Ok(asset)
+    }
+
+    pub fn positions(&self) -> &Vec<[f32; 3]> {
+        &self.positions
+    }
+
+    pub fn normals(&self) -> &Vec<[f32; 3]> {
+        &self.normals
+    }
+
+    pub fn num_triangles(&self) -> usize {
+        self.positions.len() / 3
+    }
+
+    // a single mesh at the origin with the default material
+    pub fn into_builder(self) -> Result<CustomSceneBuilder> {
+        if self.positions.is_empty() {
+            return Err(ErrorCode::MeshNotFound.into());
+        }
+        let indices = (0..self.positions.len() as u32).collect();
+        let mesh = CustomMesh::new(self.positions, indices)
+            .with_normals(self.normals);
+        let mut builder = CustomSceneBuilder::new();
+        let mesh_index = builder.add_mesh(mesh)?;
+        builder.add_instance(mesh_index, glm::identity())?;
+        Ok(builder)
+    }
+
+    fn read_binary(&mut self, data: &[u8]) {
+        let read_vec3 = |bytes: &[u8]| {
+            let mut v = [0.0f32; 3];
+            for (i, value) in v.iter_mut().enumerate() {
+                let b = &bytes[i * 4..i * 4 + 4];
+                *value = f32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+            }
+            v
+        };
+        // normal, three vertices and the attribute byte count
+        for facet in data[84..].chunks_exact(50) {
+            let normal = read_vec3(&facet[0..12]);
+            let vertices = [
+                read_vec3(&facet[12..24]),
+                read_vec3(&facet[24..36]),
+                read_vec3(&facet[36..48]),
+            ];
+            self.add_facet(normal, &vertices);
+        }
+    }
+
+    fn read_ascii(&mut self, data: &[u8]) -> Result<()> {
+        let text = std::str::from_utf8(data)
+            .map_err(|_| ErrorCode::MeshFormatInvalid)?;
+        let mut tokens = text.split_whitespace();
+        let mut normal = [0.0f32; 3];
+        let mut vertices: Vec<[f32; 3]> = vec![];
+        let read_vec3 = |tokens: &mut std::str::SplitWhitespace| -> Result<[f32; 3]> {
+            let mut v = [0.0f32; 3];
+            for value in v.iter_mut() {
+                *value = tokens.next()
+                    .and_then(|v| v.parse().ok())
+                    .ok_or(ErrorCode::MeshFormatInvalid)?;
+            }
+            Ok(v)
+        };
+        while let Some(token) = tokens.next() {
+            match token {
+                "facet" => {
+                    if tokens.next() != Some("normal") {
+                        return Err(ErrorCode::MeshFormatInvalid.into());
+                    }
+                    normal = read_vec3(&mut tokens)?;
+                    vertices.clear();
+                },
+                "vertex" => vertices.push(read_vec3(&mut tokens)?),
+                "endfacet" => {
+                    // a few exporters write polygons with more than three vertices
+                    for i in 1..vertices.len().saturating_sub(1) {
+                        self.add_facet(normal, &[vertices[0], vertices[i], vertices[i + 1]]);
+                    }
+                },
+                _ => {},
+            }
+        }
+        Ok(())
+    }
+
+    // the normal is derived from the winding, and the stored one is used for degenerate facets
+    fn add_facet(&mut self, normal: [f32; 3], vertices: &[[f32; 3]; 3]) {
+        let a = glm::make_vec3(&vertices[0]);
+        let b = glm::make_vec3(&vertices[1]);
+        let c = glm::make_vec3(&vertices[2]);
+        let n = glm::cross(&(b - a), &(c - a));
+        let n = if glm::length(&n) > 0.0 {
+            glm::normalize(&n)
+        } else {
+            glm::make_vec3(&normal)
+        };
+        for v in vertices.iter() {
+            self.positions.push(*v);
+            self.normals.push([n.x, n.y, n.z]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRIANGLE: [[f32; 3]; 3] = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+
+    fn binary(facets: &[([f32; 3], [[f32; 3]; 3])]) -> Vec<u8> {
+        // binary files may begin with `solid` too
+        let mut data = b"solid binary".to_vec();
+        data.resize(80, 0);
+        data.extend_from_slice(&(facets.len() as u32).to_le_bytes());
+        for (normal, vertices) in facets.iter() {
+            for v in std::iter::once(normal).chain(vertices.iter()).flatten() {
+                data.extend_from_slice(&v.to_le_bytes());
+            }
+            data.extend_from_slice(&[0, 0]);
+        }
+        data
+    }
+
+    #[test]
+    fn ascii() {
+        let data = "solid triangle
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 0 1 0
+    endloop
+  endfacet
+endsolid triangle
+";
+        let asset = StlAsset::from_slice(data.as_bytes()).unwrap();
+        assert_eq!(asset.num_triangles(), 1);
+        assert_eq!(asset.positions(), &TRIANGLE.to_vec());
+        assert_eq!(asset.normals(), &vec![[0.0, 0.0, 1.0]; 3]);
+    }
+
+    #[test]
+    fn binary_beginning_with_solid() {
+        let asset = StlAsset::from_slice(&binary(&[([0.0, 0.0, 1.0], TRIANGLE)])).unwrap();
+        assert_eq!(asset.num_triangles(), 1);
+        assert_eq!(asset.positions(), &TRIANGLE.to_vec());
+    }
+
+    #[test]
+    fn normals_follow_the_winding() {
+        let reversed = [TRIANGLE[0], TRIANGLE[2], TRIANGLE[1]];
+        let degenerate = [TRIANGLE[0], TRIANGLE[0], TRIANGLE[1]];
+        let asset = StlAsset::from_slice(&binary(&[([0.0, 0.0, 1.0], reversed), ([1.0, 0.0, 0.0], degenerate)])).unwrap();
+        assert_eq!(asset.normals()[0], [0.0, 0.0, -1.0]);
+        // the stored one for the degenerate facet
+        assert_eq!(asset.normals()[3], [1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn invalid_files() {
+        assert!(StlAsset::from_slice(b"ply\nformat ascii 1.0\n").is_err());
+        assert!(StlAsset::from_slice(b"solid broken\nfacet normal 0 0\n").is_err());
+    }
+}
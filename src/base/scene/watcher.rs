@@ -0,0 +1,235 @@
+
+use crate::vk::Result;
+use crate::vk::*;
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
+
+use super::asset::SceneAsset;
+use super::cache::SceneCache;
+use super::resolver::referenced_files;
+
+// editors tend to save by renaming a new file over the old one, which drops watches
+// placed on the file itself. the parent directories are watched instead.
+const WATCH_MASK: u32 = libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO | libc::IN_CREATE;
+// a single save produces a burst of events, and exporters write several files in a row
+const SETTLE_DURATION: Duration = Duration::from_millis(200);
+
+// reloads a glTF asset in the background whenever it or one of its external buffers and images
+// changes on disk. parsing and image decoding happen on the watcher thread; the resulting cache
+// is uploaded with `SceneCache::build` by the thread owning the command pool.
+pub struct SceneWatcher {
+    receiver: Receiver<Result<SceneCache>>,
+}
+
+impl SceneWatcher {
+    pub fn new<P>(asset_path: P) -> Result<Self> where P: AsRef<Path> {
+        let asset_path = asset_path.as_ref().to_owned();
+        let inotify = Inotify::new()?;
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            if let Err(e) = watch(inotify, &asset_path, &sender) {
+                let _ = sender.send(Err(e));
+            }
+        });
+        Ok(Self { receiver })
+    }
+
+    // the latest reload since the previous call, if any
+    pub fn try_recv(&self) -> Option<Result<SceneCache>> {
+        let mut latest = None;
+        while let Ok(v) = self.receiver.try_recv() {
+            latest = Some(v);
+        }
+        latest
+    }
+}
+
+fn watch(mut inotify: Inotify, asset_path: &Path, sender: &Sender<Result<SceneCache>>) -> Result<()> {
+    let mut watched = WatchedFiles::new();
+    let document = std::fs::read(asset_path)
+        .map_err(Error::from)
+        .and_then(|data| gltf::Gltf::from_slice(&data).map_err(Error::from));
+    let files = match document {
+        Ok(gltf) => referenced_files(asset_path, &gltf.document),
+        Err(e) => {
+            log_debug!("scene watcher: {}", error_chain(&e));
+            vec![asset_path.to_owned()]
+        },
+    };
+    watched.update(&mut inotify, &files)?;
+    loop {
+        let changed = inotify.wait(None)?;
+        if !changed.iter().any(|v| watched.contains(v)) {
+            continue
+        }
+        while !inotify.wait(Some(SETTLE_DURATION))?.is_empty() {}
+        log_debug!("scene watcher: reloading {}", asset_path.display());
+        let result = reload(asset_path);
+        if let Ok((_, ref files)) = result {
+            watched.update(&mut inotify, files)?;
+        }
+        // the receiver is gone once the renderer shuts down
+        if sender.send(result.map(|(cache, _)| cache)).is_err() {
+            return Ok(())
+        }
+    }
+}
+
+fn reload(asset_path: &Path) -> Result<(SceneCache, Vec<PathBuf>)> {
+    let asset = SceneAsset::new(asset_path)?;
+    let cache = SceneCache::from_asset(asset_path, &asset)?;
+    // keeps the next launch from restoring the content before the change
+    if let Err(e) = cache.save(SceneCache::default_path(asset_path)) {
+        log_debug!("scene cache not saved: {}", error_chain(&e));
+    }
+    let files = referenced_files(asset_path, asset.document());
+    Ok((cache, files))
+}
+
+struct WatchedFiles {
+    directories: HashSet<PathBuf>,
+    files: HashSet<PathBuf>,
+}
+
+impl WatchedFiles {
+    fn new() -> Self {
+        Self {
+            directories: HashSet::new(),
+            files: HashSet::new(),
+        }
+    }
+
+    // watches added for earlier versions of the asset are kept; they only cost spurious wakeups
+    fn update(&mut self, inotify: &mut Inotify, files: &[PathBuf]) -> Result<()> {
+        self.files.clear();
+        for file in files {
+            let file = match canonical_file(file) {
+                Some(v) => v,
+                None => continue,
+            };
+            if let Some(directory) = file.parent() {
+                if !self.directories.contains(directory) {
+                    inotify.add_watch(directory)?;
+                    self.directories.insert(directory.to_owned());
+                }
+            }
+            self.files.insert(file);
+        }
+        Ok(())
+    }
+
+    fn contains(&self, path: &Path) -> bool {
+        self.files.contains(path)
+    }
+}
+
+// only the directory is resolved since the file may be missing in the middle of a save
+fn canonical_file(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?;
+    let directory = match path.parent() {
+        Some(v) if !v.as_os_str().is_empty() => v,
+        _ => Path::new("./"),
+    };
+    let directory = std::fs::canonicalize(directory).ok()?;
+    Some(directory.join(name))
+}
+
+struct Inotify {
+    fd: libc::c_int,
+    directories: HashMap<libc::c_int, PathBuf>,
+}
+
+impl Inotify {
+    fn new() -> Result<Self> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error().into())
+        }
+        let this = Self {
+            fd,
+            directories: HashMap::new(),
+        };
+        Ok(this)
+    }
+
+    fn add_watch(&mut self, directory: &Path) -> Result<()> {
+        let path = CString::new(directory.as_os_str().as_bytes())?;
+        let descriptor = unsafe { libc::inotify_add_watch(self.fd, path.as_ptr(), WATCH_MASK) };
+        if descriptor < 0 {
+            let e = Error::from(std::io::Error::last_os_error());
+            return Err(e.with_path(directory))
+        }
+        self.directories.insert(descriptor, directory.to_owned());
+        Ok(())
+    }
+
+    // blocks until events arrive or the timeout elapses, returning the paths they refer to
+    fn wait(&self, timeout: Option<Duration>) -> Result<Vec<PathBuf>> {
+        let mut poll_fd = libc::pollfd {
+            fd: self.fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout = timeout
+            .map(|v| v.as_millis() as libc::c_int)
+            .unwrap_or(-1);
+        let ready = unsafe { libc::poll(&mut poll_fd, 1, timeout) };
+        if ready < 0 {
+            let e = std::io::Error::last_os_error();
+            if e.kind() == std::io::ErrorKind::Interrupted {
+                return Ok(vec![])
+            }
+            return Err(e.into())
+        }
+        if ready == 0 {
+            return Ok(vec![])
+        }
+        self.read()
+    }
+
+    fn read(&self) -> Result<Vec<PathBuf>> {
+        // aligned for `inotify_event` and large enough for any single event
+        let mut buffer = [0u64; 1024];
+        let capacity = std::mem::size_of_val(&buffer);
+        let size = unsafe { libc::read(self.fd, buffer.as_mut_ptr() as *mut libc::c_void, capacity) };
+        if size < 0 {
+            let e = std::io::Error::last_os_error();
+            if e.kind() == std::io::ErrorKind::WouldBlock {
+                return Ok(vec![])
+            }
+            return Err(e.into())
+        }
+        let bytes = unsafe { std::slice::from_raw_parts(buffer.as_ptr() as *const u8, size as usize) };
+        let header_size = std::mem::size_of::<libc::inotify_event>();
+        let mut paths = vec![];
+        let mut offset = 0usize;
+        while offset + header_size <= bytes.len() {
+            let event = unsafe { std::ptr::read_unaligned(bytes[offset..].as_ptr() as *const libc::inotify_event) };
+            let name_start = offset + header_size;
+            let name_end = (name_start + event.len as usize).min(bytes.len());
+            // the name is padded with NULs
+            let name = &bytes[name_start..name_end];
+            let name = &name[..name.iter().position(|&v| v == 0).unwrap_or(name.len())];
+            if let Some(directory) = self.directories.get(&event.wd) {
+                if !name.is_empty() {
+                    paths.push(directory.join(std::ffi::OsStr::from_bytes(name)));
+                }
+            }
+            offset = name_end;
+        }
+        Ok(paths)
+    }
+}
+
+impl Drop for Inotify {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
@@ -0,0 +1,30 @@
+extern crate kaldera;
+
+use kaldera::base::*;
+use kaldera::vk::error_chain;
+
+use std::process;
+
+// prints a report of a glTF/GLB asset without touching the GPU.
+// exits with 1 when the asset fails to load and 2 when the renderer would reject it.
+fn main() {
+    let filename = match std::env::args().nth(1) {
+        Some(v) => v,
+        None => {
+            eprintln!("usage: kaldera-inspect <asset.gltf|asset.glb>");
+            process::exit(1);
+        },
+    };
+    let asset = match SceneAsset::new(&filename) {
+        Ok(v) => v,
+        Err(error) => {
+            eprintln!("{}", error_chain(&error));
+            process::exit(1);
+        },
+    };
+    let report = SceneReport::new(&asset);
+    println!("{}", report);
+    if !report.is_renderable() {
+        process::exit(2);
+    }
+}
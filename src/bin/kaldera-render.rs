@@ -0,0 +1,83 @@
+extern crate kaldera;
+
+use kaldera::base::*;
+use kaldera::vk::error_chain;
+
+use std::process;
+
+const USAGE: &str = "usage: kaldera-render <asset.gltf|asset.glb|asset.obj|asset.ply|asset.stl> <output.png> [width height [samples [aperture]]]";
+
+// renders a glTF/GLB, OBJ, PLY or STL asset on the CPU from the camera the viewer starts with, without a GPU.
+// exits with 1 when the arguments or the asset are invalid and 2 when the image fails to save.
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let (filename, output) = match (args.get(1), args.get(2)) {
+        (Some(filename), Some(output)) => (filename, output),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        },
+    };
+    let number = |index: usize, default: usize| -> usize {
+        match args.get(index).map(|v| v.parse::<usize>()) {
+            None => default,
+            Some(Ok(v)) if v > 0 => v,
+            Some(_) => {
+                eprintln!("{}", USAGE);
+                process::exit(1);
+            },
+        }
+    };
+    let width = number(3, 1280);
+    let height = number(4, 720);
+    let samples = number(5, 1);
+    // the radius of the lens in world units, focused on the center of the scene
+    let aperture = match args.get(6).map(|v| v.parse::<f32>()) {
+        None => 0.0,
+        Some(Ok(v)) if v >= 0.0 => v,
+        Some(_) => {
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        },
+    };
+    let scene = match asset_extension(filename).as_deref() {
+        Some("obj") => ObjAsset::new(filename)
+            .and_then(|v| v.into_builder())
+            .and_then(|v| ReferenceScene::from_builder(&v)),
+        Some("ply") => PlyAsset::new(filename)
+            .and_then(|v| v.into_builder())
+            .and_then(|v| ReferenceScene::from_builder(&v)),
+        Some("stl") => StlAsset::new(filename)
+            .and_then(|v| v.into_builder())
+            .and_then(|v| ReferenceScene::from_builder(&v)),
+        _ => {
+            let cache_path = SceneCache::default_path(filename);
+            SceneCache::load_or_create(filename, &cache_path)
+                .and_then(|v| ReferenceScene::new(&v))
+        },
+    };
+    let scene = match scene {
+        Ok(v) => v,
+        Err(error) => {
+            eprintln!("{}", error_chain(&error));
+            process::exit(1);
+        },
+    };
+    let mut camera = FreeLookCamera::new(width as f32, height as f32);
+    camera.frame(scene.bounds());
+    camera.set_aperture(aperture);
+    let image = ReferenceRenderer::new(width, height)
+        .with_samples(samples)
+        .render(&scene, &camera);
+    if let Err(error) = image.save(output) {
+        eprintln!("{}", error_chain(&error));
+        process::exit(2);
+    }
+}
+
+fn asset_extension(filename: &str) -> Option<String> {
+    std::path::Path::new(filename)
+        .extension()
+        .and_then(|v| v.to_str())
+        .map(|v| v.to_ascii_lowercase())
+}
@@ -3,7 +3,25 @@
 #[derive(Debug)]
 pub enum InputEvent {
     MoveDelta(f32, f32),
+    // dragged with the middle button, or the primary button with shift held, in window pixels
+    PanDelta(f32, f32),
+    // wheel steps, positive away from the user
+    Zoom(f32),
     Key(InputKeyEvent),
+    // the primary button released where it was pressed, in window pixels
+    Click(f32, f32),
+    // a key bound to a viewer command went down
+    Action(InputAction),
+    // the window changed its size, in pixels
+    Resize(f32, f32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputAction {
+    ToggleDepthOfField,
+    RecordKeyframe,
+    RemoveKeyframe,
+    TogglePlayback,
 }
 
 #[derive(Debug)]
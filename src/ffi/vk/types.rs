@@ -2539,7 +2539,7 @@ pub struct VkDescriptorSetVariableDescriptorCountAllocateInfo {
     pub pDescriptorCounts: *const u32,
 }
 
-#[link(name = "vulkan")]
+#[cfg_attr(feature = "with-vulkan", link(name = "vulkan"))]
 extern "C" {
     // @see https://www.khronos.org/registry/vulkan/specs/1.1-extensions/man/html/vkCreateInstance.html
     pub fn vkCreateInstance(
@@ -3130,4 +3130,11 @@ extern "C" {
         firstQuery: u32,
         queryCount: u32,
     );
+    // @see https://registry.khronos.org/vulkan/specs/1.3-extensions/man/html/vkCmdWriteTimestamp.html
+    pub fn vkCmdWriteTimestamp(
+        commandBuffer: VkCommandBuffer,
+        pipelineStage: VkPipelineStageFlagBits,
+        queryPool: VkQueryPool,
+        query: u32,
+    );
 }
@@ -154,7 +154,7 @@ pub struct VkPresentInfoKHR {
     pub pResults: *mut VkResult,
 }
 
-#[link(name = "vulkan")]
+#[cfg_attr(feature = "with-vulkan", link(name = "vulkan"))]
 extern "C" {
     // @see https://www.khronos.org/registry/vulkan/specs/1.2-extensions/man/html/vkGetPhysicalDeviceSurfaceCapabilitiesKHR.html
     pub fn vkGetPhysicalDeviceSurfaceCapabilitiesKHR(
@@ -259,7 +259,7 @@ mod xcb {
         }
     }
   
-    #[link(name = "vulkan")]
+    #[cfg_attr(feature = "with-vulkan", link(name = "vulkan"))]
     extern "C" {
         // @see https://www.khronos.org/registry/vulkan/specs/1.2-extensions/man/html/vkCreateXcbSurfaceKHR.html
         pub fn vkCreateXcbSurfaceKHR(
@@ -177,7 +177,7 @@ pub enum VkGeometryTypeKHR {
 #[repr(C)]
 pub union VkAccelerationStructureGeometryDataKHR {
     pub triangles: VkAccelerationStructureGeometryTrianglesDataKHR,
-    //pub aabbs: VkAccelerationStructureGeometryAabbsDataKHR,
+    pub aabbs: VkAccelerationStructureGeometryAabbsDataKHR,
     pub instances: VkAccelerationStructureGeometryInstancesDataKHR,
 }
 
@@ -196,6 +196,16 @@ pub struct VkAccelerationStructureGeometryTrianglesDataKHR {
     pub transformData: VkDeviceOrHostAddressConstKHR,
 }
 
+// @see https://www.khronos.org/registry/vulkan/specs/1.2-extensions/man/html/VkAccelerationStructureGeometryAabbsDataKHR.html
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct VkAccelerationStructureGeometryAabbsDataKHR {
+    pub sType: VkStructureTypeExtRay,
+    pub pNext: *const c_void,
+    pub data: VkDeviceOrHostAddressConstKHR,
+    pub stride: VkDeviceSize,
+}
+
 // @see https://www.khronos.org/registry/vulkan/specs/1.2-extensions/man/html/VkAccelerationStructureGeometryInstancesDataKHR.html
 #[repr(C)]
 #[derive(Clone, Copy)]
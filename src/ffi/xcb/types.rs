@@ -338,7 +338,7 @@ pub struct xcb_get_keyboard_mapping_reply_t {
     pub pad0: [u8; 24],
 }
 
-#[link(name = "xcb")]
+#[cfg_attr(feature = "with-xcb", link(name = "xcb"))]
 extern "C" {
     // @see https://github.com/freedesktop/xcb-libxcb/blob/ee9dfc9a7658e7fe75d27483bb5ed1ba4d1e2c86/src/xcb.h#L567
     pub fn xcb_connect(
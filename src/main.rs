@@ -5,6 +5,7 @@ use kaldera::ffi::vk::*;
 use kaldera::ffi::xcb::*;
 use kaldera::vk::*;
 use kaldera::base::*;
+use kaldera::cores::{InputEvent, InputAction};
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
@@ -12,6 +13,9 @@ const WIDTH: usize = 1600;
 const HEIGHT: usize = 900;
 
 const ASSET_FILENAME: &'static str = "submodules/kaldera-asset/models/Sponza/glTF/Sponza.gltf";
+const CAMERA_PATH_FILENAME: &str = "camera_path.txt";
+// of the camera path playback, in seconds per frame
+const PLAYBACK_STEP: f32 = 1.0 / 60.0;
 
 struct Context {
     camera: Arc<Mutex<dyn Camera>>,
@@ -20,6 +24,10 @@ struct Context {
     device_queues: Arc<DeviceQueues>,
     scene: Option<Scene>,
     descriptor_sets: Option<Arc<RayTracingDescriptorSets>>,
+    command_pool: Arc<CommandPool>,
+    watcher: Option<SceneWatcher>,
+    // built on the first click
+    query: Option<SceneQuery>,
 }
 
 fn main() {
@@ -33,7 +41,7 @@ fn main() {
     let surface = XcbSurface::new(&instance, &window).unwrap();
     // choose render strategy
     let capabilities = PhysicalDeviceCapabilities::new(&instance).unwrap();
-    let context = if capabilities.has_raytracing() { 
+    let mut context = if capabilities.has_raytracing() { 
         raytracing_render(&surface) 
     } else {
         rasterization_render(&surface)
@@ -41,6 +49,10 @@ fn main() {
     // render loop
     let interpreter = XcbInputInterpreter::new(&window);
     let mut camera = context.camera.lock().unwrap();
+    let mut camera_path = load_camera_path();
+    let mut playback: Option<CameraPlayback> = None;
+    // the projection and the picking follow the window as it is resized
+    let mut window_size = (WIDTH as f32, HEIGHT as f32);
     let mut instant = Instant::now();
     loop {
         let delta_time = instant.elapsed().as_secs_f32().max(0.00001);
@@ -48,20 +60,69 @@ fn main() {
         // camera
         if let Some(events) = interpreter.next() {
             for event in events {
-                camera.apply(event, delta_time);
+                if let InputEvent::Resize(width, height) = event {
+                    window_size = (width, height);
+                    camera.set_viewport(width, height);
+                    continue
+                }
+                if let InputEvent::Click(x, y) = event {
+                    if let Some(ref scene) = context.scene {
+                        if let Some(hit) = pick(scene, &mut context.query, &*camera, x, y, window_size) {
+                            focus(&mut *camera, &hit);
+                        }
+                    }
+                    continue
+                }
+                if let InputEvent::Action(action) = event {
+                    match action {
+                        InputAction::ToggleDepthOfField => {
+                            if let Some(ref scene) = context.scene {
+                                toggle_depth_of_field(&mut *camera, &scene.bounds());
+                            }
+                        },
+                        _ => edit_camera_path(action, &mut camera_path, &mut playback, &*camera),
+                    }
+                    continue
+                }
+                // the path drives the camera while playing
+                if playback.is_none() {
+                    camera.apply(event, delta_time);
+                }
             }
         }
-        camera.update(delta_time);
+        // a fixed step of the path per frame instead of the elapsed time
+        match playback.as_mut().map(|v| v.next()) {
+            Some(Some(keyframe)) => keyframe.apply(&mut *camera),
+            Some(None) => {
+                playback = None;
+                println!("camera path playback finished");
+            },
+            None => camera.update(delta_time),
+        }
         // uniform buffer
-        let model = RayTracingUniformBufferModel {
-            view_inverse: camera.view_inverse(),
-            proj_inverse: camera.projection_inverse(),
-        };
+        let model = uniform_buffer_model(&*camera);
         context.uniform_buffer.update(&vec![model]);
+        // hot reload
+        if let Some(result) = context.watcher.as_ref().and_then(|v| v.try_recv()) {
+            if let Some(ref descriptor_sets) = context.descriptor_sets {
+                let scene = result.and_then(|cache| swap_scene(&cache, &context.command_pool, descriptor_sets, &context.graphics_render, &context.device_queues));
+                match scene {
+                    Ok(scene) => {
+                        context.scene = Some(scene);
+                        context.query = None;
+                    },
+                    Err(e) => println!("scene reload failed: {}", error_chain(&e)),
+                }
+            }
+        }
         // scene
         if let Some(ref scene) = context.scene {
             if let Some(ref descriptor_sets) = context.descriptor_sets {
-                scene.update(delta_time, descriptor_sets);
+                match scene.update(delta_time, descriptor_sets) {
+                    Ok(true) => context.graphics_render.prerender(),
+                    Ok(false) => (),
+                    Err(e) => println!("scene update failed: {}", error_chain(&e)),
+                }
             }
         }
         // draw
@@ -75,21 +136,230 @@ fn main() {
         .unwrap();
 }
 
+fn uniform_buffer_model(camera: &dyn Camera) -> RayTracingUniformBufferModel {
+    let lens = camera.lens();
+    RayTracingUniformBufferModel {
+        view_inverse: camera.view_inverse(),
+        proj_inverse: camera.projection_inverse(),
+        aperture: lens.aperture(),
+        focus_distance: lens.focus_distance(),
+        blades: lens.blades(),
+        blade_rotation: lens.blade_rotation(),
+        view_projection: camera.view_projection(),
+    }
+}
+
+// the second argument overrides the camera path file
+fn camera_path_filename() -> String {
+    std::env::args().nth(2)
+        .unwrap_or_else(|| CAMERA_PATH_FILENAME.to_owned())
+}
+
+// starts with an empty path when there is no file yet
+fn load_camera_path() -> CameraPath {
+    let filename = camera_path_filename();
+    if !std::path::Path::new(&filename).exists() {
+        return CameraPath::new()
+    }
+    match CameraPath::from_file(&filename) {
+        Ok(path) => {
+            println!("loaded {} camera keyframes from {}", path.len(), filename);
+            path
+        },
+        Err(e) => {
+            println!("camera path disabled: {}", error_chain(&e));
+            CameraPath::new()
+        },
+    }
+}
+
+// records and removes keyframes, saving the path after each edit, and starts or stops the playback
+fn edit_camera_path(action: InputAction, path: &mut CameraPath, playback: &mut Option<CameraPlayback>, camera: &dyn Camera) {
+    match action {
+        InputAction::RecordKeyframe => {
+            let time = path.record(camera).time;
+            println!("recorded camera keyframe {} at {:.2}s", path.len() - 1, time);
+        },
+        InputAction::RemoveKeyframe => {
+            match path.remove_last() {
+                Some(_) => println!("removed camera keyframe {}", path.len()),
+                None => return,
+            }
+        },
+        InputAction::TogglePlayback => {
+            if playback.take().is_some() {
+                println!("camera path playback stopped");
+            } else if path.is_empty() {
+                println!("no camera keyframes to play back");
+            } else {
+                println!("playing back {} camera keyframes over {:.2}s", path.len(), path.duration());
+                *playback = Some(path.playback(PLAYBACK_STEP));
+            }
+            return
+        },
+        InputAction::ToggleDepthOfField => return,
+    }
+    let filename = camera_path_filename();
+    if let Err(e) = path.save(&filename) {
+        println!("camera path save failed: {}", error_chain(&e));
+    }
+}
+
+// opens the aperture in proportion to the scene, or closes it back to a pinhole
+fn toggle_depth_of_field(camera: &mut dyn Camera, bounds: &AABB) {
+    if !camera.lens().is_pinhole() {
+        camera.set_aperture(0.0);
+        println!("depth of field off");
+        return
+    }
+    let size = if bounds.is_empty() { 1.0 } else { bounds.extent().norm() };
+    camera.set_aperture(size * 0.005);
+    println!("depth of field on, aperture {:.4} focus distance {:.3}, click to focus",
+        camera.lens().aperture(), camera.lens().focus_distance());
+}
+
+// moves the focal plane through the picked point
+fn focus(camera: &mut dyn Camera, hit: &SceneHit) {
+    camera.focus_on(&hit.position);
+    if !camera.lens().is_pinhole() {
+        println!("  focus distance {:.3}", camera.lens().focus_distance());
+    }
+}
+
+// selects the instance under the cursor and returns the hit, or clears the selection when nothing is hit
+fn pick(scene: &Scene, query: &mut Option<SceneQuery>, camera: &dyn Camera, x: f32, y: f32, window_size: (f32, f32)) -> Option<SceneHit> {
+    let query = match query {
+        Some(query) => {
+            query.update(scene);
+            query
+        },
+        None => query.get_or_insert_with(|| scene.query()),
+    };
+    let (origin, direction) = camera.ray(x, y, window_size.0, window_size.1);
+    let hit = query.raycast(&origin, &direction);
+    let instance = hit.as_ref()
+        .and_then(|hit| scene.instances().into_iter().find(|v| v.id() == hit.instance));
+    if let Err(e) = scene.set_selected_instance(instance.as_ref()) {
+        println!("selection failed: {}", error_chain(&e));
+        return None
+    }
+    let (hit, instance) = match (hit, instance) {
+        (Some(hit), Some(instance)) => (hit, instance),
+        _ => {
+            println!("selection cleared");
+            return None
+        },
+    };
+    let name = instance.name().ok().flatten();
+    println!("selected instance {} node {}", instance.id(), name.as_deref().unwrap_or("(unnamed)"));
+    if let Some(summary) = scene.mesh_summary(hit.primitive) {
+        println!("  mesh {} primitive {}, {} triangles, hit triangle {} at distance {:.3}",
+            summary.source_mesh_index, hit.primitive, summary.triangle_count, hit.triangle, hit.t);
+        match summary.material_index {
+            Some(index) => println!("  material {} {}", index, summary.material_name.as_deref().unwrap_or("(unnamed)")),
+            None => println!("  no material"),
+        }
+    }
+    Some(hit)
+}
+
+// the first argument overrides the asset path
+fn asset_filename() -> String {
+    std::env::args().nth(1)
+        .unwrap_or_else(|| ASSET_FILENAME.to_owned())
+}
+
+fn asset_extension(filename: &str) -> Option<String> {
+    std::path::Path::new(filename)
+        .extension()
+        .and_then(|v| v.to_str())
+        .map(|v| v.to_ascii_lowercase())
+}
+
+fn load_scene(command_pool: &Arc<CommandPool>) -> Scene {
+    let filename = asset_filename();
+    match asset_extension(&filename).as_deref() {
+        Some("obj") => {
+            ObjAsset::new(&filename)
+                .and_then(|v| v.into_builder())
+                .unwrap()
+                .build(command_pool)
+                .unwrap()
+        },
+        Some("ply") => {
+            PlyAsset::new(&filename)
+                .and_then(|v| v.into_builder())
+                .unwrap()
+                .build(command_pool)
+                .unwrap()
+        },
+        Some("stl") => {
+            StlAsset::new(&filename)
+                .and_then(|v| v.into_builder())
+                .unwrap()
+                .build(command_pool)
+                .unwrap()
+        },
+        _ => {
+            // the second launch skips parsing and image decoding
+            let cache_path = SceneCache::default_path(&filename);
+            SceneCache::load_or_create(&filename, &cache_path)
+                .unwrap()
+                .build(command_pool)
+                .unwrap()
+        },
+    }
+}
+
+// only glTF assets are reloaded since they go through the scene cache
+fn scene_watcher() -> Option<SceneWatcher> {
+    let filename = asset_filename();
+    match asset_extension(&filename).as_deref() {
+        Some("obj") | Some("ply") | Some("stl") => None,
+        _ => SceneWatcher::new(&filename)
+            .map_err(|e| println!("scene watcher disabled: {}", error_chain(&e)))
+            .ok(),
+    }
+}
+
+// uploads the reloaded scene and points the descriptor sets at it. the camera is left untouched.
+fn swap_scene(
+    cache: &SceneCache,
+    command_pool: &Arc<CommandPool>,
+    descriptor_sets: &Arc<RayTracingDescriptorSets>,
+    graphics_render: &Arc<GraphicsRender>,
+    device_queues: &Arc<DeviceQueues>,
+) -> kaldera::vk::Result<Scene> {
+    device_queues.graphics_queue().wait_idle()?;
+    let scene = cache.build(command_pool)?;
+    descriptor_sets.update_scene(
+        &scene.top_level_acceleration_structure(),
+        scene.vertex_staging_buffer(),
+        scene.index_staging_buffer(),
+        scene.normal_staging_buffer(),
+        scene.description_staging_buffer(),
+        scene.texcoord_staging_buffer(),
+        &scene.textures(),
+        &scene.procedural_staging_buffer(),
+        &scene.procedural_material_staging_buffer(),
+        scene.material_description_staging_buffer(),
+        scene.tangent_staging_buffer(),
+        scene.color_staging_buffer(),
+    )?;
+    graphics_render.prerender();
+    Ok(scene)
+}
+
 fn raytracing_render(surface: &Arc<Surface>) -> Context {
     let device_queues = DeviceQueuesBuilder::new(&surface)
         .with_raytracing()
         .build()
         .unwrap();
     let command_pool = CommandPool::new(device_queues.graphics_queue()).unwrap();
-    let scene_asset = SceneAsset::new(ASSET_FILENAME).unwrap();
-    //let scene_asset = SceneAsset::new("/home/user/Documents/models/san_miguel.glb").unwrap();
-    let scene = SceneBuilder::new(&scene_asset)
-        .build(&command_pool);
-    let camera = FreeLookCamera::new(WIDTH as f32, HEIGHT as f32);
-    let uniform_buffer_model = RayTracingUniformBufferModel {
-        view_inverse: camera.view_inverse(),
-        proj_inverse: camera.projection_inverse(),
-    };
+    let scene = load_scene(&command_pool);
+    let mut camera = FreeLookCamera::new(WIDTH as f32, HEIGHT as f32);
+    camera.frame(&scene.bounds());
+    let uniform_buffer_model = uniform_buffer_model(&camera);
     let uniform_buffer = UniformBuffer::new(&command_pool, &vec![uniform_buffer_model])
         .unwrap();
     let extent = VkExtent2D {
@@ -101,10 +371,9 @@ fn raytracing_render(surface: &Arc<Surface>) -> Context {
     let textures = scene.textures();
     let raytracing_pipeline = RayTracingGraphicsPipeline::new(device_queues.device(), textures.len())
         .unwrap();
-    let dummy_staging_buffer = scene.texcoord_staging_buffer();
     let descriptor_sets = RayTracingDescriptorSets::new(
         &raytracing_pipeline, 
-        scene.top_level_acceleration_structure(), 
+        &scene.top_level_acceleration_structure(), 
         framebuffer.color_image(),
         &uniform_buffer,
         scene.vertex_staging_buffer(),
@@ -113,8 +382,8 @@ fn raytracing_render(surface: &Arc<Surface>) -> Context {
         scene.description_staging_buffer(),
         scene.texcoord_staging_buffer(),
         &textures,
-        dummy_staging_buffer,
-        dummy_staging_buffer,
+        &scene.procedural_staging_buffer(),
+        &scene.procedural_material_staging_buffer(),
         scene.material_description_staging_buffer(),
         scene.tangent_staging_buffer(),
         scene.color_staging_buffer(),
@@ -141,6 +410,9 @@ fn raytracing_render(surface: &Arc<Surface>) -> Context {
         device_queues,
         scene: Some(scene),
         descriptor_sets: Some(descriptor_sets),
+        command_pool,
+        watcher: scene_watcher(),
+        query: None,
     }
 }
 
@@ -150,10 +422,7 @@ fn rasterization_render(surface: &Arc<Surface>) -> Context {
         .unwrap();
     let command_pool = CommandPool::new(device_queues.graphics_queue()).unwrap();
     let camera = OrbitalCamera::new(WIDTH as f32, HEIGHT as f32);
-    let uniform_buffer_model = RayTracingUniformBufferModel {
-        view_inverse: camera.view_inverse(),
-        proj_inverse: camera.projection_inverse(),
-    };
+    let uniform_buffer_model = uniform_buffer_model(&camera);
     let model = TriangleModel::new().unwrap();
     let vertices = model.vertices();
     let indices = model.indices();
@@ -169,7 +438,7 @@ fn rasterization_render(surface: &Arc<Surface>) -> Context {
     let offscreen_framebuffer = OffscreenFramebuffer::new(device_queues.device(), extent)
         .unwrap();
     offscreen_framebuffer.barrier_initial_layout(&command_pool);
-    let offscreen_pipeline_layout = OffscreenGraphicsPipelineLayout::new(device_queues.device()).unwrap();
+    let offscreen_pipeline_layout = OffscreenGraphicsPipelineLayout::new(device_queues.device(), &uniform_buffer).unwrap();
     let offscreen_pipeline = OffscreenGraphicsPipeline::new(offscreen_framebuffer.render_pass(), &offscreen_pipeline_layout).unwrap();
     let offscreen_render = OffscreenGraphicsRender::new(&command_pool, &offscreen_pipeline, &offscreen_framebuffer, &vertex_staging_buffer)
         .unwrap();
@@ -189,5 +458,8 @@ fn rasterization_render(surface: &Arc<Surface>) -> Context {
         device_queues,
         scene: None,
         descriptor_sets: None,
+        command_pool,
+        watcher: None,
+        query: None,
     }
 }
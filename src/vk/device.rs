@@ -2,6 +2,7 @@
 use crate::ffi::vk::*;
 use super::error::Result;
 use super::error::ErrorCode;
+use super::error::Error;
 use super::instance::{QueueFamily, PhysicalDevice};
 
 use std::ptr;
@@ -298,11 +299,11 @@ impl ShaderModuleSource {
     fn load(self) -> Result<(Vec<u8>, usize)> {
         match self {
             ShaderModuleSource::FilePath(filename) => {
-                let mut file = std::fs::File::open(filename)
-                    .map_err(|v| ErrorCode::ShaderLoadIO(v))?;
+                let mut file = std::fs::File::open(&filename)
+                    .map_err(|v| Error::from(ErrorCode::ShaderLoadIO(v)).with_path(&filename))?;
                 let mut buffer = Vec::<u8>::new();
                 let bytes = file.read_to_end(&mut buffer)
-                    .map_err(|v| ErrorCode::ShaderLoadIO(v))?;
+                    .map_err(|v| Error::from(ErrorCode::ShaderLoadIO(v)).with_path(&filename))?;
                 if bytes > 0 && (bytes % 4) == 0 {
                     Ok((buffer, bytes))
                 } else {
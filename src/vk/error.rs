@@ -2,6 +2,8 @@
 
 use crate::ffi::vk::VkResult;
 
+use std::fmt;
+use std::path::PathBuf;
 use std::result;
 
 pub type Result<T> = result::Result<T, Error>;
@@ -9,8 +11,19 @@ pub type Result<T> = result::Result<T, Error>;
 #[derive(Debug)]
 pub enum ErrorCode {
     VkResult(VkResult),
+    // a Vulkan command failed
+    Vulkan {
+        operation: &'static str,
+        result: VkResult,
+    },
     FFI(std::ffi::NulError),
     Io,
+    IoError(std::io::Error),
+    #[cfg(feature = "with-gltf")]
+    Gltf(gltf::Error),
+    ImageDecode(image_crate::ImageError),
+    Base64(base64::DecodeError),
+    UriUnsupported(String),
     SuitablePhysicalDeviceNotFound,
     SuitableBufferMemoryTypeNotFound,
     SuitableImageMemoryTypeNotFound,
@@ -22,6 +35,24 @@ pub enum ErrorCode {
     ShaderLoadUnaligned,
     ImageFormatInvalid,
     ImageNotFound,
+    MeshFormatInvalid,
+    MeshNotFound,
+    MaterialNotFound,
+    SceneNotFound,
+    AccessorInvalid,
+    BufferNotFound,
+    CacheInvalid,
+    InstanceNotFound,
+    AccelerationStructureCapacityExceeded,
+    AccelerationStructureUpdateNotAllowed,
+    CameraPathInvalid,
+    HitGroupNotFound,
+    MaterialTypeNotFound,
+    InstanceCustomIndexOutOfRange,
+    // the following wrap the error that occurred within the context
+    Path(PathBuf, Error),
+    Accessor(usize, Error),
+    Image(usize, Error),
 }
 
 #[derive(Debug)]
@@ -29,6 +60,117 @@ pub struct Error {
     u: Box<ErrorCode>
 }
 
+impl Error {
+    pub fn code(&self) -> &ErrorCode {
+        &self.u
+    }
+
+    // the innermost error code without the contexts
+    pub fn root_code(&self) -> &ErrorCode {
+        match self.code() {
+            ErrorCode::Path(_, inner)
+                | ErrorCode::Accessor(_, inner)
+                | ErrorCode::Image(_, inner) => inner.root_code(),
+            code => code,
+        }
+    }
+
+    pub fn with_path<P>(self, path: P) -> Self where P: Into<PathBuf> {
+        ErrorCode::Path(path.into(), self).into()
+    }
+
+    // glTF accessor index
+    pub fn with_accessor(self, index: usize) -> Self {
+        ErrorCode::Accessor(index, self).into()
+    }
+
+    // glTF image index
+    pub fn with_image(self, index: usize) -> Self {
+        ErrorCode::Image(index, self).into()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.code() {
+            ErrorCode::VkResult(result) => write!(f, "Vulkan error {:?}", result),
+            ErrorCode::Vulkan { operation, result } => write!(f, "{} failed with {:?}", operation, result),
+            ErrorCode::FFI(error) => write!(f, "invalid C string: {}", error),
+            ErrorCode::Io => write!(f, "I/O error"),
+            ErrorCode::IoError(error) => write!(f, "I/O error: {}", error),
+            #[cfg(feature = "with-gltf")]
+            ErrorCode::Gltf(error) => write!(f, "glTF error: {}", error),
+            ErrorCode::ImageDecode(error) => write!(f, "image decode error: {}", error),
+            ErrorCode::Base64(error) => write!(f, "base64 decode error: {}", error),
+            ErrorCode::UriUnsupported(uri) => write!(f, "unsupported URI {}", uri),
+            ErrorCode::SuitablePhysicalDeviceNotFound => write!(f, "suitable physical device not found"),
+            ErrorCode::SuitableBufferMemoryTypeNotFound => write!(f, "suitable buffer memory type not found"),
+            ErrorCode::SuitableImageMemoryTypeNotFound => write!(f, "suitable image memory type not found"),
+            ErrorCode::SwapchainSurfaceFormatNotSupported => write!(f, "swapchain surface format not supported"),
+            ErrorCode::SwapchainImageNotFound => write!(f, "swapchain image not found"),
+            ErrorCode::RenderFrameNotFound => write!(f, "render frame not found"),
+            ErrorCode::SuitableQueueFamilyNotFound => write!(f, "suitable queue family not found"),
+            ErrorCode::ShaderLoadIO(error) => write!(f, "shader load error: {}", error),
+            ErrorCode::ShaderLoadUnaligned => write!(f, "shader code is not aligned to 4 bytes"),
+            ErrorCode::ImageFormatInvalid => write!(f, "invalid image format"),
+            ErrorCode::ImageNotFound => write!(f, "image not found"),
+            ErrorCode::MeshFormatInvalid => write!(f, "invalid mesh format"),
+            ErrorCode::MeshNotFound => write!(f, "mesh not found"),
+            ErrorCode::MaterialNotFound => write!(f, "material not found"),
+            ErrorCode::SceneNotFound => write!(f, "scene not found"),
+            ErrorCode::AccessorInvalid => write!(f, "invalid accessor"),
+            ErrorCode::BufferNotFound => write!(f, "buffer not found"),
+            ErrorCode::CacheInvalid => write!(f, "invalid scene cache"),
+            ErrorCode::InstanceNotFound => write!(f, "instance not found"),
+            ErrorCode::AccelerationStructureCapacityExceeded => write!(f, "acceleration structure capacity exceeded"),
+            ErrorCode::AccelerationStructureUpdateNotAllowed => write!(f, "acceleration structure update not allowed"),
+            ErrorCode::CameraPathInvalid => write!(f, "invalid camera path"),
+            ErrorCode::HitGroupNotFound => write!(f, "hit group not found"),
+            ErrorCode::MaterialTypeNotFound => write!(f, "material type not found"),
+            ErrorCode::InstanceCustomIndexOutOfRange => write!(f, "instance custom index out of range"),
+            ErrorCode::Path(path, _) => write!(f, "failed to load {}", path.display()),
+            ErrorCode::Accessor(index, _) => write!(f, "failed to read accessor {}", index),
+            ErrorCode::Image(index, _) => write!(f, "failed to load image {}", index),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self.code() {
+            ErrorCode::FFI(error) => Some(error),
+            ErrorCode::IoError(error) => Some(error),
+            #[cfg(feature = "with-gltf")]
+            ErrorCode::Gltf(error) => Some(error),
+            ErrorCode::ImageDecode(error) => Some(error),
+            ErrorCode::Base64(error) => Some(error),
+            ErrorCode::ShaderLoadIO(error) => Some(error),
+            ErrorCode::Path(_, inner) => Some(inner),
+            ErrorCode::Accessor(_, inner) => Some(inner),
+            ErrorCode::Image(_, inner) => Some(inner),
+            _ => None,
+        }
+    }
+}
+
+// joins the contexts such as `failed to read accessor 3: invalid accessor`. the messages of the
+// wrapped I/O, glTF and decoding errors are part of the innermost message already.
+pub fn error_chain(error: &Error) -> String {
+    let mut message = error.to_string();
+    let mut source = inner_error(error);
+    while let Some(error) = source {
+        message.push_str(": ");
+        message.push_str(&error.to_string());
+        source = inner_error(error);
+    }
+    message
+}
+
+fn inner_error(error: &Error) -> Option<&Error> {
+    std::error::Error::source(error)
+        .and_then(|v| v.downcast_ref::<Error>())
+}
+
 impl From<VkResult> for Error {
     fn from(code: VkResult) -> Self {
         Error {
@@ -45,6 +187,39 @@ impl From<std::ffi::NulError> for Error {
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error {
+            u: Box::new(ErrorCode::IoError(error))
+        }
+    }
+}
+
+#[cfg(feature = "with-gltf")]
+impl From<gltf::Error> for Error {
+    fn from(error: gltf::Error) -> Self {
+        Error {
+            u: Box::new(ErrorCode::Gltf(error))
+        }
+    }
+}
+
+impl From<image_crate::ImageError> for Error {
+    fn from(error: image_crate::ImageError) -> Self {
+        Error {
+            u: Box::new(ErrorCode::ImageDecode(error))
+        }
+    }
+}
+
+impl From<base64::DecodeError> for Error {
+    fn from(error: base64::DecodeError) -> Self {
+        Error {
+            u: Box::new(ErrorCode::Base64(error))
+        }
+    }
+}
+
 impl From<ErrorCode> for Error {
     fn from(code: ErrorCode) -> Self {
         Error {
@@ -61,4 +236,25 @@ impl VkResult {
             Err(self.into())
         }
     }
+
+    // keeps the name of the failed command such as `vkCreateImage`
+    pub fn into_result_with(self, operation: &'static str) -> Result<()> {
+        if self == VkResult::VK_SUCCESS {
+            Ok(())
+        } else {
+            Err(ErrorCode::Vulkan { operation, result: self }.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_chain_keeps_the_inner_detail_once() {
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "no such file");
+        let error = Error::from(io).with_path("scene.gltf");
+        assert_eq!(error_chain(&error), "failed to load scene.gltf: I/O error: no such file");
+    }
 }
@@ -54,8 +54,7 @@ impl ColorImage {
                 initialLayout: VkImageLayout::VK_IMAGE_LAYOUT_UNDEFINED,
             };
             vkCreateImage(device.handle(), &create_info, std::ptr::null(), image_handle.as_mut_ptr())
-                .into_result()
-                .unwrap();
+                .into_result_with("vkCreateImage")?;
         }
         let image_handle = image_handle.assume_init();
         // memory
@@ -80,8 +79,7 @@ impl ColorImage {
                 },
             };
             vkCreateImageView(device.handle(), &create_info, ptr::null(), view_handle.as_mut_ptr())
-                .into_result()
-                .unwrap();
+                .into_result_with("vkCreateImageView")?;
         }
         let view_handle = view_handle.assume_init();
         // sampler
@@ -108,8 +106,7 @@ impl ColorImage {
                 unnormalizedCoordinates: VK_FALSE,
             };
             vkCreateSampler(device.handle(), &create_info, ptr::null(), sampler_handle.as_mut_ptr())
-                .into_result()
-                .unwrap();
+                .into_result_with("vkCreateSampler")?;
         }
         let sampler_handle = sampler_handle.assume_init();
         let image = ColorImage {
@@ -214,8 +211,7 @@ impl DepthStencilImage {
                 initialLayout: VkImageLayout::VK_IMAGE_LAYOUT_UNDEFINED,
             };
             vkCreateImage(device.handle(), &create_info, std::ptr::null(), image_handle.as_mut_ptr())
-                .into_result()
-                .unwrap();
+                .into_result_with("vkCreateImage")?;
         }
         let image_handle = image_handle.assume_init();
         // memory
@@ -241,8 +237,7 @@ impl DepthStencilImage {
                 },
             };
             vkCreateImageView(device.handle(), &create_info, ptr::null(), view_handle.as_mut_ptr())
-                .into_result()
-                .unwrap();
+                .into_result_with("vkCreateImageView")?;
         }
         let view_handle = view_handle.assume_init();
         let image = DepthStencilImage {
@@ -341,8 +336,7 @@ impl DepthImage {
                 initialLayout: VkImageLayout::VK_IMAGE_LAYOUT_UNDEFINED,
             };
             vkCreateImage(device.handle(), &create_info, std::ptr::null(), image_handle.as_mut_ptr())
-                .into_result()
-                .unwrap();
+                .into_result_with("vkCreateImage")?;
         }
         let image_handle = image_handle.assume_init();
         // memory
@@ -367,8 +361,7 @@ impl DepthImage {
                 },
             };
             vkCreateImageView(device.handle(), &create_info, ptr::null(), view_handle.as_mut_ptr())
-                .into_result()
-                .unwrap();
+                .into_result_with("vkCreateImageView")?;
         }
         let view_handle = view_handle.assume_init();
         let image = DepthImage {
@@ -442,19 +435,36 @@ pub struct TextureImage {
     image_format: VkFormat,
     mip_levels: u32,
     extent: VkExtent3D,
+    premipped: bool,
 }
 
 impl TextureImage {
     pub fn new(device: &Arc<Device>, extent: VkExtent3D, format: VkFormat, mipmap: bool) -> Result<Arc<Self>> {
         unsafe {
             if mipmap {
-                Self::init_mipmap(device, extent, format)
+                Self::init_mipmap(device, extent, format, false)
             } else {
                 Self::init(device, extent, format)
             }
         }
     }
 
+    // the texture data contains every mip level from the largest one,
+    // tightly packed in the order of `mip_levels_for(extent)`
+    pub fn new_premipped(device: &Arc<Device>, extent: VkExtent3D, format: VkFormat) -> Result<Arc<Self>> {
+        unsafe {
+            Self::init_mipmap(device, extent, format, true)
+        }
+    }
+
+    pub fn mip_levels_for(extent: VkExtent3D) -> u32 {
+        extent.width
+            .max(extent.height)
+            .next_power_of_two()
+            .trailing_zeros()
+            .max(1)
+    }
+
     unsafe fn init(device: &Arc<Device>, extent: VkExtent3D, format: VkFormat) -> Result<Arc<Self>> {
         let mip_levels = 1;
         // image
@@ -480,8 +490,7 @@ impl TextureImage {
                 initialLayout: VkImageLayout::VK_IMAGE_LAYOUT_UNDEFINED,
             };
             vkCreateImage(device.handle(), &create_info, std::ptr::null(), image_handle.as_mut_ptr())
-                .into_result()
-                .unwrap();
+                .into_result_with("vkCreateImage")?;
         }
         let image_handle = image_handle.assume_init();
         // memory
@@ -507,8 +516,7 @@ impl TextureImage {
                 subresourceRange: subresource_range,
             };
             vkCreateImageView(device.handle(), &create_info, ptr::null(), view_handle.as_mut_ptr())
-                .into_result()
-                .unwrap();
+                .into_result_with("vkCreateImageView")?;
         }
         let view_handle = view_handle.assume_init();
         // sampler
@@ -535,8 +543,7 @@ impl TextureImage {
                 unnormalizedCoordinates: VK_FALSE,
             };
             vkCreateSampler(device.handle(), &create_info, ptr::null(), sampler_handle.as_mut_ptr())
-                .into_result()
-                .unwrap();
+                .into_result_with("vkCreateSampler")?;
         }
         let sampler_handle = sampler_handle.assume_init();
         let image = Self {
@@ -548,16 +555,13 @@ impl TextureImage {
             image_format: format,
             mip_levels,
             extent,
+            premipped: false,
         };
         Ok(Arc::new(image))
     }
 
-    unsafe fn init_mipmap(device: &Arc<Device>, extent: VkExtent3D, format: VkFormat) -> Result<Arc<Self>> {
-        let mip_levels = extent.width
-            .max(extent.height)
-            .next_power_of_two()
-            .trailing_zeros()
-            .max(1);
+    unsafe fn init_mipmap(device: &Arc<Device>, extent: VkExtent3D, format: VkFormat, premipped: bool) -> Result<Arc<Self>> {
+        let mip_levels = Self::mip_levels_for(extent);
         // image
         let mut image_handle = MaybeUninit::<VkImage>::zeroed();
         {
@@ -581,8 +585,7 @@ impl TextureImage {
                 initialLayout: VkImageLayout::VK_IMAGE_LAYOUT_UNDEFINED,
             };
             vkCreateImage(device.handle(), &create_info, std::ptr::null(), image_handle.as_mut_ptr())
-                .into_result()
-                .unwrap();
+                .into_result_with("vkCreateImage")?;
         }
         let image_handle = image_handle.assume_init();
         // memory
@@ -608,8 +611,7 @@ impl TextureImage {
                 subresourceRange: subresource_range,
             };
             vkCreateImageView(device.handle(), &create_info, ptr::null(), view_handle.as_mut_ptr())
-                .into_result()
-                .unwrap();
+                .into_result_with("vkCreateImageView")?;
         }
         let view_handle = view_handle.assume_init();
         // sampler
@@ -636,8 +638,7 @@ impl TextureImage {
                 unnormalizedCoordinates: VK_FALSE,
             };
             vkCreateSampler(device.handle(), &create_info, ptr::null(), sampler_handle.as_mut_ptr())
-                .into_result()
-                .unwrap();
+                .into_result_with("vkCreateSampler")?;
         }
         let sampler_handle = sampler_handle.assume_init();
         let image = Self {
@@ -649,6 +650,7 @@ impl TextureImage {
             image_format: format,
             mip_levels,
             extent,
+            premipped,
         };
         Ok(Arc::new(image))
     }
@@ -699,6 +701,8 @@ impl TextureImage {
     ) {
         if self.mip_levels == 1 {
             self.command_blit(command_buffer, buffer_memory)
+        } else if self.premipped {
+            self.command_copy_mip_levels(command_buffer, buffer_memory)
         } else {
             self.command_mipmap(command_buffer, buffer_memory)
         }
@@ -779,6 +783,90 @@ impl TextureImage {
             1, &image_memory_barrier);
     }
 
+    unsafe fn command_copy_mip_levels(&self, 
+        command_buffer: VkCommandBuffer,
+        buffer_memory: &Arc<BufferMemory>,
+    ) {
+        let image = self.image();
+        let subresource_range = VkImageSubresourceRange {
+            aspectMask: VkImageAspectFlagBits::VK_IMAGE_ASPECT_COLOR_BIT as VkImageAspectFlags,
+            baseMipLevel: 0,
+            levelCount: self.mip_levels,
+            baseArrayLayer: 0,
+            layerCount: 1,
+        };
+        // barrier
+        let image_memory_barrier = VkImageMemoryBarrier {
+            sType: VK_STRUCTURE_TYPE_IMAGE_MEMORY_BARRIER,
+            pNext: ptr::null(),
+            srcAccessMask: 0 as VkAccessFlags,
+            dstAccessMask: VK_ACCESS_TRANSFER_WRITE_BIT as VkAccessFlags,
+            oldLayout: VkImageLayout::VK_IMAGE_LAYOUT_UNDEFINED,
+            newLayout: VkImageLayout::VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL,
+            srcQueueFamilyIndex: VK_QUEUE_FAMILY_IGNORED,
+            dstQueueFamilyIndex: VK_QUEUE_FAMILY_IGNORED,
+            image: image,
+            subresourceRange: subresource_range.clone(),
+        };
+        vkCmdPipelineBarrier(command_buffer,
+            VK_PIPELINE_STAGE_HOST_BIT as VkPipelineStageFlags, 
+            VK_PIPELINE_STAGE_TRANSFER_BIT as VkPipelineStageFlags, 
+            0 as VkDependencyFlags, 
+            0, ptr::null(), 
+            0, ptr::null(), 
+            1, &image_memory_barrier);
+        // copy every level at once. the texels are 4 bytes in RGBA8
+        let extent = self.extent();
+        let mut offset: VkDeviceSize = 0;
+        let regions: Vec<VkBufferImageCopy> = (0..self.mip_levels)
+            .map(|level| {
+                let width = (extent.width >> level).max(1);
+                let height = (extent.height >> level).max(1);
+                let region = VkBufferImageCopy {
+                    bufferOffset: offset,
+                    bufferRowLength: 0,
+                    bufferImageHeight: 0,
+                    imageSubresource: VkImageSubresourceLayers {
+                        aspectMask: VkImageAspectFlagBits::VK_IMAGE_ASPECT_COLOR_BIT as VkImageAspectFlags,
+                        mipLevel: level,
+                        baseArrayLayer: 0,
+                        layerCount: 1,
+                    },
+                    imageOffset: VkOffset3D { x: 0, y: 0, z: 0 },
+                    imageExtent: VkExtent3D { width, height, depth: 1 },
+                };
+                offset += width as VkDeviceSize * height as VkDeviceSize * 4;
+                region
+            })
+            .collect();
+        vkCmdCopyBufferToImage(command_buffer, 
+            buffer_memory.buffer(), 
+            image, 
+            VkImageLayout::VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL, 
+            regions.len() as u32, 
+            regions.as_ptr());
+        // barrier
+        let image_memory_barrier = VkImageMemoryBarrier {
+            sType: VK_STRUCTURE_TYPE_IMAGE_MEMORY_BARRIER,
+            pNext: ptr::null(),
+            srcAccessMask: VK_ACCESS_TRANSFER_WRITE_BIT as VkAccessFlags,
+            dstAccessMask: VK_ACCESS_SHADER_READ_BIT as VkAccessFlags,
+            oldLayout: VkImageLayout::VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL,
+            newLayout: VkImageLayout::VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL,
+            srcQueueFamilyIndex: VK_QUEUE_FAMILY_IGNORED,
+            dstQueueFamilyIndex: VK_QUEUE_FAMILY_IGNORED,
+            image: image,
+            subresourceRange: subresource_range,
+        };
+        vkCmdPipelineBarrier(command_buffer,
+            VK_PIPELINE_STAGE_TRANSFER_BIT as VkPipelineStageFlags, 
+            VK_PIPELINE_STAGE_ALL_COMMANDS_BIT as VkPipelineStageFlags, 
+            0 as VkDependencyFlags, 
+            0, ptr::null(), 
+            0, ptr::null(), 
+            1, &image_memory_barrier);
+    }
+
     unsafe fn command_mipmap(&self, 
         command_buffer: VkCommandBuffer,
         buffer_memory: &Arc<BufferMemory>,
@@ -1021,12 +1109,11 @@ impl Texture {
                 VK_BUFFER_USAGE_TRANSFER_SRC_BIT as VkFlags, 
                 VK_MEMORY_PROPERTY_HOST_VISIBLE_BIT as VkFlags 
                     | VK_MEMORY_PROPERTY_HOST_COHERENT_BIT as VkFlags, 
-                data_size as VkDeviceSize).unwrap();
+                data_size as VkDeviceSize)?;
             {
                 let mut mapped = MaybeUninit::<*mut c_void>::zeroed();
                 vkMapMemory(device.handle(), buffer_memory.memory(), 0, data_size as VkDeviceSize, 0, mapped.as_mut_ptr())
-                    .into_result()
-                    .unwrap();
+                    .into_result_with("vkMapMemory")?;
                 let mapped = mapped.assume_init();
                 std::ptr::copy_nonoverlapping(data, mapped, data_size);
                 vkUnmapMemory(device.handle(), buffer_memory.memory());
@@ -14,6 +14,14 @@ pub struct Vertex {
 pub struct RayTracingUniformBufferModel {
     pub view_inverse: Mat4,
     pub proj_inverse: Mat4,
+    // the radius of the lens, zero for a pinhole
+    pub aperture: f32,
+    pub focus_distance: f32,
+    // a disk below three
+    pub blades: u32,
+    pub blade_rotation: f32,
+    // read by the rasterization pipeline sharing the buffer
+    pub view_projection: Mat4,
 }
 
 pub struct TriangleModel {
@@ -3,6 +3,7 @@ use crate::ffi::vk::*;
 use super::error::Result;
 use super::device::{Device, CommandPool, CommandBufferBuilder, ShaderModule, ShaderModuleSource};
 use super::staging::VertexStagingBuffer;
+use super::memory::UniformBuffer;
 use super::geometry::{Vec3};
 use super::image::{ColorImage, DepthImage};
 
@@ -227,28 +228,86 @@ impl Drop for OffscreenFramebuffer {
     }
 }
 
+#[allow(dead_code)]
 pub struct OffscreenGraphicsPipelineLayout {
     device: Arc<Device>,
     handle: VkPipelineLayout,
+    descriptor_pool: VkDescriptorPool,
+    descriptor_set_layout: VkDescriptorSetLayout,
+    descriptor_set: VkDescriptorSet,
+    uniform_buffer: Arc<UniformBuffer>,
 }
 
 impl OffscreenGraphicsPipelineLayout {
-    pub fn new(device: &Arc<Device>) -> Result<Arc<Self>> {
-        unsafe { Self::init(device) }
+    pub fn new(device: &Arc<Device>, uniform_buffer: &Arc<UniformBuffer>) -> Result<Arc<Self>> {
+        unsafe { Self::init(device, uniform_buffer) }
     }
 
-    unsafe fn init(device: &Arc<Device>) -> Result<Arc<Self>> {
+    unsafe fn init(device: &Arc<Device>, uniform_buffer: &Arc<UniformBuffer>) -> Result<Arc<Self>> {
+        let buffer_info = VkDescriptorBufferInfo::new(
+            uniform_buffer.device_buffer_memory().buffer(),
+            0,
+            uniform_buffer.device_buffer_memory().size(),
+        );
+        // Descriptor Pool
+        let mut descriptor_pool = MaybeUninit::<VkDescriptorPool>::zeroed();
+        {
+            let size = VkDescriptorPoolSize::new(VkDescriptorType::VK_DESCRIPTOR_TYPE_UNIFORM_BUFFER, 1);
+            let create_info = VkDescriptorPoolCreateInfo::new(1, 1, &size, 0);
+            vkCreateDescriptorPool(device.handle(), &create_info, ptr::null(), descriptor_pool.as_mut_ptr())
+                .into_result_with("vkCreateDescriptorPool")?;
+        }
+        let descriptor_pool = descriptor_pool.assume_init();
+        // Descriptor Set Layout
+        let mut descriptor_set_layout = MaybeUninit::<VkDescriptorSetLayout>::zeroed();
+        {
+            let bindings = [
+                VkDescriptorSetLayoutBinding::new(
+                    VkDescriptorType::VK_DESCRIPTOR_TYPE_UNIFORM_BUFFER, 
+                    VkShaderStageFlagBits::VK_SHADER_STAGE_VERTEX_BIT as u32,
+                    0,
+                )
+            ];
+            let create_info = VkDescriptorSetLayoutCreateInfo::new(bindings.len() as u32, bindings.as_ptr());
+            vkCreateDescriptorSetLayout(device.handle(), &create_info, ptr::null(), descriptor_set_layout.as_mut_ptr())
+                .into_result_with("vkCreateDescriptorSetLayout")?;
+        }
+        let descriptor_set_layout = descriptor_set_layout.assume_init();
+        // Pipeline Layout
         let mut handle = MaybeUninit::<VkPipelineLayout>::zeroed();
         {
-            let create_info = VkPipelineLayoutCreateInfo::new(0, ptr::null());
+            let create_info = VkPipelineLayoutCreateInfo::new(1, &descriptor_set_layout);
             vkCreatePipelineLayout(device.handle(), &create_info, ptr::null(), handle.as_mut_ptr())
-                .into_result()
-                .unwrap();
+                .into_result_with("vkCreatePipelineLayout")?;
         }
         let handle = handle.assume_init();
+        // Instantiate
+        let mut descriptor_set = MaybeUninit::<VkDescriptorSet>::zeroed();
+        {
+            let alloc_info = VkDescriptorSetAllocateInfo::new(descriptor_pool, 1, &descriptor_set_layout);
+            vkAllocateDescriptorSets(device.handle(), &alloc_info, descriptor_set.as_mut_ptr())
+                .into_result_with("vkAllocateDescriptorSets")?;
+        }
+        let descriptor_set = descriptor_set.assume_init();
+        // Write Descriptor
+        {
+            let write_sets = [
+                VkWriteDescriptorSet::from_buffer(
+                    descriptor_set, 
+                    VkDescriptorType::VK_DESCRIPTOR_TYPE_UNIFORM_BUFFER, 
+                    0,
+                    &buffer_info,
+                )
+            ];
+            vkUpdateDescriptorSets(device.handle(), write_sets.len() as u32, write_sets.as_ptr(), 0, ptr::null());
+        }
         let layout = OffscreenGraphicsPipelineLayout {
             device: Arc::clone(device),
             handle,
+            descriptor_pool,
+            descriptor_set_layout,
+            descriptor_set,
+            uniform_buffer: Arc::clone(uniform_buffer),
         };
         Ok(Arc::new(layout))
     }
@@ -257,13 +316,21 @@ impl OffscreenGraphicsPipelineLayout {
     pub fn handle(&self) -> VkPipelineLayout {
         self.handle
     }
+
+    #[inline]
+    pub fn descriptor_set(&self) -> VkDescriptorSet {
+        self.descriptor_set
+    }
 }
 
 impl Drop for OffscreenGraphicsPipelineLayout {
     fn drop(&mut self) {
         log_debug!("Drop OffscreenGraphicsPipelineLayout");
         unsafe {
-            vkDestroyPipelineLayout(self.device.handle(), self.handle, ptr::null());
+            let device = &self.device;
+            vkDestroyPipelineLayout(device.handle(), self.handle, ptr::null());
+            vkDestroyDescriptorSetLayout(device.handle(), self.descriptor_set_layout, ptr::null());
+            vkDestroyDescriptorPool(device.handle(), self.descriptor_pool, ptr::null());
         }
     }
 }
@@ -485,6 +552,11 @@ impl OffscreenGraphicsPipeline {
     pub fn handle(&self) -> VkPipeline {
         self.handle
     }
+
+    #[inline]
+    pub fn layout(&self) -> &Arc<OffscreenGraphicsPipelineLayout> {
+        &self.layout
+    }
 }
 
 impl Drop for OffscreenGraphicsPipeline {
@@ -565,6 +637,9 @@ impl OffscreenGraphicsRender {
         vkCmdSetViewport(command_buffer, 0, 1, &viewport);
         let scissor = area;
         vkCmdSetScissor(command_buffer, 0, 1, &scissor);
+        let descriptor_set = self.pipeline.layout().descriptor_set();
+        vkCmdBindDescriptorSets(command_buffer, VkPipelineBindPoint::VK_PIPELINE_BIND_POINT_GRAPHICS, 
+            self.pipeline.layout().handle(), 0, 1, &descriptor_set, 0, ptr::null());
         vkCmdBindPipeline(command_buffer, VkPipelineBindPoint::VK_PIPELINE_BIND_POINT_GRAPHICS, self.pipeline.handle());
         let offset: VkDeviceSize = 0;
         let vertex_buffer: VkBuffer = staging_buffer.vertex_buffer().device_buffer_memory().buffer();
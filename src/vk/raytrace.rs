@@ -1,6 +1,6 @@
 
 use crate::ffi::vk::*;
-use super::error::Result;
+use super::error::{Result, ErrorCode};
 use super::device::{Device, CommandPool, ShaderModule, ShaderModuleSource, CommandBufferRecording};
 use super::memory::{UniformBuffer, DedicatedBufferMemory, DedicatedStagingBuffer};
 use super::image::{ColorImage, Texture};
@@ -8,16 +8,102 @@ use super::image::{ColorImage, Texture};
 use std::ptr;
 use std::mem::MaybeUninit;
 use libc::{c_void, size_t};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::ffi::CString;
+use std::time::Duration;
 
 use VkStructureTypeExtRay::*;
 use VkStructureType::*;
 
 const TEXTURES_MAX_COUNT: usize = 65535;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccelerationStructurePreference {
+    FastTrace,
+    FastBuild,
+}
+
+// compaction only applies to bottom level structures
+#[derive(Clone, Copy, Debug)]
+pub struct AccelerationStructureBuildOptions {
+    preference: AccelerationStructurePreference,
+    allow_update: bool,
+    allow_compaction: bool,
+}
+
+impl AccelerationStructureBuildOptions {
+    pub fn new() -> Self {
+        Self {
+            preference: AccelerationStructurePreference::FastTrace,
+            allow_update: false,
+            allow_compaction: true,
+        }
+    }
+
+    pub fn with_preference(mut self, preference: AccelerationStructurePreference) -> Self {
+        self.preference = preference;
+        self
+    }
+
+    // lets `update` refit the structure in place at the cost of some trace performance
+    pub fn with_update(mut self, allow_update: bool) -> Self {
+        self.allow_update = allow_update;
+        self
+    }
+
+    pub fn with_compaction(mut self, allow_compaction: bool) -> Self {
+        self.allow_compaction = allow_compaction;
+        self
+    }
+
+    pub fn preference(&self) -> AccelerationStructurePreference {
+        self.preference
+    }
+
+    pub fn allows_update(&self) -> bool {
+        self.allow_update
+    }
+
+    pub fn allows_compaction(&self) -> bool {
+        self.allow_compaction
+    }
+
+    fn flags(&self) -> VkBuildAccelerationStructureFlagsKHR {
+        use VkBuildAccelerationStructureFlagBitsKHR::*;
+        let mut flags = match self.preference {
+            AccelerationStructurePreference::FastTrace => VK_BUILD_ACCELERATION_STRUCTURE_PREFER_FAST_TRACE_BIT_KHR,
+            AccelerationStructurePreference::FastBuild => VK_BUILD_ACCELERATION_STRUCTURE_PREFER_FAST_BUILD_BIT_KHR,
+        } as VkBuildAccelerationStructureFlagsKHR;
+        if self.allow_update {
+            flags |= VK_BUILD_ACCELERATION_STRUCTURE_ALLOW_UPDATE_BIT_KHR as VkBuildAccelerationStructureFlagsKHR;
+        }
+        if self.allow_compaction {
+            flags |= VK_BUILD_ACCELERATION_STRUCTURE_ALLOW_COMPACTION_BIT_KHR as VkBuildAccelerationStructureFlagsKHR;
+        }
+        flags
+    }
+}
+
+impl Default for AccelerationStructureBuildOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// the latest build or update of a structure. the sizes are in bytes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AccelerationStructureStats {
+    // measured with timestamps on the device
+    pub build_time: Duration,
+    pub size: VkDeviceSize,
+    // equals `size` unless the structure was compacted
+    pub compacted_size: VkDeviceSize,
+    pub scratch_size: VkDeviceSize,
+}
+
 pub enum BottomLevelAccelerationStructureGeometry {
     Triangles(BottomLevelAccelerationStructureTriangles),
+    AABBs(BottomLevelAccelerationStructureAABBs),
 }
 
 impl BottomLevelAccelerationStructureGeometry {
@@ -44,25 +130,108 @@ impl BottomLevelAccelerationStructureGeometry {
         Arc::new(Self::Triangles(triangles))
     }
 
+    // `aabb_buffer_memory` holds tightly packed `VkAabbPositionsKHR`, i.e. six floats of min and max
+    pub fn aabbs(
+        num_aabbs: u32,
+        aabb_offset_index: u32,
+        aabb_buffer_memory: &Arc<DedicatedBufferMemory>,
+        is_opaque: bool,
+    ) -> Arc<Self> {
+        let aabbs = BottomLevelAccelerationStructureAABBs::new(
+            num_aabbs,
+            aabb_offset_index,
+            aabb_buffer_memory,
+            is_opaque,
+        );
+        Arc::new(Self::AABBs(aabbs))
+    }
+
     fn geometry(&self) -> VkAccelerationStructureGeometryKHR {
-        match &self {
-            &Self::Triangles(triangles) => triangles.geometry(),
+        match self {
+            Self::Triangles(triangles) => triangles.geometry(),
+            Self::AABBs(aabbs) => aabbs.geometry(),
         }
     }
 
     fn range_info(&self) -> VkAccelerationStructureBuildRangeInfoKHR {
-        match &self {
-            &Self::Triangles(triangles) => triangles.range_info(),
+        match self {
+            Self::Triangles(triangles) => triangles.range_info(),
+            Self::AABBs(aabbs) => aabbs.range_info(),
         }
     }
 
     fn max_primitive_count(&self) -> u32 {
-        match &self {
-            &Self::Triangles(triangles) => triangles.max_primitive_count(),
+        match self {
+            Self::Triangles(triangles) => triangles.max_primitive_count(),
+            Self::AABBs(aabbs) => aabbs.max_primitive_count(),
         }
     }
 }
 
+pub struct BottomLevelAccelerationStructureAABBs {
+    num_aabbs: u32,
+    aabb_offset_index: u32,
+    is_opaque: bool,
+    aabb_buffer_memory: Arc<DedicatedBufferMemory>,
+}
+
+impl BottomLevelAccelerationStructureAABBs {
+    const STRIDE: usize = std::mem::size_of::<[f32; 6]>();
+
+    fn new(
+        num_aabbs: u32,
+        aabb_offset_index: u32,
+        aabb_buffer_memory: &Arc<DedicatedBufferMemory>,
+        is_opaque: bool,
+    ) -> Self {
+        Self {
+            num_aabbs,
+            aabb_offset_index,
+            is_opaque,
+            aabb_buffer_memory: Arc::clone(aabb_buffer_memory),
+        }
+    }
+
+    fn geometry(&self) -> VkAccelerationStructureGeometryKHR {
+        use VkGeometryFlagBitsKHR::*;
+        let aabbs = VkAccelerationStructureGeometryAabbsDataKHR {
+            sType: VK_STRUCTURE_TYPE_ACCELERATION_STRUCTURE_GEOMETRY_AABBS_DATA_KHR,
+            pNext: ptr::null(),
+            data: VkDeviceOrHostAddressConstKHR {
+                deviceAddress: self.aabb_buffer_memory.buffer_device_address(),
+            },
+            stride: Self::STRIDE as VkDeviceSize,
+        };
+        let geometry_flags: VkGeometryFlagBitsKHR = if self.is_opaque { 
+            VK_GEOMETRY_OPAQUE_BIT_KHR 
+        } else { 
+            VK_GEOMETRY_NO_DUPLICATE_ANY_HIT_INVOCATION_BIT_KHR 
+        };
+        VkAccelerationStructureGeometryKHR {
+            sType: VkStructureTypeExtRay::VK_STRUCTURE_TYPE_ACCELERATION_STRUCTURE_GEOMETRY_KHR,
+            pNext: ptr::null(),
+            geometryType: VkGeometryTypeKHR::VK_GEOMETRY_TYPE_AABBS_KHR,
+            geometry: VkAccelerationStructureGeometryDataKHR {
+                aabbs,
+            },
+            flags: geometry_flags as VkGeometryFlagsKHR,
+        }
+    }
+
+    fn range_info(&self) -> VkAccelerationStructureBuildRangeInfoKHR {
+        VkAccelerationStructureBuildRangeInfoKHR {
+            primitiveCount: self.num_aabbs,
+            primitiveOffset: self.aabb_offset_index * Self::STRIDE as u32,
+            firstVertex: 0,
+            transformOffset: 0,
+        }
+    }
+
+    fn max_primitive_count(&self) -> u32 {
+        self.num_aabbs
+    }
+}
+
 pub struct BottomLevelAccelerationStructureTriangles {
     num_vertices: u32,
     vertex_stride: VkDeviceSize,
@@ -177,7 +346,7 @@ struct AccelerationStructureBufferMemory {
 }
 
 impl AccelerationStructureBufferMemory {
-    fn new(device: &Arc<Device>, structure_size: VkDeviceSize) -> Arc<Self> {
+    fn new(device: &Arc<Device>, structure_size: VkDeviceSize) -> Result<Arc<Self>> {
         unsafe {
             // creates buffer
             let mut buffer = MaybeUninit::<VkBuffer>::zeroed();
@@ -190,49 +359,55 @@ impl AccelerationStructureBufferMemory {
                     VkSharingMode::VK_SHARING_MODE_EXCLUSIVE,
                 );
                 vkCreateBuffer(device.handle(), &buffer_create_info, ptr::null(), buffer.as_mut_ptr())
-                    .into_result()
-                    .unwrap();
+                    .into_result_with("vkCreateBuffer")?;
             }
             let buffer = buffer.assume_init();
-            // creates memory
-            let mut memory = MaybeUninit::<VkDeviceMemory>::zeroed();
-            {
-                use VkMemoryAllocateFlagBits::*;
-                let mut requirements = MaybeUninit::<VkMemoryRequirements>::zeroed();
-                vkGetBufferMemoryRequirements(device.handle(), buffer, requirements.as_mut_ptr());
-                let requirements = requirements.assume_init();
-                let flags_info = VkMemoryAllocateFlagsInfo {
-                    sType: VK_STRUCTURE_TYPE_MEMORY_ALLOCATE_FLAGS_INFO,
-                    pNext: ptr::null(),
-                    flags: VK_MEMORY_ALLOCATE_DEVICE_ADDRESS_BIT as VkMemoryAllocateFlags,
-                    deviceMask: 0,
-                };
-                // physical memory properties
-                use VkMemoryPropertyFlagBits::*;
-                let memory_type_index = device.physical_device()
-                    .memory_type_index(&requirements, VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT as VkMemoryPropertyFlags)
-                    .unwrap();
-                let allocate_info = VkMemoryAllocateInfo {
-                    sType: VK_STRUCTURE_TYPE_MEMORY_ALLOCATE_INFO,
-                    pNext: &flags_info as *const _ as *const c_void,
-                    allocationSize: requirements.size,
-                    memoryTypeIndex: memory_type_index,
-                };
-                vkAllocateMemory(device.handle(), &allocate_info, ptr::null(), memory.as_mut_ptr())
-                    .into_result()
-                    .unwrap();
-            }
-            let memory = memory.assume_init();
+            // creates memory, destroying the buffer when none can be allocated for it
+            let memory = match Self::allocate_memory(device, buffer) {
+                Ok(v) => v,
+                Err(e) => {
+                    vkDestroyBuffer(device.handle(), buffer, ptr::null());
+                    return Err(e)
+                },
+            };
             vkBindBufferMemory(device.handle(), buffer, memory, 0);
             let buffer_memory = Self {
                 device: Arc::clone(device),
                 buffer,
                 memory,
             };
-            Arc::new(buffer_memory)
+            Ok(Arc::new(buffer_memory))
         }
     }
 
+    unsafe fn allocate_memory(device: &Arc<Device>, buffer: VkBuffer) -> Result<VkDeviceMemory> {
+        let mut memory = MaybeUninit::<VkDeviceMemory>::zeroed();
+        use VkMemoryAllocateFlagBits::*;
+        let mut requirements = MaybeUninit::<VkMemoryRequirements>::zeroed();
+        vkGetBufferMemoryRequirements(device.handle(), buffer, requirements.as_mut_ptr());
+        let requirements = requirements.assume_init();
+        let flags_info = VkMemoryAllocateFlagsInfo {
+            sType: VK_STRUCTURE_TYPE_MEMORY_ALLOCATE_FLAGS_INFO,
+            pNext: ptr::null(),
+            flags: VK_MEMORY_ALLOCATE_DEVICE_ADDRESS_BIT as VkMemoryAllocateFlags,
+            deviceMask: 0,
+        };
+        // physical memory properties
+        use VkMemoryPropertyFlagBits::*;
+        let memory_type_index = device.physical_device()
+            .memory_type_index(&requirements, VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT as VkMemoryPropertyFlags)
+            .ok_or(ErrorCode::SuitableBufferMemoryTypeNotFound)?;
+        let allocate_info = VkMemoryAllocateInfo {
+            sType: VK_STRUCTURE_TYPE_MEMORY_ALLOCATE_INFO,
+            pNext: &flags_info as *const _ as *const c_void,
+            allocationSize: requirements.size,
+            memoryTypeIndex: memory_type_index,
+        };
+        vkAllocateMemory(device.handle(), &allocate_info, ptr::null(), memory.as_mut_ptr())
+            .into_result_with("vkAllocateMemory")?;
+        Ok(memory.assume_init())
+    }
+
     fn buffer(&self) -> VkBuffer {
         self.buffer
     }
@@ -261,9 +436,9 @@ impl AccelerationStructure {
         device: &Arc<Device>,
         structure_size: VkDeviceSize, 
         structure_type: VkAccelerationStructureTypeKHR,
-    ) -> Arc<Self> {
+    ) -> Result<Arc<Self>> {
         unsafe {
-            let buffer_memory = AccelerationStructureBufferMemory::new(device, structure_size);
+            let buffer_memory = AccelerationStructureBufferMemory::new(device, structure_size)?;
             let create_info = VkAccelerationStructureCreateInfoKHR {
                 sType: VK_STRUCTURE_TYPE_ACCELERATION_STRUCTURE_CREATE_INFO_KHR,
                 pNext: ptr::null(),
@@ -276,8 +451,7 @@ impl AccelerationStructure {
             };
             let mut handle = MaybeUninit::<VkAccelerationStructureKHR>::zeroed();
             vkCreateAccelerationStructureKHR(device.handle(), &create_info, ptr::null(), handle.as_mut_ptr())
-                .into_result()
-                .unwrap();
+                .into_result_with("vkCreateAccelerationStructureKHR")?;
             let handle = handle.assume_init();
             let structure = Self {
                 handle,
@@ -285,7 +459,7 @@ impl AccelerationStructure {
                 device: Arc::clone(device),
                 size: structure_size,
             };
-            Arc::new(structure)
+            Ok(Arc::new(structure))
         }
     }
 
@@ -312,6 +486,7 @@ impl Drop for AccelerationStructure {
 // represents the method to build a structure
 pub struct BottomLevelAccelerationStructureBuildQuery {
     geometries: Vec<Arc<BottomLevelAccelerationStructureGeometry>>,
+    options: AccelerationStructureBuildOptions,
 }
 
 impl BottomLevelAccelerationStructureBuildQuery {
@@ -320,11 +495,17 @@ impl BottomLevelAccelerationStructureBuildQuery {
     ) -> Self {
         Self {
             geometries,
+            options: AccelerationStructureBuildOptions::new(),
         }
     }
 
+    pub fn with_options(mut self, options: AccelerationStructureBuildOptions) -> Self {
+        self.options = options;
+        self
+    }
+
     fn build(self, device: &Arc<Device>, index: usize) -> BottomLevelAccelerationStructureBuild {
-        BottomLevelAccelerationStructureBuild::new(device, self.geometries, index)
+        BottomLevelAccelerationStructureBuild::new(device, self.geometries, self.options, index)
     }
 }
 
@@ -332,6 +513,7 @@ impl BottomLevelAccelerationStructureBuildQuery {
 struct BottomLevelAccelerationStructureBuild {
     device: Arc<Device>,
     geometries: Vec<Arc<BottomLevelAccelerationStructureGeometry>>,
+    options: AccelerationStructureBuildOptions,
     index: usize,
     sizes_info: VkAccelerationStructureBuildSizesInfoKHR,
     geometries_vec: Vec<VkAccelerationStructureGeometryKHR>,
@@ -342,6 +524,7 @@ impl BottomLevelAccelerationStructureBuild {
     fn new(
         device: &Arc<Device>,
         geometries: Vec<Arc<BottomLevelAccelerationStructureGeometry>>,
+        options: AccelerationStructureBuildOptions,
         // store an index to identify the associated query within query pool
         index: usize,
     ) -> Self {
@@ -354,10 +537,11 @@ impl BottomLevelAccelerationStructureBuild {
             let max_primitive_count_vec: Vec<u32> = geometries.iter()
                 .map(|v| v.max_primitive_count())
                 .collect();
-            let sizes_info = Self::make_sizes_info(device, &geometries_vec, &max_primitive_count_vec);
+            let sizes_info = Self::make_sizes_info(device, &geometries_vec, &max_primitive_count_vec, options.flags());
             Self {
                 device: Arc::clone(device),
                 geometries,
+                options,
                 index,
                 sizes_info,
                 geometries_vec,
@@ -374,7 +558,8 @@ impl BottomLevelAccelerationStructureBuild {
         recording: &CommandBufferRecording,
         scratch_buffer_memory: &Arc<DedicatedBufferMemory>,
         query_pool: &Arc<AccelerationStructureCompactionQueryPool>,
-    ) -> BottomLevelAccelerationStructureBuildProcess {
+        timestamp_pool: &Arc<TimestampQueryPool>,
+    ) -> Result<BottomLevelAccelerationStructureBuildProcess> {
         use VkAccelerationStructureTypeKHR::*;
         use VkAccessFlagBits::*;
         use VkPipelineStageFlagBits::*;
@@ -386,15 +571,19 @@ impl BottomLevelAccelerationStructureBuild {
             device, 
             sizes_info.accelerationStructureSize, 
             VK_ACCELERATION_STRUCTURE_TYPE_BOTTOM_LEVEL_KHR,
-        );
+        )?;
         let build_info = Self::make_build_info(
             &geometries_vec, 
             &structure, 
             scratch_buffer_memory,
+            self.options.flags(),
+            VkBuildAccelerationStructureModeKHR::VK_BUILD_ACCELERATION_STRUCTURE_MODE_BUILD_KHR,
         );
         let range_info_vec: Vec<VkAccelerationStructureBuildRangeInfoKHR> = geometries.iter()
             .map(|v| v.range_info())
             .collect();
+        let index = self.index;
+        timestamp_pool.command_write(recording.command_buffer(), index * 2);
         dispatch_vkCmdBuildAccelerationStructuresKHR(
             device.handle(), 
             recording.command_buffer(),
@@ -418,41 +607,51 @@ impl BottomLevelAccelerationStructureBuild {
             0, ptr::null(),
             0, ptr::null(),
         );
+        timestamp_pool.command_write(recording.command_buffer(), index * 2 + 1);
         // query compacted size
-        let index = self.index;
-        dispatch_vkCmdWriteAccelerationStructuresPropertiesKHR(
-            device.handle(),
-            recording.command_buffer(),
-            1,
-            &structure.handle(),
-            VkQueryType::VK_QUERY_TYPE_ACCELERATION_STRUCTURE_COMPACTED_SIZE_KHR,
-            query_pool.handle(),
-            index as u32,
-        );
-        BottomLevelAccelerationStructureBuildProcess::new(
+        if self.options.allows_compaction() {
+            dispatch_vkCmdWriteAccelerationStructuresPropertiesKHR(
+                device.handle(),
+                recording.command_buffer(),
+                1,
+                &structure.handle(),
+                VkQueryType::VK_QUERY_TYPE_ACCELERATION_STRUCTURE_COMPACTED_SIZE_KHR,
+                query_pool.handle(),
+                index as u32,
+            );
+        }
+        let process = BottomLevelAccelerationStructureBuildProcess::new(
             geometries, 
             structure, 
             scratch_buffer_memory,
+            self.options,
+            sizes_info,
             index,
-        )
+        );
+        Ok(process)
     }
 
     fn make_build_info(
         geometries_vec: &Vec<VkAccelerationStructureGeometryKHR>,
         structure: &Arc<AccelerationStructure>,
         scratch_buffer_memory: &Arc<DedicatedBufferMemory>,
+        flags: VkBuildAccelerationStructureFlagsKHR,
+        mode: VkBuildAccelerationStructureModeKHR,
     ) -> VkAccelerationStructureBuildGeometryInfoKHR {
         use VkAccelerationStructureTypeKHR::*;
-        use VkBuildAccelerationStructureFlagBitsKHR::*;
         use VkBuildAccelerationStructureModeKHR::*;
+        // updates refit the structure in place
+        let source = match mode {
+            VK_BUILD_ACCELERATION_STRUCTURE_MODE_UPDATE_KHR => structure.handle(),
+            _ => ptr::null_mut(),
+        };
         VkAccelerationStructureBuildGeometryInfoKHR {
             sType: VK_STRUCTURE_TYPE_ACCELERATION_STRUCTURE_BUILD_GEOMETRY_INFO_KHR,
             pNext: ptr::null(),
             r#type: VK_ACCELERATION_STRUCTURE_TYPE_BOTTOM_LEVEL_KHR,
-            flags: VK_BUILD_ACCELERATION_STRUCTURE_PREFER_FAST_TRACE_BIT_KHR as VkBuildAccelerationStructureFlagsKHR
-                | VK_BUILD_ACCELERATION_STRUCTURE_ALLOW_COMPACTION_BIT_KHR as VkBuildAccelerationStructureFlagsKHR,
-            mode: VK_BUILD_ACCELERATION_STRUCTURE_MODE_BUILD_KHR,
-            srcAccelerationStructure: ptr::null_mut(),
+            flags,
+            mode,
+            srcAccelerationStructure: source,
             dstAccelerationStructure: structure.handle(),
             geometryCount: geometries_vec.len() as u32,
             pGeometries: geometries_vec.as_ptr(),
@@ -465,16 +664,15 @@ impl BottomLevelAccelerationStructureBuild {
 
     fn make_scratch_build_info(
         geometries_vec: &Vec<VkAccelerationStructureGeometryKHR>,
+        flags: VkBuildAccelerationStructureFlagsKHR,
     ) -> VkAccelerationStructureBuildGeometryInfoKHR {
         use VkAccelerationStructureTypeKHR::*;
-        use VkBuildAccelerationStructureFlagBitsKHR::*;
         use VkBuildAccelerationStructureModeKHR::*;
         VkAccelerationStructureBuildGeometryInfoKHR {
             sType: VK_STRUCTURE_TYPE_ACCELERATION_STRUCTURE_BUILD_GEOMETRY_INFO_KHR,
             pNext: ptr::null(),
             r#type: VK_ACCELERATION_STRUCTURE_TYPE_BOTTOM_LEVEL_KHR,
-            flags: VK_BUILD_ACCELERATION_STRUCTURE_PREFER_FAST_TRACE_BIT_KHR as VkBuildAccelerationStructureFlagsKHR
-                | VK_BUILD_ACCELERATION_STRUCTURE_ALLOW_COMPACTION_BIT_KHR as VkBuildAccelerationStructureFlagsKHR,
+            flags,
             mode: VK_BUILD_ACCELERATION_STRUCTURE_MODE_BUILD_KHR,
             srcAccelerationStructure: ptr::null_mut(),
             dstAccelerationStructure: ptr::null_mut(),
@@ -491,9 +689,10 @@ impl BottomLevelAccelerationStructureBuild {
         device: &Arc<Device>,
         geometries_vec: &Vec<VkAccelerationStructureGeometryKHR>, 
         max_primitive_count_vec: &Vec<u32>,
+        flags: VkBuildAccelerationStructureFlagsKHR,
     ) -> VkAccelerationStructureBuildSizesInfoKHR {
         use VkAccelerationStructureBuildTypeKHR::*;
-        let build_info = Self::make_scratch_build_info(geometries_vec);
+        let build_info = Self::make_scratch_build_info(geometries_vec, flags);
         let mut sizes_info = MaybeUninit::<VkAccelerationStructureBuildSizesInfoKHR>::zeroed();
         {
             let sizes_info = &mut *sizes_info.as_mut_ptr();
@@ -518,7 +717,7 @@ struct AccelerationStructureCompactionQueryPool {
 }
 
 impl AccelerationStructureCompactionQueryPool {
-    fn new(device: &Arc<Device>, query_count: usize) -> Arc<Self> {
+    fn new(device: &Arc<Device>, query_count: usize) -> Result<Arc<Self>> {
         unsafe {
             let create_info = VkQueryPoolCreateInfo {
                 sType: VK_STRUCTURE_TYPE_QUERY_POOL_CREATE_INFO,
@@ -530,36 +729,36 @@ impl AccelerationStructureCompactionQueryPool {
             };
             let mut handle = MaybeUninit::<VkQueryPool>::zeroed();
             vkCreateQueryPool(device.handle(), &create_info, ptr::null(), handle.as_mut_ptr())
-                .into_result()
-                .unwrap();
+                .into_result_with("vkCreateQueryPool")?;
             let handle = handle.assume_init();
             let query_pool = Self {
                 handle,
                 device: Arc::clone(device),
                 query_count,
             };
-            Arc::new(query_pool)
+            Ok(Arc::new(query_pool))
         }
     }
 
-    fn compact_sizes(&self) -> Result<Vec<VkDeviceSize>> {
+    // only the queries written for compacting builds are available
+    fn compact_size(&self, index: usize) -> Result<VkDeviceSize> {
         unsafe {
             use VkQueryResultFlagBits::*; 
-            let mut vec: Vec<VkDeviceSize> = Vec::with_capacity(self.query_count);
-            vec.resize(self.query_count, Default::default());
+            let mut size: VkDeviceSize = 0;
             let stride = std::mem::size_of::<VkDeviceSize>();
             vkGetQueryPoolResults(
                 self.device.handle(), 
                 self.handle, 
-                0, 
-                self.query_count as u32, 
-                self.query_count * stride,
-                vec.as_mut_ptr() as *mut c_void,
+                index as u32, 
+                1, 
+                stride,
+                &mut size as *mut _ as *mut c_void,
                 stride as VkDeviceSize,
-                VK_QUERY_RESULT_WAIT_BIT as VkQueryResultFlags,
+                VK_QUERY_RESULT_WAIT_BIT as VkQueryResultFlags
+                    | VK_QUERY_RESULT_64_BIT as VkQueryResultFlags,
             )
-                .into_result()?;
-            Ok(vec)
+                .into_result_with("vkGetQueryPoolResults")?;
+            Ok(size)
         }
     }
 
@@ -582,11 +781,96 @@ impl Drop for AccelerationStructureCompactionQueryPool {
     }
 }
 
+// measures builds on the device with a pair of timestamps around each one
+struct TimestampQueryPool {
+    handle: VkQueryPool,
+    device: Arc<Device>,
+    query_count: usize,
+}
+
+impl TimestampQueryPool {
+    fn new(device: &Arc<Device>, query_count: usize) -> Result<Arc<Self>> {
+        unsafe {
+            let create_info = VkQueryPoolCreateInfo {
+                sType: VK_STRUCTURE_TYPE_QUERY_POOL_CREATE_INFO,
+                pNext: ptr::null(),
+                flags: 0,
+                queryType: VkQueryType::VK_QUERY_TYPE_TIMESTAMP,
+                queryCount: query_count as u32,
+                pipelineStatistics: 0,
+            };
+            let mut handle = MaybeUninit::<VkQueryPool>::zeroed();
+            vkCreateQueryPool(device.handle(), &create_info, ptr::null(), handle.as_mut_ptr())
+                .into_result_with("vkCreateQueryPool")?;
+            let handle = handle.assume_init();
+            let query_pool = Self {
+                handle,
+                device: Arc::clone(device),
+                query_count,
+            };
+            Ok(Arc::new(query_pool))
+        }
+    }
+
+    // the time between the queries `first` and `first + 1`
+    fn duration(&self, first: usize) -> Result<Duration> {
+        unsafe {
+            use VkQueryResultFlagBits::*; 
+            let mut timestamps = [0u64; 2];
+            let stride = std::mem::size_of::<u64>();
+            vkGetQueryPoolResults(
+                self.device.handle(), 
+                self.handle, 
+                first as u32, 
+                2, 
+                stride * 2,
+                timestamps.as_mut_ptr() as *mut c_void,
+                stride as VkDeviceSize,
+                VK_QUERY_RESULT_WAIT_BIT as VkQueryResultFlags
+                    | VK_QUERY_RESULT_64_BIT as VkQueryResultFlags,
+            )
+                .into_result_with("vkGetQueryPoolResults")?;
+            // nanoseconds per tick
+            let period = self.device.physical_device().properties().limits.timestampPeriod as f64;
+            let ticks = timestamps[1].saturating_sub(timestamps[0]);
+            Ok(Duration::from_nanos((ticks as f64 * period) as u64))
+        }
+    }
+
+    fn command_reset(&self, command_buffer_handle: VkCommandBuffer) {
+        unsafe {
+            vkCmdResetQueryPool(command_buffer_handle, self.handle, 0, self.query_count as u32);
+        }
+    }
+
+    // written once the preceding builds complete
+    fn command_write(&self, command_buffer_handle: VkCommandBuffer, query: usize) {
+        unsafe {
+            vkCmdWriteTimestamp(
+                command_buffer_handle, 
+                VkPipelineStageFlagBits::VK_PIPELINE_STAGE_ACCELERATION_STRUCTURE_BUILD_BIT_KHR, 
+                self.handle, 
+                query as u32,
+            );
+        }
+    }
+}
+
+impl Drop for TimestampQueryPool {
+    fn drop(&mut self) {
+        unsafe {
+            vkDestroyQueryPool(self.device.handle(), self.handle, ptr::null());
+        }
+    }
+}
+
 #[allow(dead_code)]
 struct BottomLevelAccelerationStructureBuildProcess {
     geometries: Vec<Arc<BottomLevelAccelerationStructureGeometry>>,
     structure: Arc<AccelerationStructure>,
     scratch: Arc<DedicatedBufferMemory>,
+    options: AccelerationStructureBuildOptions,
+    sizes_info: VkAccelerationStructureBuildSizesInfoKHR,
     index: usize,
 }
 
@@ -595,12 +879,16 @@ impl BottomLevelAccelerationStructureBuildProcess {
         geometries: Vec<Arc<BottomLevelAccelerationStructureGeometry>>,
         structure: Arc<AccelerationStructure>,
         scratch: &Arc<DedicatedBufferMemory>,
+        options: AccelerationStructureBuildOptions,
+        sizes_info: VkAccelerationStructureBuildSizesInfoKHR,
         index: usize,
     ) -> Self {
         let building = Self {
             geometries,
             structure,
             scratch: Arc::clone(scratch),
+            options,
+            sizes_info,
             index,
         };
         building
@@ -608,19 +896,37 @@ impl BottomLevelAccelerationStructureBuildProcess {
 
     fn compact(self, 
         recording: &CommandBufferRecording,
-        compact_sizes: &Vec<VkDeviceSize>) -> BottomLevelAccelerationStructureCompactionProcess {
+        query_pool: &AccelerationStructureCompactionQueryPool,
+        timestamp_pool: &TimestampQueryPool) -> Result<BottomLevelAccelerationStructureCompactionProcess> {
         use VkAccelerationStructureTypeKHR::*;
         use VkCopyAccelerationStructureModeKHR::*;
-        let compact_size = compact_sizes.get(self.index)
-            .unwrap()
-            .clone();
+        let stats = AccelerationStructureStats {
+            build_time: timestamp_pool.duration(self.index * 2)?,
+            size: self.structure.size(),
+            compacted_size: self.structure.size(),
+            scratch_size: self.sizes_info.buildScratchSize,
+        };
+        if !self.options.allows_compaction() {
+            let process = BottomLevelAccelerationStructureCompactionProcess::new(
+                self.geometries, 
+                Arc::clone(&self.structure), 
+                self.structure, 
+                self.options,
+                stats,
+                self.sizes_info.updateScratchSize,
+                self.index,
+            );
+            return Ok(process)
+        }
+        // the query pool has a query for each build
+        let compact_size = query_pool.compact_size(self.index)?;
         let device = recording.command_pool().queue().device();
         let original_structure = self.structure;
         let compacted_structure = AccelerationStructure::new(
             device,
             compact_size,
             VK_ACCELERATION_STRUCTURE_TYPE_BOTTOM_LEVEL_KHR,
-        );
+        )?;
         let copy_info = VkCopyAccelerationStructureInfoKHR {
             sType: VK_STRUCTURE_TYPE_COPY_ACCELERATION_STRUCTURE_INFO_KHR,
             pNext: ptr::null(),
@@ -633,12 +939,20 @@ impl BottomLevelAccelerationStructureBuildProcess {
             recording.command_buffer(),
             &copy_info,
         );
-        BottomLevelAccelerationStructureCompactionProcess::new(
+        let stats = AccelerationStructureStats {
+            compacted_size: compacted_structure.size(),
+            ..stats
+        };
+        let process = BottomLevelAccelerationStructureCompactionProcess::new(
             self.geometries, 
             original_structure, 
             compacted_structure, 
+            self.options,
+            stats,
+            self.sizes_info.updateScratchSize,
             self.index,
-        )
+        );
+        Ok(process)
     }
 }
 
@@ -647,6 +961,9 @@ struct BottomLevelAccelerationStructureCompactionProcess {
     geometries: Vec<Arc<BottomLevelAccelerationStructureGeometry>>,
     original_structure: Arc<AccelerationStructure>,
     compacted_structure: Arc<AccelerationStructure>,
+    options: AccelerationStructureBuildOptions,
+    stats: AccelerationStructureStats,
+    update_scratch_size: VkDeviceSize,
     index: usize,
 }
 
@@ -655,21 +972,34 @@ impl BottomLevelAccelerationStructureCompactionProcess {
         geometries: Vec<Arc<BottomLevelAccelerationStructureGeometry>>,
         original_structure: Arc<AccelerationStructure>,
         compacted_structure: Arc<AccelerationStructure>,
+        options: AccelerationStructureBuildOptions,
+        stats: AccelerationStructureStats,
+        update_scratch_size: VkDeviceSize,
         index: usize,
     ) -> Self {
         let compaction = Self {
             geometries,
             original_structure,
             compacted_structure,
+            options,
+            stats,
+            update_scratch_size,
             index,
         };
         compaction
     }
 
     fn finalize(self) -> Arc<BottomLevelAccelerationStructure> {
-        // log_debug!("BLAS Compaction #{}: {} -> {}", self.index, self.original_structure.size(), self.compacted_structure.size());
+        let stats = self.stats;
+        log_debug!("BLAS #{}: built in {:?}, {} -> {} bytes", self.index, stats.build_time, stats.size, stats.compacted_size);
         // discards original structure
-        BottomLevelAccelerationStructure::new(self.geometries, self.compacted_structure)
+        BottomLevelAccelerationStructure::new(
+            self.geometries, 
+            self.compacted_structure, 
+            self.options, 
+            stats, 
+            self.update_scratch_size,
+        )
     }
 }
 
@@ -679,12 +1009,18 @@ pub struct BottomLevelAccelerationStructure {
     geometries: Vec<Arc<BottomLevelAccelerationStructureGeometry>>,
     structure: Arc<AccelerationStructure>,
     device_address: VkDeviceAddress,
+    options: AccelerationStructureBuildOptions,
+    update_scratch_size: VkDeviceSize,
+    stats: Mutex<AccelerationStructureStats>,
 }
 
 impl BottomLevelAccelerationStructure {
     fn new(
         geometries: Vec<Arc<BottomLevelAccelerationStructureGeometry>>,
         structure: Arc<AccelerationStructure>,
+        options: AccelerationStructureBuildOptions,
+        stats: AccelerationStructureStats,
+        update_scratch_size: VkDeviceSize,
     ) -> Arc<Self> {
         let device = structure.device();
         // device address is unknown until its build process completes
@@ -700,10 +1036,76 @@ impl BottomLevelAccelerationStructure {
             geometries,
             structure,
             device_address,
+            options,
+            update_scratch_size,
+            stats: Mutex::new(stats),
         };
         Arc::new(structure)
     }
 
+    // refits the structure to the current contents of its vertex buffers.
+    // the topology must be unchanged and no command buffer tracing against it may be in flight.
+    pub fn update(&self, command_pool: &Arc<CommandPool>) -> Result<()> {
+        use VkBufferUsageFlagBits::*;
+        use VkMemoryPropertyFlagBits::*;
+        use VkBuildAccelerationStructureModeKHR::*;
+        if !self.options.allows_update() {
+            return Err(ErrorCode::AccelerationStructureUpdateNotAllowed.into())
+        }
+        let device = command_pool.queue().device();
+        let scratch_buffer_memory = DedicatedBufferMemory::new(
+            device, 
+            VK_BUFFER_USAGE_STORAGE_BUFFER_BIT as VkBufferUsageFlags
+                | VK_BUFFER_USAGE_SHADER_DEVICE_ADDRESS_BIT as VkBufferUsageFlags, 
+            VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT as VkMemoryPropertyFlags,
+            self.update_scratch_size.max(1),
+        )?;
+        let timestamp_pool = TimestampQueryPool::new(device, 2)?;
+        let geometries_vec: Vec<VkAccelerationStructureGeometryKHR> = self.geometries.iter()
+            .map(|v| v.geometry())
+            .collect();
+        let build_info = BottomLevelAccelerationStructureBuild::make_build_info(
+            &geometries_vec, 
+            &self.structure, 
+            &scratch_buffer_memory,
+            self.options.flags(),
+            VK_BUILD_ACCELERATION_STRUCTURE_MODE_UPDATE_KHR,
+        );
+        let range_info_vec: Vec<VkAccelerationStructureBuildRangeInfoKHR> = self.geometries.iter()
+            .map(|v| v.range_info())
+            .collect();
+        let recording = CommandBufferRecording::new_onetime_submit(command_pool)?;
+        timestamp_pool.command_reset(recording.command_buffer());
+        timestamp_pool.command_write(recording.command_buffer(), 0);
+        dispatch_vkCmdBuildAccelerationStructuresKHR(
+            device.handle(), 
+            recording.command_buffer(),
+            1,
+            &build_info,
+            &range_info_vec.as_ptr(),
+        );
+        timestamp_pool.command_write(recording.command_buffer(), 1);
+        let command_buffer = recording.complete();
+        unsafe {
+            command_pool.queue()
+                .submit_then_wait(&[command_buffer.handle()])?;
+        }
+        let mut stats = self.stats.lock().unwrap();
+        stats.build_time = timestamp_pool.duration(0)?;
+        stats.scratch_size = self.update_scratch_size;
+        Ok(())
+    }
+
+    // reflects the latest build or update
+    pub fn stats(&self) -> AccelerationStructureStats {
+        *self.stats.lock().unwrap()
+    }
+
+    #[inline]
+    pub fn options(&self) -> AccelerationStructureBuildOptions {
+        self.options
+    }
+
     #[inline]
     pub fn handle(&self) -> VkAccelerationStructureKHR {
         self.structure.handle()
@@ -731,7 +1133,7 @@ impl<'a> BottomLevelAccelerationStructuresBuilder<'a> {
         }
     }
 
-    pub fn build(self) -> Vec<Arc<BottomLevelAccelerationStructure>> {
+    pub fn build(self) -> Result<Vec<Arc<BottomLevelAccelerationStructure>>> {
         use VkBufferUsageFlagBits::*;
         use VkMemoryPropertyFlagBits::*;
         let command_pool = self.command_pool;
@@ -743,13 +1145,15 @@ impl<'a> BottomLevelAccelerationStructuresBuilder<'a> {
                 .map(|(i, v)| v.build(device, i))
                 .collect();
             // creates query pool for compaction
-            let query_pool = AccelerationStructureCompactionQueryPool::new(device, builds.len());
+            let query_pool = AccelerationStructureCompactionQueryPool::new(device, builds.len().max(1))?;
+            // and a pair of timestamps for each build
+            let timestamp_pool = TimestampQueryPool::new(device, builds.len().max(1) * 2)?;
             let build_processes: Vec<_> = {
                 // estimate required scratch size
                 let build_scratch_size = builds.iter()
                     .map(|v| v.scratch_size())
                     .max()
-                    .unwrap();
+                    .unwrap_or(0);
                 // creates shared scratch memory
                 let scratch_buffer_memory = DedicatedBufferMemory::new(
                     device, 
@@ -757,43 +1161,37 @@ impl<'a> BottomLevelAccelerationStructuresBuilder<'a> {
                         | VK_BUFFER_USAGE_SHADER_DEVICE_ADDRESS_BIT as VkBufferUsageFlags, 
                     VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT as VkMemoryPropertyFlags,
                     build_scratch_size,
-                )
-                    .unwrap();
-                let recording = CommandBufferRecording::new_onetime_submit(command_pool)
-                    .unwrap();
+                )?;
+                let recording = CommandBufferRecording::new_onetime_submit(command_pool)?;
                 // reset queries in the query pool before the query pool used
                 query_pool.command_reset(recording.command_buffer());
+                timestamp_pool.command_reset(recording.command_buffer());
                 let build_processes: Vec<_> = builds.into_iter()
-                    .map(|v| v.begin(&recording, &scratch_buffer_memory, &query_pool))
-                    .collect();
+                    .map(|v| v.begin(&recording, &scratch_buffer_memory, &query_pool, &timestamp_pool))
+                    .collect::<Result<_>>()?;
                 let command_buffer = recording.complete();
                 // wait until all commands complete
                 command_pool.queue()
-                    .submit_then_wait(&[command_buffer.handle()])
-                    .unwrap();
+                    .submit_then_wait(&[command_buffer.handle()])?;
                 // discards scratch buffers once it completes
                 build_processes
             };
             let compaction_processes: Vec<_> = {
-                let recording = CommandBufferRecording::new_onetime_submit(command_pool)
-                    .unwrap();
-                let compact_sizes = query_pool.compact_sizes()
-                    .unwrap();
+                let recording = CommandBufferRecording::new_onetime_submit(command_pool)?;
                 // compaction
                 let compaction_processes: Vec<_> = build_processes.into_iter()
-                    .map(|v| v.compact(&recording, &compact_sizes))
-                    .collect();
+                    .map(|v| v.compact(&recording, &query_pool, &timestamp_pool))
+                    .collect::<Result<_>>()?;
                 let command_buffer = recording.complete();
                 // wait until all commands complete
                 command_pool.queue()
-                    .submit_then_wait(&[command_buffer.handle()])
-                    .unwrap();
+                    .submit_then_wait(&[command_buffer.handle()])?;
                 compaction_processes
             };
             let blas_vec: Vec<_> = compaction_processes.into_iter()
                 .map(|v| v.finalize())
                 .collect();
-            blas_vec
+            Ok(blas_vec)
         }
     }
 }
@@ -804,20 +1202,40 @@ pub struct TopLevelAccelerationStructureInstance {
     instance_custom_index: u32,
     transform: VkTransformMatrixKHR,
     hit_group: u32,
+    mask: u8,
     bottom_level_acceleration_structure: Arc<BottomLevelAccelerationStructure>,
 }
 
 impl TopLevelAccelerationStructureInstance {
+    // the custom index and the hit group are packed into 24 bits
+    pub const MAX_CUSTOM_INDEX: u32 = (1 << 24) - 1;
+    pub const MAX_HIT_GROUP: u32 = (1 << 24) - 1;
+
     pub fn new(
         instance_custom_index: u32, 
         transform: VkTransformMatrixKHR,
         hit_group: u32,
         bottom_level_acceleration_structure: &Arc<BottomLevelAccelerationStructure>,
     ) -> Result<Arc<Self>> {
+        Self::with_mask(instance_custom_index, transform, hit_group, 0xff, bottom_level_acceleration_structure)
+    }
+
+    // rays only hit instances whose mask shares a bit with the ray's, so 0 hides the instance
+    pub fn with_mask(
+        instance_custom_index: u32, 
+        transform: VkTransformMatrixKHR,
+        hit_group: u32,
+        mask: u8,
+        bottom_level_acceleration_structure: &Arc<BottomLevelAccelerationStructure>,
+    ) -> Result<Arc<Self>> {
+        if instance_custom_index > Self::MAX_CUSTOM_INDEX || hit_group > Self::MAX_HIT_GROUP {
+            return Err(ErrorCode::InstanceCustomIndexOutOfRange.into())
+        }
         let instance = Self {
             instance_custom_index,
             transform,
             hit_group,
+            mask,
             bottom_level_acceleration_structure: Arc::clone(bottom_level_acceleration_structure),
         };
         Ok(Arc::new(instance))
@@ -848,10 +1266,10 @@ impl TopLevelAccelerationStructureInstance {
         use VkGeometryInstanceFlagBitsKHR::*;
         VkAccelerationStructureInstanceKHR {
             transform: self.transform.clone(),
-            instanceCustomIndexAndMask: (0xff << 24) | (self.instance_custom_index() & ((1u32 << 25) - 1)),
+            instanceCustomIndexAndMask: ((self.mask as u32) << 24) | (self.instance_custom_index() & Self::MAX_CUSTOM_INDEX),
             instanceShaderBindingTableRecordOffsetAndFlags: 
                 ((VK_GEOMETRY_INSTANCE_TRIANGLE_FACING_CULL_DISABLE_BIT_KHR as VkFlags) << 24)
-                    | (self.hit_group() & 0xff),
+                    | (self.hit_group() & Self::MAX_HIT_GROUP),
             accelerationStructureReference: self.bottom_level_acceleration_structure().device_address(),
         }
     }
@@ -859,111 +1277,93 @@ impl TopLevelAccelerationStructureInstance {
 
 #[allow(dead_code)]
 pub struct TopLevelAccelerationStructure {
-    instances_buffer: Arc<DedicatedStagingBuffer>,
     structure: Arc<AccelerationStructure>,
-    instances: Vec<Arc<TopLevelAccelerationStructureInstance>>,
     device_address: VkDeviceAddress,
+    capacity: usize,
+    options: AccelerationStructureBuildOptions,
+    state: Mutex<TopLevelAccelerationStructureState>,
+}
+
+struct TopLevelAccelerationStructureState {
+    instances_buffer: Arc<DedicatedStagingBuffer>,
+    scratch_buffer_memory: Arc<DedicatedBufferMemory>,
+    instances: Vec<Arc<TopLevelAccelerationStructureInstance>>,
+    stats: AccelerationStructureStats,
 }
 
 impl TopLevelAccelerationStructure {
     pub fn new(
         command_pool: &Arc<CommandPool>, 
         instances: Vec<Arc<TopLevelAccelerationStructureInstance>>,
+    ) -> Result<Arc<Self>> {
+        let capacity = instances.len();
+        Self::with_capacity(command_pool, instances, capacity)
+    }
+
+    // reserves room for `capacity` instances so that `update` can add instances up to it
+    // without replacing the structure
+    pub fn with_capacity(
+        command_pool: &Arc<CommandPool>, 
+        instances: Vec<Arc<TopLevelAccelerationStructureInstance>>,
+        capacity: usize,
+    ) -> Result<Arc<Self>> {
+        let options = AccelerationStructureBuildOptions::new()
+            .with_update(true)
+            .with_compaction(false);
+        Self::with_options(command_pool, instances, capacity, options)
+    }
+
+    // top level structures are never compacted since `update` rebuilds them in place
+    pub fn with_options(
+        command_pool: &Arc<CommandPool>, 
+        instances: Vec<Arc<TopLevelAccelerationStructureInstance>>,
+        capacity: usize,
+        options: AccelerationStructureBuildOptions,
     ) -> Result<Arc<Self>> {
         use VkBufferUsageFlagBits::*;
         use VkMemoryPropertyFlagBits::*;
-        use VkGeometryFlagBitsKHR::*;
         use VkAccelerationStructureTypeKHR::*;
-        use VkBuildAccelerationStructureFlagBitsKHR::*;
         use VkBuildAccelerationStructureModeKHR::*;
         unsafe {
-            // sends instance structs to the GPU
-            let instance_structs: Vec<VkAccelerationStructureInstanceKHR> = instances.iter()
-                .map(|v| v.instance_struct())
-                .collect();
-            let instances_size = instance_structs.len() * std::mem::size_of::<VkAccelerationStructureInstanceKHR>();
+            let capacity = capacity.max(instances.len()).max(1);
+            let instances_size = capacity * std::mem::size_of::<VkAccelerationStructureInstanceKHR>();
             let instances_buffer = DedicatedStagingBuffer::new(
                 command_pool, 
                 VK_BUFFER_USAGE_SHADER_DEVICE_ADDRESS_BIT as VkBufferUsageFlags
                     | VK_BUFFER_USAGE_ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_BIT_KHR as VkBufferUsageFlags, 
                 VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT as VkMemoryPropertyFlags,
                 instances_size as VkDeviceSize,
-            )
-                .unwrap();
-            // TODO: optimize (write blocks)
-            instances_buffer.write(instance_structs.as_ptr() as *const c_void, instances_size);
-            // build info construction
-            let geometry = VkAccelerationStructureGeometryKHR {
-                sType: VK_STRUCTURE_TYPE_ACCELERATION_STRUCTURE_GEOMETRY_KHR,
-                pNext: ptr::null(),
-                geometryType: VkGeometryTypeKHR::VK_GEOMETRY_TYPE_INSTANCES_KHR,
-                geometry: VkAccelerationStructureGeometryDataKHR {
-                    instances: VkAccelerationStructureGeometryInstancesDataKHR {
-                        sType: VK_STRUCTURE_TYPE_ACCELERATION_STRUCTURE_GEOMETRY_INSTANCES_DATA_KHR,
-                        pNext: ptr::null(),
-                        arrayOfPointers: VK_FALSE,
-                        data: VkDeviceOrHostAddressConstKHR {
-                            deviceAddress: instances_buffer.device_buffer_memory().buffer_device_address(),
-                        },
-                    },
-                },
-                flags: VK_GEOMETRY_NO_DUPLICATE_ANY_HIT_INVOCATION_BIT_KHR as VkGeometryFlagsKHR,
-            };
+            )?;
             let device = command_pool.queue().device();
-            let geometries_vec = vec![geometry];
-            let max_primitive_count_vec = vec![instance_structs.len() as u32];
-            let sizes_info = Self::make_sizes_info(device, &geometries_vec, &max_primitive_count_vec);
+            let geometries_vec = vec![Self::geometry(&instances_buffer)];
+            let max_primitive_count_vec = vec![capacity as u32];
+            let options = options.with_compaction(false);
+            let sizes_info = Self::make_sizes_info(device, &geometries_vec, &max_primitive_count_vec, options.flags());
             let structure = AccelerationStructure::new(
                 device, 
                 sizes_info.accelerationStructureSize, 
                 VK_ACCELERATION_STRUCTURE_TYPE_TOP_LEVEL_KHR,
-            );
+            )?;
             let scratch_buffer_memory = DedicatedBufferMemory::new(
                 device, 
                 VK_BUFFER_USAGE_STORAGE_BUFFER_BIT as VkBufferUsageFlags
                     | VK_BUFFER_USAGE_SHADER_DEVICE_ADDRESS_BIT as VkBufferUsageFlags, 
                 VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT as VkMemoryPropertyFlags,
-                sizes_info.buildScratchSize,
-            )
-                .unwrap();
-            let build_info = VkAccelerationStructureBuildGeometryInfoKHR {
-                sType: VK_STRUCTURE_TYPE_ACCELERATION_STRUCTURE_BUILD_GEOMETRY_INFO_KHR,
-                pNext: ptr::null(),
-                r#type: VK_ACCELERATION_STRUCTURE_TYPE_TOP_LEVEL_KHR,
-                flags: VK_BUILD_ACCELERATION_STRUCTURE_PREFER_FAST_TRACE_BIT_KHR as VkBuildAccelerationStructureFlagsKHR,
-                mode: VK_BUILD_ACCELERATION_STRUCTURE_MODE_BUILD_KHR,
-                srcAccelerationStructure: ptr::null_mut(),
-                dstAccelerationStructure: structure.handle(),
-                geometryCount: geometries_vec.len() as u32,
-                pGeometries: geometries_vec.as_ptr(),
-                ppGeometries: ptr::null(),
-                scratchData: VkDeviceOrHostAddressKHR {
-                    deviceAddress: scratch_buffer_memory.buffer_device_address(),
+                sizes_info.buildScratchSize.max(sizes_info.updateScratchSize),
+            )?;
+            let mut state = TopLevelAccelerationStructureState {
+                instances_buffer,
+                scratch_buffer_memory,
+                instances,
+                stats: AccelerationStructureStats {
+                    size: sizes_info.accelerationStructureSize,
+                    compacted_size: sizes_info.accelerationStructureSize,
+                    scratch_size: sizes_info.buildScratchSize,
+                    ..Default::default()
                 },
             };
-            let range_info = VkAccelerationStructureBuildRangeInfoKHR {
-                primitiveCount: instance_structs.len() as u32,
-                primitiveOffset: 0,
-                firstVertex: 0,
-                transformOffset: 0,
-            };
-            let range_info_vec = vec![range_info];
-            let range_info_vec_vec: Vec<*const VkAccelerationStructureBuildRangeInfoKHR> = vec![range_info_vec.as_ptr()];
-            // command dispatch
-            let recording = CommandBufferRecording::new_onetime_submit(command_pool)
-                .unwrap();
-            dispatch_vkCmdBuildAccelerationStructuresKHR(
-                device.handle(), 
-                recording.command_buffer(),
-                1,
-                &build_info,
-                range_info_vec_vec.as_ptr(),
-            );
-            let command_buffer = recording.complete();
-            let command_buffer_vec = vec![command_buffer.handle()];
-            command_pool.queue()
-                .submit_then_wait(&command_buffer_vec)
-                .unwrap();
+            state.command_build(command_pool, &structure, options.flags(), VK_BUILD_ACCELERATION_STRUCTURE_MODE_BUILD_KHR)?;
+            log_debug!("TLAS: built in {:?}, {} bytes", state.stats.build_time, state.stats.size);
             // device address is unknown until its build process completes
             let device_address = {
                 let info = VkAccelerationStructureDeviceAddressInfoKHR {
@@ -974,15 +1374,87 @@ impl TopLevelAccelerationStructure {
                 vkGetAccelerationStructureDeviceAddressKHR(device.handle(), &info)
             };
             let structure = Self {
-                instances_buffer,
-                instances,
                 structure,
                 device_address,
+                capacity,
+                options,
+                state: Mutex::new(state),
             };
             Ok(Arc::new(structure))
         }
     }
 
+    // replaces the instances in place so that the descriptors pointing at the structure stay valid.
+    // the structure is refitted when the instance count is unchanged and updates are allowed,
+    // otherwise rebuilt. no command buffer tracing against it may be in flight.
+    pub fn update(
+        &self,
+        command_pool: &Arc<CommandPool>,
+        instances: Vec<Arc<TopLevelAccelerationStructureInstance>>,
+    ) -> Result<()> {
+        use VkBuildAccelerationStructureModeKHR::*;
+        if instances.len() > self.capacity {
+            return Err(ErrorCode::AccelerationStructureCapacityExceeded.into())
+        }
+        let mut state = self.state.lock().unwrap();
+        let mode = if self.options.allows_update() && state.instances.len() == instances.len() {
+            VK_BUILD_ACCELERATION_STRUCTURE_MODE_UPDATE_KHR
+        } else {
+            VK_BUILD_ACCELERATION_STRUCTURE_MODE_BUILD_KHR
+        };
+        state.instances = instances;
+        unsafe {
+            state.command_build(command_pool, &self.structure, self.options.flags(), mode)
+        }
+    }
+
+    // refits the structure after the bottom level structures it references were updated
+    pub fn refit(&self, command_pool: &Arc<CommandPool>) -> Result<()> {
+        use VkBuildAccelerationStructureModeKHR::*;
+        if !self.options.allows_update() {
+            return Err(ErrorCode::AccelerationStructureUpdateNotAllowed.into())
+        }
+        let mut state = self.state.lock().unwrap();
+        unsafe {
+            state.command_build(command_pool, &self.structure, self.options.flags(), VK_BUILD_ACCELERATION_STRUCTURE_MODE_UPDATE_KHR)
+        }
+    }
+
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    #[inline]
+    pub fn options(&self) -> AccelerationStructureBuildOptions {
+        self.options
+    }
+
+    // reflects the latest build, update or refit
+    pub fn stats(&self) -> AccelerationStructureStats {
+        self.state.lock().unwrap().stats
+    }
+
+    unsafe fn geometry(instances_buffer: &DedicatedStagingBuffer) -> VkAccelerationStructureGeometryKHR {
+        use VkGeometryFlagBitsKHR::*;
+        VkAccelerationStructureGeometryKHR {
+            sType: VK_STRUCTURE_TYPE_ACCELERATION_STRUCTURE_GEOMETRY_KHR,
+            pNext: ptr::null(),
+            geometryType: VkGeometryTypeKHR::VK_GEOMETRY_TYPE_INSTANCES_KHR,
+            geometry: VkAccelerationStructureGeometryDataKHR {
+                instances: VkAccelerationStructureGeometryInstancesDataKHR {
+                    sType: VK_STRUCTURE_TYPE_ACCELERATION_STRUCTURE_GEOMETRY_INSTANCES_DATA_KHR,
+                    pNext: ptr::null(),
+                    arrayOfPointers: VK_FALSE,
+                    data: VkDeviceOrHostAddressConstKHR {
+                        deviceAddress: instances_buffer.device_buffer_memory().buffer_device_address(),
+                    },
+                },
+            },
+            flags: VK_GEOMETRY_NO_DUPLICATE_ANY_HIT_INVOCATION_BIT_KHR as VkGeometryFlagsKHR,
+        }
+    }
+
     #[inline]
     pub fn handle(&self) -> VkAccelerationStructureKHR {
         self.structure.handle()
@@ -992,16 +1464,16 @@ impl TopLevelAccelerationStructure {
         device: &Arc<Device>,
         geometries_vec: &Vec<VkAccelerationStructureGeometryKHR>, 
         max_primitive_count_vec: &Vec<u32>,
+        flags: VkBuildAccelerationStructureFlagsKHR,
     ) -> VkAccelerationStructureBuildSizesInfoKHR {
         use VkAccelerationStructureTypeKHR::*;
-        use VkBuildAccelerationStructureFlagBitsKHR::*;
         use VkBuildAccelerationStructureModeKHR::*;
         use VkAccelerationStructureBuildTypeKHR::*;
         let build_info = VkAccelerationStructureBuildGeometryInfoKHR {
             sType: VK_STRUCTURE_TYPE_ACCELERATION_STRUCTURE_BUILD_GEOMETRY_INFO_KHR,
             pNext: ptr::null(),
             r#type: VK_ACCELERATION_STRUCTURE_TYPE_TOP_LEVEL_KHR,
-            flags: VK_BUILD_ACCELERATION_STRUCTURE_PREFER_FAST_TRACE_BIT_KHR as VkBuildAccelerationStructureFlagsKHR,
+            flags,
             mode: VK_BUILD_ACCELERATION_STRUCTURE_MODE_BUILD_KHR,
             srcAccelerationStructure: ptr::null_mut(),
             dstAccelerationStructure: ptr::null_mut(),
@@ -1029,13 +1501,82 @@ impl TopLevelAccelerationStructure {
     }
 }
 
+impl TopLevelAccelerationStructureState {
+    unsafe fn command_build(
+        &mut self,
+        command_pool: &Arc<CommandPool>,
+        structure: &AccelerationStructure,
+        flags: VkBuildAccelerationStructureFlagsKHR,
+        mode: VkBuildAccelerationStructureModeKHR,
+    ) -> Result<()> {
+        use VkAccelerationStructureTypeKHR::*;
+        use VkBuildAccelerationStructureModeKHR::*;
+        // sends instance structs to the GPU
+        let instance_structs: Vec<VkAccelerationStructureInstanceKHR> = self.instances.iter()
+            .map(|v| v.instance_struct())
+            .collect();
+        if !instance_structs.is_empty() {
+            let instances_size = instance_structs.len() * std::mem::size_of::<VkAccelerationStructureInstanceKHR>();
+            self.instances_buffer.write(instance_structs.as_ptr() as *const c_void, instances_size);
+        }
+        let device = command_pool.queue().device();
+        let geometries = [TopLevelAccelerationStructure::geometry(&self.instances_buffer)];
+        let source = match mode {
+            VK_BUILD_ACCELERATION_STRUCTURE_MODE_UPDATE_KHR => structure.handle(),
+            _ => ptr::null_mut(),
+        };
+        let build_info = VkAccelerationStructureBuildGeometryInfoKHR {
+            sType: VK_STRUCTURE_TYPE_ACCELERATION_STRUCTURE_BUILD_GEOMETRY_INFO_KHR,
+            pNext: ptr::null(),
+            r#type: VK_ACCELERATION_STRUCTURE_TYPE_TOP_LEVEL_KHR,
+            flags,
+            mode,
+            srcAccelerationStructure: source,
+            dstAccelerationStructure: structure.handle(),
+            geometryCount: geometries.len() as u32,
+            pGeometries: geometries.as_ptr(),
+            ppGeometries: ptr::null(),
+            scratchData: VkDeviceOrHostAddressKHR {
+                deviceAddress: self.scratch_buffer_memory.buffer_device_address(),
+            },
+        };
+        let range_info = VkAccelerationStructureBuildRangeInfoKHR {
+            primitiveCount: instance_structs.len() as u32,
+            primitiveOffset: 0,
+            firstVertex: 0,
+            transformOffset: 0,
+        };
+        let range_info_vec = vec![range_info];
+        let range_info_vec_vec: Vec<*const VkAccelerationStructureBuildRangeInfoKHR> = vec![range_info_vec.as_ptr()];
+        // command dispatch
+        let timestamp_pool = TimestampQueryPool::new(device, 2)?;
+        let recording = CommandBufferRecording::new_onetime_submit(command_pool)?;
+        timestamp_pool.command_reset(recording.command_buffer());
+        timestamp_pool.command_write(recording.command_buffer(), 0);
+        dispatch_vkCmdBuildAccelerationStructuresKHR(
+            device.handle(), 
+            recording.command_buffer(),
+            1,
+            &build_info,
+            range_info_vec_vec.as_ptr(),
+        );
+        timestamp_pool.command_write(recording.command_buffer(), 1);
+        let command_buffer = recording.complete();
+        let command_buffer_vec = vec![command_buffer.handle()];
+        command_pool.queue()
+            .submit_then_wait(&command_buffer_vec)?;
+        self.stats.build_time = timestamp_pool.duration(0)?;
+        Ok(())
+    }
+}
+
 struct DescriptorSetLayout {
     device: Arc<Device>,
     handle: VkDescriptorSetLayout,
 }
 
 impl DescriptorSetLayout {
-    unsafe fn new_primary(device: &Arc<Device>, textures_count: usize) -> Arc<Self> {
+    unsafe fn new_primary(device: &Arc<Device>, textures_count: usize) -> Result<Arc<Self>> {
         let bindings = vec![
             VkDescriptorSetLayoutBinding::new(
                 VkDescriptorType::VK_DESCRIPTOR_TYPE_ACCELERATION_STRUCTURE_KHR, 
@@ -1131,17 +1672,16 @@ impl DescriptorSetLayout {
             pBindings: bindings.as_ptr(),
         };
         vkCreateDescriptorSetLayout(device.handle(), &create_info, ptr::null(), handle.as_mut_ptr())
-            .into_result()
-            .unwrap();
+            .into_result_with("vkCreateDescriptorSetLayout")?;
         let handle = handle.assume_init();
         let this = Self {
             device: Arc::clone(device),
             handle: handle,
         };
-        Arc::new(this)
+        Ok(Arc::new(this))
     }
 
-    unsafe fn new_secondary(device: &Arc<Device>) -> Arc<Self> {
+    unsafe fn new_secondary(device: &Arc<Device>) -> Result<Arc<Self>> {
         let bindings = vec![
             VkDescriptorSetLayoutBinding::new_array(
                 VkDescriptorType::VK_DESCRIPTOR_TYPE_COMBINED_IMAGE_SAMPLER, 
@@ -1170,14 +1710,13 @@ impl DescriptorSetLayout {
             pBindings: bindings.as_ptr(),
         };
         vkCreateDescriptorSetLayout(device.handle(), &create_info, ptr::null(), handle.as_mut_ptr())
-            .into_result()
-            .unwrap();
+            .into_result_with("vkCreateDescriptorSetLayout")?;
         let handle = handle.assume_init();
         let this = Self {
             device: Arc::clone(device),
             handle,
         };
-        Arc::new(this)
+        Ok(Arc::new(this))
     }
 
     fn handle(&self) -> VkDescriptorSetLayout {
@@ -1200,7 +1739,7 @@ struct DescriptorPool {
 }
 
 impl DescriptorPool {
-    unsafe fn new_primary(device: &Arc<Device>, textures_count: usize) -> Arc<Self> {
+    unsafe fn new_primary(device: &Arc<Device>, textures_count: usize) -> Result<Arc<Self>> {
         let mut handle = MaybeUninit::<VkDescriptorPool>::zeroed();
         {
             let sizes = vec![
@@ -1212,7 +1751,7 @@ impl DescriptorPool {
                 VkDescriptorPoolSize::new(VkDescriptorType::VK_DESCRIPTOR_TYPE_STORAGE_BUFFER, 1),
                 VkDescriptorPoolSize::new(VkDescriptorType::VK_DESCRIPTOR_TYPE_STORAGE_BUFFER, 1),
                 VkDescriptorPoolSize::new(VkDescriptorType::VK_DESCRIPTOR_TYPE_STORAGE_BUFFER, 1),
-                VkDescriptorPoolSize::new(VkDescriptorType::VK_DESCRIPTOR_TYPE_COMBINED_IMAGE_SAMPLER, textures_count as u32),
+                VkDescriptorPoolSize::new(VkDescriptorType::VK_DESCRIPTOR_TYPE_COMBINED_IMAGE_SAMPLER, textures_count.max(1) as u32),
                 VkDescriptorPoolSize::new(VkDescriptorType::VK_DESCRIPTOR_TYPE_STORAGE_BUFFER, 1),
                 VkDescriptorPoolSize::new(VkDescriptorType::VK_DESCRIPTOR_TYPE_STORAGE_BUFFER, 1),
                 VkDescriptorPoolSize::new(VkDescriptorType::VK_DESCRIPTOR_TYPE_STORAGE_BUFFER, 1),
@@ -1221,18 +1760,17 @@ impl DescriptorPool {
             ];
             let create_info = VkDescriptorPoolCreateInfo::new(1, sizes.len() as u32, sizes.as_ptr(), 0);
             vkCreateDescriptorPool(device.handle(), &create_info, ptr::null(), handle.as_mut_ptr())
-                .into_result()
-                .unwrap();
+                .into_result_with("vkCreateDescriptorPool")?;
         }
         let handle = handle.assume_init();
         let this = Self {
             handle,
             device: Arc::clone(device),
         };
-        Arc::new(this)
+        Ok(Arc::new(this))
     }
 
-    unsafe fn new_secondary(device: &Arc<Device>) -> Arc<Self> {
+    unsafe fn new_secondary(device: &Arc<Device>) -> Result<Arc<Self>> {
         let mut handle = MaybeUninit::<VkDescriptorPool>::zeroed();
         {
             let sizes = vec![
@@ -1241,15 +1779,14 @@ impl DescriptorPool {
             let flags = VkDescriptorPoolCreateFlagBits::VK_DESCRIPTOR_POOL_CREATE_UPDATE_AFTER_BIND_BIT as VkFlags;
             let create_info = VkDescriptorPoolCreateInfo::new(1, sizes.len() as u32, sizes.as_ptr(), flags);
             vkCreateDescriptorPool(device.handle(), &create_info, ptr::null(), handle.as_mut_ptr())
-                .into_result()
-                .unwrap();
+                .into_result_with("vkCreateDescriptorPool")?;
         }
         let handle = handle.assume_init();
         let this = Self {
             handle,
             device: Arc::clone(device),
         };
-        Arc::new(this)
+        Ok(Arc::new(this))
     }
 
     fn handle(&self) -> VkDescriptorPool {
@@ -1273,7 +1810,7 @@ struct DescriptorSet {
 }
 
 impl DescriptorSet {
-    unsafe fn new_primary(descriptor_set_layout: &Arc<DescriptorSetLayout>, descriptor_pool: &Arc<DescriptorPool>) -> Arc<Self> {
+    unsafe fn new_primary(descriptor_set_layout: &Arc<DescriptorSetLayout>, descriptor_pool: &Arc<DescriptorPool>) -> Result<Arc<Self>> {
         let device = &descriptor_pool.device;
         let mut handle = MaybeUninit::<VkDescriptorSet>::zeroed();
         {
@@ -1286,8 +1823,7 @@ impl DescriptorSet {
                 pSetLayouts: &descriptor_set_layout,
             };
             vkAllocateDescriptorSets(device.handle(), &alloc_info, handle.as_mut_ptr())
-                .into_result()
-                .unwrap();
+                .into_result_with("vkAllocateDescriptorSets")?;
         }
         let handle = handle.assume_init();
         let this = Self {
@@ -1295,10 +1831,10 @@ impl DescriptorSet {
             descriptor_pool: Arc::clone(descriptor_pool),
             descriptor_set_layout: Arc::clone(descriptor_set_layout),
         };
-        Arc::new(this)
+        Ok(Arc::new(this))
     }
 
-    unsafe fn new_secondary(descriptor_set_layout: &Arc<DescriptorSetLayout>, descriptor_pool: &Arc<DescriptorPool>) -> Arc<Self> {
+    unsafe fn new_secondary(descriptor_set_layout: &Arc<DescriptorSetLayout>, descriptor_pool: &Arc<DescriptorPool>) -> Result<Arc<Self>> {
         let device = &descriptor_pool.device;
         let mut handle = MaybeUninit::<VkDescriptorSet>::zeroed();
         {
@@ -1318,8 +1854,7 @@ impl DescriptorSet {
                 pSetLayouts: &descriptor_set_layout,
             };
             vkAllocateDescriptorSets(device.handle(), &alloc_info, handle.as_mut_ptr())
-                .into_result()
-                .unwrap();
+                .into_result_with("vkAllocateDescriptorSets")?;
         }
         let handle = handle.assume_init();
         let this = Self {
@@ -1327,7 +1862,7 @@ impl DescriptorSet {
             descriptor_pool: Arc::clone(descriptor_pool),
             descriptor_set_layout: Arc::clone(descriptor_set_layout),
         };
-        Arc::new(this)
+        Ok(Arc::new(this))
     }
 
     fn handle(&self) -> VkDescriptorSet {
@@ -1575,7 +2110,7 @@ struct SecondaryDescriptorSet {
     descriptor_set_layout: Arc<DescriptorSetLayout>,
     descriptor_set_pool: Arc<DescriptorPool>,
     descriptor_set: Arc<DescriptorSet>,
-    textures: Vec<Arc<Texture>>,
+    textures: Mutex<Vec<Arc<Texture>>>,
 }
 
 impl SecondaryDescriptorSet {
@@ -1610,7 +2145,7 @@ impl SecondaryDescriptorSet {
             descriptor_set_layout: Arc::clone(descriptor_set_layout),
             descriptor_set_pool: Arc::clone(descriptor_set_pool),
             descriptor_set: Arc::clone(descriptor_set),
-            textures,
+            textures: Mutex::new(textures),
         };
         Arc::new(descriptors)
     }
@@ -1637,6 +2172,119 @@ impl SecondaryDescriptorSet {
             write_descriptor_sets.as_ptr(), 
             0, 
             ptr::null());
+        // the previous textures are released once nothing else refers to them
+        *self.textures.lock().unwrap() = textures;
+    }
+}
+
+// a hit group registered in addition to the built-in triangle, procedural and curve ones.
+// instances select it by its index within `RayTracingGraphicsPipeline::with_hit_groups` plus
+// `RayTracingGraphicsPipeline::BUILTIN_HIT_GROUP_COUNT`.
+pub struct RayTracingHitGroup {
+    closest_hit: ShaderModuleSource,
+    any_hit: Option<ShaderModuleSource>,
+    intersection: Option<ShaderModuleSource>,
+}
+
+impl RayTracingHitGroup {
+    pub fn triangles(closest_hit: ShaderModuleSource) -> Self {
+        Self {
+            closest_hit,
+            any_hit: None,
+            intersection: None,
+        }
+    }
+
+    pub fn procedural(intersection: ShaderModuleSource, closest_hit: ShaderModuleSource) -> Self {
+        Self {
+            closest_hit,
+            any_hit: None,
+            intersection: Some(intersection),
+        }
+    }
+
+    // also runs for shadow rays, which use the hit group of the instance they hit
+    pub fn with_any_hit(mut self, any_hit: ShaderModuleSource) -> Self {
+        self.any_hit = Some(any_hit);
+        self
+    }
+}
+
+// collects the stages of a pipeline while keeping their modules alive until it is created
+struct RayTracingShaderStages {
+    device: Arc<Device>,
+    entry_point: CString,
+    modules: Vec<Arc<ShaderModule>>,
+    stages: Vec<VkPipelineShaderStageCreateInfo>,
+}
+
+impl RayTracingShaderStages {
+    fn new(device: &Arc<Device>) -> Result<Self> {
+        let stages = Self {
+            device: Arc::clone(device),
+            entry_point: CString::new("main")?,
+            modules: vec![],
+            stages: vec![],
+        };
+        Ok(stages)
+    }
+
+    // returns the index of the stage to refer to from a shader group
+    fn push(&mut self, stage: VkShaderStageFlagBits, source: ShaderModuleSource) -> Result<u32> {
+        let module = ShaderModule::new(&self.device, source)?;
+        let index = self.stages.len() as u32;
+        self.stages.push(VkPipelineShaderStageCreateInfo {
+            sType: VkStructureType::VK_STRUCTURE_TYPE_PIPELINE_SHADER_STAGE_CREATE_INFO,
+            pNext: ptr::null(),
+            flags: 0,
+            stage,
+            module: module.handle(),
+            pName: self.entry_point.as_ptr(),
+            pSpecializationInfo: ptr::null(),
+        });
+        self.modules.push(module);
+        Ok(index)
+    }
+
+    fn general_group(index: u32) -> VkRayTracingShaderGroupCreateInfoKHR {
+        VkRayTracingShaderGroupCreateInfoKHR {
+            sType: VK_STRUCTURE_TYPE_RAY_TRACING_SHADER_GROUP_CREATE_INFO_KHR,
+            pNext: ptr::null(),
+            r#type: VkRayTracingShaderGroupTypeKHR::VK_RAY_TRACING_SHADER_GROUP_TYPE_GENERAL_KHR,
+            generalShader: index,
+            closestHitShader: VK_SHADER_UNUSED_KHR,
+            anyHitShader: VK_SHADER_UNUSED_KHR,
+            intersectionShader: VK_SHADER_UNUSED_KHR,
+            pShaderGroupCaptureReplayHandle: ptr::null(),
+        }
+    }
+
+    fn hit_group(&mut self, hit_group: RayTracingHitGroup) -> Result<VkRayTracingShaderGroupCreateInfoKHR> {
+        use VkShaderStageFlagBits::*;
+        use VkRayTracingShaderGroupTypeKHR::*;
+        let closest_hit = self.push(VK_SHADER_STAGE_CLOSEST_HIT_BIT_KHR, hit_group.closest_hit)?;
+        let any_hit = hit_group.any_hit
+            .map(|v| self.push(VK_SHADER_STAGE_ANY_HIT_BIT_KHR, v))
+            .transpose()?;
+        let intersection = hit_group.intersection
+            .map(|v| self.push(VK_SHADER_STAGE_INTERSECTION_BIT_KHR, v))
+            .transpose()?;
+        let group_type = if intersection.is_some() {
+            VK_RAY_TRACING_SHADER_GROUP_TYPE_PROCEDURAL_HIT_GROUP_KHR
+        } else {
+            VK_RAY_TRACING_SHADER_GROUP_TYPE_TRIANGLES_HIT_GROUP_KHR
+        };
+        let group = VkRayTracingShaderGroupCreateInfoKHR {
+            sType: VK_STRUCTURE_TYPE_RAY_TRACING_SHADER_GROUP_CREATE_INFO_KHR,
+            pNext: ptr::null(),
+            r#type: group_type,
+            generalShader: VK_SHADER_UNUSED_KHR,
+            closestHitShader: closest_hit,
+            anyHitShader: any_hit.unwrap_or(VK_SHADER_UNUSED_KHR),
+            intersectionShader: intersection.unwrap_or(VK_SHADER_UNUSED_KHR),
+            pShaderGroupCaptureReplayHandle: ptr::null(),
+        };
+        Ok(group)
     }
 }
 
@@ -1647,21 +2295,57 @@ pub struct RayTracingGraphicsPipeline {
     primary_descriptor_set_layout: Arc<DescriptorSetLayout>,
     secondary_descriptor_set_layout: Arc<DescriptorSetLayout>,
     shader_group_count: u32,
+    miss_group_count: u32,
+    hit_group_count: u32,
+    callable_group_count: u32,
+    textures_count: usize,
 }
 
 impl RayTracingGraphicsPipeline {
+    pub const TRIANGLES_HIT_GROUP: u32 = 0;
+    pub const PROCEDURAL_HIT_GROUP: u32 = 1;
+    pub const CURVE_HIT_GROUP: u32 = 2;
+    pub const BUILTIN_HIT_GROUP_COUNT: u32 = 3;
+    // materials of this type are shaded within the triangle closest hit shader
+    pub const BUILTIN_MATERIAL_TYPE: u32 = 0;
+
     pub fn new(
         device: &Arc<Device>,
         textures_count: usize,
+    ) -> Result<Arc<Self>> {
+        Self::with_hit_groups(device, textures_count, vec![])
+    }
+
+    pub fn with_hit_groups(
+        device: &Arc<Device>,
+        textures_count: usize,
+        hit_groups: Vec<RayTracingHitGroup>,
+    ) -> Result<Arc<Self>> {
+        Self::with_shaders(device, textures_count, hit_groups, vec![])
+    }
+
+    // `material_callables` evaluate the materials whose type is their index plus one.
+    // see `data/shaders/ray.common.material.glsl` for the data they receive.
+    pub fn with_shaders(
+        device: &Arc<Device>,
+        textures_count: usize,
+        hit_groups: Vec<RayTracingHitGroup>,
+        material_callables: Vec<ShaderModuleSource>,
     ) -> Result<Arc<Self>> {
         unsafe {
-            Self::init(device, textures_count)
+            Self::init(device, textures_count, hit_groups, material_callables)
         }
     }
 
-    unsafe fn init(device: &Arc<Device>, textures_count: usize) -> Result<Arc<Self>> {
-        let primary_descriptor_set_layout = DescriptorSetLayout::new_primary(device, textures_count);
-        let secondary_descriptor_set_layout = DescriptorSetLayout::new_secondary(device);
+    unsafe fn init(
+        device: &Arc<Device>, 
+        textures_count: usize, 
+        hit_groups: Vec<RayTracingHitGroup>,
+        material_callables: Vec<ShaderModuleSource>,
+    ) -> Result<Arc<Self>> {
+        use VkShaderStageFlagBits::*;
+        let primary_descriptor_set_layout = DescriptorSetLayout::new_primary(device, textures_count)?;
+        let secondary_descriptor_set_layout = DescriptorSetLayout::new_secondary(device)?;
         let descriptor_set_layouts = vec![
             &primary_descriptor_set_layout,
             &secondary_descriptor_set_layout,
@@ -1674,151 +2358,56 @@ impl RayTracingGraphicsPipeline {
         {
             let create_info = VkPipelineLayoutCreateInfo::new(descriptor_set_layout_handles.len() as u32, descriptor_set_layout_handles.as_ptr());
             vkCreatePipelineLayout(device.handle(), &create_info, ptr::null(), pipeline_layout.as_mut_ptr())
-                .into_result()
-                .unwrap();
+                .into_result_with("vkCreatePipelineLayout")?;
         }
         let pipeline_layout = pipeline_layout.assume_init();
         // Shader Stages
-        let raygen_shader_module = ShaderModule::new(device, ShaderModuleSource::from_file("data/shaders/ray.rgen.spv")).unwrap();
-        let rmiss_shader_module = ShaderModule::new(device, ShaderModuleSource::from_file("data/shaders/ray.rmiss.spv")).unwrap();
-        let shadow_rmiss_shader_module = ShaderModule::new(device, ShaderModuleSource::from_file("data/shaders/ray.shadow.rmiss.spv")).unwrap();
-        let triangles_rchit_shader_module = ShaderModule::new(device, ShaderModuleSource::from_file("data/shaders/ray.triangles.rchit.spv")).unwrap();
-        let triangles_rahit_shader_module = ShaderModule::new(device, ShaderModuleSource::from_file("data/shaders/ray.triangles.rahit.spv")).unwrap();
-        let procedural_rint_shader_module = ShaderModule::new(device, ShaderModuleSource::from_file("data/shaders/ray.procedural.rint.spv")).unwrap();
-        let procedural_rchit_shader_module = ShaderModule::new(device, ShaderModuleSource::from_file("data/shaders/ray.procedural.rchit.spv")).unwrap();
-        let shader_entry_point = CString::new("main").unwrap();
-        const INDEX_RAYGEN: u32 = 0;
-        const INDEX_MISS: u32 = 1;
-        const INDEX_SHADOW_MISS: u32 = 2;
-        const INDEX_TRIANGLES_CLOSEST_HIT: u32 = 3;
-        const INDEX_TRIANGLES_ANY_HIT: u32 = 4;
-        const INDEX_PROCEDURAL_INTERSECTION: u32 = 5;
-        const INDEX_PROCEDURAL_CLOSEST_HIT: u32 = 6;
-        let shader_stages = vec![
-            VkPipelineShaderStageCreateInfo {
-                sType: VkStructureType::VK_STRUCTURE_TYPE_PIPELINE_SHADER_STAGE_CREATE_INFO,
-                pNext: ptr::null(),
-                flags: 0,
-                stage: VkShaderStageFlagBits::VK_SHADER_STAGE_RAYGEN_BIT_KHR,
-                module: raygen_shader_module.handle(),
-                pName: shader_entry_point.as_ptr(),
-                pSpecializationInfo: ptr::null(),
-            },
-            VkPipelineShaderStageCreateInfo {
-                sType: VkStructureType::VK_STRUCTURE_TYPE_PIPELINE_SHADER_STAGE_CREATE_INFO,
-                pNext: ptr::null(),
-                flags: 0,
-                stage: VkShaderStageFlagBits::VK_SHADER_STAGE_MISS_BIT_KHR,
-                module: rmiss_shader_module.handle(),
-                pName: shader_entry_point.as_ptr(),
-                pSpecializationInfo: ptr::null(),
-            },
-            VkPipelineShaderStageCreateInfo {
-                sType: VkStructureType::VK_STRUCTURE_TYPE_PIPELINE_SHADER_STAGE_CREATE_INFO,
-                pNext: ptr::null(),
-                flags: 0,
-                stage: VkShaderStageFlagBits::VK_SHADER_STAGE_MISS_BIT_KHR,
-                module: shadow_rmiss_shader_module.handle(),
-                pName: shader_entry_point.as_ptr(),
-                pSpecializationInfo: ptr::null(),
-            },
-            VkPipelineShaderStageCreateInfo {
-                sType: VkStructureType::VK_STRUCTURE_TYPE_PIPELINE_SHADER_STAGE_CREATE_INFO,
-                pNext: ptr::null(),
-                flags: 0,
-                stage: VkShaderStageFlagBits::VK_SHADER_STAGE_CLOSEST_HIT_BIT_KHR,
-                module: triangles_rchit_shader_module.handle(),
-                pName: shader_entry_point.as_ptr(),
-                pSpecializationInfo: ptr::null(),
-            },
-            VkPipelineShaderStageCreateInfo {
-                sType: VkStructureType::VK_STRUCTURE_TYPE_PIPELINE_SHADER_STAGE_CREATE_INFO,
-                pNext: ptr::null(),
-                flags: 0,
-                stage: VkShaderStageFlagBits::VK_SHADER_STAGE_ANY_HIT_BIT_KHR,
-                module: triangles_rahit_shader_module.handle(),
-                pName: shader_entry_point.as_ptr(),
-                pSpecializationInfo: ptr::null(),
-            },
-            VkPipelineShaderStageCreateInfo {
-                sType: VkStructureType::VK_STRUCTURE_TYPE_PIPELINE_SHADER_STAGE_CREATE_INFO,
-                pNext: ptr::null(),
-                flags: 0,
-                stage: VkShaderStageFlagBits::VK_SHADER_STAGE_INTERSECTION_BIT_KHR,
-                module: procedural_rint_shader_module.handle(),
-                pName: shader_entry_point.as_ptr(),
-                pSpecializationInfo: ptr::null(),
-            },
-            VkPipelineShaderStageCreateInfo {
-                sType: VkStructureType::VK_STRUCTURE_TYPE_PIPELINE_SHADER_STAGE_CREATE_INFO,
-                pNext: ptr::null(),
-                flags: 0,
-                stage: VkShaderStageFlagBits::VK_SHADER_STAGE_CLOSEST_HIT_BIT_KHR,
-                module: procedural_rchit_shader_module.handle(),
-                pName: shader_entry_point.as_ptr(),
-                pSpecializationInfo: ptr::null(),
-            },
+        // | RAYGEN |
+        // | MISS | MISS (SHADOW) |
+        // | HIT (TRIANGLE) | HIT (PROCEDURAL) | HIT (CURVE) | HIT (REGISTERED)... |
+        // | CALLABLE (MATERIAL)... |
+        let mut stages = RayTracingShaderStages::new(device)?;
+        let raygen = stages.push(VK_SHADER_STAGE_RAYGEN_BIT_KHR, ShaderModuleSource::from_file("data/shaders/ray.rgen.spv"))?;
+        let miss = stages.push(VK_SHADER_STAGE_MISS_BIT_KHR, ShaderModuleSource::from_file("data/shaders/ray.rmiss.spv"))?;
+        let shadow_miss = stages.push(VK_SHADER_STAGE_MISS_BIT_KHR, ShaderModuleSource::from_file("data/shaders/ray.shadow.rmiss.spv"))?;
+        let mut shader_groups = vec![
+            RayTracingShaderStages::general_group(raygen),
+            RayTracingShaderStages::general_group(miss),
+            RayTracingShaderStages::general_group(shadow_miss),
         ];
-        let shader_groups = vec![
-            VkRayTracingShaderGroupCreateInfoKHR {
-                sType: VK_STRUCTURE_TYPE_RAY_TRACING_SHADER_GROUP_CREATE_INFO_KHR,
-                pNext: ptr::null(),
-                r#type: VkRayTracingShaderGroupTypeKHR::VK_RAY_TRACING_SHADER_GROUP_TYPE_GENERAL_KHR,
-                generalShader: INDEX_RAYGEN,
-                closestHitShader: VK_SHADER_UNUSED_KHR,
-                anyHitShader: VK_SHADER_UNUSED_KHR,
-                intersectionShader: VK_SHADER_UNUSED_KHR,
-                pShaderGroupCaptureReplayHandle: ptr::null(),
-            },
-            VkRayTracingShaderGroupCreateInfoKHR {
-                sType: VK_STRUCTURE_TYPE_RAY_TRACING_SHADER_GROUP_CREATE_INFO_KHR,
-                pNext: ptr::null(),
-                r#type: VkRayTracingShaderGroupTypeKHR::VK_RAY_TRACING_SHADER_GROUP_TYPE_GENERAL_KHR,
-                generalShader: INDEX_MISS,
-                closestHitShader: VK_SHADER_UNUSED_KHR,
-                anyHitShader: VK_SHADER_UNUSED_KHR,
-                intersectionShader: VK_SHADER_UNUSED_KHR,
-                pShaderGroupCaptureReplayHandle: ptr::null(),
-            },
-            VkRayTracingShaderGroupCreateInfoKHR {
-                sType: VK_STRUCTURE_TYPE_RAY_TRACING_SHADER_GROUP_CREATE_INFO_KHR,
-                pNext: ptr::null(),
-                r#type: VkRayTracingShaderGroupTypeKHR::VK_RAY_TRACING_SHADER_GROUP_TYPE_GENERAL_KHR,
-                generalShader: INDEX_SHADOW_MISS,
-                closestHitShader: VK_SHADER_UNUSED_KHR,
-                anyHitShader: VK_SHADER_UNUSED_KHR,
-                intersectionShader: VK_SHADER_UNUSED_KHR,
-                pShaderGroupCaptureReplayHandle: ptr::null(),
-            },
-            VkRayTracingShaderGroupCreateInfoKHR {
-                sType: VK_STRUCTURE_TYPE_RAY_TRACING_SHADER_GROUP_CREATE_INFO_KHR,
-                pNext: ptr::null(),
-                r#type: VkRayTracingShaderGroupTypeKHR::VK_RAY_TRACING_SHADER_GROUP_TYPE_TRIANGLES_HIT_GROUP_KHR,
-                generalShader: VK_SHADER_UNUSED_KHR,
-                closestHitShader: INDEX_TRIANGLES_CLOSEST_HIT,
-                anyHitShader: INDEX_TRIANGLES_ANY_HIT,
-                intersectionShader: VK_SHADER_UNUSED_KHR,
-                pShaderGroupCaptureReplayHandle: ptr::null(),
-            },
-            VkRayTracingShaderGroupCreateInfoKHR {
-                sType: VK_STRUCTURE_TYPE_RAY_TRACING_SHADER_GROUP_CREATE_INFO_KHR,
-                pNext: ptr::null(),
-                r#type: VkRayTracingShaderGroupTypeKHR::VK_RAY_TRACING_SHADER_GROUP_TYPE_PROCEDURAL_HIT_GROUP_KHR,
-                generalShader: VK_SHADER_UNUSED_KHR,
-                closestHitShader: INDEX_PROCEDURAL_CLOSEST_HIT,
-                anyHitShader: VK_SHADER_UNUSED_KHR,
-                intersectionShader: INDEX_PROCEDURAL_INTERSECTION,
-                pShaderGroupCaptureReplayHandle: ptr::null(),
-            },
+        let miss_group_count = 2;
+        let builtin_hit_groups = vec![
+            RayTracingHitGroup::triangles(ShaderModuleSource::from_file("data/shaders/ray.triangles.rchit.spv"))
+                .with_any_hit(ShaderModuleSource::from_file("data/shaders/ray.triangles.rahit.spv")),
+            RayTracingHitGroup::procedural(
+                ShaderModuleSource::from_file("data/shaders/ray.procedural.rint.spv"),
+                ShaderModuleSource::from_file("data/shaders/ray.procedural.rchit.spv"),
+            ),
+            RayTracingHitGroup::procedural(
+                ShaderModuleSource::from_file("data/shaders/ray.curve.rint.spv"),
+                ShaderModuleSource::from_file("data/shaders/ray.procedural.rchit.spv"),
+            ),
         ];
+        let hit_groups: Vec<_> = builtin_hit_groups.into_iter()
+            .chain(hit_groups)
+            .map(|v| stages.hit_group(v))
+            .collect::<Result<_>>()?;
+        let hit_group_count = hit_groups.len() as u32;
+        shader_groups.extend(hit_groups);
+        let callable_groups: Vec<_> = material_callables.into_iter()
+            .map(|v| stages.push(VK_SHADER_STAGE_CALLABLE_BIT_KHR, v))
+            .map(|v| v.map(RayTracingShaderStages::general_group))
+            .collect::<Result<_>>()?;
+        let callable_group_count = callable_groups.len() as u32;
+        shader_groups.extend(callable_groups);
         // allows casting a shadow ray from the closest hit shader
         let max_recursion_depth = 2;
         let create_info = VkRayTracingPipelineCreateInfoKHR {
             sType: VK_STRUCTURE_TYPE_RAY_TRACING_PIPELINE_CREATE_INFO_KHR,
             pNext: ptr::null(),
             flags: 0,
-            stageCount: shader_stages.len() as u32,
-            pStages: shader_stages.as_ptr(),
+            stageCount: stages.stages.len() as u32,
+            pStages: stages.stages.as_ptr(),
             groupCount: shader_groups.len() as u32,
             pGroups: shader_groups.as_ptr(),
             maxPipelineRayRecursionDepth: max_recursion_depth,
@@ -1831,8 +2420,7 @@ impl RayTracingGraphicsPipeline {
         };
         let mut handle = MaybeUninit::<VkPipeline>::zeroed();
         vkCreateRayTracingPipelinesKHR(device.handle(), ptr::null_mut(), ptr::null_mut(), 1, &create_info, ptr::null(), handle.as_mut_ptr())
-            .into_result()
-            .unwrap();
+            .into_result_with("vkCreateRayTracingPipelinesKHR")?;
         let handle = handle.assume_init();
         let layout = RayTracingGraphicsPipeline {
             device: Arc::clone(device),
@@ -1841,6 +2429,10 @@ impl RayTracingGraphicsPipeline {
             primary_descriptor_set_layout,
             secondary_descriptor_set_layout,
             shader_group_count: shader_groups.len() as u32,
+            miss_group_count,
+            hit_group_count,
+            callable_group_count,
+            textures_count,
         };
         Ok(Arc::new(layout))
     }
@@ -1864,6 +2456,29 @@ impl RayTracingGraphicsPipeline {
         self.shader_group_count
     }
 
+    pub fn miss_group_count(&self) -> u32 {
+        self.miss_group_count
+    }
+
+    // including the built-in ones
+    pub fn hit_group_count(&self) -> u32 {
+        self.hit_group_count
+    }
+
+    pub fn callable_group_count(&self) -> u32 {
+        self.callable_group_count
+    }
+
+    // the material types available to `Scene::set_material_type`
+    pub fn material_type_count(&self) -> u32 {
+        1 + self.callable_group_count
+    }
+
+    // the array size of the texture binding in the primary set
+    pub fn textures_count(&self) -> usize {
+        self.textures_count
+    }
+
     #[inline]
     fn primary_descriptor_set_layout(&self) -> &Arc<DescriptorSetLayout> {
         &self.primary_descriptor_set_layout
@@ -1887,7 +2502,8 @@ impl Drop for RayTracingGraphicsPipeline {
 }
 
 pub struct RayTracingDescriptorSets {
-    primary: Arc<PrimaryDescriptorSet>,
+    pipeline: Arc<RayTracingGraphicsPipeline>,
+    primary: Mutex<Arc<PrimaryDescriptorSet>>,
     secondary: Arc<SecondaryDescriptorSet>,
 }
 
@@ -1910,8 +2526,8 @@ impl RayTracingDescriptorSets {
         color_storage_buffer: &Arc<DedicatedStagingBuffer>,
     ) -> Result<Arc<Self>> {
         unsafe {
-            let primary_descriptor_pool = DescriptorPool::new_primary(pipeline.device(), textures.len());
-            let primary_descriptor_set = DescriptorSet::new_primary(pipeline.primary_descriptor_set_layout(), &primary_descriptor_pool);
+            let primary_descriptor_pool = DescriptorPool::new_primary(pipeline.device(), textures.len())?;
+            let primary_descriptor_set = DescriptorSet::new_primary(pipeline.primary_descriptor_set_layout(), &primary_descriptor_pool)?;
             let primary = PrimaryDescriptorSet::new(
                 pipeline,
                 pipeline.primary_descriptor_set_layout(),
@@ -1932,8 +2548,8 @@ impl RayTracingDescriptorSets {
                 tangent_storage_buffer,
                 color_storage_buffer,
             );
-            let secondary_descriptor_pool = DescriptorPool::new_secondary(pipeline.device());
-            let secondary_descriptor_set = DescriptorSet::new_secondary(pipeline.secondary_descriptor_set_layout(), &secondary_descriptor_pool);
+            let secondary_descriptor_pool = DescriptorPool::new_secondary(pipeline.device())?;
+            let secondary_descriptor_set = DescriptorSet::new_secondary(pipeline.secondary_descriptor_set_layout(), &secondary_descriptor_pool)?;
             let secondary = SecondaryDescriptorSet::new(
                 pipeline,
                 pipeline.secondary_descriptor_set_layout(),
@@ -1942,7 +2558,8 @@ impl RayTracingDescriptorSets {
                 textures,
             );
             let this = Self {
-                primary,
+                pipeline: Arc::clone(pipeline),
+                primary: Mutex::new(primary),
                 secondary,
             };
             Ok(Arc::new(this))
@@ -1954,6 +2571,121 @@ impl RayTracingDescriptorSets {
             self.secondary.update_textures(textures)
         }
     }
+
+    // points the descriptors at the resources of another scene while keeping the storage image
+    // and the uniform buffer. the primary set is not update-after-bind, so a new one is allocated;
+    // the device must be idle and the command buffers binding the sets must be recorded again.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_scene(
+        &self,
+        acceleration_structure: &Arc<TopLevelAccelerationStructure>,
+        vertex_storage_buffer: &Arc<DedicatedStagingBuffer>,
+        index_storage_buffer: &Arc<DedicatedStagingBuffer>,
+        normal_storage_buffer: &Arc<DedicatedStagingBuffer>,
+        description_storage_buffer: &Arc<DedicatedStagingBuffer>,
+        texcoord_storage_buffer: &Arc<DedicatedStagingBuffer>,
+        textures: &[Arc<Texture>],
+        sphere_storage_buffer: &Arc<DedicatedStagingBuffer>,
+        material_storage_buffer: &Arc<DedicatedStagingBuffer>,
+        material_description_storage_buffer: &Arc<DedicatedStagingBuffer>,
+        tangent_storage_buffer: &Arc<DedicatedStagingBuffer>,
+        color_storage_buffer: &Arc<DedicatedStagingBuffer>,
+    ) -> Result<()> {
+        unsafe {
+            let pipeline = &self.pipeline;
+            let mut primary = self.primary.lock().unwrap();
+            // the shaders sample from the secondary set, which has room for any texture count
+            let primary_textures = &textures[..textures.len().min(pipeline.textures_count())];
+            let primary_descriptor_pool = DescriptorPool::new_primary(pipeline.device(), primary_textures.len())?;
+            let primary_descriptor_set = DescriptorSet::new_primary(pipeline.primary_descriptor_set_layout(), &primary_descriptor_pool)?;
+            let storage_image = Arc::clone(&primary.storage_image);
+            let scene_uniform_buffer = Arc::clone(&primary.scene_uniform_buffer);
+            *primary = PrimaryDescriptorSet::new(
+                pipeline,
+                pipeline.primary_descriptor_set_layout(),
+                &primary_descriptor_pool,
+                &primary_descriptor_set,
+                acceleration_structure,
+                &storage_image,
+                &scene_uniform_buffer,
+                vertex_storage_buffer,
+                index_storage_buffer,
+                normal_storage_buffer,
+                description_storage_buffer,
+                texcoord_storage_buffer,
+                primary_textures,
+                sphere_storage_buffer,
+                material_storage_buffer,
+                material_description_storage_buffer,
+                tangent_storage_buffer,
+                color_storage_buffer,
+            );
+            self.secondary.update_textures(textures);
+            Ok(())
+        }
+    }
+
+    // points the descriptors at a reallocated acceleration structure. the same conditions as
+    // `update_scene` apply.
+    pub fn update_acceleration_structure(&self, acceleration_structure: &Arc<TopLevelAccelerationStructure>) -> Result<()> {
+        unsafe {
+            self.reallocate_primary(Some(acceleration_structure), None)
+        }
+    }
+
+    // points the descriptors at reallocated procedural primitive and material buffers. the same
+    // conditions as `update_scene` apply.
+    pub fn update_procedural_buffers(
+        &self,
+        sphere_storage_buffer: &Arc<DedicatedStagingBuffer>,
+        material_storage_buffer: &Arc<DedicatedStagingBuffer>,
+    ) -> Result<()> {
+        unsafe {
+            self.reallocate_primary(None, Some((sphere_storage_buffer, material_storage_buffer)))
+        }
+    }
+
+    // allocates a primary set reusing the current resources except for the given ones
+    unsafe fn reallocate_primary(
+        &self,
+        acceleration_structure: Option<&Arc<TopLevelAccelerationStructure>>,
+        procedural_buffers: Option<(&Arc<DedicatedStagingBuffer>, &Arc<DedicatedStagingBuffer>)>,
+    ) -> Result<()> {
+        let pipeline = &self.pipeline;
+        let mut primary = self.primary.lock().unwrap();
+        let primary_descriptor_pool = DescriptorPool::new_primary(pipeline.device(), primary.textures.len())?;
+        let primary_descriptor_set = DescriptorSet::new_primary(pipeline.primary_descriptor_set_layout(), &primary_descriptor_pool)?;
+        let current = Arc::clone(&primary);
+        let acceleration_structure = acceleration_structure
+            .unwrap_or(&current.acceleration_structure);
+        let (sphere_storage_buffer, material_storage_buffer) = procedural_buffers
+            .unwrap_or((&current.sphere_storage_buffer, &current.material_storage_buffer));
+        *primary = PrimaryDescriptorSet::new(
+            pipeline,
+            pipeline.primary_descriptor_set_layout(),
+            &primary_descriptor_pool,
+            &primary_descriptor_set,
+            acceleration_structure,
+            &current.storage_image,
+            &current.scene_uniform_buffer,
+            &current.vertex_storage_buffer,
+            &current.index_storage_buffer,
+            &current.normal_storage_buffer,
+            &current.description_storage_buffer,
+            &current.texcoord_storage_buffer,
+            &current.textures,
+            sphere_storage_buffer,
+            material_storage_buffer,
+            &current.material_description_storage_buffer,
+            &current.tangent_storage_buffer,
+            &current.color_storage_buffer,
+        );
+        Ok(())
+    }
+
+    fn primary_handle(&self) -> VkDescriptorSet {
+        self.primary.lock().unwrap().handle()
+    }
 }
 
 #[allow(dead_code)]
@@ -1977,11 +2709,7 @@ impl ShaderBindingTable {
         use VkBufferUsageFlagBits::*;
         use VkMemoryPropertyFlagBits::*;
         let device = command_pool.queue().device();
-        // | RAYGEN |
-        // | MISS |
-        // | MISS (SHADOW) |
-        // | HIT (TRIANGLE) |
-        // | HIT (PROCEDURAL) |
+        // the groups are laid out by kind in the order the pipeline created them
         let group_count = pipeline.shader_group_count();
         let properties = device.physical_device().properties_ray_tracing();
         let group_handle_size = properties.shaderGroupHandleSize;
@@ -1996,22 +2724,21 @@ impl ShaderBindingTable {
             buffer_flags,
             memory_flags,
             table_size,
-        ).unwrap();
+        )?;
+        let mut data: Vec<u8> = vec![];
+        let data_size = group_handle_size * group_count;
+        data.resize(data_size as usize, 0);
+        vkGetRayTracingShaderGroupHandlesKHR(
+            device.handle(), 
+            pipeline.handle(), 
+            0,
+            group_count,
+            data_size as size_t, 
+            data.as_mut_ptr() as *mut c_void,
+        )
+            .into_result_with("vkGetRayTracingShaderGroupHandlesKHR")?;
         storage_buffer.update(table_size, |buffer_data| {
             let buffer_data = buffer_data as *mut u8;
-            let mut data: Vec<u8> = vec![];
-            let data_size = group_handle_size * group_count;
-            data.resize(data_size as usize, 0);
-            vkGetRayTracingShaderGroupHandlesKHR(
-                device.handle(), 
-                pipeline.handle(), 
-                0,
-                group_count,
-                data_size as size_t, 
-                data.as_mut_ptr() as *mut c_void,
-            )
-                .into_result()
-                .unwrap();
             for i in 0..(group_count as isize) {
                 let src_offset = i * group_handle_size as isize;
                 let dst_offset = i * group_size_aligned as isize;
@@ -2028,17 +2755,31 @@ impl ShaderBindingTable {
             stride: group_size,
             size: group_size,
         };
+        let miss_offset = 1;
+        let miss_count = pipeline.miss_group_count() as VkDeviceSize;
         let miss_entry = VkStridedDeviceAddressRegionKHR {
-            deviceAddress: base_device_address + 1 * group_size,
+            deviceAddress: base_device_address + miss_offset * group_size,
             stride: group_size,
-            size: group_size * 2,
+            size: group_size * miss_count,
         };
+        let hit_offset = miss_offset + miss_count;
+        let hit_count = pipeline.hit_group_count() as VkDeviceSize;
         let hit_entry = VkStridedDeviceAddressRegionKHR {
-            deviceAddress: base_device_address + 3 * group_size,
+            deviceAddress: base_device_address + hit_offset * group_size,
             stride: group_size,
-            size: group_size * 2,
+            size: group_size * hit_count,
+        };
+        let callable_offset = hit_offset + hit_count;
+        let callable_count = pipeline.callable_group_count() as VkDeviceSize;
+        let callable_entry = if callable_count > 0 {
+            VkStridedDeviceAddressRegionKHR {
+                deviceAddress: base_device_address + callable_offset * group_size,
+                stride: group_size,
+                size: group_size * callable_count,
+            }
+        } else {
+            VkStridedDeviceAddressRegionKHR::default()
         };
-        let callable_entry = VkStridedDeviceAddressRegionKHR::default();
         let table = Self {
             storage_buffer,
             pipeline: Arc::clone(pipeline),
@@ -2103,8 +2844,7 @@ impl RayTracingGraphicsRender {
         pipeline: &Arc<RayTracingGraphicsPipeline>,
         descriptor_sets: &Arc<RayTracingDescriptorSets>,
     ) -> Result<Arc<Self>> {
-        let shader_binding_table = ShaderBindingTable::new(command_pool, pipeline)
-            .unwrap();
+        let shader_binding_table = ShaderBindingTable::new(command_pool, pipeline)?;
         let device = command_pool.queue().device();
         let properties = device.physical_device().properties_ray_tracing();
         let render = Self {
@@ -2121,7 +2861,7 @@ impl RayTracingGraphicsRender {
         let device = self.command_pool.queue().device();
         let shader_binding_table = &self.shader_binding_table;
         vkCmdBindPipeline(command_buffer, VkPipelineBindPoint::VK_PIPELINE_BIND_POINT_RAY_TRACING_KHR, self.pipeline.handle());
-        let primary_set = self.descriptor_sets.primary.handle();
+        let primary_set = self.descriptor_sets.primary_handle();
         vkCmdBindDescriptorSets(command_buffer, VkPipelineBindPoint::VK_PIPELINE_BIND_POINT_RAY_TRACING_KHR,
             self.pipeline.layout(), 0, 1, &primary_set, 0, ptr::null());
         let secondary_set = self.descriptor_sets.secondary.handle();
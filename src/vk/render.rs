@@ -10,11 +10,12 @@ use super::raytrace::{RayTracingGraphicsRender};
 
 use std::ptr;
 use std::mem::MaybeUninit;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 #[allow(dead_code)]
 pub struct GraphicsRender {
-    frames: Vec<GraphicsFramePrerender>,
+    frames: Mutex<Vec<GraphicsFramePrerender>>,
+    area: VkRect2D,
     swapchain_framebuffers: Arc<SwapchainFramebuffers>,
     frame_renderer: Arc<GraphicsFrameRenderer>,
     command_pool: Arc<CommandPool>,
@@ -54,7 +55,8 @@ impl GraphicsRender {
             .map(|framebuffer| frame_renderer.render(command_pool, framebuffer, area))
             .collect();
         let render = GraphicsRender {
-            frames,
+            frames: Mutex::new(frames),
+            area,
             swapchain_framebuffers: Arc::clone(swapchain_framebuffers),
             frame_renderer: Arc::clone(frame_renderer),
             command_pool: Arc::clone(command_pool),
@@ -71,7 +73,8 @@ impl GraphicsRender {
             swapchain.queue_present(image, self.render_semaphore.handle())?;
         }
         let image = swapchain.acquire_next_image(self.present_semaphore.handle())?;
-        let frame = self.frames.get(image.index())
+        let frames = self.frames.lock().unwrap();
+        let frame = frames.get(image.index())
             .ok_or_else(|| ErrorCode::RenderFrameNotFound)?;
         let command_buffer = frame.command_buffer();
         command_buffer.wait_and_reset();
@@ -82,6 +85,18 @@ impl GraphicsRender {
         )?;
         Ok(())
     }
+
+    // records the frames again so that they pick up replaced descriptor sets.
+    // the queue must be idle.
+    pub fn prerender(&self) {
+        unsafe {
+            let frames = self.swapchain_framebuffers.framebuffers()
+                .iter()
+                .map(|framebuffer| self.frame_renderer.render(&self.command_pool, framebuffer, self.area))
+                .collect();
+            *self.frames.lock().unwrap() = frames;
+        }
+    }
 }
 
 struct Semaphore {